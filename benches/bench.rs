@@ -1,5 +1,6 @@
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
 use pacmog::imaadpcm::{ImaAdpcmPlayer, I1F15};
+use pacmog::writer::PcmWriterBuilder;
 use pacmog::{PcmPlayer, PcmReader};
 
 fn parse_wav(c: &mut Criterion) {
@@ -11,10 +12,28 @@ fn parse_wav(c: &mut Criterion) {
     });
 }
 
+fn parse_wav_large_ixml(c: &mut Criterion) {
+    // The 16bit WAV with a 1 MB iXML chunk inserted before the fmt chunk.
+    let wav = include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_16.wav");
+    let mut file = wav[..12].to_vec();
+    file.extend_from_slice(b"iXML");
+    file.extend_from_slice(&1_000_000u32.to_le_bytes());
+    file.resize(file.len() + 1_000_000, b' ');
+    file.extend_from_slice(&wav[12..]);
+    let riff_size = file.len() as u32 - 8;
+    file[4..8].copy_from_slice(&riff_size.to_le_bytes());
+
+    c.bench_function("Parse WAV 16bit with 1MB iXML", |b| {
+        b.iter(|| {
+            let _reader = PcmReader::new(black_box(&file)).unwrap();
+        })
+    });
+}
+
 fn read_sample(c: &mut Criterion) {
     let wav = include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_16.wav");
     let reader = PcmReader::new(wav).unwrap();
-    let pcm_specs = reader.get_pcm_specs();
+    let pcm_specs = reader.specs();
     c.bench_function("Read a sample 16bit", |b| {
         b.iter(|| {
             for sample in 0..48000 {
@@ -26,6 +45,166 @@ fn read_sample(c: &mut Criterion) {
     });
 }
 
+fn decode_block(c: &mut Criterion) {
+    let wav = include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_16.wav");
+    let reader = PcmReader::new(wav).unwrap();
+    let mut buffer = [0f32; 256];
+    c.bench_function("Decode block 16bit", |b| {
+        b.iter(|| {
+            // Same number of samples as "Read a sample 16bit"
+            let mut frame = 0;
+            while frame < 48000 {
+                frame += reader.decode_block_f32(frame, &mut buffer).unwrap() as u32;
+                black_box(&buffer);
+            }
+        })
+    });
+}
+
+fn read_frames(c: &mut Criterion) {
+    let files: [(&str, &[u8]); 2] = [
+        (
+            "16bit",
+            include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_16.wav"),
+        ),
+        (
+            "32bit float",
+            include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_32FP.wav"),
+        ),
+    ];
+    for (name, data) in files {
+        let reader = PcmReader::new(data).unwrap();
+        let num_samples = reader.specs().num_samples;
+        let mut buffer = [0f32; 256];
+        let mut group = c.benchmark_group(format!("Read frames {name}"));
+        group.throughput(Throughput::Elements(num_samples.into()));
+        group.bench_function("read_frames", |b| {
+            b.iter(|| {
+                let mut frame = 0;
+                while frame < num_samples {
+                    frame += reader.read_frames(frame, &mut buffer).unwrap() as u32;
+                    black_box(&buffer);
+                }
+            })
+        });
+        group.bench_function("read_sample", |b| {
+            b.iter(|| {
+                let mut frame = 0;
+                while frame < num_samples {
+                    let n = 256.min(num_samples - frame);
+                    for i in 0..n {
+                        buffer[i as usize] = reader.read_sample(0, frame + i).unwrap();
+                    }
+                    frame += n;
+                    black_box(&buffer);
+                }
+            })
+        });
+        group.finish();
+    }
+}
+
+fn read_sample_24bit(c: &mut Criterion) {
+    let aiff = include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_24.aif");
+    let reader = PcmReader::new(aiff).unwrap();
+    c.bench_function("Read a sample 24bit random access", |b| {
+        b.iter(|| {
+            let mut sample = 0u32;
+            for _ in 0..48000 {
+                // Stride over the file to avoid a purely sequential access pattern.
+                sample = (sample + 7919) % 240000;
+                let _s = reader.read_sample(0, black_box(sample)).unwrap();
+            }
+        })
+    });
+}
+
+fn decode_planar(c: &mut Criterion) {
+    // 4ch 24bit WAV made from the mono sine wave.
+    let data = include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_24.wav");
+    let mono = PcmReader::new(data).unwrap();
+    let mut wav = vec![0u8; 3_000_000];
+    let mut writer = PcmWriterBuilder::new(4, 48000)
+        .with_format(pacmog::AudioFormat::LinearPcmLe, 24)
+        .build(&mut wav)
+        .unwrap();
+    for i in 0..mono.specs().num_samples {
+        let s = mono.read_sample(0, i).unwrap();
+        writer.write_frame(&[s, -s, s * 0.5, -s * 0.5]).unwrap();
+    }
+    writer.finalize().unwrap();
+    let len = writer.flush().len();
+    let reader = PcmReader::new(&wav[..len]).unwrap();
+    let num_samples = reader.specs().num_samples;
+
+    let mut buffers = [[0f32; 256]; 4];
+    c.bench_function("Planar 4ch 24bit forward", |b| {
+        b.iter(|| {
+            let mut frame = 0;
+            while frame < num_samples {
+                let [b0, b1, b2, b3] = &mut buffers;
+                let mut out: [&mut [f32]; 4] = [b0, b1, b2, b3];
+                frame += reader.decode_block_planar_f32(frame, &mut out).unwrap() as u32;
+                black_box(&out);
+            }
+        })
+    });
+
+    // Naive planar copy: each channel walks the interleaved data with a stride of a frame,
+    // so the data is read 4 times. The decoding is the same as in the library.
+    let data = reader.raw_data();
+    let bytes_per_frame = reader.bytes_per_frame();
+    c.bench_function("Planar 4ch 24bit channel by channel", |b| {
+        b.iter(|| {
+            let mut frame = 0;
+            while frame < num_samples as usize {
+                let n = 256.min(num_samples as usize - frame);
+                for (ch, buffer) in buffers.iter_mut().enumerate() {
+                    for (i, out) in buffer[..n].iter_mut().enumerate() {
+                        let offset = (frame + i) * bytes_per_frame + ch * 3;
+                        let [b0, b1, b2] = data[offset..offset + 3].try_into().unwrap();
+                        *out = (i32::from_le_bytes([0, b0, b1, b2]) >> 8) as f32 / 8388608.0;
+                    }
+                }
+                frame += n;
+                black_box(&buffers);
+            }
+        })
+    });
+}
+
+fn g711(c: &mut Criterion) {
+    let bytes: Vec<u8> = (0..48000u32).map(|i| (i * 7) as u8).collect();
+    c.bench_function("A-law lookup table", |b| {
+        b.iter(|| {
+            for byte in &bytes {
+                black_box(pacmog::g711::alaw_to_i16(black_box(*byte)));
+            }
+        })
+    });
+    c.bench_function("A-law computed", |b| {
+        b.iter(|| {
+            for byte in &bytes {
+                black_box(pacmog::g711::alaw_expand(black_box(*byte)));
+            }
+        })
+    });
+    c.bench_function("µ-law lookup table", |b| {
+        b.iter(|| {
+            for byte in &bytes {
+                black_box(pacmog::g711::ulaw_to_i16(black_box(*byte)));
+            }
+        })
+    });
+    c.bench_function("µ-law computed", |b| {
+        b.iter(|| {
+            for byte in &bytes {
+                black_box(pacmog::g711::ulaw_expand(black_box(*byte)));
+            }
+        })
+    });
+}
+
 fn player(c: &mut Criterion) {
     let data = include_bytes!("../tests/resources/MLKDream.wav");
     let reader = PcmReader::new(data).unwrap();
@@ -43,15 +222,71 @@ fn player(c: &mut Criterion) {
     });
 }
 
+fn player_stereo(c: &mut Criterion) {
+    // Stereo 16bit WAV made from the mono sine wave.
+    let data = include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_16.wav");
+    let mono = PcmReader::new(data).unwrap();
+    let mut wav = vec![0u8; 1_000_000];
+    let mut writer = PcmWriterBuilder::new(2, 48000).build(&mut wav).unwrap();
+    for i in 0..mono.specs().num_samples {
+        let s = mono.read_sample(0, i).unwrap();
+        writer.write_frame(&[s, -s]).unwrap();
+    }
+    writer.finalize().unwrap();
+    let len = writer.flush().len();
+
+    let reader = PcmReader::new(&wav[..len]).unwrap();
+    let mut player = PcmPlayer::new(reader);
+    player.set_loop_playing(true);
+    let mut buffer: [f32; 2] = [0.0, 0.0];
+    let buf = buffer.as_mut_slice();
+
+    c.bench_function("PcmPlayer 2ch", |b| {
+        b.iter(|| {
+            player.set_position(0).unwrap();
+            for _ in 0..1_000_000 {
+                player.get_next_frame(buf).unwrap();
+            }
+        })
+    });
+}
+
+fn refill_half_buffer(c: &mut Criterion) {
+    let mut group = c.benchmark_group("refill_half_buffer 256 frames");
+    group.throughput(Throughput::Elements(256));
+    let files: [(&str, &[u8]); 2] = [
+        (
+            "16bit",
+            include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_16.wav"),
+        ),
+        (
+            "24bit",
+            include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_24.wav"),
+        ),
+    ];
+    for (name, data) in files {
+        let reader = PcmReader::new(data).unwrap();
+        let mut player = PcmPlayer::new(reader);
+        player.set_loop_playing(true);
+        let mut half = [0i16; 256];
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                player.refill_half_buffer(black_box(&mut half)).unwrap();
+            })
+        });
+    }
+    group.finish();
+}
+
 fn parse_decode_ima_adpcm(c: &mut Criterion) {
     let data = include_bytes!("../tests/resources/Sine440Hz_2ch_48000Hz_4bit_IMAADPCM.wav");
     let mut buffer: [I1F15; 2] = [I1F15::ZERO, I1F15::ZERO];
 
     c.bench_function("Decode IMA-ADPCM", |b| {
-        let mut player = ImaAdpcmPlayer::new(data);
+        let mut player = ImaAdpcmPlayer::new(data).unwrap();
         let buf = buffer.as_mut_slice();
         b.iter(|| {
-            // player = ImaAdpcmPlayer::new(data);
+            // player = ImaAdpcmPlayer::new(data).unwrap();
             player.rewind();
             for _ in 0..192000 {
                 //4sec
@@ -64,8 +299,16 @@ fn parse_decode_ima_adpcm(c: &mut Criterion) {
 criterion_group!(
     benches,
     parse_wav,
+    parse_wav_large_ixml,
     read_sample,
+    read_sample_24bit,
+    decode_block,
+    read_frames,
+    decode_planar,
+    g711,
     parse_decode_ima_adpcm,
-    player
+    player,
+    player_stereo,
+    refill_half_buffer
 );
 criterion_main!(benches);
@@ -0,0 +1,13 @@
+//! Tester for AIFF/AIFF-C test suites such as toisto.
+//! Reads each file given on the command line, or every .aif/.aiff/.aifc file in a given directory.
+//! See the compliance module for the output.
+//!
+//! ```bash
+//! cargo run --example aiff_compliance -- path/to/toisto-aiff-test-suite/tests
+//! ```
+
+mod compliance;
+
+fn main() {
+    compliance::run("aiff_compliance", &["aif", "aiff", "aifc"]);
+}
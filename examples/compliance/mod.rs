@@ -0,0 +1,199 @@
+//! Shared part of the compliance testers, which read edge-case files such as the toisto test suites.
+//! Each file is printed as one JSON object per line:
+//!
+//! ```text
+//! {"file":"a.aif","result":"ok","format":"aifc","codec":"LinearPcmBe","channels":1,"sampleRate":44100,
+//!  "bitsPerSample":16,"sampleFrames":100,"startSamples":[[0,12,...]],"endSamples":[[...]]}
+//! {"file":"b.aifc","result":"error","error":"Unsupported audio format"}
+//! ```
+//!
+//! Linear PCM samples are printed as integers of the bit depth, e.g. -524288..=524287 for 20bit,
+//! floating point samples as they are and the compressed formats as 16bit integers.
+//! The number of files read successfully is printed to stderr at the end.
+
+use pacmog::imaadpcm::{ImaAdpcmPlayer, I1F15};
+use pacmog::msadpcm::MsAdpcmPlayer;
+use pacmog::{AudioFormat, PcmReader, PcmReaderError, PcmSpecs};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+/// Number of frames printed at the start and at the end.
+const NUM_PRINTED_FRAMES: u32 = 10;
+
+/// Printed samples of each channel.
+type Samples = Vec<Vec<String>>;
+
+/// Run the tester over the files of the arguments.
+/// Directories are searched for files with one of the extensions.
+pub fn run(name: &str, extensions: &[&str]) {
+    let mut files = Vec::new();
+    for arg in env::args().skip(1) {
+        let path = PathBuf::from(arg);
+        if path.is_dir() {
+            let mut entries: Vec<_> = fs::read_dir(&path)
+                .unwrap()
+                .map(|e| e.unwrap().path())
+                .filter(|p| has_extension(p, extensions))
+                .collect();
+            entries.sort();
+            files.extend(entries);
+        } else {
+            files.push(path);
+        }
+    }
+    if files.is_empty() {
+        eprintln!("Usage: {name} <file or directory>...");
+        std::process::exit(2);
+    }
+
+    let mut num_ok = 0;
+    for file in &files {
+        let data = fs::read(file).unwrap();
+        let json = match PcmReader::new(&data) {
+            Ok(reader) => match report(&reader, &data) {
+                Ok(report) => {
+                    num_ok += 1;
+                    format!(r#"{{"file":{},"result":"ok",{report}}}"#, quote(file))
+                }
+                Err(e) => error(file, e),
+            },
+            Err(e) => error(file, e.into()),
+        };
+        println!("{json}");
+    }
+    eprintln!("{num_ok}/{} files read", files.len());
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| extensions.iter().any(|x| x.eq_ignore_ascii_case(e)))
+}
+
+/// Specs and the samples at the start and at the end of the file.
+fn report(reader: &PcmReader, data: &[u8]) -> Result<String, Box<dyn Error>> {
+    let specs = reader.specs();
+    let format = match data.get(8..12) {
+        Some(b"AIFC") => "aifc",
+        Some(b"AIFF") => "aiff",
+        Some(b"WAVE") => "wav",
+        _ => "unknown",
+    };
+    let mut json = format!(
+        r#""format":"{format}","codec":"{:?}","channels":{},"sampleRate":{},"bitsPerSample":{},"sampleFrames":{}"#,
+        specs.audio_format,
+        specs.num_channels,
+        specs.sample_rate,
+        specs.bit_depth,
+        specs.num_samples
+    );
+
+    let (start, end) = if specs.audio_format == AudioFormat::ImaAdpcmLe {
+        let mut player = ImaAdpcmPlayer::new(data)?;
+        adpcm_samples(specs, |frame| Ok(player.get_next_frame(frame)?))?
+    } else if specs.audio_format == AudioFormat::MsAdpcmLe {
+        let mut player = MsAdpcmPlayer::new(data)?;
+        adpcm_samples(specs, |frame| Ok(player.get_next_frame(frame)?))?
+    } else {
+        let num_printed = specs.num_samples.min(NUM_PRINTED_FRAMES);
+        let samples = |frames: std::ops::Range<u32>| {
+            (0..specs.num_channels)
+                .map(|channel| {
+                    frames
+                        .clone()
+                        .map(|frame| {
+                            Ok(match specs.audio_format {
+                                AudioFormat::LinearPcmLe | AudioFormat::LinearPcmBe => {
+                                    reader.read_sample_exact(channel, frame)?.to_string()
+                                }
+                                AudioFormat::IeeeFloatLe | AudioFormat::IeeeFloatBe => {
+                                    reader.read_sample(channel, frame)?.to_string()
+                                }
+                                // G.711 expands to more than its 8 bits, so it is printed as 16bit like ADPCM.
+                                _ => {
+                                    let sample = reader.read_sample(channel, frame)?;
+                                    ((sample * 32768.0).round() as i32).to_string()
+                                }
+                            })
+                        })
+                        .collect::<Result<Vec<_>, PcmReaderError>>()
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+        (
+            samples(0..num_printed)?,
+            samples(specs.num_samples - num_printed..specs.num_samples)?,
+        )
+    };
+    write!(
+        json,
+        r#","startSamples":{},"endSamples":{}"#,
+        array(&start),
+        array(&end)
+    )
+    .unwrap();
+    Ok(json)
+}
+
+/// ADPCM is decoded from the beginning by ImaAdpcmPlayer or MsAdpcmPlayer. The samples are printed as 16bit integers.
+fn adpcm_samples(
+    specs: &PcmSpecs,
+    mut next_frame: impl FnMut(&mut [I1F15]) -> Result<(), Box<dyn Error>>,
+) -> Result<(Samples, Samples), Box<dyn Error>> {
+    let num_channels = specs.num_channels as usize;
+    let num_samples = specs.num_samples;
+    let mut start = vec![Vec::new(); num_channels];
+    let mut end = vec![VecDeque::new(); num_channels];
+    let mut frame = [I1F15::ZERO; 2];
+    for i in 0..num_samples {
+        next_frame(&mut frame)?;
+        for ch in 0..num_channels {
+            let sample = frame[ch].to_bits().to_string();
+            if i < NUM_PRINTED_FRAMES {
+                start[ch].push(sample.clone());
+            }
+            if end[ch].len() == NUM_PRINTED_FRAMES as usize {
+                end[ch].pop_front();
+            }
+            end[ch].push_back(sample);
+        }
+    }
+    let end = end.into_iter().map(Vec::from).collect();
+    Ok((start, end))
+}
+
+/// JSON array of the samples of each channel.
+fn array(channels: &Samples) -> String {
+    let channels: Vec<String> = channels
+        .iter()
+        .map(|samples| format!("[{}]", samples.join(",")))
+        .collect();
+    format!("[{}]", channels.join(","))
+}
+
+fn error(file: &Path, e: Box<dyn Error>) -> String {
+    format!(
+        r#"{{"file":{},"result":"error","error":{}}}"#,
+        quote(file),
+        quote(&e.to_string())
+    )
+}
+
+/// JSON string literal of the path or the message.
+fn quote(s: &(impl AsRef<std::ffi::OsStr> + ?Sized)) -> String {
+    let s = s.as_ref().to_string_lossy();
+    let mut quoted = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if c.is_control() => write!(quoted, "\\u{:04x}", c as u32).unwrap(),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -5,7 +5,7 @@ fn main() {
     let wav = include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_64FP.wav");
     println!("Wave length in bytes: {}", wav.len());
     let reader = PcmReader::new(wav).unwrap();
-    println!("PCM spec: {:?}", reader.get_pcm_specs());
+    println!("PCM spec: {:?}", reader.specs());
 
     let mut file = fs::File::create("sinewave.txt").unwrap();
 
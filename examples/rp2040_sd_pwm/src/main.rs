@@ -0,0 +1,220 @@
+//! Play a WAV file from an SD card with the PWM of a Raspberry Pi Pico (RP2040).
+//!
+//! The SD card is connected to SPI0 (MISO: GPIO16, CS: GPIO17, SCK: GPIO18, MOSI: GPIO19) and mounted with embedded-sdmmc.
+//! [StreamingPlayer] reads the file through a 512-byte scratch buffer, so the file can be larger than RAM.
+//! The left and right channels are output by PWM slice 0 on GPIO0 and GPIO1. Put an RC low-pass filter after the pins.
+//!
+//! The PWM wraps once per sample, and the wrap interrupt sets the next duty cycle from the active half of a [PingPongBuffer].
+//! When the active half has been played, the interrupt swaps the halves and the main loop refills the inactive one.
+//! The SD card is read outside of the critical section, so the interrupt is not blocked by the storage.
+//!
+//! This crate is not a member of the pacmog workspace. Build it from this directory:
+//!
+//! ```bash
+//! rustup target add thumbv6m-none-eabi
+//! cargo run --release
+//! ```
+
+#![no_std]
+#![no_main]
+
+use core::cell::RefCell;
+use core::convert::Infallible;
+use critical_section::Mutex;
+use embedded_hal::pwm::SetDutyCycle;
+use embedded_hal_bus::spi::ExclusiveDevice;
+use embedded_sdmmc::{Mode, SdCard, TimeSource, Timestamp, VolumeIdx, VolumeManager};
+use fugit::RateExtU32;
+use pacmog::dma::PingPongBuffer;
+use pacmog::stream::{StreamError, StreamingPlayer};
+use pacmog::PcmPlayerError;
+use panic_halt as _;
+use rp2040_hal::{self as hal, clocks::Clock, pac, pac::interrupt, pwm};
+
+#[link_section = ".boot2"]
+#[used]
+pub static BOOT2_FIRMWARE: [u8; 256] = rp2040_boot2::BOOT_LOADER_W25Q080;
+
+const XTAL_FREQ_HZ: u32 = 12_000_000;
+
+/// File to play from the root directory of the SD card. 16-bit mono or stereo.
+const FILE_NAME: &str = "SINE.WAV";
+
+/// Samples per half of the ping-pong buffer.
+/// 128 stereo frames are 2.7 ms at 48 kHz, which is the time the main loop has to read the next ones.
+const BUFFER_LEN: usize = 256;
+
+/// State shared with the PWM interrupt.
+struct Output {
+    pwm: pwm::Slice<pwm::Pwm0, pwm::FreeRunning>,
+    buffer: PingPongBuffer<BUFFER_LEN, i16>,
+    /// Position of the next frame in the active buffer.
+    index: usize,
+    num_channels: usize,
+    top: u16,
+}
+
+static OUTPUT: Mutex<RefCell<Option<Output>>> = Mutex::new(RefCell::new(None));
+
+/// Files are only read, so the time is not used.
+struct NoClock;
+
+impl TimeSource for NoClock {
+    fn get_timestamp(&self) -> Timestamp {
+        Timestamp {
+            year_since_1970: 0,
+            zero_indexed_month: 0,
+            zero_indexed_day: 0,
+            hours: 0,
+            minutes: 0,
+            seconds: 0,
+        }
+    }
+}
+
+#[hal::entry]
+fn main() -> ! {
+    let mut pac = pac::Peripherals::take().unwrap();
+    let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
+    let clocks = hal::clocks::init_clocks_and_plls(
+        XTAL_FREQ_HZ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    )
+    .ok()
+    .unwrap();
+    let timer = hal::Timer::new(pac.TIMER, &mut pac.RESETS, &clocks);
+    let sio = hal::Sio::new(pac.SIO);
+    let pins = hal::gpio::Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+
+    // The SD card is initialized at 400 kHz.
+    let miso = pins.gpio16.into_function::<hal::gpio::FunctionSpi>();
+    let cs = pins.gpio17.into_push_pull_output();
+    let sck = pins.gpio18.into_function::<hal::gpio::FunctionSpi>();
+    let mosi = pins.gpio19.into_function::<hal::gpio::FunctionSpi>();
+    let spi = hal::spi::Spi::<_, _, _, 8>::new(pac.SPI0, (mosi, miso, sck)).init(
+        &mut pac.RESETS,
+        clocks.peripheral_clock.freq(),
+        400.kHz(),
+        embedded_hal::spi::MODE_0,
+    );
+    let spi = ExclusiveDevice::new(spi, cs, timer).unwrap();
+    let sdcard = SdCard::new(spi, timer);
+    sdcard.num_bytes().unwrap();
+    sdcard.spi(|device| {
+        device
+            .bus_mut()
+            .set_baudrate(clocks.peripheral_clock.freq(), 16.MHz());
+    });
+
+    let mut volume_mgr: VolumeManager<_, _> = VolumeManager::new(sdcard, NoClock);
+    let mut volume = volume_mgr.open_volume(VolumeIdx(0)).unwrap();
+    let mut root_dir = volume.open_root_dir().unwrap();
+    let file = root_dir
+        .open_file_in_dir(FILE_NAME, Mode::ReadOnly)
+        .unwrap();
+
+    let mut scratch = [0u8; 512];
+    let mut player = StreamingPlayer::new(file, &mut scratch).unwrap();
+    player.set_loop_playing(true);
+    let sample_rate = player.specs().sample_rate;
+    let num_channels = player.specs().num_channels as usize;
+    assert!(num_channels == 1 || num_channels == 2);
+
+    // The counter wraps at the sample rate, e.g. 2604 steps (11.3 bits) at 48 kHz with the 125 MHz system clock.
+    let top = (clocks.system_clock.freq().to_Hz() / sample_rate - 1) as u16;
+    let slices = pwm::Slices::new(pac.PWM, &mut pac.RESETS);
+    let mut pwm = slices.pwm0;
+    pwm.set_div_int(1);
+    pwm.set_top(top);
+    pwm.channel_a.output_to(pins.gpio0);
+    pwm.channel_b.output_to(pins.gpio1);
+    pwm.enable_interrupt();
+    pwm.enable();
+
+    critical_section::with(|cs| {
+        OUTPUT.borrow_ref_mut(cs).replace(Output {
+            pwm,
+            buffer: PingPongBuffer::new(),
+            index: 0,
+            num_channels,
+            top,
+        });
+    });
+    // SAFETY: The state of the interrupt is initialized above.
+    unsafe { pac::NVIC::unmask(pac::Interrupt::PWM_IRQ_WRAP) };
+
+    let mut frames = [0f32; BUFFER_LEN];
+    let mut samples = [0i16; BUFFER_LEN];
+    loop {
+        let needs_fill = critical_section::with(|cs| {
+            OUTPUT
+                .borrow_ref(cs)
+                .as_ref()
+                .is_some_and(|output| output.buffer.needs_fill())
+        });
+        if !needs_fill {
+            cortex_m::asm::wfi();
+            continue;
+        }
+
+        // Read the SD card without holding the critical section.
+        let n = match player.fill_interleaved(&mut frames) {
+            Ok(n) => n,
+            Err(StreamError::Player(PcmPlayerError::FinishPlaying)) => 0,
+            Err(_) => panic!("Failed to read the SD card"),
+        };
+        let len = n * num_channels;
+        for (sample, frame) in samples.iter_mut().zip(&frames[..len]) {
+            *sample = (frame * 32767.0) as i16;
+        }
+
+        // The rest of the buffer is filled with silence.
+        critical_section::with(|cs| {
+            if let Some(output) = OUTPUT.borrow_ref_mut(cs).as_mut() {
+                let _ = output.buffer.fill_inactive_with(num_channels, |out| {
+                    out[..len].copy_from_slice(&samples[..len]);
+                    Ok::<_, Infallible>(n)
+                });
+            }
+        });
+    }
+}
+
+/// Duty cycle of a sample, from 0 to top + 1.
+fn duty(sample: i16, top: u16) -> u16 {
+    (((i32::from(sample) + 32768) as u32 * (u32::from(top) + 1)) >> 16) as u16
+}
+
+#[interrupt]
+fn PWM_IRQ_WRAP() {
+    critical_section::with(|cs| {
+        let mut output = OUTPUT.borrow_ref_mut(cs);
+        let Some(output) = output.as_mut() else {
+            return;
+        };
+        output.pwm.clear_interrupt();
+
+        // Mono is output to both channels.
+        let frame = &output.buffer.active()[output.index..output.index + output.num_channels];
+        let left = duty(frame[0], output.top);
+        let right = duty(frame[output.num_channels - 1], output.top);
+        let _ = output.pwm.channel_a.set_duty_cycle(left);
+        let _ = output.pwm.channel_b.set_duty_cycle(right);
+
+        output.index += output.num_channels;
+        if output.index >= BUFFER_LEN {
+            output.index = 0;
+            output.buffer.swap();
+        }
+    });
+}
@@ -0,0 +1,109 @@
+//! Stream a WAV file from a FAT formatted SD card with embedded-sdmmc.
+//! The header and the samples are read through a 512-byte scratch buffer, so the file can be larger than RAM.
+//!
+//! On a microcontroller, the block device is the SD card driver, e.g. embedded_sdmmc::SdCard.
+//! This example reads a disk image instead, so it runs on the host:
+//!
+//! ```bash
+//! cargo run --example sd_streaming --features embedded-sdmmc -- sdcard.img SINE.WAV
+//! ```
+use embedded_sdmmc::{
+    Block, BlockCount, BlockDevice, BlockIdx, Mode, TimeSource, Timestamp, VolumeIdx, VolumeManager,
+};
+use pacmog::stream::StreamingPlayer;
+use std::cell::RefCell;
+use std::io::{Read, Seek, SeekFrom};
+
+/// Disk image as a block device.
+struct ImageFile(RefCell<std::fs::File>);
+
+impl BlockDevice for ImageFile {
+    type Error = std::io::Error;
+
+    fn read(
+        &self,
+        blocks: &mut [Block],
+        start_block_idx: BlockIdx,
+        _reason: &str,
+    ) -> Result<(), Self::Error> {
+        let mut file = self.0.borrow_mut();
+        file.seek(SeekFrom::Start(
+            u64::from(start_block_idx.0) * Block::LEN as u64,
+        ))?;
+        for block in blocks {
+            file.read_exact(&mut block.contents)?;
+        }
+        Ok(())
+    }
+
+    fn write(&self, _blocks: &[Block], _start_block_idx: BlockIdx) -> Result<(), Self::Error> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            "the disk image is read only",
+        ))
+    }
+
+    fn num_blocks(&self) -> Result<BlockCount, Self::Error> {
+        let len = self.0.borrow().metadata()?.len();
+        Ok(BlockCount((len / Block::LEN as u64) as u32))
+    }
+}
+
+/// Files are only read, so the time is not used.
+struct NoClock;
+
+impl TimeSource for NoClock {
+    fn get_timestamp(&self) -> Timestamp {
+        Timestamp {
+            year_since_1970: 0,
+            zero_indexed_month: 0,
+            zero_indexed_day: 0,
+            hours: 0,
+            minutes: 0,
+            seconds: 0,
+        }
+    }
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let image = args.next().expect("Path of the disk image");
+    let name = args.next().unwrap_or_else(|| "SINE.WAV".to_string());
+
+    let device = ImageFile(RefCell::new(std::fs::File::open(image).unwrap()));
+    let mut volume_mgr: VolumeManager<_, _> = VolumeManager::new(device, NoClock);
+    let mut volume = volume_mgr.open_volume(VolumeIdx(0)).unwrap();
+    let mut root_dir = volume.open_root_dir().unwrap();
+    let file = root_dir
+        .open_file_in_dir(name.as_str(), Mode::ReadOnly)
+        .unwrap();
+
+    let mut scratch = [0u8; 512];
+    let mut player = StreamingPlayer::new(file, &mut scratch).unwrap();
+    println!("PCM spec: {:?}", player.specs());
+
+    // On a microcontroller, this loop would refill the DMA buffer of the I2S peripheral.
+    let mut buffer = [0f32; 256];
+    let mut num_frames = 0;
+    let mut peak = 0f32;
+    loop {
+        match player.fill_interleaved(&mut buffer) {
+            Ok(n) => {
+                num_frames += n;
+                let num_channels = player.specs().num_channels as usize;
+                for sample in &buffer[..n * num_channels] {
+                    peak = peak.max(sample.abs());
+                }
+            }
+            Err(pacmog::stream::StreamError::Player(pacmog::PcmPlayerError::FinishPlaying)) => {
+                break
+            }
+            Err(e) => {
+                // Read errors of the SD card end playback.
+                println!("{e}");
+                break;
+            }
+        }
+    }
+    println!("{num_frames} frames, peak {peak}");
+}
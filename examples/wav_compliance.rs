@@ -0,0 +1,13 @@
+//! Tester for WAV test suites such as toisto.
+//! Reads each file given on the command line, or every .wav file in a given directory.
+//! See the compliance module for the output.
+//!
+//! ```bash
+//! cargo run --example wav_compliance -- path/to/toisto-wav-test-suite/tests
+//! ```
+
+mod compliance;
+
+fn main() {
+    compliance::run("wav_compliance", &["wav", "wave"]);
+}
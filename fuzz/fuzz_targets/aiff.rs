@@ -0,0 +1,32 @@
+//! Parses arbitrary chunks in an AIFF container, so that the fuzzer does not have to find the FORM header.
+//! The header size is filled in, so the input reaches the chunk parsers.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pacmog::PcmReader;
+
+/// Upper bound of the frames read, so that a huge num_samples does not stall the fuzzer.
+const MAX_FRAMES: u32 = 1024;
+
+fuzz_target!(|data: &[u8]| {
+    let [aifc, chunks @ ..] = data else {
+        return;
+    };
+    let mut file = Vec::with_capacity(12 + chunks.len());
+    file.extend_from_slice(b"FORM");
+    file.extend_from_slice(&(4 + chunks.len() as u32).to_be_bytes());
+    file.extend_from_slice(if aifc & 1 == 0 { b"AIFF" } else { b"AIFC" });
+    file.extend_from_slice(chunks);
+
+    let Ok(reader) = PcmReader::new(&file) else {
+        return;
+    };
+    let specs = reader.get_pcm_specs();
+    for sample in 0..specs.num_samples.min(MAX_FRAMES) {
+        for channel in 0..specs.num_channels.min(8) {
+            let _ = reader.read_sample(channel, sample);
+        }
+    }
+    let mut out = [0f32; 64];
+    let _ = reader.decode_block_f32(0, &mut out);
+});
@@ -0,0 +1,55 @@
+//! Exercises the block decoder built with `unchecked-decode` on valid files only.
+//! The fuzzer chooses the format and the sample data, and the file is made by PcmWriter.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pacmog::writer::PcmWriterBuilder;
+use pacmog::{AudioFormat, PcmReader};
+
+const FORMATS: [(AudioFormat, u16); 5] = [
+    (AudioFormat::LinearPcmLe, 16),
+    (AudioFormat::LinearPcmLe, 24),
+    (AudioFormat::LinearPcmLe, 32),
+    (AudioFormat::IeeeFloatLe, 32),
+    (AudioFormat::IeeeFloatLe, 64),
+];
+
+fuzz_target!(|data: &[u8]| {
+    let [format, num_channels, block_size, start, samples @ ..] = data else {
+        return;
+    };
+    let (audio_format, bit_depth) = FORMATS[*format as usize % FORMATS.len()].clone();
+    let num_channels = (*num_channels % 8 + 1) as usize;
+
+    let mut wav = vec![0u8; 128 + samples.len() * 8];
+    let mut writer = PcmWriterBuilder::new(num_channels as u16, 48000)
+        .with_format(audio_format, bit_depth)
+        .build(&mut wav)
+        .unwrap();
+    let frame_values: Vec<f32> = samples.iter().map(|b| *b as i8 as f32 / 128.0).collect();
+    for frame in frame_values.chunks_exact(num_channels) {
+        writer.write_frame(frame).unwrap();
+    }
+    writer.finalize().unwrap();
+    let len = writer.flush().len();
+
+    let Ok(reader) = PcmReader::new(&wav[..len]) else {
+        return;
+    };
+    let num_samples = reader.get_pcm_specs().num_samples;
+    let mut out = vec![0f32; *block_size as usize];
+    let mut frame = *start as u32 % (num_samples + 1);
+    loop {
+        let n = reader.decode_block_f32(frame, &mut out).unwrap();
+        for (i, sample) in out[..n * num_channels].iter().enumerate() {
+            let expected = reader
+                .read_sample((i % num_channels) as u16, frame + (i / num_channels) as u32)
+                .unwrap();
+            assert_eq!(sample.to_bits(), expected.to_bits());
+        }
+        if n == 0 {
+            break;
+        }
+        frame += n as u32;
+    }
+});
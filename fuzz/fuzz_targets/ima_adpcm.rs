@@ -0,0 +1,22 @@
+//! Parses arbitrary bytes as an IMA-ADPCM file and plays a bounded number of frames.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pacmog::imaadpcm::{ImaAdpcmPlayer, I1F15};
+
+/// Upper bound of the frames played, so that a huge num_samples does not stall the fuzzer.
+const MAX_FRAMES: u32 = 4096;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(mut player) = ImaAdpcmPlayer::new(data) else {
+        return;
+    };
+    let mut out = [I1F15::ZERO; 2];
+    for _ in 0..player.specs().num_samples.min(MAX_FRAMES) {
+        if player.get_next_frame(&mut out).is_err() {
+            break;
+        }
+    }
+    player.rewind();
+    let _ = player.get_next_frame(&mut out);
+});
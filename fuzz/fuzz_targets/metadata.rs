@@ -0,0 +1,18 @@
+//! Parses arbitrary bytes as the payload of a LIST chunk of type INFO and as a text field.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pacmog::metadata::{info_tags, text_lossy, trim_text};
+
+fuzz_target!(|data: &[u8]| {
+    let mut list = b"INFO".to_vec();
+    list.extend_from_slice(data);
+    for (_id, text) in info_tags(&list) {
+        assert!(!text.contains(&0));
+        let _ = text_lossy(text);
+    }
+
+    let text = trim_text(data);
+    assert_eq!(trim_text(text), text);
+    let _ = text_lossy(data);
+});
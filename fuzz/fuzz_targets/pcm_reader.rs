@@ -0,0 +1,46 @@
+//! Parses arbitrary bytes as a PCM file and reads the samples if the parse succeeds.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pacmog::{PcmPlayer, PcmReader};
+
+/// Upper bound of the frames read, so that a huge num_samples does not stall the fuzzer.
+const MAX_FRAMES: u32 = 1024;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(reader) = PcmReader::new(data) else {
+        return;
+    };
+    let specs = reader.get_pcm_specs();
+    for chunk in reader.chunks() {
+        let _ = reader.chunk_data(&chunk.id);
+    }
+    let _ = reader.sampler_info();
+    let _ = reader.instrument();
+    let _ = reader.aiff_instrument();
+    let _ = reader.aiff_comments();
+    let _ = reader.application_chunks().count();
+    let _ = reader.broadcast_extension();
+    let _ = reader.channel_layout();
+    let _ = reader.cart_chunk();
+    let _ = reader.cue_points().count();
+
+    let num_frames = specs.num_samples.min(MAX_FRAMES);
+    for sample in 0..num_frames {
+        for channel in 0..specs.num_channels.min(8) {
+            let _ = reader.read_sample(channel, sample);
+        }
+    }
+    let _ = reader.read_sample(0, specs.num_samples);
+
+    let mut out = [0f32; 64];
+    let _ = reader.decode_block_f32(0, &mut out);
+
+    let mut player = PcmPlayer::new(reader);
+    player.set_loop_playing(true);
+    for _ in 0..num_frames {
+        if player.get_next_frame(&mut out).is_err() {
+            break;
+        }
+    }
+});
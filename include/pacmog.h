@@ -0,0 +1,143 @@
+#ifndef PACMOG_H
+#define PACMOG_H
+
+/* Generated by cbindgen from src/ffi.rs. Do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Size of [pacmog_reader_t] in bytes.
+#define PACMOG_READER_SIZE 512
+
+// Size of [pacmog_player_t] in bytes.
+#define PACMOG_PLAYER_SIZE 528
+
+// Result of the functions. Mirrors [PcmReaderError] and [PcmPlayerError].
+typedef enum {
+  PACMOG_OK = 0,
+  PACMOG_ERROR_UNSUPPORTED_BIT_DEPTH = 1,
+  PACMOG_ERROR_UNSUPPORTED_AUDIO_FORMAT = 2,
+  PACMOG_ERROR_INVALID_CHANNEL = 3,
+  PACMOG_ERROR_INVALID_SAMPLE = 4,
+  PACMOG_ERROR_HEADER_SIZE_MISMATCH = 5,
+  PACMOG_ERROR_OUTPUT_BUFFER_TOO_SHORT = 6,
+  PACMOG_ERROR_INVALID_POSITION = 7,
+  PACMOG_ERROR_FINISH_PLAYING = 8,
+  // A pointer argument is NULL.
+  PACMOG_ERROR_NULL_POINTER = 9,
+  PACMOG_ERROR_CHUNK_SIZE_EXCEEDS_FILE = 10,
+  PACMOG_ERROR_INCONSISTENT_SPECS = 11,
+  PACMOG_ERROR_DUPLICATE_CHUNK = 12,
+  PACMOG_ERROR_MISSING_CHUNK = 13,
+  PACMOG_ERROR_TRUNCATED_DATA = 14,
+  PACMOG_ERROR_INVALID_CHUNK_SIZE = 15,
+} pacmog_error_t;
+
+// Mirrors [AudioFormat].
+typedef enum {
+  PACMOG_AUDIO_FORMAT_UNKNOWN = 0,
+  PACMOG_AUDIO_FORMAT_LINEAR_PCM_LE = 1,
+  PACMOG_AUDIO_FORMAT_LINEAR_PCM_BE = 2,
+  PACMOG_AUDIO_FORMAT_IEEE_FLOAT_LE = 3,
+  PACMOG_AUDIO_FORMAT_IEEE_FLOAT_BE = 4,
+  PACMOG_AUDIO_FORMAT_IMA_ADPCM_LE = 5,
+  PACMOG_AUDIO_FORMAT_ALAW = 6,
+  PACMOG_AUDIO_FORMAT_MULAW = 7,
+  PACMOG_AUDIO_FORMAT_MS_ADPCM_LE = 8,
+} pacmog_audio_format_t;
+
+// Storage of a [PcmReader]. Initialize it with [pacmog_reader_new].
+typedef struct {
+  uint64_t _storage[64];
+} pacmog_reader_t;
+
+// Mirrors [PcmSpecs].
+typedef struct {
+  pacmog_audio_format_t audio_format;
+  uint16_t num_channels;
+  uint16_t bit_depth;
+  uint32_t sample_rate;
+  uint32_t num_samples;
+} pacmog_specs_t;
+
+// Storage of a [PcmPlayer]. Initialize it with [pacmog_player_new].
+typedef struct {
+  uint64_t _storage[66];
+} pacmog_player_t;
+
+// Parse the file into the reader.
+//
+// # Safety
+//
+// `data` must point to `len` bytes which outlive the reader and the players created from it.
+// `out` must point to a writable [pacmog_reader_t]. A reader initialized before is overwritten without [pacmog_reader_drop].
+pacmog_error_t pacmog_reader_new(const uint8_t *data,
+                                 size_t len,
+                                 pacmog_reader_t *out);
+
+// Release the reader. Only needed with the `alloc` feature, which stores the chunk table on the heap.
+//
+// # Safety
+//
+// `reader` must have been initialized by [pacmog_reader_new] and must not be used afterwards.
+void pacmog_reader_drop(pacmog_reader_t *reader);
+
+// Get the specs of the file.
+//
+// # Safety
+//
+// `reader` must have been initialized by [pacmog_reader_new]. `out` must point to a writable [pacmog_specs_t].
+pacmog_error_t pacmog_reader_specs(const pacmog_reader_t *reader,
+                                   pacmog_specs_t *out);
+
+// Read a sample of a channel as a normalized value. See [PcmReader::read_sample].
+//
+// # Safety
+//
+// `reader` must have been initialized by [pacmog_reader_new]. `out` must point to a writable float.
+pacmog_error_t pacmog_read_sample_f32(const pacmog_reader_t *reader,
+                                      uint16_t channel,
+                                      uint32_t sample,
+                                      float *out);
+
+// Create a player of the file. The reader is copied, so it can be dropped afterwards.
+//
+// # Safety
+//
+// `reader` must have been initialized by [pacmog_reader_new]. `out` must point to a writable [pacmog_player_t].
+// A player initialized before is overwritten without [pacmog_player_drop].
+pacmog_error_t pacmog_player_new(const pacmog_reader_t *reader,
+                                 pacmog_player_t *out);
+
+// Release the player. Only needed with the `alloc` feature.
+//
+// # Safety
+//
+// `player` must have been initialized by [pacmog_player_new] and must not be used afterwards.
+void pacmog_player_drop(pacmog_player_t *player);
+
+// Enable or disable loop playback. See [PcmPlayer::set_loop_playing].
+//
+// # Safety
+//
+// `player` must have been initialized by [pacmog_player_new].
+pacmog_error_t pacmog_player_set_loop_playing(pacmog_player_t *player, bool enable);
+
+// Fill the buffer with the next interleaved frames as 16bit samples. See [PcmPlayer::refill_half_buffer].
+// When playback finishes, the rest of the buffer is filled with silence and `num_frames` is less than the buffer holds.
+// * 'out' - Buffer of `len` samples.
+// * 'num_frames' - Receives the number of frames taken from the file.
+//
+// # Safety
+//
+// `player` must have been initialized by [pacmog_player_new]. `out` must point to `len` writable samples.
+// `num_frames` must point to a writable size_t.
+pacmog_error_t pacmog_player_fill_i16(pacmog_player_t *player,
+                                      int16_t *out,
+                                      size_t len,
+                                      size_t *num_frames);
+
+#endif  /* PACMOG_H */
@@ -1,7 +1,8 @@
-use crate::{AudioFormat, PcmSpecs};
+use crate::metadata::{AiffInstrument, ChannelLayout, Comment, Comments};
+use crate::{AudioFormat, NumSamplesSource, PcmSpecs};
 use nom::branch::alt;
 use nom::bytes::complete::{tag, take};
-use nom::number::complete::{be_i16, be_i32, be_u32};
+use nom::number::complete::{be_i16, be_i32, be_u16, be_u32, i8, u8};
 use nom::IResult;
 
 #[derive(thiserror::Error, Debug)]
@@ -18,6 +19,16 @@ impl From<AiffError> for nom::Err<nom::error::Error<&[u8]>> {
     }
 }
 
+// Layout tags of Core Audio in the CHAN chunk, with the number of channels in the lower 16 bits.
+const LAYOUT_TAG_USE_CHANNEL_DESCRIPTIONS: u32 = 0;
+const LAYOUT_TAG_USE_CHANNEL_BITMAP: u32 = 1 << 16;
+const LAYOUT_TAG_MONO: u32 = (100 << 16) | 1;
+const LAYOUT_TAG_STEREO: u32 = (101 << 16) | 2;
+const LAYOUT_TAG_QUADRAPHONIC: u32 = (108 << 16) | 4;
+const LAYOUT_TAG_MPEG_5_0_A: u32 = (117 << 16) | 5;
+const LAYOUT_TAG_MPEG_5_1_A: u32 = (121 << 16) | 6;
+const LAYOUT_TAG_MPEG_7_1_C: u32 = (128 << 16) | 8;
+
 /// ckID chunkの種類
 #[derive(Debug, PartialEq, Default)]
 pub(super) enum ChunkId {
@@ -65,47 +76,84 @@ impl TryFrom<&[u8]> for ChunkId {
     }
 }
 
+/// Header of a chunk. The payload is not sliced until it is needed.
 #[derive(Debug, Default)]
-pub(super) struct Chunk<'a> {
+pub(super) struct ChunkHeader {
     pub id: ChunkId,
-    #[allow(dead_code)]
+    pub fourcc: [u8; 4],
+    /// Size of the payload in bytes.
     pub size: u32,
-    pub data: &'a [u8],
 }
 
 /// AIFFチャンクの情報
 /// * 'size' - ファイルサイズ(byte) - 8
+/// * 'is_aifc' - Whether the form type is AIFC.
 pub(super) struct AiffHeader {
     pub size: u32,
+    pub is_aifc: bool,
 }
 
 /// SSNDチャンクのOffset, BlockSize
 /// ほとんどの場合、offsetもblock_sizeも0になる
 ///
-/// * 'offset' - ほとんどの場合0
-/// * 'block_size' - ほとんどの場合0
+/// * 'offset' - ほとんどの場合0. Bytes before the first sample frame, e.g. to align the samples to blocks.
+/// * 'block_size' - ほとんどの場合0. Size of the blocks, after whose last one the sound data may be padded.
 #[derive(Debug)]
 pub(super) struct SsndBlockInfo {
     pub offset: i32,
     pub block_size: i32,
 }
 
+impl SsndBlockInfo {
+    /// Position of the first sample frame in the sound data after offset and blockSize, whose size is data_size.
+    /// Returns None if the offset is negative or beyond the sound data.
+    pub fn first_frame(&self, data_size: u64) -> Option<u64> {
+        let offset = u64::try_from(self.offset).ok().filter(|&o| o <= data_size);
+        if offset.is_none() {
+            debug!("SSND offset {} out of the {} bytes", self.offset, data_size);
+        }
+        offset
+    }
+}
+
+/// A loop of the INST chunk, which begins and ends at markers of the MARK chunk.
+/// * 'play_mode' - 0 is no loop, 1 is forward and 2 is forward and backward.
+#[derive(Debug)]
+pub(super) struct InstLoop {
+    pub play_mode: i16,
+    pub begin: i16,
+    pub end: i16,
+}
+
 /// ファイルがFORMから始まり、識別子がAIFFもしくはAIFF-Cであることのチェック
 pub(super) fn parse_aiff_header(input: &[u8]) -> IResult<&[u8], AiffHeader> {
     let (input, _) = tag(b"FORM")(input)?;
     let (input, size) = be_u32(input)?;
-    let (input, _id) = alt((tag(b"AIFF"), tag(b"AIFC")))(input)?;
-    Ok((input, AiffHeader { size }))
+    let (input, id) = alt((tag(b"AIFF"), tag(b"AIFC")))(input)?;
+    let is_aifc = id == b"AIFC";
+    Ok((input, AiffHeader { size, is_aifc }))
 }
 
-/// 先頭のチャンクを取得する
-pub(super) fn parse_chunk(input: &[u8]) -> IResult<&[u8], Chunk> {
-    let (input, id) = take(4usize)(input)?;
-    let id: ChunkId = id.try_into().unwrap();
+/// 先頭のチャンクのIDとサイズを取得する。返り値のinputはペイロードの先頭を指す
+pub(super) fn parse_chunk_header(input: &[u8]) -> IResult<&[u8], ChunkHeader> {
+    let (input, chunk_id) = take(4usize)(input)?;
+    let id: ChunkId = chunk_id.try_into().unwrap();
+    let fourcc = chunk_id.try_into().unwrap();
     let (input, size) = be_u32(input)?;
-    let (input, data) = take(size)(input)?;
 
-    Ok((input, Chunk { id, size, data }))
+    Ok((input, ChunkHeader { id, fourcc, size }))
+}
+
+/// Find the next known chunk after a corrupt one, whose payload fits in the input.
+/// An SSND chunk may be cut off, as in a truncated file. Returns the input from the chunk header.
+pub(super) fn find_chunk_header(input: &[u8]) -> Option<&[u8]> {
+    (0..input.len()).map(|i| &input[i..]).find(|candidate| {
+        parse_chunk_header(candidate).is_ok_and(|(payload, header)| match header.id {
+            ChunkId::Unknown => false,
+            ChunkId::SoundData => true,
+            _ => header.size as usize <= payload.len(),
+        })
+    })
 }
 
 /// COMMONチャンクのパース
@@ -113,18 +161,32 @@ pub(super) fn parse_comm(input: &[u8]) -> IResult<&[u8], PcmSpecs> {
     let mut audio_format: AudioFormat = AudioFormat::LinearPcmBe;
 
     let (input, num_channels) = be_i16(input)?;
+    // 0 channels is reported by the validation of the specs.
+    if num_channels < 0 {
+        debug!("unsupported AIFF number of channels {}", num_channels);
+        return Err(nom::Err::Error(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Verify,
+        )));
+    }
     let num_channels = num_channels as u16;
     let (input, num_sample_frames) = be_u32(input)?;
     let (input, bit_depth) = be_i16(input)?;
     let mut bit_depth = bit_depth as u16;
     let (input, sample_rate) = take(10usize)(input)?;
-    let sample_rate = extended2double(sample_rate).map_err(nom::Err::from)? as u32;
+    let sample_rate = extended2u32(sample_rate)
+        .inspect_err(|_| debug!("AIFF sample rate out of range"))
+        .map_err(nom::Err::from)?;
 
     if input.len() >= 4 {
         //AIFF-C parameters
         let (_input, compression_type_id) = take(4usize)(input)?;
         let Ok((f, b)) = aifc_compression_type(compression_type_id) else {
             // Unknown compression type
+            debug!(
+                "unsupported AIFF-C compression type {}",
+                crate::diag::fourcc(compression_type_id)
+            );
             return Err(nom::Err::Error(nom::error::Error::new(
                 input,
                 nom::error::ErrorKind::Fail,
@@ -135,7 +197,15 @@ pub(super) fn parse_comm(input: &[u8]) -> IResult<&[u8], PcmSpecs> {
             //bit-depthが指定されている場合は上書き
             bit_depth = b;
         }
+        // 8-bit samples of AIFF are signed in either byte order, and LinearPcmLe is unsigned as in WAV.
+        if audio_format == AudioFormat::LinearPcmLe && bit_depth <= 8 {
+            audio_format = AudioFormat::LinearPcmBe;
+        }
     }
+    debug!(
+        "AIFF COMM -> {:?}, {} bits, {} channels, {} Hz",
+        audio_format, bit_depth, num_channels, sample_rate
+    );
 
     Ok((
         input,
@@ -144,7 +214,10 @@ pub(super) fn parse_comm(input: &[u8]) -> IResult<&[u8], PcmSpecs> {
             num_channels,
             sample_rate,
             bit_depth,
+            // Samples are left-justified in whole bytes.
+            container_bytes_per_sample: bit_depth.div_ceil(8),
             num_samples: num_sample_frames,
+            num_samples_source: NumSamplesSource::CommChunk,
             ..Default::default()
         },
     ))
@@ -154,8 +227,9 @@ pub(super) fn parse_comm(input: &[u8]) -> IResult<&[u8], PcmSpecs> {
 fn aifc_compression_type(compression_type_id: &[u8]) -> Result<(AudioFormat, Option<u16>), ()> {
     let t = match compression_type_id {
         b"NONE" => (AudioFormat::LinearPcmBe, None),
-        b"twos" => (AudioFormat::LinearPcmBe, Some(16)),
-        b"sowt" => (AudioFormat::LinearPcmLe, Some(16)),
+        // The bit depth of COMM is kept, e.g. 24-bit sowt of Logic.
+        b"twos" => (AudioFormat::LinearPcmBe, None),
+        b"sowt" => (AudioFormat::LinearPcmLe, None),
         b"fl32" => (AudioFormat::IeeeFloatBe, Some(32)),
         b"FL32" => (AudioFormat::IeeeFloatBe, Some(32)),
         b"fl64" => (AudioFormat::IeeeFloatBe, Some(64)),
@@ -164,11 +238,62 @@ fn aifc_compression_type(compression_type_id: &[u8]) -> Result<(AudioFormat, Opt
         b"in32" => (AudioFormat::LinearPcmBe, Some(32)),
         b"42ni" => (AudioFormat::LinearPcmLe, Some(24)),
         b"23ni" => (AudioFormat::LinearPcmLe, Some(32)),
+        // sampleSize is typically 16, the size of the expanded samples.
+        b"alaw" | b"ALAW" => (AudioFormat::ALaw, Some(8)),
+        b"ulaw" | b"ULAW" => (AudioFormat::MuLaw, Some(8)),
         _ => return Err(()), //Unknown compression type
     };
     Ok(t)
 }
 
+/// CHANチャンクのパース
+/// The AudioChannelLayout of Core Audio, as a layout tag, a bitmap of the speakers or the descriptions of the channels.
+pub(super) fn parse_chan(input: &[u8]) -> IResult<&[u8], ChannelLayout> {
+    let (input, layout_tag) = be_u32(input)?;
+    let (input, bitmap) = be_u32(input)?;
+    let (mut input, num_descriptions) = be_u32(input)?;
+    let layout = match layout_tag {
+        LAYOUT_TAG_USE_CHANNEL_DESCRIPTIONS => {
+            let mut labels = heapless::Vec::<u32, 8>::new();
+            for _ in 0..num_descriptions {
+                let (rest, label) = be_u32(input)?;
+                // mChannelFlags and mCoordinates
+                let (rest, _) = take(16usize)(rest)?;
+                input = rest;
+                if labels.push(label).is_err() {
+                    return Ok((input, ChannelLayout::Unknown));
+                }
+            }
+            ChannelLayout::from_labels(&labels)
+        }
+        LAYOUT_TAG_USE_CHANNEL_BITMAP => {
+            // The label of the speaker of a bit is the bit plus 1, e.g. 1 of the left speaker for bit 0.
+            let labels: heapless::Vec<u32, 32> = (0..32)
+                .filter(|bit| bitmap & (1 << bit) != 0)
+                .map(|bit| bit + 1)
+                .collect();
+            ChannelLayout::from_labels(&labels)
+        }
+        LAYOUT_TAG_MONO => ChannelLayout::Mono,
+        LAYOUT_TAG_STEREO => ChannelLayout::Stereo,
+        LAYOUT_TAG_QUADRAPHONIC => ChannelLayout::Quad,
+        LAYOUT_TAG_MPEG_5_0_A => ChannelLayout::Surround50,
+        LAYOUT_TAG_MPEG_5_1_A => ChannelLayout::Surround51,
+        LAYOUT_TAG_MPEG_7_1_C => ChannelLayout::Surround71,
+        _ => {
+            debug!("unknown CHAN layout tag {:#x}", layout_tag);
+            ChannelLayout::Unknown
+        }
+    };
+    Ok((input, layout))
+}
+
+/// FVERチャンクのパース
+/// Returns the timestamp of the version of AIFF-C.
+pub(super) fn parse_fver(input: &[u8]) -> IResult<&[u8], u32> {
+    be_u32(input)
+}
+
 // SSNDチャンクのパース
 pub(super) fn parse_ssnd(input: &[u8]) -> IResult<&[u8], SsndBlockInfo> {
     let (input, offset) = be_i32(input)?;
@@ -176,52 +301,192 @@ pub(super) fn parse_ssnd(input: &[u8]) -> IResult<&[u8], SsndBlockInfo> {
     Ok((input, SsndBlockInfo { offset, block_size }))
 }
 
-/// 80 bit floating point value according to the IEEE-754 specification and the Standard Apple Numeric Environment specification:
-/// 1 bit sign, 15 bit exponent, 1 bit normalization indication, 63 bit mantissa
-/// https://stackoverflow.com/a/3949358
-fn extended2double(buffer: &[u8]) -> Result<f64, AiffError> {
-    if buffer.len() != 10 {
-        return Err(AiffError::InvalidBufferLength);
+/// INSTチャンクのパース
+/// The loops of the instrument are None. They are resolved from the returned sustain and release loops.
+pub(super) fn parse_inst(input: &[u8]) -> IResult<&[u8], (AiffInstrument, InstLoop, InstLoop)> {
+    let (input, base_note) = u8(input)?;
+    let (input, detune) = i8(input)?;
+    let (input, low_note) = u8(input)?;
+    let (input, high_note) = u8(input)?;
+    let (input, low_velocity) = u8(input)?;
+    let (input, high_velocity) = u8(input)?;
+    let (input, gain) = be_i16(input)?;
+    let (input, sustain_loop) = parse_inst_loop(input)?;
+    let (input, release_loop) = parse_inst_loop(input)?;
+    let instrument = AiffInstrument {
+        base_note,
+        detune,
+        low_note,
+        high_note,
+        low_velocity,
+        high_velocity,
+        gain,
+        sustain_loop: None,
+        release_loop: None,
+    };
+    Ok((input, (instrument, sustain_loop, release_loop)))
+}
+
+fn parse_inst_loop(input: &[u8]) -> IResult<&[u8], InstLoop> {
+    let (input, play_mode) = be_i16(input)?;
+    let (input, begin) = be_i16(input)?;
+    let (input, end) = be_i16(input)?;
+    Ok((
+        input,
+        InstLoop {
+            play_mode,
+            begin,
+            end,
+        },
+    ))
+}
+
+/// Find the position of a marker of the MARK chunk in sample frames.
+/// Returns None if the marker is not in the chunk or the chunk is broken before it.
+pub(super) fn marker_position(mark: &[u8], id: i16) -> Option<u32> {
+    parse_mark(mark, id).ok()?.1
+}
+
+/// MARKチャンクのパース
+/// Returns the position of the marker with the ID, or None if it is not in the chunk.
+fn parse_mark(input: &[u8], id: i16) -> IResult<&[u8], Option<u32>> {
+    let (mut input, num_markers) = be_u16(input)?;
+    for _ in 0..num_markers {
+        let (rest, marker_id) = be_i16(input)?;
+        let (rest, position) = be_u32(rest)?;
+        if marker_id == id {
+            return Ok((rest, Some(position)));
+        }
+        // markerName is a pstring, whose count byte and text are padded to an even length.
+        let (rest, count) = u8(rest)?;
+        let (rest, _name) = take(count as usize | 1)(rest)?;
+        input = rest;
     }
+    Ok((input, None))
+}
 
-    let sign = if (buffer[0] & 0x80) == 0x00 {
-        1f64
-    } else {
-        -1f64
+/// COMTチャンクのパース
+/// The comments are read up to the count in the chunk, a comment cut off by the end of the chunk and [crate::metadata::MAX_NUM_COMMENTS] without the `alloc` feature.
+pub(super) fn parse_comt(input: &[u8]) -> Comments<'_> {
+    let mut comments = Comments::default();
+    let Some((num_comments, mut input)) = input.split_first_chunk::<2>() else {
+        return comments;
     };
-    let exponent: u32 = ((buffer[0] as u32 & 0x7F) << 8) | buffer[1] as u32;
-    let mut mantissa: u64 = ((buffer[2] as u64) << 56)
-        | ((buffer[3] as u64) << 48)
-        | ((buffer[4] as u64) << 40)
-        | ((buffer[5] as u64) << 32)
-        | ((buffer[6] as u64) << 24)
-        | ((buffer[7] as u64) << 16)
-        | ((buffer[8] as u64) << 8)
-        | (buffer[9] as u64);
-
-    //If the highest bit of the mantissa is set, then this is a normalized number.
-    let normalize_correction = if (mantissa & 0x8000000000000000) != 0x00 {
-        1f64
+    for _ in 0..u16::from_be_bytes(*num_comments) {
+        let Ok((rest, comment)) = parse_comment(input) else {
+            break;
+        };
+        input = rest;
+        if !comments.comments.push(comment) {
+            debug!(
+                "more than {} comments in COMT",
+                crate::metadata::MAX_NUM_COMMENTS
+            );
+            break;
+        }
+    }
+    comments
+}
+
+fn parse_comment(input: &[u8]) -> IResult<&[u8], Comment<'_>> {
+    let (input, timestamp) = be_u32(input)?;
+    let (input, marker_id) = be_i16(input)?;
+    let (input, count) = be_u16(input)?;
+    let (input, text) = take(count)(input)?;
+    // Odd-sized text is followed by a pad byte, which may be missing at the end of the chunk.
+    let input = if count % 2 == 1 {
+        input.get(1..).unwrap_or(input)
     } else {
-        0f64
+        input
+    };
+    Ok((
+        input,
+        Comment {
+            timestamp,
+            marker_id,
+            text,
+        },
+    ))
+}
+
+/// Resolve the markers of a loop of the INST chunk to the first frame and the frame after the loop.
+/// Returns None if the loop is not played or a marker is missing.
+pub(super) fn resolve_loop(inst_loop: &InstLoop, mark: Option<&[u8]>) -> Option<(u32, u32)> {
+    if inst_loop.play_mode == 0 {
+        return None;
+    }
+    let mark = mark?;
+    let begin = marker_position(mark, inst_loop.begin);
+    let end = marker_position(mark, inst_loop.end);
+    if begin.is_none() || end.is_none() {
+        debug!(
+            "INST loop markers {} and {} are not in MARK",
+            inst_loop.begin, inst_loop.end
+        );
+    }
+    Some((begin?, end?))
+}
+
+/// Replace a numSampleFrames of 0, e.g. left by a streaming recorder, with the number of frames in the sound data.
+/// A non-zero value of COMM is kept. Specs which cannot be counted are reported by the validation of the specs.
+pub(super) fn count_missing_frames(specs: &mut PcmSpecs, data_size: u64) {
+    if specs.num_samples != 0 {
+        return;
+    }
+    if let Ok(num_samples) = crate::wav::calc_num_samples_per_channel(data_size, specs) {
+        debug!("numSampleFrames is 0, {} frames in SSND", num_samples);
+        specs.num_samples = num_samples;
+        specs.num_samples_source = NumSamplesSource::DataSize;
+    }
+}
+
+/// Convert an 80 bit floating point value to an integer, e.g. the sample rate.
+/// The fraction is truncated. Negative values become 0 and values too large for u32 become u32::MAX.
+/// Computed in integer arithmetic, so no floating point routines are needed.
+///
+/// 80 bit floating point value according to the IEEE-754 specification and the Standard Apple Numeric Environment specification:
+/// 1 bit sign, 15 bit exponent, 1 bit normalization indication, 63 bit mantissa
+/// https://stackoverflow.com/a/3949358
+fn extended2u32(buffer: &[u8]) -> Result<u32, AiffError> {
+    let Ok(buffer): Result<&[u8; 10], _> = buffer.try_into() else {
+        return Err(AiffError::InvalidBufferLength);
     };
-    mantissa &= 0x7FFFFFFFFFFFFFFF;
 
-    //value = (-1) ^ s * (normalizeCorrection + m / 2 ^ 63) * 2 ^ (e - 16383)
-    Ok(sign
-        * (normalize_correction + mantissa as f64 / (1u64 << 63) as f64)
-        * (1u64 << (exponent as i32 - 16383)) as f64)
+    if (buffer[0] & 0x80) != 0x00 {
+        return Ok(0);
+    }
+    let exponent = ((buffer[0] as u32 & 0x7F) << 8) | buffer[1] as u32;
+    // The mantissa includes the normalization bit as its highest bit.
+    let mantissa = u64::from_be_bytes(buffer[2..].try_into().unwrap());
+
+    //value = m / 2 ^ 63 * 2 ^ (e - 16383)
+    match exponent.checked_sub(16383) {
+        None => Ok(0),
+        Some(e) if e < 32 => Ok((mantissa >> (63 - e)) as u32),
+        Some(_) => Ok(u32::MAX),
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{extended2double, ChunkId};
-    use approx::assert_relative_eq;
+    use super::{extended2u32, ChunkId};
 
     #[test]
-    fn extended2double_test() {
+    fn extended2u32_test() {
         let array: [u8; 10] = [64, 14, 187, 128, 0, 0, 0, 0, 0, 0];
-        assert_relative_eq!(extended2double(&array).unwrap(), 48000.0f64);
+        assert_eq!(extended2u32(&array).unwrap(), 48000);
+        let array: [u8; 10] = [64, 14, 172, 68, 0, 0, 0, 0, 0, 0];
+        assert_eq!(extended2u32(&array).unwrap(), 44100);
+        // 0.5
+        let array: [u8; 10] = [63, 254, 128, 0, 0, 0, 0, 0, 0, 0];
+        assert_eq!(extended2u32(&array).unwrap(), 0);
+        // 2^32
+        let array: [u8; 10] = [64, 31, 128, 0, 0, 0, 0, 0, 0, 0];
+        assert_eq!(extended2u32(&array).unwrap(), u32::MAX);
+        // -48000
+        let array: [u8; 10] = [192, 14, 187, 128, 0, 0, 0, 0, 0, 0];
+        assert_eq!(extended2u32(&array).unwrap(), 0);
+        assert!(extended2u32(&array[..9]).is_err());
     }
 
     #[test]
@@ -0,0 +1,183 @@
+//! Core Audio Format of Apple.
+//! The file header is followed by chunks with big endian 64-bit sizes and no padding.
+//! The desc chunk describes the format, and the data chunk starts with an edit count.
+
+use crate::{AudioFormat, NumSamplesSource, PcmSpecs};
+use nom::bytes::complete::{tag, take};
+use nom::number::complete::{be_i64, be_u16, be_u32, be_u64};
+use nom::IResult;
+
+/// mFormatFlags of linear PCM
+const FORMAT_FLAG_IS_FLOAT: u32 = 1 << 0;
+const FORMAT_FLAG_IS_LITTLE_ENDIAN: u32 = 1 << 1;
+
+/// CAFのchunkの種類
+#[derive(Debug, PartialEq)]
+pub(super) enum ChunkId {
+    Desc, // b"desc" Audio description
+    Data, // b"data" Audio data
+    Unknown,
+}
+
+/// Header of a chunk.
+#[derive(Debug)]
+pub(super) struct ChunkHeader {
+    pub id: ChunkId,
+    pub fourcc: [u8; 4],
+    /// Size of the payload in bytes. None for a data chunk of size -1, which extends to the end of the file.
+    pub size: Option<u64>,
+}
+
+/// The desc chunk.
+/// * 'specs' - The specs except the number of samples.
+/// * 'bytes_per_packet' - mBytesPerPacket. Bytes per frame of linear PCM.
+/// * 'frames_per_packet' - mFramesPerPacket. 1 for linear PCM.
+#[derive(Debug)]
+pub(super) struct Desc {
+    pub specs: PcmSpecs,
+    pub bytes_per_packet: u32,
+    pub frames_per_packet: u32,
+}
+
+/// Check that the file starts with caff and the version is 1.
+pub(super) fn parse_caf_header(input: &[u8]) -> IResult<&[u8], ()> {
+    let (input, _) = tag(b"caff")(input)?;
+    let (input, _) = tag(&[0x00, 0x01][..])(input)?;
+    let (input, _flags) = be_u16(input)?;
+    Ok((input, ()))
+}
+
+/// 先頭のチャンクのIDとサイズを取得する。返り値のinputはペイロードの先頭を指す
+/// Negative sizes other than -1 of the data chunk are an error.
+pub(super) fn parse_chunk_header(input: &[u8]) -> IResult<&[u8], ChunkHeader> {
+    let (input, chunk_type) = take(4usize)(input)?;
+    let fourcc = [chunk_type[0], chunk_type[1], chunk_type[2], chunk_type[3]];
+    let id = match &fourcc {
+        b"desc" => ChunkId::Desc,
+        b"data" => ChunkId::Data,
+        _ => ChunkId::Unknown,
+    };
+    let (rest, size) = be_i64(input)?;
+    let size = match u64::try_from(size) {
+        Ok(size) => Some(size),
+        Err(_) if size == -1 && id == ChunkId::Data => None,
+        Err(_) => {
+            return Err(nom::Err::Error(nom::error::Error::new(
+                input,
+                nom::error::ErrorKind::Verify,
+            )))
+        }
+    };
+    Ok((rest, ChunkHeader { id, fourcc, size }))
+}
+
+/// descチャンクのパース
+/// Linear PCM and IEEE float are supported. Other formats, e.g. AAC, are an error.
+pub(super) fn parse_desc(input: &[u8]) -> IResult<&[u8], Desc> {
+    let (input, sample_rate) = be_u64(input)?;
+    let sample_rate = f64_bits_to_u32(sample_rate);
+    let (input, format_id) = take(4usize)(input)?;
+    let (input, format_flags) = be_u32(input)?;
+    let (input, bytes_per_packet) = be_u32(input)?;
+    let (input, frames_per_packet) = be_u32(input)?;
+    let (input, num_channels) = be_u32(input)?;
+    let (input, bit_depth) = be_u32(input)?;
+
+    if format_id != b"lpcm" {
+        debug!("unsupported CAF format {}", crate::diag::fourcc(format_id));
+        return Err(nom::Err::Error(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Fail,
+        )));
+    }
+    let is_float = format_flags & FORMAT_FLAG_IS_FLOAT != 0;
+    let is_little_endian = format_flags & FORMAT_FLAG_IS_LITTLE_ENDIAN != 0;
+    let audio_format = match (is_float, is_little_endian) {
+        (false, true) => AudioFormat::LinearPcmLe,
+        (false, false) => AudioFormat::LinearPcmBe,
+        (true, true) => AudioFormat::IeeeFloatLe,
+        (true, false) => AudioFormat::IeeeFloatBe,
+    };
+    // Out of range values are reported by the validation of the specs.
+    let num_channels = u16::try_from(num_channels).unwrap_or(0);
+    let bit_depth = u16::try_from(bit_depth).unwrap_or(0);
+    debug!(
+        "CAF desc -> {:?}, {} bits, {} channels, {} Hz",
+        audio_format, bit_depth, num_channels, sample_rate
+    );
+
+    Ok((
+        input,
+        Desc {
+            specs: PcmSpecs {
+                audio_format,
+                num_channels,
+                sample_rate,
+                bit_depth,
+                container_bytes_per_sample: bit_depth.div_ceil(8),
+                num_samples_source: NumSamplesSource::DataSize,
+                ..Default::default()
+            },
+            bytes_per_packet,
+            frames_per_packet,
+        },
+    ))
+}
+
+/// dataチャンクのパース
+/// Returns the edit count. The rest of the input is the audio data.
+pub(super) fn parse_data(input: &[u8]) -> IResult<&[u8], u32> {
+    be_u32(input)
+}
+
+/// Convert the bits of a 64 bit floating point value to an integer, e.g. the sample rate.
+/// The fraction is truncated. Negative values become 0 and values too large for u32, infinity and NaN become u32::MAX.
+/// Computed in integer arithmetic, so no floating point routines are needed.
+fn f64_bits_to_u32(bits: u64) -> u32 {
+    if bits >> 63 != 0 {
+        return 0;
+    }
+    let exponent = (bits >> 52) as u32;
+    // The mantissa with the implicit leading bit
+    let mantissa = (bits & ((1 << 52) - 1)) | (1 << 52);
+
+    //value = m / 2 ^ 52 * 2 ^ (e - 1023)
+    match exponent.checked_sub(1023) {
+        None => 0,
+        Some(e) if e < 32 => (mantissa >> (52 - e)) as u32,
+        Some(_) => u32::MAX,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{f64_bits_to_u32, parse_chunk_header, ChunkId};
+
+    #[test]
+    fn f64_bits_to_u32_test() {
+        assert_eq!(f64_bits_to_u32(48000f64.to_bits()), 48000);
+        assert_eq!(f64_bits_to_u32(44100f64.to_bits()), 44100);
+        assert_eq!(f64_bits_to_u32(44100.9f64.to_bits()), 44100);
+        assert_eq!(f64_bits_to_u32(1f64.to_bits()), 1);
+        assert_eq!(f64_bits_to_u32(0.5f64.to_bits()), 0);
+        assert_eq!(f64_bits_to_u32(0f64.to_bits()), 0);
+        assert_eq!(f64_bits_to_u32((-48000f64).to_bits()), 0);
+        assert_eq!(f64_bits_to_u32(4294967295f64.to_bits()), u32::MAX);
+        assert_eq!(f64_bits_to_u32(4294967296f64.to_bits()), u32::MAX);
+        assert_eq!(f64_bits_to_u32(f64::INFINITY.to_bits()), u32::MAX);
+    }
+
+    #[test]
+    fn chunk_size() {
+        let (_, header) = parse_chunk_header(b"desc\0\0\0\0\0\0\0\x20").unwrap();
+        assert_eq!(header.id, ChunkId::Desc);
+        assert_eq!(header.size, Some(32));
+
+        // Only the data chunk may extend to the end of the file.
+        let (_, header) = parse_chunk_header(b"data\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF").unwrap();
+        assert_eq!(header.id, ChunkId::Data);
+        assert_eq!(header.size, None);
+        assert!(parse_chunk_header(b"free\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF").is_err());
+        assert!(parse_chunk_header(b"data\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFE").is_err());
+    }
+}
@@ -0,0 +1,271 @@
+//! Playback of a player through [cpal].
+//!
+//! [play] starts an output stream which pulls frames from a [PcmPlayer], an [ImaAdpcmPlayer] or a [MsAdpcmPlayer].
+//! Output channel `ch` takes source channel `ch % num_channels`, so a mono file is played on all outputs
+//! and a stereo file alternates left and right.
+//! The samples are converted to the sample format of the device.
+//!
+//! This module requires the `cpal` feature, which enables `std`.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use cpal::traits::{DeviceTrait, HostTrait};
+//! use pacmog::{cpal_adapter, PcmPlayer, PcmReader};
+//!
+//! let wav = include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_16.wav");
+//! let player = PcmPlayer::new(PcmReader::new(wav).unwrap());
+//!
+//! let device = cpal::default_host().default_output_device().unwrap();
+//! let config = device.default_output_config().unwrap();
+//! // Playback stops when the stream is dropped.
+//! let (_stream, completion) = cpal_adapter::play(player, &device, &config).unwrap();
+//! completion.wait().unwrap();
+//! ```
+
+use crate::imaadpcm::{ImaAdpcmError, ImaAdpcmPlayer, I1F15};
+use crate::msadpcm::{MsAdpcmError, MsAdpcmPlayer};
+use crate::{PcmPlayer, PcmPlayerError};
+use cpal::traits::{DeviceTrait, StreamTrait};
+use cpal::{FromSample, SizedSample};
+use std::sync::mpsc;
+use std::vec::Vec;
+
+/// A player which can be played by [play].
+pub trait FrameSource: Send + 'static {
+    type Error: core::fmt::Debug + core::fmt::Display + Send + 'static;
+
+    /// Number of channels of the frames written by [FrameSource::fill].
+    fn num_channels(&self) -> u16;
+
+    /// Fill the buffer with the next interleaved frames.
+    ///
+    /// Returns the number of frames written. It is less than the buffer holds only when playback finishes.
+    fn fill(&mut self, out: &mut [f32]) -> Result<usize, Self::Error>;
+}
+
+impl<const MAX_CHUNKS: usize> FrameSource for PcmPlayer<'static, MAX_CHUNKS> {
+    type Error = PcmPlayerError;
+
+    fn num_channels(&self) -> u16 {
+        self.specs().num_channels
+    }
+
+    fn fill(&mut self, out: &mut [f32]) -> Result<usize, Self::Error> {
+        match self.fill_interleaved(out) {
+            Err(PcmPlayerError::FinishPlaying) => Ok(0),
+            result => result,
+        }
+    }
+}
+
+impl<const MAX_CHUNKS: usize> FrameSource for ImaAdpcmPlayer<'static, MAX_CHUNKS> {
+    type Error = ImaAdpcmError;
+
+    fn num_channels(&self) -> u16 {
+        self.specs().num_channels
+    }
+
+    fn fill(&mut self, out: &mut [f32]) -> Result<usize, Self::Error> {
+        let num_channels = self.num_channels() as usize;
+        let mut frame = [I1F15::ZERO; 2];
+        let mut filled = 0;
+        for samples in out.chunks_exact_mut(num_channels) {
+            match self.get_next_frame(&mut frame) {
+                Ok(()) => {}
+                Err(ImaAdpcmError::FinishPlaying) => break,
+                Err(e) => return Err(e),
+            }
+            for (sample, value) in samples.iter_mut().zip(frame) {
+                *sample = value.to_num::<f32>();
+            }
+            filled += 1;
+        }
+        Ok(filled)
+    }
+}
+
+impl<const MAX_CHUNKS: usize> FrameSource for MsAdpcmPlayer<'static, MAX_CHUNKS> {
+    type Error = MsAdpcmError;
+
+    fn num_channels(&self) -> u16 {
+        self.specs().num_channels
+    }
+
+    fn fill(&mut self, out: &mut [f32]) -> Result<usize, Self::Error> {
+        let num_channels = self.num_channels() as usize;
+        let mut frame = [I1F15::ZERO; 2];
+        let mut filled = 0;
+        for samples in out.chunks_exact_mut(num_channels) {
+            match self.get_next_frame(&mut frame) {
+                Ok(()) => {}
+                Err(MsAdpcmError::FinishPlaying) => break,
+                Err(e) => return Err(e),
+            }
+            for (sample, value) in samples.iter_mut().zip(frame) {
+                *sample = value.to_num::<f32>();
+            }
+            filled += 1;
+        }
+        Ok(filled)
+    }
+}
+
+/// Error type for [play].
+#[derive(Debug, thiserror::Error)]
+pub enum PlayError {
+    #[error("The stream cannot be built")]
+    Build(#[from] cpal::BuildStreamError),
+    #[error("The stream cannot be started")]
+    Play(#[from] cpal::PlayStreamError),
+}
+
+/// Error which stopped playback, reported by [Completion].
+#[derive(Debug, thiserror::Error)]
+pub enum PlaybackError<E: core::fmt::Debug> {
+    #[error("Player error: {0:?}")]
+    Player(E),
+    #[error("Stream error")]
+    Stream(#[from] cpal::StreamError),
+}
+
+/// Notified when the stream started by [play] runs out of frames or fails.
+pub struct Completion<E: core::fmt::Debug> {
+    rx: mpsc::Receiver<Result<(), PlaybackError<E>>>,
+}
+
+impl<E: core::fmt::Debug> Completion<E> {
+    /// Block until playback finishes.
+    /// Returns the error of the player or the first error of the stream if it stopped playback.
+    pub fn wait(&self) -> Result<(), PlaybackError<E>> {
+        // The senders are only dropped together with the stream, which cannot finish without sending.
+        self.rx.recv().unwrap_or(Ok(()))
+    }
+
+    /// Return the result if playback has finished, None otherwise.
+    pub fn try_wait(&self) -> Option<Result<(), PlaybackError<E>>> {
+        self.rx.try_recv().ok()
+    }
+}
+
+/// Build and start an output stream which plays the player on the device.
+/// Playback stops when the returned stream is dropped.
+/// After the player finishes, the stream outputs silence and the [Completion] is notified.
+/// * 'player' - [PcmPlayer], [ImaAdpcmPlayer] or [MsAdpcmPlayer] over `'static` data.
+/// * 'config' - Output config of the device. f32, i16 and u16 samples are supported.
+pub fn play<S: FrameSource>(
+    player: S,
+    device: &cpal::Device,
+    config: &cpal::SupportedStreamConfig,
+) -> Result<(cpal::Stream, Completion<S::Error>), PlayError> {
+    let (tx, rx) = mpsc::sync_channel(1);
+    let stream_config = config.config();
+    let stream = match config.sample_format() {
+        cpal::SampleFormat::F32 => build::<f32, S>(device, &stream_config, player, tx)?,
+        cpal::SampleFormat::I16 => build::<i16, S>(device, &stream_config, player, tx)?,
+        cpal::SampleFormat::U16 => build::<u16, S>(device, &stream_config, player, tx)?,
+        _ => return Err(cpal::BuildStreamError::StreamConfigNotSupported.into()),
+    };
+    stream.play()?;
+    Ok((stream, Completion { rx }))
+}
+
+fn build<T, S>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    mut player: S,
+    tx: mpsc::SyncSender<Result<(), PlaybackError<S::Error>>>,
+) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: SizedSample + FromSample<f32>,
+    S: FrameSource,
+{
+    let out_channels = config.channels as usize;
+    let num_channels = player.num_channels() as usize;
+    if out_channels == 0 || num_channels == 0 {
+        return Err(cpal::BuildStreamError::StreamConfigNotSupported);
+    }
+    let mut scratch = Vec::new();
+    let mut finished = false;
+    let error_tx = tx.clone();
+
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+            let num_frames = data.len() / out_channels;
+            if num_frames == 0 {
+                return;
+            }
+            // Only grows until it reaches the largest callback size.
+            scratch.resize(num_frames * num_channels, 0f32);
+            let filled = if finished {
+                0
+            } else {
+                match player.fill(&mut scratch) {
+                    Ok(n) => n,
+                    Err(e) => {
+                        finished = true;
+                        let _ = tx.try_send(Err(PlaybackError::Player(e)));
+                        0
+                    }
+                }
+            };
+            if filled < num_frames && !finished {
+                finished = true;
+                let _ = tx.try_send(Ok(()));
+            }
+
+            for (i, frame) in data.chunks_mut(out_channels).enumerate() {
+                if i < filled {
+                    let source = &scratch[i * num_channels..(i + 1) * num_channels];
+                    for (ch, sample) in frame.iter_mut().enumerate() {
+                        *sample = T::from_sample(source[ch % num_channels]);
+                    }
+                } else {
+                    frame.fill(T::EQUILIBRIUM);
+                }
+            }
+        },
+        // The channel holds one result, so an error while it is full is dropped.
+        move |err| {
+            let _ = error_tx.try_send(Err(err.into()));
+        },
+        None,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FrameSource;
+    use crate::imaadpcm::ImaAdpcmPlayer;
+    use crate::msadpcm::MsAdpcmPlayer;
+    use crate::{PcmPlayer, PcmReader};
+
+    /// All players report the end of playback as a short fill, then 0.
+    #[test]
+    fn fill_until_end() {
+        let wav = include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_16.wav");
+        let mut pcm = PcmPlayer::new(PcmReader::new(wav).unwrap());
+        let adpcm = include_bytes!("../tests/resources/Sine440Hz_2ch_48000Hz_4bit_IMAADPCM.wav");
+        let mut adpcm = ImaAdpcmPlayer::new(adpcm).unwrap();
+        let ms_adpcm = include_bytes!("../tests/resources/Sine440Hz_2ch_48000Hz_4bit_MSADPCM.wav");
+        let mut ms_adpcm = MsAdpcmPlayer::new(ms_adpcm).unwrap();
+
+        fn count<S: FrameSource>(source: &mut S) -> usize {
+            let mut buffer = [0f32; 1000];
+            let frames = buffer.len() / source.num_channels() as usize;
+            let mut total = 0;
+            loop {
+                let n = source.fill(&mut buffer).unwrap();
+                total += n;
+                if n < frames {
+                    assert_eq!(source.fill(&mut buffer).unwrap(), 0);
+                    return total;
+                }
+            }
+        }
+        assert_eq!(count(&mut pcm), pcm.specs().num_samples as usize);
+        assert_eq!(count(&mut adpcm), adpcm.specs().num_samples as usize);
+        assert_eq!(count(&mut ms_adpcm), ms_adpcm.specs().num_samples as usize);
+    }
+}
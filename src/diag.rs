@@ -0,0 +1,125 @@
+//! Parse-time diagnostics through the `log` and `defmt` features.
+//!
+//! The macros forward to `log` and/or `defmt`. Without either feature they expand to nothing, and the arguments are not evaluated.
+//! The format strings must be valid for both crates, so only `{}`, `{:?}` and `{:#x}` are used.
+
+macro_rules! debug {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "log")]
+        ::log::debug!($($arg)*);
+        #[cfg(feature = "defmt")]
+        ::defmt::debug!($($arg)*);
+    }};
+}
+
+macro_rules! trace {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "log")]
+        ::log::trace!($($arg)*);
+        #[cfg(feature = "defmt")]
+        ::defmt::trace!($($arg)*);
+    }};
+}
+
+/// Chunk ID as text, or "????" if it is not UTF-8.
+#[cfg(any(feature = "log", feature = "defmt"))]
+pub(crate) fn fourcc(id: &[u8]) -> &str {
+    core::str::from_utf8(id).unwrap_or("????")
+}
+
+#[cfg(all(test, feature = "log"))]
+mod tests {
+    use crate::PcmReader;
+    use std::cell::RefCell;
+    use std::string::{String, ToString};
+    use std::vec::Vec;
+
+    std::thread_local! {
+        /// Messages of the current thread, so that tests running in parallel do not see each other's messages.
+        static MESSAGES: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    }
+
+    struct TestLogger;
+
+    impl log::Log for TestLogger {
+        fn enabled(&self, _metadata: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            MESSAGES.with(|m| m.borrow_mut().push(record.args().to_string()));
+        }
+
+        fn flush(&self) {}
+    }
+
+    static LOGGER: TestLogger = TestLogger;
+
+    /// Parse the file and return the messages.
+    fn messages_of(file: &[u8]) -> Vec<String> {
+        // Only the first test sets the logger.
+        let _ = log::set_logger(&LOGGER);
+        log::set_max_level(log::LevelFilter::Trace);
+        MESSAGES.with(|m| m.borrow_mut().clear());
+        let _ = PcmReader::new(file);
+        MESSAGES.with(|m| m.take())
+    }
+
+    fn assert_contains(messages: &[String], expected: &str) {
+        assert!(
+            messages.iter().any(|m| m.contains(expected)),
+            "{expected:?} not in {messages:#?}"
+        );
+    }
+
+    #[test]
+    fn malformed_fixtures() {
+        let wav = include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_16.wav");
+
+        // Unknown format tag
+        let mut file = wav.to_vec();
+        let fmt = file.windows(4).position(|w| w == b"fmt ").unwrap();
+        file[fmt + 8..fmt + 10].copy_from_slice(&0x55u16.to_le_bytes());
+        let messages = messages_of(&file);
+        assert_contains(&messages, "chunk fmt  size 16");
+        assert_contains(&messages, "unsupported WAV format tag 0x55");
+        assert_contains(&messages, "WAV rejected");
+
+        // RIFF size which does not match the file
+        let mut file = wav.to_vec();
+        file[4] ^= 1;
+        let messages = messages_of(&file);
+        assert_contains(&messages, "file length");
+
+        // The size of the data chunk exceeds the file.
+        let messages = messages_of(&wav[..1000]);
+        assert_contains(&messages, "file length");
+        let mut file = wav[..1000].to_vec();
+        let riff_size = file.len() as u32 - 8;
+        file[4..8].copy_from_slice(&riff_size.to_le_bytes());
+        let messages = messages_of(&file);
+        assert_contains(&messages, "chunk data size 480000 exceeds the file");
+
+        // AIFF with a negative number of channels
+        let aiff = include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_16.aif");
+        let mut file = aiff.to_vec();
+        let comm = file.windows(4).position(|w| w == b"COMM").unwrap();
+        file[comm + 8..comm + 10].copy_from_slice(&[0xFF, 0xFF]);
+        let messages = messages_of(&file);
+        assert_contains(&messages, "chunk COMM");
+        assert_contains(&messages, "AIFF number of channels -1");
+        assert_contains(&messages, "AIFF rejected");
+
+        // AIFF without channels
+        file[comm + 8..comm + 10].copy_from_slice(&[0, 0]);
+        let messages = messages_of(&file);
+        assert_contains(&messages, "inconsistent specs: NumChannels");
+
+        // Valid files report the selected format.
+        let messages = messages_of(wav);
+        assert_contains(
+            &messages,
+            "WAV format tag 0x1 -> LinearPcmLe, 16 bits, 1 channels",
+        );
+    }
+}
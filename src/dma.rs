@@ -0,0 +1,292 @@
+//! Double buffering for DMA, e.g. to feed I2S.
+//!
+//! [PingPongBuffer] owns two buffers. The DMA reads the active one while the main loop fills the inactive one.
+//! When the DMA completes, the interrupt handler swaps them.
+//!
+//! # Examples
+//!
+//! ```
+//! use pacmog::dma::PingPongBuffer;
+//! use pacmog::{PcmPlayer, PcmReader};
+//!
+//! let wav = include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_16.wav");
+//! let mut player = PcmPlayer::new(PcmReader::new(wav).unwrap());
+//! let mut buffer = PingPongBuffer::<256, i16>::new();
+//!
+//! // Main loop
+//! buffer.fill_inactive(&mut player).unwrap();
+//! // DMA complete interrupt
+//! buffer.swap();
+//! let samples = buffer.active(); // Start the next DMA transfer with these samples.
+//! assert_eq!(buffer.underruns(), 0);
+//! ```
+
+use crate::{PcmPlayer, PcmPlayerError};
+
+/// Sample type of [PingPongBuffer].
+pub trait DmaSample: Copy {
+    /// Value of silence.
+    const SILENCE: Self;
+
+    /// Fill the buffer with the next interleaved frames of the player and the rest with silence.
+    /// Returns the number of frames taken from the player. 0 when playback has finished.
+    fn fill<const MAX_CHUNKS: usize>(
+        player: &mut PcmPlayer<'_, MAX_CHUNKS>,
+        out: &mut [Self],
+    ) -> Result<usize, PcmPlayerError>;
+}
+
+impl DmaSample for i16 {
+    const SILENCE: Self = 0;
+
+    fn fill<const MAX_CHUNKS: usize>(
+        player: &mut PcmPlayer<'_, MAX_CHUNKS>,
+        out: &mut [Self],
+    ) -> Result<usize, PcmPlayerError> {
+        player.refill_half_buffer(out)
+    }
+}
+
+impl DmaSample for f32 {
+    const SILENCE: Self = 0.0;
+
+    fn fill<const MAX_CHUNKS: usize>(
+        player: &mut PcmPlayer<'_, MAX_CHUNKS>,
+        out: &mut [Self],
+    ) -> Result<usize, PcmPlayerError> {
+        let num_frames = match player.fill_interleaved(out) {
+            Ok(n) => n,
+            Err(PcmPlayerError::FinishPlaying) => 0,
+            Err(e) => return Err(e),
+        };
+        let num_channels = player.specs().num_channels as usize;
+        out[num_frames * num_channels..].fill(0.0);
+        Ok(num_frames)
+    }
+}
+
+/// Two buffers of N interleaved samples, i.e. N / (number of channels) frames each.
+///
+/// [PingPongBuffer::fill_inactive] is called from the main loop, and [PingPongBuffer::swap] and [PingPongBuffer::active]
+/// from the DMA complete interrupt. Share it between them with a critical section, e.g. a `Mutex<RefCell<_>>`.
+/// If the DMA completes before the inactive buffer is filled, the swap counts as an underrun and silence is played instead.
+pub struct PingPongBuffer<const N: usize, T: DmaSample> {
+    buffers: [[T; N]; 2],
+    /// Index of the buffer which the DMA reads.
+    active: usize,
+    /// The inactive buffer has been filled since the last swap.
+    ready: bool,
+    underruns: u32,
+}
+
+impl<const N: usize, T: DmaSample> PingPongBuffer<N, T> {
+    /// Create a new PingPongBuffer. Both buffers are silent and the inactive one needs to be filled.
+    pub const fn new() -> Self {
+        PingPongBuffer {
+            buffers: [[T::SILENCE; N]; 2],
+            active: 0,
+            ready: false,
+            underruns: 0,
+        }
+    }
+
+    /// Fill the inactive buffer with the next frames of the player, padded with silence at the end of playback.
+    /// Does nothing if it has already been filled since the last swap.
+    ///
+    /// Returns the number of frames taken from the player.
+    pub fn fill_inactive<const MAX_CHUNKS: usize>(
+        &mut self,
+        player: &mut PcmPlayer<'_, MAX_CHUNKS>,
+    ) -> Result<usize, PcmPlayerError> {
+        if self.ready {
+            return Ok(0);
+        }
+        let num_frames = T::fill(player, &mut self.buffers[self.active ^ 1])?;
+        self.ready = true;
+        Ok(num_frames)
+    }
+
+    /// Fill the inactive buffer by a function and the rest with silence.
+    /// Does nothing if it has already been filled since the last swap.
+    ///
+    /// Use it for sources other than [PcmPlayer], e.g. a [crate::stream::StreamingPlayer] reading an SD card.
+    /// Read the frames into a buffer of the main loop first and copy them here,
+    /// so that the critical section shared with the interrupt is not held while the storage is read.
+    /// * 'num_channels' - Number of channels of the frames.
+    /// * 'fill' - Writes interleaved frames to the buffer and returns the number of frames written.
+    ///
+    /// Returns the number of frames written by the function.
+    pub fn fill_inactive_with<E>(
+        &mut self,
+        num_channels: usize,
+        fill: impl FnOnce(&mut [T]) -> Result<usize, E>,
+    ) -> Result<usize, E> {
+        if self.ready {
+            return Ok(0);
+        }
+        let buffer = &mut self.buffers[self.active ^ 1];
+        let num_frames = fill(buffer)?;
+        let filled = (num_frames * num_channels).min(N);
+        buffer[filled..].fill(T::SILENCE);
+        self.ready = true;
+        Ok(num_frames)
+    }
+
+    /// Make the inactive buffer active. Call it when the DMA has completed the active buffer.
+    /// If the inactive buffer has not been filled, it is silenced and an underrun is counted.
+    pub fn swap(&mut self) {
+        self.active ^= 1;
+        if !self.ready {
+            self.buffers[self.active].fill(T::SILENCE);
+            self.underruns = self.underruns.saturating_add(1);
+        }
+        self.ready = false;
+    }
+
+    /// Returns the buffer for the DMA.
+    pub fn active(&self) -> &[T] {
+        &self.buffers[self.active]
+    }
+
+    /// Returns true if the inactive buffer needs to be filled.
+    pub fn needs_fill(&self) -> bool {
+        !self.ready
+    }
+
+    /// Returns the number of swaps which were requested before the inactive buffer was filled.
+    pub fn underruns(&self) -> u32 {
+        self.underruns
+    }
+}
+
+impl<const N: usize, T: DmaSample> Default for PingPongBuffer<N, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PingPongBuffer;
+    use crate::stream::{BlockSource, StreamError, StreamingPlayer};
+    use crate::{PcmPlayer, PcmPlayerError, PcmReader};
+
+    const WAV: &[u8] = include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_16.wav");
+
+    #[test]
+    fn fill_and_swap() {
+        let reader = PcmReader::new(WAV).unwrap();
+        let mut player = PcmPlayer::new(reader.clone());
+        let mut buffer = PingPongBuffer::<64, f32>::new();
+
+        // Prefill, then alternate as the main loop and the interrupt would.
+        assert_eq!(buffer.fill_inactive(&mut player).unwrap(), 64);
+        // Filling twice before a swap does not skip frames.
+        assert_eq!(buffer.fill_inactive(&mut player).unwrap(), 0);
+        for block in 0..10u32 {
+            buffer.swap();
+            for (i, sample) in buffer.active().iter().enumerate() {
+                assert_eq!(
+                    *sample,
+                    reader.read_sample(0, block * 64 + i as u32).unwrap()
+                );
+            }
+            assert!(buffer.needs_fill());
+            assert_eq!(buffer.fill_inactive(&mut player).unwrap(), 64);
+        }
+        assert_eq!(buffer.underruns(), 0);
+    }
+
+    #[test]
+    fn underrun() {
+        let reader = PcmReader::new(WAV).unwrap();
+        let mut player = PcmPlayer::new(reader.clone());
+        let mut buffer = PingPongBuffer::<64, i16>::new();
+        buffer.fill_inactive(&mut player).unwrap();
+        buffer.swap();
+        buffer.fill_inactive(&mut player).unwrap();
+        buffer.swap();
+        // The DMA completes again before the main loop fills the buffer.
+        buffer.swap();
+        assert_eq!(buffer.underruns(), 1);
+        assert!(buffer.active().iter().all(|s| *s == 0));
+        buffer.swap();
+        assert_eq!(buffer.underruns(), 2);
+
+        // Playback continues where it stopped.
+        buffer.fill_inactive(&mut player).unwrap();
+        buffer.swap();
+        let expected = (reader.read_sample(0, 128).unwrap() * 32768.0) as i16;
+        assert_eq!(buffer.active()[0], expected);
+    }
+
+    /// Reads at most up to the next 512-byte sector boundary, like a file on an SD card.
+    struct SectorSource(&'static [u8]);
+
+    impl BlockSource for SectorSource {
+        type Error = core::convert::Infallible;
+
+        fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            let len = buf.len().min(512 - (offset % 512) as usize);
+            self.0.read_at(offset, &mut buf[..len])
+        }
+    }
+
+    #[test]
+    fn fill_with_streaming_player() {
+        let reader = PcmReader::new(WAV).unwrap();
+        let num_samples = reader.specs().num_samples;
+        let mut scratch = [0u8; 512];
+        let mut player = StreamingPlayer::new(SectorSource(WAV), &mut scratch).unwrap();
+        player.set_position(num_samples - 100).unwrap();
+        let mut buffer = PingPongBuffer::<64, f32>::new();
+
+        // The main loop reads the frames, then copies them in the critical section.
+        let mut frames = [0f32; 64];
+        let mut position = num_samples - 100;
+        for expected_frames in [64, 36, 0] {
+            assert!(buffer.needs_fill());
+            let n = match player.fill_interleaved(&mut frames) {
+                Err(StreamError::Player(PcmPlayerError::FinishPlaying)) => 0,
+                result => result.unwrap(),
+            };
+            let filled = buffer
+                .fill_inactive_with(1, |out| {
+                    out[..n].copy_from_slice(&frames[..n]);
+                    Ok::<_, core::convert::Infallible>(n)
+                })
+                .unwrap();
+            assert_eq!(filled, expected_frames);
+            buffer.swap();
+            for (i, sample) in buffer.active().iter().enumerate() {
+                if i < n {
+                    assert_eq!(*sample, reader.read_sample(0, position + i as u32).unwrap());
+                } else {
+                    assert_eq!(*sample, 0.0);
+                }
+            }
+            position += n as u32;
+        }
+        assert_eq!(buffer.underruns(), 0);
+
+        // The function is not called until the next swap.
+        buffer.fill_inactive_with(1, |_| Ok::<_, ()>(0)).unwrap();
+        assert_eq!(buffer.fill_inactive_with(1, |_| Err(())), Ok(0));
+    }
+
+    #[test]
+    fn end_of_playback() {
+        let reader = PcmReader::new(WAV).unwrap();
+        let num_samples = reader.specs().num_samples;
+        let mut player = PcmPlayer::new(reader);
+        player.set_position(num_samples - 10).unwrap();
+        let mut buffer = PingPongBuffer::<64, f32>::new();
+        assert_eq!(buffer.fill_inactive(&mut player).unwrap(), 10);
+        buffer.swap();
+        assert!(buffer.active()[10..].iter().all(|s| *s == 0.0));
+        assert_eq!(buffer.fill_inactive(&mut player).unwrap(), 0);
+        buffer.swap();
+        assert!(buffer.active().iter().all(|s| *s == 0.0));
+        assert_eq!(buffer.underruns(), 0);
+    }
+}
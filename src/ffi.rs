@@ -0,0 +1,394 @@
+//! C API of the reader and the player.
+//!
+//! No memory is allocated. The caller provides the storage of [pacmog_reader_t] and [pacmog_player_t],
+//! e.g. as static or stack variables, and the bytes of the file must outlive them.
+//! The header is `include/pacmog.h`, generated by cbindgen with `cbindgen.toml`.
+//!
+//! ```c
+//! pacmog_reader_t reader;
+//! pacmog_player_t player;
+//! int16_t buffer[256];
+//! size_t num_frames;
+//!
+//! if (pacmog_reader_new(wav, sizeof(wav), &reader) != PACMOG_OK) { /* ... */ }
+//! pacmog_player_new(&reader, &player);
+//! pacmog_player_fill_i16(&player, buffer, 256, &num_frames);
+//! ```
+//!
+//! This module requires the `ffi` feature.
+#![allow(non_camel_case_types)]
+
+use crate::{AudioFormat, PcmPlayer, PcmPlayerError, PcmReader, PcmReaderError, PcmSpecs};
+use core::mem::{align_of, size_of};
+
+/// Size of [pacmog_reader_t] in bytes.
+pub const PACMOG_READER_SIZE: usize = 512;
+/// Size of [pacmog_player_t] in bytes.
+pub const PACMOG_PLAYER_SIZE: usize = 528;
+
+const _: () = {
+    assert!(size_of::<PcmReader<'static>>() <= PACMOG_READER_SIZE);
+    assert!(align_of::<PcmReader<'static>>() <= align_of::<pacmog_reader_t>());
+    assert!(size_of::<pacmog_reader_t>() == PACMOG_READER_SIZE);
+    assert!(size_of::<PcmPlayer<'static>>() <= PACMOG_PLAYER_SIZE);
+    assert!(align_of::<PcmPlayer<'static>>() <= align_of::<pacmog_player_t>());
+    assert!(size_of::<pacmog_player_t>() == PACMOG_PLAYER_SIZE);
+};
+
+/// Result of the functions. Mirrors [PcmReaderError] and [PcmPlayerError].
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum pacmog_error_t {
+    PACMOG_OK = 0,
+    PACMOG_ERROR_UNSUPPORTED_BIT_DEPTH = 1,
+    PACMOG_ERROR_UNSUPPORTED_AUDIO_FORMAT = 2,
+    PACMOG_ERROR_INVALID_CHANNEL = 3,
+    PACMOG_ERROR_INVALID_SAMPLE = 4,
+    PACMOG_ERROR_HEADER_SIZE_MISMATCH = 5,
+    PACMOG_ERROR_OUTPUT_BUFFER_TOO_SHORT = 6,
+    PACMOG_ERROR_INVALID_POSITION = 7,
+    PACMOG_ERROR_FINISH_PLAYING = 8,
+    /// A pointer argument is NULL.
+    PACMOG_ERROR_NULL_POINTER = 9,
+    PACMOG_ERROR_CHUNK_SIZE_EXCEEDS_FILE = 10,
+    PACMOG_ERROR_INCONSISTENT_SPECS = 11,
+    PACMOG_ERROR_DUPLICATE_CHUNK = 12,
+    PACMOG_ERROR_MISSING_CHUNK = 13,
+    PACMOG_ERROR_TRUNCATED_DATA = 14,
+    PACMOG_ERROR_INVALID_CHUNK_SIZE = 15,
+}
+
+impl From<PcmReaderError> for pacmog_error_t {
+    fn from(e: PcmReaderError) -> Self {
+        match e {
+            PcmReaderError::UnsupportedBitDepth => {
+                pacmog_error_t::PACMOG_ERROR_UNSUPPORTED_BIT_DEPTH
+            }
+            PcmReaderError::UnsupportedAudioFormat => {
+                pacmog_error_t::PACMOG_ERROR_UNSUPPORTED_AUDIO_FORMAT
+            }
+            PcmReaderError::InvalidChannel => pacmog_error_t::PACMOG_ERROR_INVALID_CHANNEL,
+            PcmReaderError::InvalidSample => pacmog_error_t::PACMOG_ERROR_INVALID_SAMPLE,
+            PcmReaderError::HeaderSizeMismatch => pacmog_error_t::PACMOG_ERROR_HEADER_SIZE_MISMATCH,
+            PcmReaderError::ChunkSizeExceedsFile { .. } => {
+                pacmog_error_t::PACMOG_ERROR_CHUNK_SIZE_EXCEEDS_FILE
+            }
+            PcmReaderError::InconsistentSpecs(_) => pacmog_error_t::PACMOG_ERROR_INCONSISTENT_SPECS,
+            PcmReaderError::DuplicateChunk(_) => pacmog_error_t::PACMOG_ERROR_DUPLICATE_CHUNK,
+            PcmReaderError::MissingChunk(_) => pacmog_error_t::PACMOG_ERROR_MISSING_CHUNK,
+            PcmReaderError::TruncatedData { .. } => pacmog_error_t::PACMOG_ERROR_TRUNCATED_DATA,
+            PcmReaderError::InvalidChunkSize { .. } => {
+                pacmog_error_t::PACMOG_ERROR_INVALID_CHUNK_SIZE
+            }
+        }
+    }
+}
+
+impl From<PcmPlayerError> for pacmog_error_t {
+    fn from(e: PcmPlayerError) -> Self {
+        match e {
+            PcmPlayerError::OutputBufferTooShort => {
+                pacmog_error_t::PACMOG_ERROR_OUTPUT_BUFFER_TOO_SHORT
+            }
+            PcmPlayerError::InvalidPosition => pacmog_error_t::PACMOG_ERROR_INVALID_POSITION,
+            PcmPlayerError::FinishPlaying => pacmog_error_t::PACMOG_ERROR_FINISH_PLAYING,
+            PcmPlayerError::Reader(e) => e.into(),
+        }
+    }
+}
+
+/// Mirrors [AudioFormat].
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum pacmog_audio_format_t {
+    PACMOG_AUDIO_FORMAT_UNKNOWN = 0,
+    PACMOG_AUDIO_FORMAT_LINEAR_PCM_LE = 1,
+    PACMOG_AUDIO_FORMAT_LINEAR_PCM_BE = 2,
+    PACMOG_AUDIO_FORMAT_IEEE_FLOAT_LE = 3,
+    PACMOG_AUDIO_FORMAT_IEEE_FLOAT_BE = 4,
+    PACMOG_AUDIO_FORMAT_IMA_ADPCM_LE = 5,
+    PACMOG_AUDIO_FORMAT_ALAW = 6,
+    PACMOG_AUDIO_FORMAT_MULAW = 7,
+    PACMOG_AUDIO_FORMAT_MS_ADPCM_LE = 8,
+}
+
+/// Mirrors [PcmSpecs].
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct pacmog_specs_t {
+    pub audio_format: pacmog_audio_format_t,
+    pub num_channels: u16,
+    pub bit_depth: u16,
+    pub sample_rate: u32,
+    pub num_samples: u32,
+}
+
+impl From<&PcmSpecs> for pacmog_specs_t {
+    fn from(specs: &PcmSpecs) -> Self {
+        let audio_format = match specs.audio_format {
+            AudioFormat::Unknown => pacmog_audio_format_t::PACMOG_AUDIO_FORMAT_UNKNOWN,
+            AudioFormat::LinearPcmLe => pacmog_audio_format_t::PACMOG_AUDIO_FORMAT_LINEAR_PCM_LE,
+            AudioFormat::LinearPcmBe => pacmog_audio_format_t::PACMOG_AUDIO_FORMAT_LINEAR_PCM_BE,
+            AudioFormat::IeeeFloatLe => pacmog_audio_format_t::PACMOG_AUDIO_FORMAT_IEEE_FLOAT_LE,
+            AudioFormat::IeeeFloatBe => pacmog_audio_format_t::PACMOG_AUDIO_FORMAT_IEEE_FLOAT_BE,
+            AudioFormat::ImaAdpcmLe => pacmog_audio_format_t::PACMOG_AUDIO_FORMAT_IMA_ADPCM_LE,
+            AudioFormat::ALaw => pacmog_audio_format_t::PACMOG_AUDIO_FORMAT_ALAW,
+            AudioFormat::MuLaw => pacmog_audio_format_t::PACMOG_AUDIO_FORMAT_MULAW,
+            AudioFormat::MsAdpcmLe => pacmog_audio_format_t::PACMOG_AUDIO_FORMAT_MS_ADPCM_LE,
+        };
+        pacmog_specs_t {
+            audio_format,
+            num_channels: specs.num_channels,
+            bit_depth: specs.bit_depth,
+            sample_rate: specs.sample_rate,
+            num_samples: specs.num_samples,
+        }
+    }
+}
+
+/// Storage of a [PcmReader]. Initialize it with [pacmog_reader_new].
+#[repr(C)]
+pub struct pacmog_reader_t {
+    _storage: [u64; 64],
+}
+
+/// Storage of a [PcmPlayer]. Initialize it with [pacmog_player_new].
+#[repr(C)]
+pub struct pacmog_player_t {
+    _storage: [u64; 66],
+}
+
+/// Parse the file into the reader.
+///
+/// # Safety
+///
+/// `data` must point to `len` bytes which outlive the reader and the players created from it.
+/// `out` must point to a writable [pacmog_reader_t]. A reader initialized before is overwritten without [pacmog_reader_drop].
+#[no_mangle]
+pub unsafe extern "C" fn pacmog_reader_new(
+    data: *const u8,
+    len: usize,
+    out: *mut pacmog_reader_t,
+) -> pacmog_error_t {
+    if data.is_null() || out.is_null() {
+        return pacmog_error_t::PACMOG_ERROR_NULL_POINTER;
+    }
+    let input: &'static [u8] = core::slice::from_raw_parts(data, len);
+    match PcmReader::new(input) {
+        Ok(reader) => {
+            out.cast::<PcmReader<'static>>().write(reader);
+            pacmog_error_t::PACMOG_OK
+        }
+        Err(e) => e.into(),
+    }
+}
+
+/// Release the reader. Only needed with the `alloc` feature, which stores the chunk table on the heap.
+///
+/// # Safety
+///
+/// `reader` must have been initialized by [pacmog_reader_new] and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn pacmog_reader_drop(reader: *mut pacmog_reader_t) {
+    if !reader.is_null() {
+        reader.cast::<PcmReader<'static>>().drop_in_place();
+    }
+}
+
+/// Get the specs of the file.
+///
+/// # Safety
+///
+/// `reader` must have been initialized by [pacmog_reader_new]. `out` must point to a writable [pacmog_specs_t].
+#[no_mangle]
+pub unsafe extern "C" fn pacmog_reader_specs(
+    reader: *const pacmog_reader_t,
+    out: *mut pacmog_specs_t,
+) -> pacmog_error_t {
+    let (Some(reader), false) = (reader.cast::<PcmReader<'static>>().as_ref(), out.is_null())
+    else {
+        return pacmog_error_t::PACMOG_ERROR_NULL_POINTER;
+    };
+    out.write(reader.specs().into());
+    pacmog_error_t::PACMOG_OK
+}
+
+/// Read a sample of a channel as a normalized value. See [PcmReader::read_sample].
+///
+/// # Safety
+///
+/// `reader` must have been initialized by [pacmog_reader_new]. `out` must point to a writable float.
+#[no_mangle]
+pub unsafe extern "C" fn pacmog_read_sample_f32(
+    reader: *const pacmog_reader_t,
+    channel: u16,
+    sample: u32,
+    out: *mut f32,
+) -> pacmog_error_t {
+    let (Some(reader), false) = (reader.cast::<PcmReader<'static>>().as_ref(), out.is_null())
+    else {
+        return pacmog_error_t::PACMOG_ERROR_NULL_POINTER;
+    };
+    match reader.read_sample(channel, sample) {
+        Ok(value) => {
+            out.write(value);
+            pacmog_error_t::PACMOG_OK
+        }
+        Err(e) => e.into(),
+    }
+}
+
+/// Create a player of the file. The reader is copied, so it can be dropped afterwards.
+///
+/// # Safety
+///
+/// `reader` must have been initialized by [pacmog_reader_new]. `out` must point to a writable [pacmog_player_t].
+/// A player initialized before is overwritten without [pacmog_player_drop].
+#[no_mangle]
+pub unsafe extern "C" fn pacmog_player_new(
+    reader: *const pacmog_reader_t,
+    out: *mut pacmog_player_t,
+) -> pacmog_error_t {
+    let (Some(reader), false) = (reader.cast::<PcmReader<'static>>().as_ref(), out.is_null())
+    else {
+        return pacmog_error_t::PACMOG_ERROR_NULL_POINTER;
+    };
+    out.cast::<PcmPlayer<'static>>()
+        .write(PcmPlayer::new(reader.clone()));
+    pacmog_error_t::PACMOG_OK
+}
+
+/// Release the player. Only needed with the `alloc` feature.
+///
+/// # Safety
+///
+/// `player` must have been initialized by [pacmog_player_new] and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn pacmog_player_drop(player: *mut pacmog_player_t) {
+    if !player.is_null() {
+        player.cast::<PcmPlayer<'static>>().drop_in_place();
+    }
+}
+
+/// Enable or disable loop playback. See [PcmPlayer::set_loop_playing].
+///
+/// # Safety
+///
+/// `player` must have been initialized by [pacmog_player_new].
+#[no_mangle]
+pub unsafe extern "C" fn pacmog_player_set_loop_playing(
+    player: *mut pacmog_player_t,
+    enable: bool,
+) -> pacmog_error_t {
+    let Some(player) = player.cast::<PcmPlayer<'static>>().as_mut() else {
+        return pacmog_error_t::PACMOG_ERROR_NULL_POINTER;
+    };
+    player.set_loop_playing(enable);
+    pacmog_error_t::PACMOG_OK
+}
+
+/// Fill the buffer with the next interleaved frames as 16bit samples. See [PcmPlayer::refill_half_buffer].
+/// When playback finishes, the rest of the buffer is filled with silence and `num_frames` is less than the buffer holds.
+/// * 'out' - Buffer of `len` samples.
+/// * 'num_frames' - Receives the number of frames taken from the file.
+///
+/// # Safety
+///
+/// `player` must have been initialized by [pacmog_player_new]. `out` must point to `len` writable samples.
+/// `num_frames` must point to a writable size_t.
+#[no_mangle]
+pub unsafe extern "C" fn pacmog_player_fill_i16(
+    player: *mut pacmog_player_t,
+    out: *mut i16,
+    len: usize,
+    num_frames: *mut usize,
+) -> pacmog_error_t {
+    let Some(player) = player.cast::<PcmPlayer<'static>>().as_mut() else {
+        return pacmog_error_t::PACMOG_ERROR_NULL_POINTER;
+    };
+    if out.is_null() || num_frames.is_null() {
+        return pacmog_error_t::PACMOG_ERROR_NULL_POINTER;
+    }
+    let out = core::slice::from_raw_parts_mut(out, len);
+    match player.refill_half_buffer(out) {
+        Ok(n) => {
+            num_frames.write(n);
+            pacmog_error_t::PACMOG_OK
+        }
+        Err(e) => e.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::mem::MaybeUninit;
+
+    #[test]
+    fn read_and_play() {
+        let wav = include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_16.wav");
+        let expected = PcmReader::new(wav).unwrap();
+        let mut reader = MaybeUninit::<pacmog_reader_t>::uninit();
+        let mut player = MaybeUninit::<pacmog_player_t>::uninit();
+        let mut specs = MaybeUninit::<pacmog_specs_t>::uninit();
+        let mut sample = 0f32;
+        let mut buffer = [0i16; 64];
+        let mut num_frames = 0;
+        unsafe {
+            assert_eq!(
+                pacmog_reader_new(wav.as_ptr(), wav.len(), reader.as_mut_ptr()),
+                pacmog_error_t::PACMOG_OK
+            );
+            assert_eq!(
+                pacmog_reader_specs(reader.as_ptr(), specs.as_mut_ptr()),
+                pacmog_error_t::PACMOG_OK
+            );
+            let specs = specs.assume_init();
+            assert_eq!(
+                specs.audio_format,
+                pacmog_audio_format_t::PACMOG_AUDIO_FORMAT_LINEAR_PCM_LE
+            );
+            assert_eq!(specs.num_samples, 240000);
+
+            assert_eq!(
+                pacmog_read_sample_f32(reader.as_ptr(), 0, 10, &mut sample),
+                pacmog_error_t::PACMOG_OK
+            );
+            assert_eq!(sample, expected.read_sample(0, 10).unwrap());
+            assert_eq!(
+                pacmog_read_sample_f32(reader.as_ptr(), 1, 10, &mut sample),
+                pacmog_error_t::PACMOG_ERROR_INVALID_CHANNEL
+            );
+
+            assert_eq!(
+                pacmog_player_new(reader.as_ptr(), player.as_mut_ptr()),
+                pacmog_error_t::PACMOG_OK
+            );
+            pacmog_reader_drop(reader.as_mut_ptr());
+            assert_eq!(
+                pacmog_player_fill_i16(
+                    player.as_mut_ptr(),
+                    buffer.as_mut_ptr(),
+                    buffer.len(),
+                    &mut num_frames
+                ),
+                pacmog_error_t::PACMOG_OK
+            );
+            assert_eq!(num_frames, 64);
+            assert_eq!(
+                buffer[10],
+                (expected.read_sample(0, 10).unwrap() * 32768.0) as i16
+            );
+            pacmog_player_drop(player.as_mut_ptr());
+
+            assert_eq!(
+                pacmog_reader_new(wav.as_ptr(), 12, reader.as_mut_ptr()),
+                pacmog_error_t::PACMOG_ERROR_TRUNCATED_DATA
+            );
+            assert_eq!(
+                pacmog_reader_new(core::ptr::null(), 0, reader.as_mut_ptr()),
+                pacmog_error_t::PACMOG_ERROR_NULL_POINTER
+            );
+        }
+    }
+}
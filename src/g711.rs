@@ -0,0 +1,217 @@
+//! Decoders of 8-bit samples: A-law and µ-law (ITU-T G.711) and 8-bit linear PCM.
+//!
+//! By default, each decoder is a lookup in a 256-entry table generated at compile time.
+//! The tables take 512 bytes of ROM each. With the `computed-decode` feature, the samples are expanded arithmetically instead.
+//!
+//! # Examples
+//!
+//! ```
+//! use pacmog::g711;
+//!
+//! assert_eq!(g711::alaw_to_i16(0xD5), 8);
+//! assert_eq!(g711::ulaw_to_i16(0x80), 32124);
+//! assert_eq!(g711::u8_to_f32(0x80), 0.0);
+//! ```
+
+/// Normalize factor of the 16bit values: 2^15
+const NORMALIZE: f32 = 1.0 / 32768.0;
+
+const SIGN_BIT: u8 = 0x80;
+const QUANT_MASK: u8 = 0x0F;
+const SEG_MASK: u8 = 0x70;
+const SEG_SHIFT: u8 = 4;
+/// Bias for µ-law
+const ULAW_BIAS: i16 = 0x84;
+
+/// Expand an A-law byte into a 16bit linear value.
+/// The result is in the range +/-32256.
+pub const fn alaw_expand(a_val: u8) -> i16 {
+    let a_val = a_val ^ 0x55;
+    let mut t = ((a_val & QUANT_MASK) as i16) << 4;
+    let seg = (a_val & SEG_MASK) >> SEG_SHIFT;
+    match seg {
+        0 => t += 8,
+        1 => t += 0x108,
+        _ => {
+            t += 0x108;
+            t <<= seg - 1;
+        }
+    }
+    if a_val & SIGN_BIT != 0 {
+        t
+    } else {
+        -t
+    }
+}
+
+/// Expand a µ-law byte into a 16bit linear value.
+/// The result is in the range +/-32124.
+pub const fn ulaw_expand(u_val: u8) -> i16 {
+    let u_val = !u_val;
+    let mut t = (((u_val & QUANT_MASK) as i16) << 3) + ULAW_BIAS;
+    t <<= (u_val & SEG_MASK) >> SEG_SHIFT;
+    if u_val & SIGN_BIT != 0 {
+        ULAW_BIAS - t
+    } else {
+        t - ULAW_BIAS
+    }
+}
+
+/// Expand an unsigned 8bit linear PCM sample (WAV) into a 16bit linear value.
+pub const fn u8_expand(sample: u8) -> i16 {
+    ((sample as i16) - 128) << 8
+}
+
+/// Expand a signed 8bit linear PCM sample (AIFF) into a 16bit linear value.
+pub const fn i8_expand(sample: u8) -> i16 {
+    (sample as i8 as i16) << 8
+}
+
+#[cfg(not(feature = "computed-decode"))]
+const ALAW_TABLE: [i16; 256] = {
+    let mut table = [0i16; 256];
+    let mut i = 0;
+    while i < 256 {
+        table[i] = alaw_expand(i as u8);
+        i += 1;
+    }
+    table
+};
+
+#[cfg(not(feature = "computed-decode"))]
+const ULAW_TABLE: [i16; 256] = {
+    let mut table = [0i16; 256];
+    let mut i = 0;
+    while i < 256 {
+        table[i] = ulaw_expand(i as u8);
+        i += 1;
+    }
+    table
+};
+
+#[cfg(not(feature = "computed-decode"))]
+const U8_TABLE: [i16; 256] = {
+    let mut table = [0i16; 256];
+    let mut i = 0;
+    while i < 256 {
+        table[i] = u8_expand(i as u8);
+        i += 1;
+    }
+    table
+};
+
+#[cfg(not(feature = "computed-decode"))]
+const I8_TABLE: [i16; 256] = {
+    let mut table = [0i16; 256];
+    let mut i = 0;
+    while i < 256 {
+        table[i] = i8_expand(i as u8);
+        i += 1;
+    }
+    table
+};
+
+/// Decode an A-law byte into a 16bit linear value.
+#[inline]
+pub fn alaw_to_i16(a_val: u8) -> i16 {
+    #[cfg(not(feature = "computed-decode"))]
+    return ALAW_TABLE[a_val as usize];
+    #[cfg(feature = "computed-decode")]
+    return alaw_expand(a_val);
+}
+
+/// Decode a µ-law byte into a 16bit linear value.
+#[inline]
+pub fn ulaw_to_i16(u_val: u8) -> i16 {
+    #[cfg(not(feature = "computed-decode"))]
+    return ULAW_TABLE[u_val as usize];
+    #[cfg(feature = "computed-decode")]
+    return ulaw_expand(u_val);
+}
+
+/// Decode an unsigned 8bit linear PCM sample (WAV) into a 16bit linear value.
+#[inline]
+pub fn u8_to_i16(sample: u8) -> i16 {
+    #[cfg(not(feature = "computed-decode"))]
+    return U8_TABLE[sample as usize];
+    #[cfg(feature = "computed-decode")]
+    return u8_expand(sample);
+}
+
+/// Decode a signed 8bit linear PCM sample (AIFF) into a 16bit linear value.
+#[inline]
+pub fn i8_to_i16(sample: u8) -> i16 {
+    #[cfg(not(feature = "computed-decode"))]
+    return I8_TABLE[sample as usize];
+    #[cfg(feature = "computed-decode")]
+    return i8_expand(sample);
+}
+
+/// Decode an A-law byte into a normalized value in the range +/-1.0.
+#[inline]
+pub fn alaw_to_f32(a_val: u8) -> f32 {
+    alaw_to_i16(a_val) as f32 * NORMALIZE
+}
+
+/// Decode a µ-law byte into a normalized value in the range +/-1.0.
+#[inline]
+pub fn ulaw_to_f32(u_val: u8) -> f32 {
+    ulaw_to_i16(u_val) as f32 * NORMALIZE
+}
+
+/// Decode an unsigned 8bit linear PCM sample (WAV) into a normalized value in the range +/-1.0.
+#[inline]
+pub fn u8_to_f32(sample: u8) -> f32 {
+    u8_to_i16(sample) as f32 * NORMALIZE
+}
+
+/// Decode a signed 8bit linear PCM sample (AIFF) into a normalized value in the range +/-1.0.
+#[inline]
+pub fn i8_to_f32(sample: u8) -> f32 {
+    i8_to_i16(sample) as f32 * NORMALIZE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alaw() {
+        assert_eq!(alaw_expand(0xD5), 8);
+        assert_eq!(alaw_expand(0x55), -8);
+        assert_eq!(alaw_expand(0xAA), 32256);
+        assert_eq!(alaw_expand(0x2A), -32256);
+        assert_eq!(alaw_expand(0x80), 5504);
+    }
+
+    #[test]
+    fn ulaw() {
+        assert_eq!(ulaw_expand(0xFF), 0);
+        assert_eq!(ulaw_expand(0x7F), 0);
+        assert_eq!(ulaw_expand(0x80), 32124);
+        assert_eq!(ulaw_expand(0x00), -32124);
+        assert_eq!(ulaw_expand(0xFE), 8);
+        assert_eq!(ulaw_expand(0x7E), -8);
+    }
+
+    #[test]
+    fn linear_8bit() {
+        assert_eq!(u8_to_f32(0x00), -1.0);
+        assert_eq!(u8_to_f32(0x80), 0.0);
+        assert_eq!(u8_to_f32(0xC0), 0.5);
+        assert_eq!(i8_to_f32(0x80), -1.0);
+        assert_eq!(i8_to_f32(0x00), 0.0);
+        assert_eq!(i8_to_f32(0x40), 0.5);
+    }
+
+    /// The tables and the computed versions give the same values.
+    #[test]
+    fn tables_match_computed() {
+        for i in 0..=255u8 {
+            assert_eq!(alaw_to_i16(i), alaw_expand(i));
+            assert_eq!(ulaw_to_i16(i), ulaw_expand(i));
+            assert_eq!(u8_to_i16(i), u8_expand(i));
+            assert_eq!(i8_to_i16(i), i8_expand(i));
+        }
+    }
+}
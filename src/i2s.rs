@@ -0,0 +1,160 @@
+//! Formatting of samples into the 32bit slots of an I2S peripheral.
+//!
+//! Codecs expect the bits of a sample at different positions in the slot.
+//! [format_frames] quantizes the samples to the bit depth of the codec and places them according to the [FrameFormat].
+//! The bit patterns for full scale of a 24bit codec are:
+//!
+//! | Format | +full scale | -full scale |
+//! | :--- | :--- | :--- |
+//! | [FrameFormat::Philips] | 0x3FFF_FF80 | 0x4000_0000 |
+//! | [FrameFormat::LeftJustified] | 0x7FFF_FF00 | 0x8000_0000 |
+//! | [FrameFormat::RightJustified] | 0x007F_FFFF | 0x0080_0000 |
+//!
+//! # Examples
+//!
+//! 16bit data in 32bit slots, left-justified.
+//! ```
+//! use pacmog::i2s::{format_frames, FrameFormat};
+//!
+//! let src = [0.5f32, -1.0];
+//! let mut dst = [0u32; 2];
+//! format_frames(&src, &mut dst, FrameFormat::LeftJustified, 16);
+//! assert_eq!(dst, [0x4000_0000, 0x8000_0000]);
+//! ```
+
+use crate::imaadpcm::I1F15;
+use crate::writer::quantize;
+
+/// Position of the sample bits in a 32bit slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameFormat {
+    /// Philips I2S. The MSB starts one bit clock after the word select changes, i.e. at bit 30 of the slot.
+    Philips,
+    /// The MSB is at bit 31 of the slot, e.g. MSB-justified codecs and 16-in-32 modes.
+    LeftJustified,
+    /// The LSB is at bit 0 of the slot. The bits above the sample are 0.
+    RightJustified,
+}
+
+/// Sample type which can be formatted with [format_frames].
+pub trait FormatSample: Copy {
+    /// Convert to a signed integer of the bit depth, with rounding and clipping where needed.
+    fn to_bits(self, bits: u8) -> i32;
+}
+
+impl FormatSample for f32 {
+    fn to_bits(self, bits: u8) -> i32 {
+        quantize(self, u32::from(bits) - 1)
+    }
+}
+
+impl FormatSample for I1F15 {
+    fn to_bits(self, bits: u8) -> i32 {
+        let v = i32::from(self.to_bits());
+        if bits >= 16 {
+            v << (bits - 16)
+        } else {
+            v >> (16 - bits)
+        }
+    }
+}
+
+/// Format interleaved samples into 32bit slots.
+/// * 'src' - Normalized samples.
+/// * 'dst' - One slot per sample.
+/// * 'fmt' - Position of the sample bits in the slot.
+/// * 'bits' - Bit depth of the codec, from 8 to 32. With [FrameFormat::Philips] and 32bit, the LSB is dropped.
+///
+/// Returns the number of samples formatted, the shorter of src and dst.
+///
+/// # Panics
+///
+/// Panics if bits is not in the range 8..=32.
+pub fn format_frames<S: FormatSample>(
+    src: &[S],
+    dst: &mut [u32],
+    fmt: FrameFormat,
+    bits: u8,
+) -> usize {
+    assert!((8..=32).contains(&bits), "bits must be from 8 to 32");
+    let shift = 32 - u32::from(bits);
+    let mask = u32::MAX >> shift;
+    for (slot, sample) in dst.iter_mut().zip(src) {
+        let v = sample.to_bits(bits) as u32;
+        *slot = match fmt {
+            FrameFormat::Philips => (v << shift) >> 1,
+            FrameFormat::LeftJustified => v << shift,
+            FrameFormat::RightJustified => v & mask,
+        };
+    }
+    src.len().min(dst.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_frames, FrameFormat};
+    use crate::imaadpcm::I1F15;
+
+    /// (format, bits, +full scale, -full scale)
+    const FULL_SCALE: [(FrameFormat, u8, u32, u32); 12] = [
+        (FrameFormat::Philips, 16, 0x3FFF_8000, 0x4000_0000),
+        (FrameFormat::Philips, 24, 0x3FFF_FF80, 0x4000_0000),
+        (FrameFormat::Philips, 32, 0x3FFF_FFFF, 0x4000_0000),
+        (FrameFormat::LeftJustified, 16, 0x7FFF_0000, 0x8000_0000),
+        (FrameFormat::LeftJustified, 24, 0x7FFF_FF00, 0x8000_0000),
+        (FrameFormat::LeftJustified, 32, 0x7FFF_FFFF, 0x8000_0000),
+        (FrameFormat::RightJustified, 16, 0x0000_7FFF, 0x0000_8000),
+        (FrameFormat::RightJustified, 24, 0x007F_FFFF, 0x0080_0000),
+        (FrameFormat::RightJustified, 32, 0x7FFF_FFFF, 0x8000_0000),
+        (FrameFormat::Philips, 8, 0x3F80_0000, 0x4000_0000),
+        (FrameFormat::LeftJustified, 8, 0x7F00_0000, 0x8000_0000),
+        (FrameFormat::RightJustified, 8, 0x0000_007F, 0x0000_0080),
+    ];
+
+    #[test]
+    fn full_scale_f32() {
+        for (fmt, bits, positive, negative) in FULL_SCALE {
+            let mut dst = [0u32; 4];
+            let n = format_frames(&[1.0f32, -1.0, 0.0, 2.0], &mut dst, fmt, bits);
+            assert_eq!(n, 4);
+            assert_eq!(dst, [positive, negative, 0, positive], "{fmt:?} {bits}bit");
+        }
+    }
+
+    #[test]
+    fn full_scale_i1f15() {
+        // I1F15::MAX is 1 - 2^-15, so above 16bit the lower bits of +full scale are 0.
+        let full_scale = [
+            (FrameFormat::Philips, 16, 0x3FFF_8000, 0x4000_0000),
+            (FrameFormat::Philips, 24, 0x3FFF_8000, 0x4000_0000),
+            (FrameFormat::LeftJustified, 16, 0x7FFF_0000, 0x8000_0000),
+            (FrameFormat::LeftJustified, 24, 0x7FFF_0000, 0x8000_0000),
+            (FrameFormat::RightJustified, 16, 0x0000_7FFF, 0x0000_8000),
+            (FrameFormat::RightJustified, 24, 0x007F_FF00, 0x0080_0000),
+            (FrameFormat::RightJustified, 8, 0x0000_007F, 0x0000_0080),
+        ];
+        for (fmt, bits, positive, negative) in full_scale {
+            let mut dst = [0u32; 3];
+            format_frames(&[I1F15::MAX, I1F15::MIN, I1F15::ZERO], &mut dst, fmt, bits);
+            assert_eq!(dst, [positive, negative, 0], "{fmt:?} {bits}bit");
+        }
+    }
+
+    #[test]
+    fn lengths() {
+        let mut dst = [0xFFFF_FFFFu32; 3];
+        assert_eq!(
+            format_frames(&[0.5f32], &mut dst, FrameFormat::LeftJustified, 24),
+            1
+        );
+        assert_eq!(dst, [0x4000_0000, 0xFFFF_FFFF, 0xFFFF_FFFF]);
+        // -1 LSB
+        format_frames(
+            &[-1.0f32 / 8388608.0],
+            &mut dst,
+            FrameFormat::RightJustified,
+            24,
+        );
+        assert_eq!(dst[0], 0x00FF_FFFF);
+    }
+}
@@ -7,7 +7,7 @@
 //! use pacmog::imaadpcm::{ImaAdpcmPlayer, I1F15};
 //!
 //! let data = include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_4bit_IMAADPCM.wav");
-//! let mut player = ImaAdpcmPlayer::new(data);
+//! let mut player = ImaAdpcmPlayer::new(data).unwrap();
 //! let mut buffer: [I1F15; 2] = [I1F15::ZERO, I1F15::ZERO];
 //! let b = buffer.as_mut_slice();
 //!
@@ -16,7 +16,7 @@
 //! }
 //! ```
 
-use crate::{AudioFormat, PcmReader, PcmSpecs};
+use crate::{AudioFormat, PcmReader, PcmReaderError, PcmSpecs, SpecsField, MAX_NUM_CHUNKS};
 use arbitrary_int::u4;
 use heapless::spsc::Queue;
 use nom::bits::{bits, complete::take};
@@ -62,6 +62,10 @@ pub enum ImaAdpcmError {
     FinishPlaying,
     #[error("Block length does not match block align")]
     BlockLengthMismatch,
+    #[error("Only mono and stereo IMA-ADPCM are supported.")]
+    UnsupportedChannels,
+    #[error("Reader error")]
+    Reader(#[from] PcmReaderError),
 }
 
 /// IMA-ADPCMのHeader Wordをパースする
@@ -128,32 +132,61 @@ fn compute_step_size(nibble: u4, mut step_size_table_index: i8) -> i8 {
 }
 
 /// Calculate the number of samples per channel for IMA-ADPCM files.
+/// The final block may be cut short by the encoder. It holds the sample of its header and 8 samples per data word.
+///
+/// Never panics. Returns UnsupportedAudioFormat if the specs are not IMA-ADPCM or lack the fields of the fmt extension,
+/// and InconsistentSpecs for a block align or a number of channels of 0, or too many samples to count in u32.
 pub(crate) fn calc_num_samples_per_channel(
-    data_chunk_size_in_bytes: u32,
+    data_chunk_size_in_bytes: u64,
     spec: &PcmSpecs,
-) -> Result<u32, ImaAdpcmError> {
+) -> Result<u32, PcmReaderError> {
     if spec.audio_format != AudioFormat::ImaAdpcmLe {
-        return Err(ImaAdpcmError::NotImaAdpcm);
+        return Err(PcmReaderError::UnsupportedAudioFormat);
     }
 
-    let num_block_align = spec.ima_adpcm_num_block_align.unwrap() as u32;
-    let num_samples_per_block = spec.ima_adpcm_num_samples_per_block.unwrap() as u32;
-    let num_blocks = data_chunk_size_in_bytes / num_block_align;
-    let num_samples = num_blocks * num_samples_per_block;
-    Ok(num_samples)
+    let (Some(num_block_align), Some(num_samples_per_block)) = (
+        spec.ima_adpcm_num_block_align,
+        spec.ima_adpcm_num_samples_per_block,
+    ) else {
+        return Err(PcmReaderError::UnsupportedAudioFormat);
+    };
+    let Some(num_blocks) = data_chunk_size_in_bytes.checked_div(num_block_align as u64) else {
+        return Err(PcmReaderError::InconsistentSpecs(SpecsField::BlockAlign));
+    };
+    // The header of a block and a data word are 4 bytes per channel.
+    let word_size = 4 * spec.num_channels as u64;
+    if word_size == 0 {
+        return Err(PcmReaderError::InconsistentSpecs(SpecsField::NumChannels));
+    }
+    let rest = data_chunk_size_in_bytes % num_block_align as u64;
+    let short_block = match rest.checked_sub(word_size) {
+        Some(data_words) => 1 + data_words / word_size * 8,
+        None => 0,
+    };
+    num_blocks
+        .checked_mul(num_samples_per_block as u64)
+        .and_then(|n| n.checked_add(short_block))
+        .and_then(|n| u32::try_from(n).ok())
+        .ok_or(PcmReaderError::InconsistentSpecs(SpecsField::NumSamples))
 }
 
 /// High level of organized players for IMA-ADPCM playback.
 #[derive(Default)]
-pub struct ImaAdpcmPlayer<'a> {
+pub struct ImaAdpcmPlayer<'a, const MAX_CHUNKS: usize = MAX_NUM_CHUNKS> {
     /// A reader to access basic information about the PCM file.
-    pub reader: PcmReader<'a>,
-    /// Frame index of the current block.
-    frame_index: u32,
+    pub reader: PcmReader<'a, MAX_CHUNKS>,
+    /// Frame index of the current block. Kept in u64 so that the offset arithmetic does not overflow.
+    frame_index: u64,
+    /// Number of samples per block. Taken from the specs at construction.
+    samples_per_block: u32,
+    /// Number of bytes per block. Taken from the specs at construction.
+    block_align: u32,
     /// The last decoded sample value.
     last_predicted_sample: [I1F15; MAX_NUM_CHANNELS],
     /// The current index of STEP_SIZE_TABLE.
     step_size_table_index: [i8; MAX_NUM_CHANNELS],
+    /// Headerless stream of [ImaAdpcmPlayer::new_raw], decoded with one running predictor per channel.
+    headerless: bool,
     /// The current block of IMA-ADPCM being read.
     reading_block: &'a [u8],
     /// A queue that stores nibble arrays when reading data words.
@@ -163,15 +196,98 @@ pub struct ImaAdpcmPlayer<'a> {
 
 impl<'a> ImaAdpcmPlayer<'a> {
     /// * 'input' - PCM data byte array.
-    pub fn new(input: &'a [u8]) -> Self {
-        //TODO unwrapではなくきちんとエラーハンドリングする
-        let reader = PcmReader::new(input).unwrap();
+    ///
+    /// Returns an error if the file cannot be parsed or is not a mono or stereo IMA-ADPCM file.
+    pub fn new(input: &'a [u8]) -> Result<Self, ImaAdpcmError> {
+        let reader = PcmReader::new(input)?;
+        Self::from_reader(reader)
+    }
 
-        ImaAdpcmPlayer {
+    /// Create a player of headerless IMA-ADPCM, e.g. a stream in a game archive.
+    /// The nibbles are continuous without block headers. The predictor starts at 0 with the step index 0 and runs across the whole data.
+    /// Each byte holds two nibbles, the high nibble first, and the channels are interleaved nibble by nibble.
+    /// The OKI ADPCM of Dialogic VOX files uses another step table and is not decoded.
+    /// * 'data' - The nibbles.
+    /// * 'sample_rate' - Sample rate in Hz, which is not stored in the data.
+    /// * 'num_channels' - Mono: 1, Stereo: 2.
+    ///
+    /// ```
+    /// use pacmog::imaadpcm::{ImaAdpcmPlayer, I1F15};
+    ///
+    /// let data = include_bytes!("../tests/resources/Sine440Hz_1ch_8000Hz_4bit_IMAADPCM.ima");
+    /// let mut player = ImaAdpcmPlayer::new_raw(data, 8000, 1).unwrap();
+    /// assert_eq!(player.specs().num_samples, 800);
+    /// let mut buffer = [I1F15::ZERO];
+    /// player.get_next_frame(&mut buffer).unwrap();
+    /// ```
+    pub fn new_raw(
+        data: &'a [u8],
+        sample_rate: u32,
+        num_channels: u16,
+    ) -> Result<Self, ImaAdpcmError> {
+        if num_channels == 0 || num_channels as usize > MAX_NUM_CHANNELS {
+            return Err(ImaAdpcmError::UnsupportedChannels);
+        }
+        if sample_rate == 0 {
+            return Err(PcmReaderError::InconsistentSpecs(SpecsField::SampleRate).into());
+        }
+        let num_samples = u32::try_from(data.len() as u64 * 2 / u64::from(num_channels))
+            .map_err(|_| PcmReaderError::InconsistentSpecs(SpecsField::NumSamples))?;
+        let reader = PcmReader {
+            specs: PcmSpecs {
+                num_samples,
+                container_bytes_per_sample: 0,
+                ..PcmSpecs::new(AudioFormat::ImaAdpcmLe, num_channels, sample_rate, 4)
+            },
+            data,
+            input: data,
+            ..Default::default()
+        };
+        Ok(ImaAdpcmPlayer {
             reader,
-            frame_index: 0,
+            headerless: true,
             ..Default::default()
+        })
+    }
+}
+
+impl<'a, const MAX_CHUNKS: usize> ImaAdpcmPlayer<'a, MAX_CHUNKS> {
+    /// Create a player whose reader records up to MAX_CHUNKS chunks. See [PcmReader::new_with_chunk_capacity].
+    /// * 'input' - PCM data byte array.
+    pub fn new_with_chunk_capacity(input: &'a [u8]) -> Result<Self, ImaAdpcmError> {
+        let reader = PcmReader::new_with_chunk_capacity(input)?;
+        Self::from_reader(reader)
+    }
+
+    /// Create a player from a reader which has already parsed an IMA-ADPCM file.
+    pub(crate) fn from_reader(reader: PcmReader<'a, MAX_CHUNKS>) -> Result<Self, ImaAdpcmError> {
+        let specs = &reader.specs;
+        let (Some(samples_per_block), Some(block_align)) = (
+            specs.ima_adpcm_num_samples_per_block,
+            specs.ima_adpcm_num_block_align,
+        ) else {
+            return Err(ImaAdpcmError::NotImaAdpcm);
+        };
+        if specs.audio_format != AudioFormat::ImaAdpcmLe || samples_per_block == 0 {
+            return Err(ImaAdpcmError::NotImaAdpcm);
         }
+        if specs.num_channels == 0 || specs.num_channels as usize > MAX_NUM_CHANNELS {
+            return Err(ImaAdpcmError::UnsupportedChannels);
+        }
+
+        Ok(ImaAdpcmPlayer {
+            reader,
+            frame_index: 0,
+            samples_per_block: samples_per_block as u32,
+            block_align: block_align as u32,
+            ..Default::default()
+        })
+    }
+
+    /// Returns a reference to basic information about the IMA-ADPCM file.
+    #[must_use]
+    pub fn specs(&self) -> &PcmSpecs {
+        &self.reader.specs
     }
 
     /// Return samples value of the next frame.
@@ -185,12 +301,20 @@ impl<'a> ImaAdpcmPlayer<'a> {
         }
 
         // 再生終了している場合はエラーを返す
-        if self.frame_index >= self.reader.specs.num_samples {
+        // The position may be beyond the end if the reader has been reloaded with a shorter file.
+        if self.frame_index >= u64::from(self.reader.specs.num_samples) {
             return Err(ImaAdpcmError::FinishPlaying);
         }
 
+        if self.headerless {
+            self.next_headerless_frame(out);
+            self.frame_index += 1;
+            return Ok(());
+        }
+
         //IMA-ADPCMのBlock切り替わりかどうか判定
-        if self.reading_block.is_empty() && self.nibble_queue[0].is_empty() {
+        // The data words after wSamplesPerBlock samples are not decoded.
+        if self.frame_index % u64::from(self.samples_per_block) == 0 {
             self.update_block()?;
             out[..(num_channels as usize)]
                 .copy_from_slice(&self.last_predicted_sample[..(num_channels as usize)]);
@@ -200,6 +324,10 @@ impl<'a> ImaAdpcmPlayer<'a> {
 
         // 次のData wordsをチャンネル数分よみこむ.
         if self.nibble_queue[0].is_empty() {
+            // A data word of every channel must be left, so that the queues stay in step.
+            if self.reading_block.len() < 4 * num_channels as usize {
+                return Err(ImaAdpcmError::BlockLengthMismatch);
+            }
             for ch in 0..num_channels as usize {
                 let (remains, nibbles) = parse_data_word(self.reading_block).unwrap();
                 self.reading_block = remains;
@@ -231,37 +359,73 @@ impl<'a> ImaAdpcmPlayer<'a> {
         Ok(())
     }
 
-    /// IMA-ADPCMのブロック更新.    
-    fn update_block(&mut self) -> Result<(), ImaAdpcmError> {
-        let samples_per_block = self.reader.specs.ima_adpcm_num_samples_per_block.unwrap() as u32;
-        let block_align = self.reader.specs.ima_adpcm_num_block_align.unwrap() as u32;
-        let offset = (self.frame_index / samples_per_block) * block_align;
-        self.reading_block = &self.reader.data[offset as usize..(offset + block_align) as usize]; //新しいBlockをreading_blockへ更新
+    /// Decode the next frame of a headerless stream. The nibbles of the frames before it have been decoded.
+    fn next_headerless_frame(&mut self, out: &mut [I1F15]) {
+        let num_channels = self.reader.specs.num_channels as usize;
+        for (ch, output_value) in out.iter_mut().enumerate().take(num_channels) {
+            // Less than num_samples * num_channels, so the nibble is in the data.
+            let nibble_index = self.frame_index as usize * num_channels + ch;
+            let byte = self.reader.data[nibble_index / 2];
+            let nibble = if nibble_index % 2 == 0 {
+                byte >> 4
+            } else {
+                byte & 0x0F
+            };
+            let (predicted_sample, table_index) = decode_sample(
+                u4::new(nibble),
+                self.last_predicted_sample[ch],
+                self.step_size_table_index[ch],
+            );
+            self.last_predicted_sample[ch] = predicted_sample;
+            self.step_size_table_index[ch] = table_index;
+            *output_value = predicted_sample;
+        }
+    }
 
-        if self.reading_block.len() != block_align as usize {
+    /// IMA-ADPCMのブロック更新.
+    /// The final block may be shorter than the block align. Returns BlockLengthMismatch if its header is cut short by the end of the data.
+    fn update_block(&mut self) -> Result<(), ImaAdpcmError> {
+        let offset = (self.frame_index / self.samples_per_block as u64) * self.block_align as u64;
+        let end = offset + self.block_align as u64;
+        let (Ok(offset), Ok(end)) = (usize::try_from(offset), usize::try_from(end)) else {
             return Err(ImaAdpcmError::BlockLengthMismatch);
-        }
+        };
+        let end = end.min(self.reader.data.len());
+        let Some(mut block) = self.reader.data.get(offset..end) else {
+            return Err(ImaAdpcmError::BlockLengthMismatch);
+        };
 
         for ch in 0..self.reader.specs.num_channels as usize {
-            // BlockのHeader wordを読み出す
-            let (block, block_header) = parse_block_header(self.reading_block).unwrap(); //Headerの1ch分は4byte
+            // BlockのHeader wordを読み出す. Headerの1ch分は4byte
+            let Ok((remains, block_header)) = parse_block_header(block) else {
+                return Err(ImaAdpcmError::BlockLengthMismatch);
+            };
             self.last_predicted_sample[ch] = block_header.i_samp_0;
-            self.step_size_table_index[ch] = block_header.b_step_table_index;
-            self.reading_block = block;
+            // The index is out of range in broken files.
+            self.step_size_table_index[ch] = block_header.b_step_table_index.clamp(0, 88);
+            block = remains;
         }
+        self.reading_block = block; //新しいBlockのData wordsをreading_blockへ更新
+        self.clear_nibble_queue();
         Ok(())
     }
 
     /// Move the playback position back to the beginning.
     pub fn rewind(&mut self) {
         self.frame_index = 0;
+        // A headerless stream starts over from the initial state of the predictor.
+        self.last_predicted_sample = Default::default();
+        self.step_size_table_index = Default::default();
         if !self.reading_block.is_empty() {
             self.reading_block = &self.reading_block[0..0]; //reading_blockを空のスライスにする
         }
+        self.clear_nibble_queue();
+    }
+
+    /// Drop the nibbles left in the queues.
+    fn clear_nibble_queue(&mut self) {
         for q in &mut self.nibble_queue {
-            for _ in 0..q.len() {
-                q.dequeue().unwrap();
-            }
+            while q.dequeue().is_some() {}
         }
     }
 }
@@ -285,7 +449,10 @@ fn parse_data_word(input: &[u8]) -> IResult<&[u8], DataWordNibbles> {
 
 #[cfg(test)]
 mod tests {
-    use crate::imaadpcm::{decode_sample, I1F15};
+    use crate::imaadpcm::{
+        calc_num_samples_per_channel, decode_sample, ImaAdpcmError, ImaAdpcmPlayer, I1F15,
+    };
+    use crate::{AudioFormat, PcmReaderError, PcmSpecs, SpecsField};
     use arbitrary_int::u4;
 
     // http://www.cs.columbia.edu/~hgs/audio/dvi/IMA_ADPCM.pdf
@@ -301,4 +468,114 @@ mod tests {
         assert_eq!(sample, I1F15::from_bits(-30913)); //0x873F
         assert_eq!(step_size_table_index, 23);
     }
+
+    #[test]
+    fn headerless_stereo() {
+        // The high nibble is the left channel.
+        let data = [0x70, 0x7F];
+        let mut player = ImaAdpcmPlayer::new_raw(&data, 8000, 2).unwrap();
+        assert_eq!(player.specs().num_samples, 2);
+        let mut expected = [(I1F15::ZERO, 0); 2];
+        let mut buffer = [I1F15::ZERO; 2];
+        for byte in data {
+            let nibbles = [byte >> 4, byte & 0x0F];
+            player.get_next_frame(&mut buffer).unwrap();
+            for ch in 0..2 {
+                let (sample, index) = expected[ch];
+                expected[ch] = decode_sample(u4::new(nibbles[ch]), sample, index);
+                assert_eq!(buffer[ch], expected[ch].0);
+            }
+        }
+        assert!(matches!(
+            player.get_next_frame(&mut buffer),
+            Err(ImaAdpcmError::FinishPlaying)
+        ));
+
+        assert!(matches!(
+            ImaAdpcmPlayer::new_raw(&data, 8000, 3),
+            Err(ImaAdpcmError::UnsupportedChannels)
+        ));
+        assert!(matches!(
+            ImaAdpcmPlayer::new_raw(&data, 0, 1),
+            Err(ImaAdpcmError::Reader(PcmReaderError::InconsistentSpecs(
+                SpecsField::SampleRate
+            )))
+        ));
+    }
+
+    #[test]
+    fn short_final_block() {
+        let data = include_bytes!("../tests/resources/Sine440Hz_2ch_48000Hz_4bit_IMAADPCM.wav");
+        let mut player = ImaAdpcmPlayer::new(data).unwrap();
+        // Every block of the data, without the limit of the fact chunk.
+        let num_samples =
+            player.reader.data.len() as u32 / player.block_align * player.samples_per_block;
+        // Pretend there is one more block than the data holds.
+        player.reader.specs.num_samples = num_samples + player.samples_per_block;
+
+        let mut buffer = [I1F15::ZERO; 2];
+        for _ in 0..num_samples {
+            player.get_next_frame(&mut buffer).unwrap();
+        }
+        assert!(matches!(
+            player.get_next_frame(&mut buffer),
+            Err(ImaAdpcmError::BlockLengthMismatch)
+        ));
+    }
+
+    #[test]
+    fn positions_near_u32_max() {
+        let data = include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_4bit_IMAADPCM.wav");
+        let mut player = ImaAdpcmPlayer::new(data).unwrap();
+        player.reader.specs.num_samples = u32::MAX;
+        player.frame_index = u64::from(u32::MAX) - 1;
+
+        let mut buffer = [I1F15::ZERO; 1];
+        assert!(matches!(
+            player.get_next_frame(&mut buffer),
+            Err(ImaAdpcmError::BlockLengthMismatch)
+        ));
+    }
+
+    #[test]
+    fn calc_num_samples_checked() {
+        let spec = |block_align, samples_per_block| PcmSpecs {
+            audio_format: AudioFormat::ImaAdpcmLe,
+            bit_depth: 4,
+            num_channels: 1,
+            ima_adpcm_num_block_align: Some(block_align),
+            ima_adpcm_num_samples_per_block: Some(samples_per_block),
+            ..Default::default()
+        };
+        assert_eq!(
+            calc_num_samples_per_channel(1024 * 10, &spec(1024, 2041)).unwrap(),
+            20410
+        );
+        // The final block is cut in the middle: the header and 2 data words.
+        assert_eq!(
+            calc_num_samples_per_channel(1024 * 10 + 12, &spec(1024, 2041)).unwrap(),
+            20410 + 17
+        );
+        // Only a part of the header is left.
+        assert_eq!(
+            calc_num_samples_per_channel(1024 * 10 + 3, &spec(1024, 2041)).unwrap(),
+            20410
+        );
+        assert!(matches!(
+            calc_num_samples_per_channel(1024, &spec(0, 2041)),
+            Err(PcmReaderError::InconsistentSpecs(SpecsField::BlockAlign))
+        ));
+        assert!(matches!(
+            calc_num_samples_per_channel(u32::MAX.into(), &spec(4, 2041)),
+            Err(PcmReaderError::InconsistentSpecs(SpecsField::NumSamples))
+        ));
+        let not_ima = PcmSpecs {
+            audio_format: AudioFormat::LinearPcmLe,
+            ..spec(1024, 2041)
+        };
+        assert!(matches!(
+            calc_num_samples_per_channel(1024, &not_ima),
+            Err(PcmReaderError::UnsupportedAudioFormat)
+        ));
+    }
 }
@@ -11,7 +11,7 @@
 //!
 //! let wav = include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_16.wav");                        
 //! let reader = PcmReader::new(wav).unwrap();
-//! let specs = reader.get_pcm_specs();
+//! let specs = reader.specs();
 //! let num_samples = specs.num_samples;
 //! let num_channels = specs.num_channels;
 //!
@@ -24,20 +24,66 @@
 //!     }
 //! }
 //! ```
-#![cfg_attr(not(test), no_std)]
+//!
+//! # Unaligned data
+//!
+//! Files may start at any address, e.g. in memory-mapped QSPI flash where some MCUs fault on unaligned 32-bit loads.
+//! Every decoder, including the block decoders and the IMA-ADPCM parser, reads the samples byte by byte
+//! and assembles them with `from_le_bytes`/`from_be_bytes`, so the compiler only emits loads which are valid at any address.
+//!
+//! # Features
+//!
+//! * `f64` - 64-bit IEEE float files can be read and written. On by default.
+//!   Without it, such files return [PcmReaderError::UnsupportedBitDepth] and no double-precision arithmetic is compiled in.
+//! * `unchecked-decode` - The inner loops of the block decoder ([PcmReader::decode_block_f32]) skip bounds checks.
+//!   The range is validated once per block. Off by default.
+//! * `computed-decode` - The 8-bit decoders in [g711] expand samples arithmetically instead of using 256-entry lookup tables.
+//!   Saves ROM at the cost of speed. Off by default.
+//! * `libm` - Math routines which are not in core, e.g. for [pitch::note_ratio], use libm instead of fixed-point approximations. Off by default.
+//! * `std` - Link the standard library. Off by default.
+//! * `alloc` - Metadata tables, e.g. [PcmReader::chunks], are stored on the heap and are not limited in size.
+//!   [metadata::text_lossy] converts text fields to strings. Off by default.
+//! * `cpal` - Playback through [cpal](https://crates.io/crates/cpal) with the `cpal_adapter` module. Enables `std`. Off by default.
+//! * `async` - Async fill APIs, e.g. `PcmPlayer::fill_interleaved_async`, and streaming from [stream::AsyncBlockSource] sources.
+//!   No executor is required. Off by default.
+//! * `embedded-sdmmc` - [stream::BlockSource] is implemented for `embedded_sdmmc::File`, to stream files from an SD card. Off by default.
+//! * `wasm` - Owned wrapper for JavaScript through wasm-bindgen in the `wasm` module. Enables `alloc`. Off by default.
+//! * `ffi` - C API of the reader and the player in the `ffi` module. The header is `include/pacmog.h`. Off by default.
+//! * `log` - Parse-time diagnostics through [log](https://crates.io/crates/log): the chunks found, the selected format
+//!   and the reason why a file is rejected. Off by default.
+//! * `defmt` - The same diagnostics through [defmt](https://crates.io/crates/defmt). [AudioFormat] and [SpecsField] implement `defmt::Format`. Off by default.
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
 
-use heapless::Vec;
-use nom::number::complete::{
-    be_f32, be_f64, be_i16, be_i24, be_i32, le_f32, le_f64, le_i16, le_i24, le_i32,
-};
-use nom::Finish;
-use nom::{multi::fold_many1, IResult};
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+use table::Table;
+
+#[macro_use]
+mod diag;
 
 mod aiff;
+mod caf;
+#[cfg(feature = "cpal")]
+pub mod cpal_adapter;
+pub mod dma;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod g711;
+pub mod i2s;
 pub mod imaadpcm;
+mod math;
+pub mod metadata;
+pub mod msadpcm;
+pub mod pitch;
+pub mod stream;
+mod table;
+pub mod transcode;
+mod w64;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 mod wav;
-
-const MAX_NUM_CHUNKS: usize = 16;
+pub mod writer;
 
 /// Error type for LinearPCM
 #[derive(Debug, thiserror::Error)]
@@ -52,10 +98,66 @@ pub enum PcmReaderError {
     InvalidSample,
     #[error("RIFF or AIFF header size mismatch")]
     HeaderSizeMismatch,
+    /// The size of a chunk is larger than the rest of the file. Returned in [ParseMode::Strict].
+    #[error("Chunk {id:?} declares {declared} bytes, but only {available} bytes remain")]
+    ChunkSizeExceedsFile {
+        /// ID of the chunk.
+        id: [u8; 4],
+        /// Size of the payload in the chunk header.
+        declared: u32,
+        /// Number of bytes after the chunk header.
+        available: u32,
+    },
+    /// A field of the header is inconsistent, e.g. the number of channels is 0.
+    #[error("Inconsistent specs: {0:?}")]
+    InconsistentSpecs(SpecsField),
+    /// A chunk which must be unique appears again, e.g. a second data chunk. Returned in [ParseMode::Strict].
+    #[error("Duplicate chunk {0:?}")]
+    DuplicateChunk([u8; 4]),
+    /// The input is shorter than the length of the file declared in its header, e.g. a file cut off during a copy.
+    /// Returned in [ParseMode::Strict]. [ParseMode::Lenient] reads the samples which are left.
+    #[error("Truncated file: {expected} bytes declared, but only {available} bytes are present")]
+    TruncatedData {
+        /// Length of the file in its header.
+        expected: u64,
+        /// Length of the input.
+        available: u64,
+    },
+    /// A chunk which describes the format is missing, e.g. the fmt chunk of WAV or the COMM chunk of AIFF.
+    #[error("Missing chunk {0:?}")]
+    MissingChunk([u8; 4]),
+    /// The size of a metadata chunk does not match its fixed structure, e.g. an inst chunk which is not 7 bytes.
+    #[error("Chunk {id:?} has an invalid size of {size} bytes")]
+    InvalidChunkSize {
+        /// ID of the chunk.
+        id: [u8; 4],
+        /// Size of the payload.
+        size: u32,
+    },
+}
+
+/// Field of the header reported by [PcmReaderError::InconsistentSpecs].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SpecsField {
+    /// Number of channels, e.g. 0.
+    NumChannels,
+    /// Sample rate, e.g. 0 Hz.
+    SampleRate,
+    /// Bit depth which is not valid for the audio format, e.g. 0 or 16-bit IEEE float.
+    BitDepth,
+    /// Number of samples per channel, e.g. more than the data holds or too many to count in u32,
+    /// or raw data which is not a whole number of frames.
+    NumSamples,
+    /// Number of bytes per block of IMA-ADPCM, or nBlockAlign of WAV which does not match the channels and the bit depth.
+    BlockAlign,
+    /// nAvgBytesPerSec of WAV which does not match nBlockAlign and the sample rate.
+    ByteRate,
 }
 
 /// Audio format
 #[derive(Debug, Default, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum AudioFormat {
     /// Unknown format
     #[default]
@@ -70,6 +172,12 @@ pub enum AudioFormat {
     IeeeFloatBe,
     /// IMA-ADPCM little endian
     ImaAdpcmLe,
+    /// A-law (ITU-T G.711). 8 bits per sample.
+    ALaw,
+    /// µ-law (ITU-T G.711). 8 bits per sample.
+    MuLaw,
+    /// Microsoft ADPCM little endian
+    MsAdpcmLe,
 }
 
 /// Basic information on the PCM file.
@@ -83,113 +191,980 @@ pub struct PcmSpecs {
     pub sample_rate: u32,
     /// Bit depth.
     pub bit_depth: u16,
-    /// Number of samples per channel.
+    /// Number of bytes in which a sample is stored, e.g. 3 for 20-bit samples in 24-bit containers.
+    /// Usually the bit depth rounded up to bytes. 0 for IMA-ADPCM and MS-ADPCM.
+    pub container_bytes_per_sample: u16,
+    /// Number of samples per channel. 0 if the data chunk is empty.
     pub num_samples: u32,
+    /// Where num_samples was taken from.
+    pub num_samples_source: NumSamplesSource,
     /// IMA-ADPCM only. Number of bytes per block of IMA-ADPCM.
     pub(crate) ima_adpcm_num_block_align: Option<u16>,
     /// IMA-ADPCM only. Number of samples per block of IMA-ADPCM.
     pub(crate) ima_adpcm_num_samples_per_block: Option<u16>,
+    /// MS-ADPCM only. Number of bytes per block of MS-ADPCM.
+    pub(crate) ms_adpcm_num_block_align: Option<u16>,
+    /// MS-ADPCM only. Number of samples per block of MS-ADPCM.
+    pub(crate) ms_adpcm_num_samples_per_block: Option<u16>,
+    /// MS-ADPCM only. Coefficient pairs of the predictors from the fmt chunk.
+    pub(crate) ms_adpcm_coefficients: Option<[[i16; 2]; msadpcm::NUM_COEFFICIENTS]>,
+}
+
+impl PcmSpecs {
+    /// Create the specs of headerless PCM data for [PcmReader::new_raw].
+    /// num_samples is computed by the reader, and the samples are stored in the bit depth rounded up to bytes.
+    pub fn new(
+        audio_format: AudioFormat,
+        num_channels: u16,
+        sample_rate: u32,
+        bit_depth: u16,
+    ) -> Self {
+        PcmSpecs {
+            audio_format,
+            num_channels,
+            sample_rate,
+            bit_depth,
+            container_bytes_per_sample: bit_depth.div_ceil(8),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the duration in seconds. 0.0 if the sample rate is 0.
+    #[must_use]
+    pub fn duration_secs(&self) -> f32 {
+        if self.sample_rate == 0 {
+            return 0.0;
+        }
+        self.num_samples as f32 / self.sample_rate as f32
+    }
+
+    /// Returns the duration in milliseconds, rounded to the nearest. 0 if the sample rate is 0.
+    /// Saturates at u32::MAX, which is only exceeded at sample rates below 1000 Hz.
+    #[must_use]
+    pub fn duration_millis(&self) -> u32 {
+        if self.sample_rate == 0 {
+            return 0;
+        }
+        let sample_rate = u64::from(self.sample_rate);
+        let millis = (u64::from(self.num_samples) * 1000 + sample_rate / 2) / sample_rate;
+        u32::try_from(millis).unwrap_or(u32::MAX)
+    }
+
+    /// Returns the exact duration, truncated to nanoseconds. Zero if the sample rate is 0.
+    #[must_use]
+    pub fn duration(&self) -> core::time::Duration {
+        if self.sample_rate == 0 {
+            return core::time::Duration::ZERO;
+        }
+        let secs = self.num_samples / self.sample_rate;
+        // The remainder is less than the sample rate, so the product fits in u64.
+        let nanos = u64::from(self.num_samples % self.sample_rate) * 1_000_000_000
+            / u64::from(self.sample_rate);
+        core::time::Duration::new(secs.into(), nanos as u32)
+    }
+
+    /// Returns the number of frames in ms milliseconds at the sample rate, rounded to the nearest, e.g. for a fade.
+    /// 0 if the sample rate is 0. Saturates at u32::MAX.
+    #[must_use]
+    pub fn frames_for_duration(&self, ms: u32) -> u32 {
+        let frames = (u64::from(ms) * u64::from(self.sample_rate) + 500) / 1000;
+        u32::try_from(frames).unwrap_or(u32::MAX)
+    }
+}
+
+/// Container of the file read by [PcmReader].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ContainerFormat {
+    /// Headerless data of [PcmReader::new_raw].
+    #[default]
+    Raw,
+    /// RIFF WAVE.
+    Wav,
+    /// RF64, WAV with 64-bit sizes.
+    Rf64,
+    /// Sony Wave64.
+    Wave64,
+    /// AIFF, which has no compression type.
+    Aiff,
+    /// AIFF-C, whose COMM chunk has a compression type.
+    AiffC,
+    /// Core Audio Format of Apple.
+    Caf,
+}
+
+/// The only version of AIFF-C, the timestamp in the FVER chunk of AIFF-C files. See [PcmReader::aifc_version].
+pub const AIFC_VERSION_1: u32 = 0xA280_5140;
+
+/// Source of [PcmSpecs::num_samples].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NumSamplesSource {
+    /// Computed from the size of the data chunk.
+    #[default]
+    DataSize,
+    /// The fact chunk of WAV, which reported fewer samples than the data chunk holds, e.g. because the data chunk is padded.
+    FactChunk,
+    /// The number of sample frames in the COMM chunk of AIFF.
+    CommChunk,
+}
+
+/// How [PcmReader] treats files which do not follow the specification.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ParseMode {
+    /// Malformed files are rejected with an error describing the problem.
+    #[default]
+    Strict,
+    /// Problems which can be repaired are repaired, e.g. a chunk larger than the rest of the file is clamped to the end of the file.
+    /// A corrupt chunk, e.g. a LIST chunk with a broken size, is skipped up to the next known chunk.
+    /// The bit depth of a WAV fmt chunk is corrected if nBlockAlign and nAvgBytesPerSec agree on another one.
+    /// Duplicates of the fmt, data, COMM and SSND chunks are skipped.
+    /// Sizes left unset by streaming writers, i.e. a RIFF or FORM size of 0 or 0xFFFFFFFF and a data or SSND size of 0, extend to the end of the input.
+    Lenient,
+}
+
+/// Options for [PcmReader::new_with_options].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ReaderOptions {
+    /// Handling of malformed files.
+    pub mode: ParseMode,
+    /// Parse only the size declared in the RIFF or FORM header, plus the 8 bytes of the header.
+    /// The input may be longer than the file, e.g. a flash partition padded after the file. It must not be shorter.
+    /// If false, the input must be exactly as long as the file.
+    pub trust_declared_size: bool,
+}
+
+/// Default maximum number of chunks recorded in the chunk table of [PcmReader].
+/// Use [PcmReader::new_with_chunk_capacity] for another capacity.
+pub const MAX_NUM_CHUNKS: usize = 16;
+
+/// Maximum number of corrupt chunks skipped per file in [ParseMode::Lenient].
+/// Each skip searches the rest of the file, so the parse time is bounded.
+const MAX_RESYNCS: usize = 8;
+
+/// Maximum number of data and slnt chunks of a LIST chunk of type wavl. Ignored with the `alloc` feature.
+pub const MAX_NUM_SEGMENTS: usize = 8;
+
+/// Maximum number of channels of the frames yielded by [Frames], e.g. 7.1 surround.
+/// Frames of more channels are read with [PcmReader::read_frames].
+pub const MAX_NUM_FRAME_CHANNELS: usize = 8;
+
+/// A frame yielded by [Frames]. The length is the number of channels.
+pub type Frame<T> = heapless::Vec<T, MAX_NUM_FRAME_CHANNELS>;
+
+/// Location of a chunk in the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkInfo {
+    /// Chunk ID, e.g. b"fmt ".
+    pub id: [u8; 4],
+    /// Offset of the payload from the beginning of the file in bytes.
+    pub offset: u32,
+    /// Size of the payload in bytes.
+    pub size: u32,
+}
+
+/// A data or slnt chunk of a LIST chunk of type wavl.
+/// The samples are the segments in turn, and a slnt chunk is a run of silent frames.
+#[derive(Debug, Default, Clone, Copy)]
+struct Segment {
+    /// Offset of the samples of a data chunk in the file.
+    offset: u32,
+    /// Size of the samples of a data chunk in bytes. 0 for a slnt chunk.
+    size: u32,
+    /// Number of frames. The frames of a data chunk are counted after all chunks are parsed, since the fmt chunk may follow.
+    num_frames: u32,
+    /// Whether it is a slnt chunk.
+    is_silence: bool,
+}
+
+/// Frames in the data, or a run of silence of a wavl list.
+enum Span<'a> {
+    Data(&'a [u8]),
+    Silence,
+}
+
+/// The chunk of the format is missing, so the samples cannot be decoded.
+fn missing_chunk(id: [u8; 4]) -> PcmReaderError {
+    debug!("no {} chunk", diag::fourcc(&id));
+    PcmReaderError::MissingChunk(id)
+}
+
+/// Errors of the nom parsers mean that the file is not supported.
+fn unsupported<E>(_: E) -> PcmReaderError {
+    PcmReaderError::UnsupportedAudioFormat
+}
+
+/// Floating point type of the samples of [PcmReader::read_frame].
+/// The samples are decoded to f32 and widened, so f64 holds the same values.
+pub trait Float: Copy {
+    /// Value of silence.
+    const ZERO: Self;
+
+    /// Convert a normalized sample.
+    fn from_f32(value: f32) -> Self;
+}
+
+impl Float for f32 {
+    const ZERO: Self = 0.0;
+
+    #[inline]
+    fn from_f32(value: f32) -> Self {
+        value
+    }
+}
+
+#[cfg(feature = "f64")]
+impl Float for f64 {
+    const ZERO: Self = 0.0;
+
+    #[inline]
+    fn from_f32(value: f32) -> Self {
+        value.into()
+    }
 }
 
+/// Decodes a sample into a normalized value. The length of the bytes must be the bytes per sample.
+type DecodeFn = fn(&[u8]) -> f32;
+
 /// Reads low level information and Data chunks from the PCM file.
-#[derive(Default)]
-pub struct PcmReader<'a> {
+/// * 'MAX_CHUNKS' - Capacity of the chunk table. Broadcast WAV files often have more than [MAX_NUM_CHUNKS] chunks.
+///   Ignored with the `alloc` feature.
+#[derive(Default, Clone)]
+pub struct PcmReader<'a, const MAX_CHUNKS: usize = MAX_NUM_CHUNKS> {
     pub(crate) specs: PcmSpecs,
     pub(crate) data: &'a [u8],
+    /// The whole file, to access chunks lazily.
+    input: &'a [u8],
+    /// Locations of the chunks in the order of the file. Chunks after the first MAX_CHUNKS are not recorded.
+    chunks: Table<ChunkInfo, MAX_CHUNKS>,
+    /// Segments of a LIST chunk of type wavl, in the order of the samples. Empty for a plain data chunk.
+    segments: Table<Segment, MAX_NUM_SEGMENTS>,
+    /// Number of bytes per sample of a channel. Cached by reload.
+    bytes_per_sample: usize,
+    /// Number of bytes per frame. Cached by reload.
+    bytes_per_frame: usize,
+    /// Decoder for the format. None if read_sample does not support the format. Cached by reload.
+    decode: Option<DecodeFn>,
+    /// Options used by reload.
+    options: ReaderOptions,
+    /// Container of the file.
+    container: ContainerFormat,
 }
 
 impl<'a> PcmReader<'a> {
     /// Create a new PcmReader instance.
     /// * 'input' - PCM data byte array
     pub fn new(input: &'a [u8]) -> Result<Self, PcmReaderError> {
-        let file_length = input.len();
+        Self::new_with_chunk_capacity(input)
+    }
+
+    /// Create a new PcmReader instance from headerless PCM data, e.g. generated at build time.
+    /// * 'data' - Interleaved samples
+    /// * 'specs' - Format of the samples. num_samples is computed from the length of the data.
+    ///
+    /// Returns [PcmReaderError::UnsupportedBitDepth] if read_sample does not decode the format, e.g. 4-bit linear PCM,
+    /// and [PcmReaderError::InconsistentSpecs] if the specs are invalid or the data is not a whole number of frames.
+    /// IMA-ADPCM is not supported, since its block size is not part of the specs.
+    ///
+    /// ```
+    /// use pacmog::{AudioFormat, PcmReader, PcmSpecs};
+    ///
+    /// // Two stereo frames of 16-bit little endian samples
+    /// let data = [0x00, 0x40, 0x00, 0xC0, 0xFF, 0x7F, 0x00, 0x80];
+    /// let specs = PcmSpecs::new(AudioFormat::LinearPcmLe, 2, 48000, 16);
+    /// let reader = PcmReader::new_raw(&data, specs).unwrap();
+    /// assert_eq!(reader.specs().num_samples, 2);
+    /// assert_eq!(reader.read_sample(1, 0).unwrap(), -0.5);
+    /// assert_eq!(reader.read_sample(1, 1).unwrap(), -1.0);
+    /// ```
+    pub fn new_raw(data: &'a [u8], specs: PcmSpecs) -> Result<Self, PcmReaderError> {
+        let mut reader = PcmReader {
+            specs: PcmSpecs {
+                num_samples: 0,
+                num_samples_source: NumSamplesSource::DataSize,
+                ..specs
+            },
+            data,
+            input: data,
+            ..Default::default()
+        };
+        if matches!(
+            reader.specs.audio_format,
+            AudioFormat::Unknown | AudioFormat::ImaAdpcmLe | AudioFormat::MsAdpcmLe
+        ) {
+            return Err(PcmReaderError::UnsupportedAudioFormat);
+        }
+        if reader.specs.container_bytes_per_sample == 0 {
+            reader.specs.container_bytes_per_sample = reader.specs.bit_depth.div_ceil(8);
+        }
+        reader.validate_specs()?;
+        if decode_fn(&reader.specs).is_none() {
+            return Err(PcmReaderError::UnsupportedBitDepth);
+        }
+        let bytes_per_frame = usize::from(reader.specs.container_bytes_per_sample)
+            * usize::from(reader.specs.num_channels);
+        if data.len() % bytes_per_frame != 0 {
+            return Err(PcmReaderError::InconsistentSpecs(SpecsField::NumSamples));
+        }
+        reader.specs.num_samples =
+            wav::calc_num_samples_per_channel(data.len() as u64, &reader.specs)?;
+        reader.update_cache();
+        Ok(reader)
+    }
+}
+
+impl<'a, const MAX_CHUNKS: usize> PcmReader<'a, MAX_CHUNKS> {
+    /// Create a new PcmReader instance which records up to MAX_CHUNKS chunks.
+    /// * 'input' - PCM data byte array
+    ///
+    /// ```
+    /// use pacmog::PcmReader;
+    ///
+    /// let wav = include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_16.wav");
+    /// let reader = PcmReader::<32>::new_with_chunk_capacity(wav).unwrap();
+    /// assert_eq!(reader.specs().num_channels, 1);
+    /// ```
+    pub fn new_with_chunk_capacity(input: &'a [u8]) -> Result<Self, PcmReaderError> {
+        Self::new_with_options(input, ReaderOptions::default())
+    }
+
+    /// Create a new PcmReader instance with options. The options are also used by [PcmReader::reload].
+    /// * 'input' - PCM data byte array
+    ///
+    /// ```
+    /// use pacmog::{ParseMode, PcmReader, PcmReaderError, ReaderOptions};
+    ///
+    /// let wav = include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_16.wav");
+    /// // The data chunk is cut off.
+    /// let mut truncated = wav[..1104].to_vec();
+    /// truncated[4..8].copy_from_slice(&1096u32.to_le_bytes());
+    /// assert!(matches!(
+    ///     PcmReader::new(&truncated),
+    ///     Err(PcmReaderError::ChunkSizeExceedsFile { .. })
+    /// ));
+    ///
+    /// let options = ReaderOptions {
+    ///     mode: ParseMode::Lenient,
+    ///     ..Default::default()
+    /// };
+    /// let reader = PcmReader::<16>::new_with_options(&truncated, options).unwrap();
+    /// assert_eq!(reader.specs().num_samples, 500);
+    ///
+    /// // A flash partition padded with 0xFF after the file.
+    /// let mut partition = wav.to_vec();
+    /// partition.resize(wav.len() + 4096, 0xFF);
+    /// assert!(PcmReader::new(&partition).is_err());
+    /// let options = ReaderOptions {
+    ///     trust_declared_size: true,
+    ///     ..Default::default()
+    /// };
+    /// let reader = PcmReader::<16>::new_with_options(&partition, options).unwrap();
+    /// assert_eq!(reader.specs().num_samples, 240000);
+    /// ```
+    pub fn new_with_options(
+        input: &'a [u8],
+        options: ReaderOptions,
+    ) -> Result<Self, PcmReaderError> {
         let mut reader = PcmReader {
-            data: &[],
-            specs: PcmSpecs::default(),
+            options,
+            ..Default::default()
         };
+        reader.reload(input)?;
+        Ok(reader)
+    }
+
+    /// Reload a new PCM byte array.
+    /// Returns [PcmReaderError::InconsistentSpecs] if the header is inconsistent, e.g. the number of channels is 0.
+    pub fn reload(&mut self, input: &'a [u8]) -> Result<(), PcmReaderError> {
+        let result = self.parse(input).and_then(|()| self.validate_specs());
+        self.update_cache();
+        if result.is_ok() {
+            debug!(
+                "{:?}, {} channels, {} Hz, {} bits, {} frames",
+                self.specs.audio_format,
+                self.specs.num_channels,
+                self.specs.sample_rate,
+                self.specs.bit_depth,
+                self.specs.num_samples
+            );
+        }
+        result
+    }
+
+    fn parse(&mut self, input: &'a [u8]) -> Result<(), PcmReaderError> {
+        self.data = &[];
+        self.specs = PcmSpecs::default();
+        self.input = input;
+        self.chunks.clear();
+        self.segments.clear();
+        self.container = ContainerFormat::Raw;
 
         // Parse WAVE format
-        if let Ok((input, riff)) = wav::parse_riff_header(input) {
-            if (file_length - 8) != riff.size as usize {
-                return Err(PcmReaderError::HeaderSizeMismatch);
-            }
+        if let Ok((_, riff)) = wav::parse_riff_header(input) {
+            self.container = ContainerFormat::Wav;
+            let file_length = self.riff_file_length(riff.size, input);
+            let chunks = self.declared_file(input, file_length, 12)?;
+            return self
+                .parse_wav(chunks, None)
+                .inspect_err(|_| debug!("WAV rejected"));
+        }
 
-            if let Ok((_, _)) = reader.parse_wav(input) {
-                return Ok(reader);
-            }
+        // Parse RF64, whose sizes are in the ds64 chunk
+        if let Ok((rest, ())) = wav::parse_rf64_header(input) {
+            self.container = ContainerFormat::Rf64;
+            let Ok((_, ds64)) = wav::parse_ds64(rest) else {
+                debug!("RF64 without a ds64 chunk");
+                return Err(PcmReaderError::UnsupportedAudioFormat);
+            };
+            let chunks = self.declared_file(input, ds64.riff_size.saturating_add(8), 12)?;
+            return self
+                .parse_wav(chunks, Some(ds64))
+                .inspect_err(|_| debug!("RF64 rejected"));
         }
 
         // Parse AIFF format
-        if let Ok((input, aiff)) = aiff::parse_aiff_header(input) {
-            if (file_length - 8) != aiff.size as usize {
-                return Err(PcmReaderError::HeaderSizeMismatch);
-            }
+        if let Ok((_, aiff)) = aiff::parse_aiff_header(input) {
+            self.container = if aiff.is_aifc {
+                ContainerFormat::AiffC
+            } else {
+                ContainerFormat::Aiff
+            };
+            let file_length = self.riff_file_length(aiff.size, input);
+            let chunks = self.declared_file(input, file_length, 12)?;
+            return self
+                .parse_aiff(chunks)
+                .inspect_err(|_| debug!("AIFF rejected"));
+        }
 
-            if let Ok((_, _)) = reader.parse_aiff(input) {
-                return Ok(reader);
-            }
+        // Parse Wave64
+        if let Ok((_, file_length)) = w64::parse_w64_header(input) {
+            self.container = ContainerFormat::Wave64;
+            let chunks = self.declared_file(input, file_length, w64::HEADER_LEN)?;
+            return self
+                .parse_w64(chunks)
+                .inspect_err(|_| debug!("W64 rejected"));
+        }
+
+        // Parse CAF, which has no file size in the header
+        if let Ok((chunks, ())) = caf::parse_caf_header(input) {
+            self.container = ContainerFormat::Caf;
+            return self
+                .parse_caf(chunks)
+                .inspect_err(|_| debug!("CAF rejected"));
         }
 
         Err(PcmReaderError::UnsupportedAudioFormat)
     }
 
-    /// Reload a new PCM byte array.
-    pub fn reload(&mut self, input: &'a [u8]) -> Result<(), PcmReaderError> {
-        let file_length = input.len();
-        self.data = &[];
-        self.specs = PcmSpecs::default();
+    /// Length of the file from the size in the RIFF or FORM header.
+    /// Streaming writers leave the size 0 or 0xFFFFFFFF until they finish. In [ParseMode::Lenient], such a file extends to the end of the input.
+    fn riff_file_length(&self, size: u32, input: &[u8]) -> u64 {
+        if self.options.mode == ParseMode::Lenient && matches!(size, 0 | u32::MAX) {
+            debug!("placeholder file size {}", size);
+            return input.len() as u64;
+        }
+        u64::from(size) + 8
+    }
 
-        // Parse WAVE format
-        if let Ok((input, riff)) = wav::parse_riff_header(input) {
-            if (file_length - 8) != riff.size as usize {
-                return Err(PcmReaderError::HeaderSizeMismatch);
+    /// Size of a data chunk which declares 0 bytes, e.g. left by a streaming writer which was interrupted.
+    /// In [ParseMode::Lenient], the samples extend to the end of the file, unless the next chunk follows right after the header.
+    /// Sizes larger than the file, e.g. 0xFFFFFFFF, are clamped by [PcmReader::split_payload].
+    fn unsized_data_len(&self, rest: &[u8], next_chunk_follows: bool) -> u64 {
+        if self.options.mode == ParseMode::Lenient && !next_chunk_follows {
+            debug!("data chunk of 0 bytes extended to {} bytes", rest.len());
+            return rest.len() as u64;
+        }
+        0
+    }
+
+    /// Check the length of the file declared in the RIFF or FORM header, or the ds64 chunk of RF64, against the input.
+    /// Returns the chunks after the header of header_len bytes. With [ReaderOptions::trust_declared_size], the bytes after the file are cut off.
+    /// A truncated input is an error, or is parsed to its end in [ParseMode::Lenient].
+    fn declared_file(
+        &mut self,
+        input: &'a [u8],
+        file_length: u64,
+        header_len: usize,
+    ) -> Result<&'a [u8], PcmReaderError> {
+        let available = input.len() as u64;
+        if file_length > available {
+            debug!(
+                "file length {} exceeds the input length {}",
+                file_length, available
+            );
+            match self.options.mode {
+                ParseMode::Strict => {
+                    return Err(PcmReaderError::TruncatedData {
+                        expected: file_length,
+                        available,
+                    })
+                }
+                // The chunks cut off are clamped to the end of the input.
+                ParseMode::Lenient => {
+                    self.input = input;
+                    return Ok(input.get(header_len..).unwrap_or_default());
+                }
             }
+        }
+        if !self.options.trust_declared_size && file_length != available {
+            debug!(
+                "file length {} does not match the input length {}",
+                file_length,
+                input.len()
+            );
+            return Err(PcmReaderError::HeaderSizeMismatch);
+        }
+        // Fits in the input, so it fits in usize.
+        let file = &input[..file_length as usize];
+        // Offsets of the chunks are relative to the file.
+        self.input = file;
+        // A header size less than 4 does not cover the form type.
+        Ok(file.get(header_len..).unwrap_or_default())
+    }
 
-            if let Ok((_, _)) = self.parse_wav(input) {
-                return Ok(());
+    /// Check the specs parsed from the header.
+    /// Bit depths which are valid for the format but not decoded, e.g. 64-bit float without the `f64` feature,
+    /// are reported by the decoders instead.
+    fn validate_specs(&mut self) -> Result<(), PcmReaderError> {
+        let specs = &self.specs;
+        let field = if specs.num_channels == 0 {
+            Some(SpecsField::NumChannels)
+        } else if specs.sample_rate == 0 {
+            Some(SpecsField::SampleRate)
+        } else if !match specs.audio_format {
+            AudioFormat::LinearPcmLe | AudioFormat::LinearPcmBe => {
+                (1..=32).contains(&specs.bit_depth)
             }
+            AudioFormat::IeeeFloatLe | AudioFormat::IeeeFloatBe => {
+                matches!(specs.bit_depth, 32 | 64)
+            }
+            AudioFormat::ImaAdpcmLe | AudioFormat::MsAdpcmLe => specs.bit_depth == 4,
+            AudioFormat::ALaw | AudioFormat::MuLaw => specs.bit_depth == 8,
+            AudioFormat::Unknown => false,
+        } {
+            Some(SpecsField::BitDepth)
+        } else if !matches!(
+            specs.audio_format,
+            AudioFormat::ImaAdpcmLe | AudioFormat::MsAdpcmLe
+        ) && specs.container_bytes_per_sample < specs.bit_depth.div_ceil(8)
+        {
+            // The samples do not fit in their containers.
+            Some(SpecsField::BlockAlign)
+        } else {
+            None
+        };
+        if let Some(field) = field {
+            debug!("inconsistent specs: {:?}", field);
+            return Err(PcmReaderError::InconsistentSpecs(field));
         }
 
-        // Parse AIFF format
-        if let Ok((input, aiff)) = aiff::parse_aiff_header(input) {
-            if (file_length - 8) != aiff.size as usize {
-                return Err(PcmReaderError::HeaderSizeMismatch);
+        // The data must hold num_samples frames. ADPCM and wavl lists count the samples from the data.
+        if self.segments.is_empty()
+            && !matches!(
+                specs.audio_format,
+                AudioFormat::ImaAdpcmLe | AudioFormat::MsAdpcmLe
+            )
+        {
+            let bytes_per_frame =
+                u64::from(specs.container_bytes_per_sample) * u64::from(specs.num_channels);
+            let available = self.data.len() as u64 / bytes_per_frame;
+            if u64::from(specs.num_samples) > available {
+                debug!(
+                    "{} frames declared, but the data holds {}",
+                    specs.num_samples, available
+                );
+                match self.options.mode {
+                    ParseMode::Strict => {
+                        return Err(PcmReaderError::InconsistentSpecs(SpecsField::NumSamples))
+                    }
+                    // Less than num_samples, so it fits in u32.
+                    ParseMode::Lenient => {
+                        self.specs.num_samples = available as u32;
+                        self.specs.num_samples_source = NumSamplesSource::DataSize;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Handle a second fmt, data, COMM or SSND chunk.
+    /// The first one is used in both modes, so the duplicate is an error in strict mode and skipped in lenient mode.
+    fn duplicate_chunk(&self, id: [u8; 4]) -> Result<(), PcmReaderError> {
+        debug!("duplicate chunk {}", diag::fourcc(&id));
+        match self.options.mode {
+            ParseMode::Strict => Err(PcmReaderError::DuplicateChunk(id)),
+            ParseMode::Lenient => Ok(()),
+        }
+    }
+
+    /// Whether the chunk scan may skip a corrupt chunk by searching for the next known chunk.
+    /// Only in [ParseMode::Lenient], and at most MAX_RESYNCS times per file.
+    fn may_resync(&self, num_resyncs: usize) -> bool {
+        self.options.mode == ParseMode::Lenient && num_resyncs < MAX_RESYNCS
+    }
+
+    /// Record the location of a chunk. The payload starts at the beginning of the input.
+    fn record_chunk(&mut self, id: [u8; 4], size: usize, payload: &[u8]) {
+        let offset = self.input.len() - payload.len();
+        // Chunks beyond 4 GiB of an RF64 file cannot be located in 32 bits, so they are not recorded.
+        let (Ok(offset), Ok(size)) = (u32::try_from(offset), u32::try_from(size)) else {
+            return;
+        };
+        // Chunks which do not fit in the table are skipped.
+        self.chunks.push(ChunkInfo { id, offset, size });
+    }
+
+    /// Split the payload of a chunk from the rest of the file, which starts at the payload.
+    /// The next chunk starts at a multiple of align bytes, e.g. 2 for RIFF and IFF, so odd-sized chunks are followed by a pad byte.
+    /// A chunk larger than the rest of the file is an error, or is clamped to the end of the file in [ParseMode::Lenient].
+    fn split_payload(
+        &self,
+        id: [u8; 4],
+        size: u64,
+        align: u64,
+        rest: &'a [u8],
+    ) -> Result<(&'a [u8], &'a [u8]), PcmReaderError> {
+        if let Some((payload, next)) = usize::try_from(size)
+            .ok()
+            .and_then(|size| rest.split_at_checked(size))
+        {
+            // The padding may be missing at the end of the file.
+            let padding = (align - size % align) % align;
+            let next = next.get(padding as usize..).unwrap_or(next);
+            return Ok((payload, next));
+        }
+        debug!(
+            "chunk {} size {} exceeds the file, {} bytes remain",
+            diag::fourcc(&id),
+            size,
+            rest.len()
+        );
+        match self.options.mode {
+            // The sizes of RF64 are saturated.
+            ParseMode::Strict => Err(PcmReaderError::ChunkSizeExceedsFile {
+                id,
+                declared: u32::try_from(size).unwrap_or(u32::MAX),
+                available: u32::try_from(rest.len()).unwrap_or(u32::MAX),
+            }),
+            ParseMode::Lenient => Ok((rest, &[])),
+        }
+    }
+
+    /// Returns the locations of the chunks in the order of the file.
+    /// Up to MAX_CHUNKS chunks are recorded, or all of them with the `alloc` feature.
+    pub fn chunks(&self) -> &[ChunkInfo] {
+        &self.chunks
+    }
+
+    /// Returns the payload of the first chunk with the ID, if it is in the chunk table.
+    pub fn chunk_data(&self, id: &[u8; 4]) -> Option<&'a [u8]> {
+        let chunk = self.chunks.iter().find(|c| &c.id == id)?;
+        self.payload(chunk)
+    }
+
+    /// Returns the tags of the first LIST chunk of type INFO in the chunk table, e.g. INAM for the name and IART for the artist.
+    /// The text is borrowed from the input and is not decoded. Yields nothing if there is no such chunk. See [metadata::info_tags].
+    ///
+    /// ```
+    /// use pacmog::PcmReader;
+    ///
+    /// let wav = include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_16_INFO.wav");
+    /// let reader = PcmReader::new(wav).unwrap();
+    /// for (id, text) in reader.info_metadata() {
+    ///     println!("{}: {}", core::str::from_utf8(&id).unwrap(), String::from_utf8_lossy(text));
+    /// }
+    /// let name = reader.info_metadata().find(|(id, _)| id == b"INAM");
+    /// assert_eq!(name, Some((*b"INAM", &b"Sine 440Hz"[..])));
+    /// ```
+    pub fn info_metadata(&self) -> metadata::InfoTags<'a> {
+        // Other LIST chunks, e.g. of type adtl, may precede it.
+        let list = self
+            .chunks
+            .iter()
+            .filter(|c| &c.id == b"LIST")
+            .filter_map(|c| self.payload(c))
+            .find(|payload| payload.starts_with(b"INFO"))
+            .unwrap_or_default();
+        metadata::info_tags(list)
+    }
+
+    /// Returns the payload of the first ID3 chunk in the chunk table, which holds ID3v2 tags to be parsed by the caller.
+    /// The ID is `id3 ` in most WAV files and `ID3 ` in AIFF. `IDv3` is also accepted.
+    pub fn id3_chunk(&self) -> Option<&'a [u8]> {
+        let chunk = self
+            .chunks
+            .iter()
+            .find(|c| matches!(&c.id, b"id3 " | b"ID3 " | b"IDv3"))?;
+        self.payload(chunk)
+    }
+
+    /// Returns the cue points of the first cue chunk of WAV in the chunk table, in the order of the chunk.
+    /// The cue points are read up to the count in the chunk and the end of the chunk. Yields nothing if there is no cue chunk.
+    pub fn cue_points(&self) -> impl Iterator<Item = metadata::CuePoint> + 'a {
+        let data = self.chunk_data(b"cue ").unwrap_or_default();
+        let (points, count) = wav::parse_cue_header(data).unwrap_or((&[], 0));
+        points
+            .chunks_exact(24)
+            .take(count as usize)
+            .filter_map(|point| wav::parse_cue_point(point).ok().map(|(_, c)| c))
+    }
+
+    /// Returns the sampler information of the smpl chunk of WAV, e.g. the loop points for wavetable playback.
+    /// Loops which are not in the samples, i.e. whose end is before the start or not less than num_samples, are dropped.
+    /// Returns None if there is no smpl chunk in the chunk table or it is too short.
+    pub fn sampler_info(&self) -> Option<metadata::SamplerInfo> {
+        let (_, mut info) = wav::parse_smpl(self.chunk_data(b"smpl")?).ok()?;
+        let num_samples = self.specs.num_samples;
+        info.loops.retain(|l| {
+            let valid = l.start <= l.end && l.end < num_samples;
+            if !valid {
+                debug!(
+                    "smpl loop {}..={} is not in the {} frames",
+                    l.start, l.end, num_samples
+                );
             }
+            valid
+        });
+        Some(info)
+    }
 
-            if let Ok((_, _)) = self.parse_aiff(input) {
-                return Ok(());
+    /// Returns the instrument information of the inst chunk of WAV, e.g. the root key and the gain of a multisample.
+    /// Returns None if there is no inst chunk in the chunk table, and [PcmReaderError::InvalidChunkSize] if it is not 7 bytes.
+    pub fn instrument(&self) -> Result<Option<metadata::Instrument>, PcmReaderError> {
+        let Some(inst) = self.chunk_data(b"inst") else {
+            return Ok(None);
+        };
+        match wav::parse_inst(inst) {
+            Ok((&[], instrument)) => Ok(Some(instrument)),
+            _ => Err(PcmReaderError::InvalidChunkSize {
+                id: *b"inst",
+                size: inst.len() as u32,
+            }),
+        }
+    }
+
+    /// Returns the instrument information of the INST chunk of AIFF, e.g. the base note and the sustain loop.
+    /// The loops are resolved to sample frames through the markers of the MARK chunk.
+    /// A loop which is not played, whose markers are missing or which is not in the samples is None.
+    /// Returns None if there is no INST chunk in the chunk table or it is too short.
+    pub fn aiff_instrument(&self) -> Option<metadata::AiffInstrument> {
+        let (_, (mut instrument, sustain_loop, release_loop)) =
+            aiff::parse_inst(self.chunk_data(b"INST")?).ok()?;
+        let mark = self.chunk_data(b"MARK");
+        let num_samples = self.specs.num_samples;
+        let resolve = |inst_loop| {
+            let (begin, end) = aiff::resolve_loop(&inst_loop, mark)?;
+            if begin >= end || end > num_samples {
+                debug!(
+                    "INST loop {}..{} is not in the {} frames",
+                    begin, end, num_samples
+                );
+                return None;
             }
+            Some((begin, end))
+        };
+        instrument.sustain_loop = resolve(sustain_loop);
+        instrument.release_loop = resolve(release_loop);
+        Some(instrument)
+    }
+
+    /// Returns the root note of the samples for [pitch::note_ratio], from the INST chunk of AIFF, the smpl chunk of WAV or the inst chunk of WAV, in this order.
+    /// Returns None if there is none of them or they are malformed.
+    pub fn root_note(&self) -> Option<pitch::Root> {
+        if let Some(inst) = self.aiff_instrument() {
+            return Some((&inst).into());
+        }
+        if let Some(info) = self.sampler_info() {
+            return Some((&info).into());
         }
+        self.instrument().ok().flatten().map(|inst| (&inst).into())
+    }
 
-        Err(PcmReaderError::UnsupportedAudioFormat)
+    /// Returns the payload of the NAME chunk of AIFF, the name of the sound. The text is not decoded.
+    pub fn name(&self) -> Option<&'a [u8]> {
+        self.chunk_data(b"NAME")
     }
 
-    fn parse_aiff(&mut self, input: &'a [u8]) -> IResult<&[u8], &[u8]> {
-        let (input, v) = fold_many1(
-            aiff::parse_chunk,
-            Vec::<aiff::Chunk, MAX_NUM_CHUNKS>::new,
-            |mut chunk_array: Vec<aiff::Chunk, MAX_NUM_CHUNKS>, item| {
-                chunk_array.push(item).unwrap();
-                chunk_array
-            },
-        )(input)?;
+    /// Returns the payload of the AUTH chunk of AIFF, the author of the sound. The text is not decoded.
+    pub fn author(&self) -> Option<&'a [u8]> {
+        self.chunk_data(b"AUTH")
+    }
+
+    /// Returns the payload of the `(c) ` chunk of AIFF, the copyright of the sound. The text is not decoded.
+    pub fn copyright(&self) -> Option<&'a [u8]> {
+        self.chunk_data(b"(c) ")
+    }
+
+    /// Returns the payloads of the ANNO chunks of AIFF in the chunk table, in the order of the file. The text is not decoded.
+    pub fn annotations(&self) -> impl Iterator<Item = &'a [u8]> + '_ {
+        self.chunks
+            .iter()
+            .filter(|c| &c.id == b"ANNO")
+            .filter_map(|c| self.payload(c))
+    }
+
+    /// Returns the APPL chunks of AIFF in the chunk table, as the OSType signature of the application and the data after it.
+    /// A chunk shorter than the signature is skipped.
+    pub fn application_chunks(&self) -> impl Iterator<Item = ([u8; 4], &'a [u8])> + '_ {
+        self.chunks
+            .iter()
+            .filter(|c| &c.id == b"APPL")
+            .filter_map(|c| self.payload(c))
+            .filter_map(|payload| {
+                let (signature, data) = payload.split_first_chunk::<4>()?;
+                Some((*signature, data))
+            })
+    }
+
+    /// Returns the data of the first APPL chunk of AIFF with the signature, e.g. `pdos` or `stoc`.
+    pub fn application_chunk(&self, signature: &[u8; 4]) -> Option<&'a [u8]> {
+        self.application_chunks()
+            .find(|(s, _)| s == signature)
+            .map(|(_, data)| data)
+    }
+
+    /// Returns the comments of the first COMT chunk of AIFF in the chunk table, with their timestamps and markers.
+    /// Empty if there is no such chunk.
+    pub fn aiff_comments(&self) -> metadata::Comments<'a> {
+        self.chunk_data(b"COMT")
+            .map(aiff::parse_comt)
+            .unwrap_or_default()
+    }
+
+    /// Returns the layout of the speakers of the channels from the CHAN chunk of AIFF-C or the chna chunk of BW64.
+    /// Returns None if there is neither chunk in the chunk table.
+    /// A chunk which is broken, or whose layout does not match the number of channels, is [metadata::ChannelLayout::Unknown].
+    pub fn channel_layout(&self) -> Option<metadata::ChannelLayout> {
+        let parsed = match self.chunk_data(b"CHAN") {
+            Some(chan) => aiff::parse_chan(chan),
+            None => wav::parse_chna(self.chunk_data(b"chna")?),
+        };
+        let layout = parsed.map_or(metadata::ChannelLayout::Unknown, |(_, layout)| layout);
+        if layout
+            .num_channels()
+            .is_some_and(|n| n != self.specs.num_channels)
+        {
+            debug!(
+                "{:?} layout of {} channels",
+                layout, self.specs.num_channels
+            );
+            return Some(metadata::ChannelLayout::Unknown);
+        }
+        Some(layout)
+    }
+
+    /// Returns the broadcast extension of the bext chunk of BWF, e.g. the time reference to align multiple takes and the loudness.
+    /// Returns None if there is no bext chunk in the chunk table or it is too short.
+    pub fn broadcast_extension(&self) -> Option<metadata::BroadcastExtension<'a>> {
+        let (_, bext) = wav::parse_bext(self.chunk_data(b"bext")?).ok()?;
+        Some(bext)
+    }
+
+    /// Returns the cart chunk of radio automation systems, e.g. the title, the cut ID and the timers.
+    /// Returns None if there is no cart chunk in the chunk table or it is shorter than the fixed fields.
+    pub fn cart_chunk(&self) -> Option<metadata::CartInfo<'a>> {
+        let (_, cart) = wav::parse_cart(self.chunk_data(b"cart")?).ok()?;
+        Some(cart)
+    }
+
+    /// Returns the payload of the iXML chunk, which holds the metadata of field recorders, e.g. the scene and the take, as XML.
+    /// The XML is not parsed.
+    pub fn ixml(&self) -> Option<&'a [u8]> {
+        self.chunk_data(b"iXML")
+    }
 
-        for chunk in v {
-            match chunk.id {
+    /// Returns the payload of the axml chunk, which holds XML metadata of BWF, e.g. EBU Core.
+    /// The XML is not parsed.
+    pub fn axml(&self) -> Option<&'a [u8]> {
+        self.chunk_data(b"axml")
+    }
+
+    /// Returns the payload of a chunk of the chunk table.
+    fn payload(&self, chunk: &ChunkInfo) -> Option<&'a [u8]> {
+        let offset = chunk.offset as usize;
+        self.input.get(offset..offset + chunk.size as usize)
+    }
+
+    /// Compute the frame geometry and select the decoder from the specs.
+    fn update_cache(&mut self) {
+        self.bytes_per_sample = self.specs.container_bytes_per_sample as usize;
+        self.bytes_per_frame = self.bytes_per_sample * self.specs.num_channels as usize;
+        self.decode = decode_fn(&self.specs);
+    }
+
+    fn parse_aiff(&mut self, mut input: &'a [u8]) -> Result<(), PcmReaderError> {
+        // Each chunk is processed as soon as it is parsed, so the number of chunks is not limited.
+        let mut num_chunks = 0;
+        let (mut has_comm, mut has_ssnd, mut has_fver) = (false, false, false);
+        // Whether the sound data may be padded after the samples to the blocks of SSND.
+        let mut is_block_aligned = false;
+        let mut num_resyncs = 0;
+        while let Ok((rest, header)) = aiff::parse_chunk_header(input) {
+            let is_corrupt = !is_plausible_fourcc(&header.fourcc)
+                || (header.size as usize > rest.len() && header.id != aiff::ChunkId::SoundData);
+            if is_corrupt && self.may_resync(num_resyncs) {
+                if let Some(next) = aiff::find_chunk_header(&input[1..]) {
+                    debug!(
+                        "chunk {} size {} at {} is corrupt, resynchronized at {}",
+                        diag::fourcc(&header.fourcc),
+                        header.size,
+                        self.input.len() - input.len(),
+                        self.input.len() - next.len()
+                    );
+                    num_resyncs += 1;
+                    input = next;
+                    continue;
+                }
+            }
+            trace!(
+                "chunk {} size {} at {}",
+                diag::fourcc(&header.fourcc),
+                header.size,
+                self.input.len() - rest.len()
+            );
+            let size = if header.id == aiff::ChunkId::SoundData && header.size == 0 {
+                let next_chunk_follows =
+                    aiff::parse_chunk_header(rest).is_ok_and(|(payload, next)| {
+                        is_plausible_fourcc(&next.fourcc) && next.size as usize <= payload.len()
+                    });
+                self.unsized_data_len(rest, next_chunk_follows)
+            } else {
+                header.size.into()
+            };
+            let (payload, next) = self.split_payload(header.fourcc, size, 2, rest)?;
+            self.record_chunk(header.fourcc, payload.len(), rest);
+            input = next;
+            num_chunks += 1;
+            match header.id {
+                aiff::ChunkId::Common if has_comm => self.duplicate_chunk(header.fourcc)?,
                 aiff::ChunkId::Common => {
-                    let (_, spec) = aiff::parse_comm(chunk.data)?;
+                    has_comm = true;
+                    let (_, spec) = aiff::parse_comm(payload).map_err(|_| {
+                        debug!("COMM chunk of {} bytes rejected", payload.len());
+                        PcmReaderError::UnsupportedAudioFormat
+                    })?;
                     self.specs = spec;
                 }
+                aiff::ChunkId::SoundData if has_ssnd => self.duplicate_chunk(header.fourcc)?,
                 aiff::ChunkId::SoundData => {
-                    let (data, ssnd_block_info) = aiff::parse_ssnd(chunk.data)?;
-                    // offset and block_size are typically 0. Therefore, this only supports files where they are set to 0.
-                    if ssnd_block_info.offset != 0 || ssnd_block_info.block_size != 0 {
-                        return Err(nom::Err::Error(nom::error::Error::new(
-                            input,
-                            nom::error::ErrorKind::Verify,
-                        )));
+                    has_ssnd = true;
+                    let (data, ssnd_block_info) = aiff::parse_ssnd(payload).map_err(unsupported)?;
+                    // offset and block_size are typically 0.
+                    let first_frame = ssnd_block_info
+                        .first_frame(data.len() as u64)
+                        .ok_or(PcmReaderError::UnsupportedAudioFormat)?;
+                    self.data = &data[first_frame as usize..];
+                    is_block_aligned = ssnd_block_info.block_size != 0;
+                }
+                aiff::ChunkId::FormatVersion if self.container == ContainerFormat::AiffC => {
+                    has_fver = true;
+                    let version = aiff::parse_fver(payload).map_or(0, |(_, v)| v);
+                    if version != AIFC_VERSION_1 {
+                        debug!("unknown AIFF-C version {:#x}", version);
+                        if self.options.mode == ParseMode::Strict {
+                            return Err(PcmReaderError::UnsupportedAudioFormat);
+                        }
                     }
-                    self.data = data;
                 }
                 aiff::ChunkId::FormatVersion => {}
                 aiff::ChunkId::Marker => {}
@@ -205,183 +1180,1168 @@ impl<'a> PcmReader<'a> {
                 aiff::ChunkId::Unknown => {}
             }
         }
-        Ok((input, &[]))
+        if num_chunks == 0 {
+            debug!("no chunks");
+            return Err(PcmReaderError::UnsupportedAudioFormat);
+        }
+        if !has_comm {
+            return Err(missing_chunk(*b"COMM"));
+        }
+        if self.container == ContainerFormat::AiffC && !has_fver {
+            // Some writers omit it. The layout of the chunks is that of version 1.
+            debug!("AIFF-C without a FVER chunk");
+        }
+        if has_ssnd {
+            aiff::count_missing_frames(&mut self.specs, self.data.len() as u64);
+        }
+        if is_block_aligned {
+            // The padding after the frames of COMM is not sound data.
+            let frames_len = u64::from(self.specs.num_samples)
+                * u64::from(self.specs.container_bytes_per_sample)
+                * u64::from(self.specs.num_channels);
+            if let Ok(frames_len) = usize::try_from(frames_len) {
+                self.data = &self.data[..frames_len.min(self.data.len())];
+            }
+        }
+        Ok(())
     }
 
-    fn parse_wav(&mut self, input: &'a [u8]) -> IResult<&[u8], &[u8]> {
-        let (input, v) = fold_many1(
-            wav::parse_chunk,
-            Vec::<wav::Chunk, MAX_NUM_CHUNKS>::new,
-            |mut chunk_array: Vec<wav::Chunk, MAX_NUM_CHUNKS>, item| {
-                chunk_array.push(item).unwrap();
-                chunk_array
-            },
-        )(input)?;
-
-        for chunk in v {
-            match chunk.id {
+    /// Parse the chunks of WAV. The ds64 chunk of RF64 supplies the size of a data chunk which declares 0xFFFFFFFF bytes.
+    fn parse_wav(
+        &mut self,
+        mut input: &'a [u8],
+        ds64: Option<wav::Ds64>,
+    ) -> Result<(), PcmReaderError> {
+        // Each chunk is processed as soon as it is parsed, so the number of chunks is not limited.
+        let mut num_chunks = 0;
+        // The sample count of ds64 is read like a fact chunk.
+        let mut fact = ds64.and_then(|ds64| u32::try_from(ds64.sample_count).ok());
+        let (mut has_fmt, mut has_data) = (false, false);
+        let mut num_resyncs = 0;
+        while let Ok((rest, header)) = wav::parse_chunk_header(input) {
+            let size = match ds64 {
+                Some(ds64) if header.id == wav::ChunkId::Data && header.size == u32::MAX => {
+                    ds64.data_size
+                }
+                _ if header.id == wav::ChunkId::Data && header.size == 0 => {
+                    let next_chunk_follows =
+                        wav::parse_chunk_header(rest).is_ok_and(|(payload, next)| {
+                            is_plausible_fourcc(&next.fourcc) && next.size as usize <= payload.len()
+                        });
+                    self.unsized_data_len(rest, next_chunk_follows)
+                }
+                _ => header.size.into(),
+            };
+            let is_corrupt = !is_plausible_fourcc(&header.fourcc)
+                || (size > rest.len() as u64 && header.id != wav::ChunkId::Data);
+            if is_corrupt && self.may_resync(num_resyncs) {
+                if let Some(next) = wav::find_chunk_header(&input[1..]) {
+                    debug!(
+                        "chunk {} size {} at {} is corrupt, resynchronized at {}",
+                        diag::fourcc(&header.fourcc),
+                        header.size,
+                        self.input.len() - input.len(),
+                        self.input.len() - next.len()
+                    );
+                    num_resyncs += 1;
+                    input = next;
+                    continue;
+                }
+            }
+            trace!(
+                "chunk {} size {} at {}",
+                diag::fourcc(&header.fourcc),
+                size,
+                self.input.len() - rest.len()
+            );
+            let (payload, next) = self.split_payload(header.fourcc, size, 2, rest)?;
+            self.record_chunk(header.fourcc, payload.len(), rest);
+            input = next;
+            num_chunks += 1;
+            match header.id {
+                wav::ChunkId::Fmt if has_fmt => self.duplicate_chunk(header.fourcc)?,
                 wav::ChunkId::Fmt => {
-                    let (_, spec) = wav::parse_fmt(chunk.data)?;
-                    self.specs.num_channels = spec.num_channels;
-                    self.specs.sample_rate = spec.sample_rate;
-                    self.specs.audio_format = spec.audio_format;
-                    self.specs.bit_depth = spec.bit_depth;
-                    if self.specs.audio_format == AudioFormat::ImaAdpcmLe {
-                        self.specs.ima_adpcm_num_block_align = spec.ima_adpcm_num_block_align;
-                        self.specs.ima_adpcm_num_samples_per_block =
-                            spec.ima_adpcm_num_samples_per_block;
-                    }
+                    has_fmt = true;
+                    self.parse_fmt(payload)?;
                 }
+                wav::ChunkId::Data if has_data => self.duplicate_chunk(header.fourcc)?,
                 wav::ChunkId::Data => {
-                    self.data = chunk.data;
+                    has_data = true;
+                    self.data = payload;
+                }
+                wav::ChunkId::Fact => {
+                    // A fact chunk which is too short is ignored.
+                    fact = wav::parse_fact(payload).ok().map(|(_, n)| n);
                 }
-                wav::ChunkId::Fact => {}
                 wav::ChunkId::IDv3 => {}
+                wav::ChunkId::Ds64 => {}
                 wav::ChunkId::Junk => {}
-                wav::ChunkId::List => {}
+                wav::ChunkId::List => {
+                    // A wavl list holds the samples instead of a data chunk.
+                    if let Some(segments) = payload.strip_prefix(b"wavl") {
+                        if has_data {
+                            self.duplicate_chunk(header.fourcc)?;
+                        } else {
+                            has_data = true;
+                            self.parse_wavl(segments)?;
+                        }
+                    }
+                }
+                wav::ChunkId::Slnt => {}
                 wav::ChunkId::Peak => {}
                 wav::ChunkId::Unknown => {}
             }
         }
-
-        match self.specs.audio_format {
-            AudioFormat::ImaAdpcmLe => {
-                self.specs.num_samples =
-                    imaadpcm::calc_num_samples_per_channel(self.data.len() as u32, &self.specs)
-                        .unwrap();
-            }
-            AudioFormat::LinearPcmLe | AudioFormat::IeeeFloatLe => {
-                self.specs.num_samples =
-                    wav::calc_num_samples_per_channel(self.data.len() as u32, &self.specs).unwrap();
-            }
-            _ => {
-                unreachable!();
-            }
+        if num_chunks == 0 {
+            debug!("no chunks");
+            return Err(PcmReaderError::UnsupportedAudioFormat);
         }
-        Ok((input, &[]))
+        // The data chunk may precede the fmt chunk, so the samples are counted after all chunks.
+        if !has_fmt {
+            return Err(missing_chunk(*b"fmt "));
+        }
+        self.count_wav_samples(fact)
     }
 
-    /// Returns basic information about the PCM file.
-    #[must_use]
-    pub fn get_pcm_specs(&self) -> PcmSpecs {
-        self.specs.clone()
+    /// Record the data and slnt chunks of a LIST chunk of type wavl as segments. Other chunks in the list are skipped.
+    /// More than MAX_NUM_SEGMENTS segments are an error, or the first ones are read in [ParseMode::Lenient].
+    fn parse_wavl(&mut self, mut input: &'a [u8]) -> Result<(), PcmReaderError> {
+        while let Ok((rest, header)) = wav::parse_chunk_header(input) {
+            trace!(
+                "wavl chunk {} size {} at {}",
+                diag::fourcc(&header.fourcc),
+                header.size,
+                self.input.len() - rest.len()
+            );
+            let (payload, next) = self.split_payload(header.fourcc, header.size.into(), 2, rest)?;
+            input = next;
+            let segment = match header.id {
+                wav::ChunkId::Data => {
+                    // Located in 32 bits like the chunk table.
+                    let offset = self.input.len() - rest.len();
+                    let (Ok(offset), Ok(size)) =
+                        (u32::try_from(offset), u32::try_from(payload.len()))
+                    else {
+                        debug!("wavl data chunk beyond 4 GiB");
+                        return Err(PcmReaderError::UnsupportedAudioFormat);
+                    };
+                    Segment {
+                        offset,
+                        size,
+                        ..Default::default()
+                    }
+                }
+                // A slnt chunk which is too short is ignored.
+                wav::ChunkId::Slnt => match wav::parse_slnt(payload) {
+                    Ok((_, num_frames)) => Segment {
+                        num_frames,
+                        is_silence: true,
+                        ..Default::default()
+                    },
+                    Err(_) => continue,
+                },
+                _ => continue,
+            };
+            if !self.segments.push(segment) {
+                debug!("more than {} segments in the wavl list", MAX_NUM_SEGMENTS);
+                match self.options.mode {
+                    ParseMode::Strict => return Err(PcmReaderError::UnsupportedAudioFormat),
+                    ParseMode::Lenient => break,
+                }
+            }
+        }
+        Ok(())
     }
 
-    /// Returns the value of a sample at an arbitrary position.  
-    /// Returns a normalized value in the range +/-1.0 regardless of AudioFormat.  
-    pub fn read_sample(&self, channel: u16, sample: u32) -> Result<f32, PcmReaderError> {
-        let num_channels = self.specs.num_channels;
-        if channel >= num_channels {
-            return Err(PcmReaderError::InvalidChannel);
+    /// Count the frames of the segments of a wavl list. Returns the total.
+    /// ADPCM is not supported, since the players decode the blocks of a single data chunk.
+    fn count_segment_frames(&mut self) -> Result<u32, PcmReaderError> {
+        if matches!(
+            self.specs.audio_format,
+            AudioFormat::ImaAdpcmLe | AudioFormat::MsAdpcmLe
+        ) {
+            return Err(PcmReaderError::UnsupportedAudioFormat);
         }
-
-        if sample >= self.specs.num_samples {
-            return Err(PcmReaderError::InvalidSample);
+        let mut num_frames = 0u32;
+        for segment in self.segments.iter_mut() {
+            if !segment.is_silence {
+                segment.num_frames =
+                    wav::calc_num_samples_per_channel(segment.size.into(), &self.specs)?;
+            }
+            num_frames = num_frames
+                .checked_add(segment.num_frames)
+                .ok_or(PcmReaderError::InconsistentSpecs(SpecsField::NumSamples))?;
         }
-
-        let byte_depth = self.specs.bit_depth / 8u16;
-        let byte_offset = ((byte_depth as u32 * sample * num_channels as u32)
-            + (byte_depth * channel) as u32) as usize;
-        let data = &self.data[byte_offset..];
-        decode_sample(&self.specs, data)
+        Ok(num_frames)
     }
-}
 
-/// Decode a sample from a byte array.
-/// Returns a normalized value in the range +/-1.0 regardless of AudioFormat.
-/// TODO return not only f32 but also Q15, Q23, f64, etc.
-/// Or make it possible to select f32 or f64.
-/// It may be better to use a function like read_raw_sample() to get fixed-point numbers.
-fn decode_sample(specs: &PcmSpecs, data: &[u8]) -> Result<f32, PcmReaderError> {
-    match specs.audio_format {
-        AudioFormat::Unknown => Err(PcmReaderError::UnsupportedAudioFormat),
-        AudioFormat::LinearPcmLe => {
-            match specs.bit_depth {
-                16 => {
-                    const MAX: u32 = 2u32.pow(15); //normalize factor: 2^(BitDepth-1)
-                    let (_remains, sample) =
-                        le_i16::<_, nom::error::Error<_>>(data).finish().unwrap();
-                    let sample = sample as f32 / MAX as f32;
-                    Ok(sample)
+    /// Parse the chunks of Wave64. The fmt, fact and data chunks are the same as WAV.
+    fn parse_w64(&mut self, mut input: &'a [u8]) -> Result<(), PcmReaderError> {
+        let mut fact = None;
+        let (mut has_fmt, mut has_data) = (false, false);
+        while let Ok((rest, header)) = w64::parse_chunk_header(input) {
+            trace!(
+                "chunk {} size {} at {}",
+                diag::fourcc(&header.fourcc),
+                header.size,
+                self.input.len() - rest.len()
+            );
+            let (payload, next) =
+                self.split_payload(header.fourcc, header.size, w64::ALIGN, rest)?;
+            self.record_chunk(header.fourcc, payload.len(), rest);
+            input = next;
+            match header.id {
+                w64::ChunkId::Fmt if has_fmt => self.duplicate_chunk(header.fourcc)?,
+                w64::ChunkId::Fmt => {
+                    has_fmt = true;
+                    self.parse_fmt(payload)?;
                 }
-                24 => {
-                    const MAX: u32 = 2u32.pow(23); //normalize factor: 2^(BitDepth-1)
-                    let (_remains, sample) =
-                        le_i24::<_, nom::error::Error<_>>(data).finish().unwrap();
-                    let sample = sample as f32 / MAX as f32;
-                    Ok(sample)
+                w64::ChunkId::Data if has_data => self.duplicate_chunk(header.fourcc)?,
+                w64::ChunkId::Data => {
+                    has_data = true;
+                    self.data = payload;
                 }
-                32 => {
-                    const MAX: u32 = 2u32.pow(31); //normalize factor: 2^(BitDepth-1)
-                    let (_remains, sample) =
-                        le_i32::<_, nom::error::Error<_>>(data).finish().unwrap();
-                    let sample = sample as f32 / MAX as f32;
-                    Ok(sample)
+                w64::ChunkId::Fact => {
+                    fact = wav::parse_fact(payload).ok().map(|(_, n)| n);
                 }
-                _ => Err(PcmReaderError::UnsupportedBitDepth),
+                w64::ChunkId::Unknown => {}
             }
         }
-        AudioFormat::LinearPcmBe => {
-            match specs.bit_depth {
-                16 => {
-                    const MAX: u32 = 2u32.pow(15); //normalize factor: 2^(BitDepth-1)
-                    let (_remains, sample) =
-                        be_i16::<_, nom::error::Error<_>>(data).finish().unwrap();
-                    let sample = sample as f32 / MAX as f32;
-                    Ok(sample)
+        if !has_fmt {
+            return Err(missing_chunk(*b"fmt "));
+        }
+        self.count_wav_samples(fact)
+    }
+
+    /// Parse the chunks of CAF.
+    fn parse_caf(&mut self, mut input: &'a [u8]) -> Result<(), PcmReaderError> {
+        let (mut has_desc, mut has_data) = (false, false);
+        while let Ok((rest, header)) = caf::parse_chunk_header(input) {
+            // A data chunk of size -1 extends to the end of the file.
+            let size = header.size.unwrap_or(rest.len() as u64);
+            trace!(
+                "chunk {} size {} at {}",
+                diag::fourcc(&header.fourcc),
+                size,
+                self.input.len() - rest.len()
+            );
+            let (payload, next) = self.split_payload(header.fourcc, size, 1, rest)?;
+            self.record_chunk(header.fourcc, payload.len(), rest);
+            input = next;
+            match header.id {
+                caf::ChunkId::Desc if has_desc => self.duplicate_chunk(header.fourcc)?,
+                caf::ChunkId::Desc => {
+                    has_desc = true;
+                    let (_, desc) = caf::parse_desc(payload).map_err(|_| {
+                        debug!("desc chunk of {} bytes rejected", payload.len());
+                        PcmReaderError::UnsupportedAudioFormat
+                    })?;
+                    if desc.frames_per_packet != 1 {
+                        debug!("CAF {} frames per packet", desc.frames_per_packet);
+                        return Err(PcmReaderError::UnsupportedAudioFormat);
+                    }
+                    let bytes_per_frame = u32::from(desc.specs.num_channels)
+                        * u32::from(desc.specs.container_bytes_per_sample);
+                    if desc.bytes_per_packet != bytes_per_frame {
+                        debug!(
+                            "desc chunk: {} bytes per packet, {} bytes per frame",
+                            desc.bytes_per_packet, bytes_per_frame
+                        );
+                        if self.options.mode == ParseMode::Strict {
+                            return Err(PcmReaderError::InconsistentSpecs(SpecsField::BlockAlign));
+                        }
+                    }
+                    self.specs = desc.specs;
                 }
-                24 => {
-                    const MAX: u32 = 2u32.pow(23); //normalize factor: 2^(BitDepth-1)
-                    let (_remains, sample) =
-                        be_i24::<_, nom::error::Error<_>>(data).finish().unwrap();
-                    let sample = sample as f32 / MAX as f32;
-                    Ok(sample)
+                caf::ChunkId::Data if has_data => self.duplicate_chunk(header.fourcc)?,
+                caf::ChunkId::Data => {
+                    has_data = true;
+                    let (data, _edit_count) = caf::parse_data(payload).map_err(|_| {
+                        debug!("data chunk without the edit count");
+                        PcmReaderError::UnsupportedAudioFormat
+                    })?;
+                    self.data = data;
                 }
-                32 => {
-                    const MAX: u32 = 2u32.pow(31); //normalize factor: 2^(BitDepth-1)
-                    let (_remains, sample) =
-                        be_i32::<_, nom::error::Error<_>>(data).finish().unwrap();
-                    let sample = sample as f32 / MAX as f32;
-                    Ok(sample)
+                caf::ChunkId::Unknown => {}
+            }
+        }
+
+        if !has_desc {
+            return Err(missing_chunk(*b"desc"));
+        }
+        self.specs.num_samples =
+            wav::calc_num_samples_per_channel(self.data.len() as u64, &self.specs)?;
+        Ok(())
+    }
+
+    /// Set the specs from the payload of a fmt chunk of WAV.
+    fn parse_fmt(&mut self, payload: &[u8]) -> Result<(), PcmReaderError> {
+        let (_, mut spec) = wav::parse_fmt(payload).map_err(|_| {
+            debug!("fmt chunk of {} bytes rejected", payload.len());
+            PcmReaderError::UnsupportedAudioFormat
+        })?;
+        if let Some(field) = spec.inconsistent_field() {
+            debug!(
+                "fmt chunk: {:?} does not match, block align {}, {} bytes/s",
+                field, spec.block_align, spec.byte_rate
+            );
+            match self.options.mode {
+                ParseMode::Strict => return Err(PcmReaderError::InconsistentSpecs(field)),
+                // The bit depth is corrected if the other fields agree with each other.
+                ParseMode::Lenient => {
+                    if let Some(bit_depth) = spec.implied_bit_depth() {
+                        debug!("bit depth {} corrected to {}", spec.bit_depth, bit_depth);
+                        spec.bit_depth = bit_depth;
+                    }
                 }
-                _ => Err(PcmReaderError::UnsupportedBitDepth),
             }
         }
-        AudioFormat::IeeeFloatLe => {
-            match specs.bit_depth {
-                32 => {
-                    //32bit float
-                    let (_remains, sample) =
-                        le_f32::<_, nom::error::Error<_>>(data).finish().unwrap();
-                    Ok(sample)
+        self.specs.num_channels = spec.num_channels;
+        self.specs.sample_rate = spec.sample_rate;
+        self.specs.container_bytes_per_sample = spec.container_bytes_per_sample();
+        self.specs.bit_depth = spec.valid_bit_depth();
+        self.specs.audio_format = spec.audio_format;
+        if self.specs.audio_format == AudioFormat::ImaAdpcmLe {
+            self.specs.ima_adpcm_num_block_align = spec.ima_adpcm_num_block_align;
+            self.specs.ima_adpcm_num_samples_per_block = spec.ima_adpcm_num_samples_per_block;
+        }
+        if self.specs.audio_format == AudioFormat::MsAdpcmLe {
+            self.specs.ms_adpcm_num_block_align = spec.ms_adpcm_num_block_align;
+            self.specs.ms_adpcm_num_samples_per_block = spec.ms_adpcm_num_samples_per_block;
+            self.specs.ms_adpcm_coefficients = spec.ms_adpcm_coefficients;
+        }
+        Ok(())
+    }
+
+    /// Compute the number of samples of WAV from the data chunk, limited by the fact chunk.
+    fn count_wav_samples(&mut self, fact: Option<u32>) -> Result<(), PcmReaderError> {
+        // Fails without a valid fmt chunk.
+        // The data chunk of RF64 may be larger than 4 GiB.
+        let num_samples = match self.specs.audio_format {
+            _ if !self.segments.is_empty() => self.count_segment_frames(),
+            AudioFormat::ImaAdpcmLe => {
+                imaadpcm::calc_num_samples_per_channel(self.data.len() as u64, &self.specs)
+            }
+            AudioFormat::MsAdpcmLe => {
+                msadpcm::calc_num_samples_per_channel(self.data.len() as u64, &self.specs)
+            }
+            AudioFormat::LinearPcmLe
+            | AudioFormat::IeeeFloatLe
+            | AudioFormat::ALaw
+            | AudioFormat::MuLaw => {
+                wav::calc_num_samples_per_channel(self.data.len() as u64, &self.specs)
+            }
+            _ => Err(PcmReaderError::UnsupportedAudioFormat),
+        };
+        self.specs.num_samples = num_samples.inspect_err(|_| {
+            debug!(
+                "number of samples cannot be computed from {:?}, {} bits, {} channels",
+                self.specs.audio_format, self.specs.bit_depth, self.specs.num_channels
+            )
+        })?;
+        wav::limit_to_fact(&mut self.specs, fact);
+        Ok(())
+    }
+
+    /// Returns basic information about the PCM file.
+    #[must_use]
+    pub fn get_pcm_specs(&self) -> PcmSpecs {
+        self.specs.clone()
+    }
+
+    /// Returns the container of the file, e.g. to know whether the COMM chunk of AIFF may have a compression type.
+    pub fn container(&self) -> ContainerFormat {
+        self.container
+    }
+
+    /// Returns the timestamp in the FVER chunk of AIFF-C, the version of the specification, e.g. [AIFC_VERSION_1].
+    /// Returns None for other containers, or if there is no FVER chunk in the chunk table.
+    /// In [ParseMode::Strict], AIFF-C files of other versions are rejected.
+    pub fn aifc_version(&self) -> Option<u32> {
+        if self.container != ContainerFormat::AiffC {
+            return None;
+        }
+        let (_, version) = aiff::parse_fver(self.chunk_data(b"FVER")?).ok()?;
+        Some(version)
+    }
+
+    /// Returns a reference to basic information about the PCM file.
+    /// Prefer this to [PcmReader::get_pcm_specs], which clones the specs.
+    #[must_use]
+    pub fn specs(&self) -> &PcmSpecs {
+        &self.specs
+    }
+
+    /// Returns the sample data as stored in the file, without decoding, e.g. to hand it to a DMA controller.
+    /// The bytes are in the endianness and the format of the file, e.g. big endian for AIFF and the blocks for IMA-ADPCM,
+    /// so check [PcmReader::specs] to decide whether they can be used as they are.
+    /// Empty for a wavl list of WAV, whose frames are read with [PcmReader::frame_bytes].
+    ///
+    /// ```
+    /// use pacmog::{AudioFormat, PcmReader};
+    ///
+    /// let wav = include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_16.wav");
+    /// let reader = PcmReader::new(wav).unwrap();
+    /// let specs = reader.specs();
+    /// if specs.audio_format == AudioFormat::LinearPcmLe && specs.bit_depth == 16 {
+    ///     let data = reader.raw_data();
+    ///     assert_eq!(data.len(), specs.num_samples as usize * reader.bytes_per_frame());
+    /// }
+    /// ```
+    #[must_use]
+    pub fn raw_data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Returns true if the samples are in the segments of a wavl list rather than in raw_data.
+    #[cfg(feature = "wasm")]
+    pub(crate) fn is_segmented(&self) -> bool {
+        !self.segments.is_empty()
+    }
+
+    /// Returns the number of bytes of an interleaved frame in [PcmReader::raw_data].
+    /// 0 for the formats which are stored in blocks, e.g. IMA-ADPCM.
+    #[must_use]
+    pub fn bytes_per_frame(&self) -> usize {
+        self.bytes_per_frame
+    }
+
+    /// Returns the bytes of the interleaved frame at the position, in the endianness and the format of the file.
+    /// Returns None if the sample is out of range, for the silence of a wavl list and for the formats which are stored in blocks.
+    #[must_use]
+    pub fn frame_bytes(&self, sample: u32) -> Option<&'a [u8]> {
+        if sample >= self.specs.num_samples || self.bytes_per_frame == 0 {
+            return None;
+        }
+        match self.frames_at(sample.into(), 1)? {
+            (Span::Data(bytes), _) => Some(bytes),
+            (Span::Silence, _) => None,
+        }
+    }
+
+    /// Returns the value of a sample at an arbitrary position.  
+    /// Returns a normalized value in the range +/-1.0 regardless of AudioFormat.  
+    pub fn read_sample(&self, channel: u16, sample: u32) -> Result<f32, PcmReaderError> {
+        let span = self.sample_bytes(channel, sample)?;
+        let Some(decode) = self.decode else {
+            // Returns the reason why the format is not supported.
+            return decode_sample(&self.specs, &[]);
+        };
+        match span {
+            Span::Data(bytes) if bytes.len() == self.bytes_per_sample => Ok(decode(bytes)),
+            Span::Data(_) => Err(PcmReaderError::InvalidSample),
+            Span::Silence => Ok(0.0),
+        }
+    }
+
+    /// Decode all channels of a frame into out, e.g. into `[f32; 2]` for stereo.
+    /// The frame is sliced once and the channels are decoded sequentially from it.
+    /// * 'sample' - Position of the frame in samples.
+    /// * 'out' - Number of elements must be equal to or greater than the number of channels. The rest is left untouched.
+    ///
+    /// Returns the number of channels written.
+    /// Returns InvalidChannel if out is shorter than the number of channels, and InvalidSample if the sample is out of range.
+    pub fn read_frame<T: Float>(
+        &self,
+        sample: u32,
+        out: &mut [T],
+    ) -> Result<usize, PcmReaderError> {
+        let num_channels = self.specs.num_channels as usize;
+        if out.len() < num_channels {
+            return Err(PcmReaderError::InvalidChannel);
+        }
+        if sample >= self.specs.num_samples {
+            return Err(PcmReaderError::InvalidSample);
+        }
+        if self.decode.is_none() {
+            // Returns the reason why the format is not supported.
+            return decode_sample(&self.specs, &[]).map(|_| 0);
+        }
+
+        let out = &mut out[..num_channels];
+        match self.frames_at(sample.into(), 1) {
+            Some((Span::Data(frame), _)) if frame.len() == self.bytes_per_frame => {
+                decode_interleaved(&self.specs, frame, out)?;
+            }
+            Some((Span::Silence, _)) => out.fill(T::ZERO),
+            _ => return Err(PcmReaderError::InvalidSample),
+        }
+        Ok(num_channels)
+    }
+
+    /// Returns the integer value of a sample of linear PCM as stored in the file, without the conversion to f32.
+    /// The value is sign-extended to i32, e.g. -8388608..=8388607 for 24bit, so 32bit samples are bit-exact.
+    /// Unsigned 8bit samples of WAV are centered at 0, i.e. -128..=127.
+    ///
+    /// Returns UnsupportedAudioFormat for the float formats and the compressed formats, e.g. IMA-ADPCM, A-law and µ-law.
+    pub fn read_sample_exact(&self, channel: u16, sample: u32) -> Result<i32, PcmReaderError> {
+        match self.sample_bytes(channel, sample)? {
+            Span::Data(bytes) => decode_sample_exact(&self.specs, bytes),
+            // Only the format is checked.
+            Span::Silence => decode_sample_exact(&self.specs, &[0; 4]).map(|_| 0),
+        }
+    }
+
+    /// Returns the container of a sample of linear PCM as stored in the file, sign-extended to i32.
+    /// Unlike [PcmReader::read_sample_exact], the padding bits of samples in larger containers are kept,
+    /// e.g. 20bit samples in 24bit containers are multiples of 16 in -8388608..=8388607, for fixed-point processing at the scale of the container.
+    /// Unsigned 8bit samples of WAV are centered at 0, i.e. -128..=127.
+    ///
+    /// Returns UnsupportedAudioFormat for the float formats, see [PcmReader::read_sample_f64], and the compressed formats.
+    pub fn read_sample_raw(&self, channel: u16, sample: u32) -> Result<i32, PcmReaderError> {
+        match self.sample_bytes(channel, sample)? {
+            Span::Data(bytes) => decode_container_exact(&self.specs, bytes),
+            // Only the format is checked.
+            Span::Silence => decode_container_exact(&self.specs, &[0; 4]).map(|_| 0),
+        }
+    }
+
+    /// Returns the value of a sample of IEEE float as stored in the file.
+    /// 64bit samples are not rounded to f32, and 32bit samples are widened to f64 exactly.
+    ///
+    /// Returns UnsupportedAudioFormat for linear PCM and IMA-ADPCM. See [PcmReader::read_sample_exact].
+    #[cfg(feature = "f64")]
+    pub fn read_sample_f64(&self, channel: u16, sample: u32) -> Result<f64, PcmReaderError> {
+        let bytes = match self.sample_bytes(channel, sample)? {
+            Span::Data(bytes) => bytes,
+            // All bits zero is 0.0 in IEEE float.
+            Span::Silence => &[0; 8],
+        };
+        match (&self.specs.audio_format, self.specs.bit_depth) {
+            (AudioFormat::IeeeFloatLe, 32) => Ok(f32::from_le_bytes(first_bytes(bytes)?).into()),
+            (AudioFormat::IeeeFloatLe, 64) => Ok(f64::from_le_bytes(first_bytes(bytes)?)),
+            (AudioFormat::IeeeFloatBe, 32) => Ok(f32::from_be_bytes(first_bytes(bytes)?).into()),
+            (AudioFormat::IeeeFloatBe, 64) => Ok(f64::from_be_bytes(first_bytes(bytes)?)),
+            (AudioFormat::IeeeFloatLe | AudioFormat::IeeeFloatBe, _) => {
+                Err(PcmReaderError::UnsupportedBitDepth)
+            }
+            _ => Err(PcmReaderError::UnsupportedAudioFormat),
+        }
+    }
+
+    /// Returns the bytes of a sample, after checking the channel and the position.
+    /// The bytes are empty if the sample is not in the data.
+    fn sample_bytes(&self, channel: u16, sample: u32) -> Result<Span<'a>, PcmReaderError> {
+        if channel >= self.specs.num_channels {
+            return Err(PcmReaderError::InvalidChannel);
+        }
+
+        if sample >= self.specs.num_samples {
+            return Err(PcmReaderError::InvalidSample);
+        }
+
+        let offset = self.bytes_per_sample * channel as usize;
+        Ok(match self.frames_at(sample.into(), 1) {
+            Some((Span::Data(frame), _)) => Span::Data(
+                frame
+                    .get(offset..offset + self.bytes_per_sample)
+                    .unwrap_or_default(),
+            ),
+            Some((Span::Silence, _)) => Span::Silence,
+            None => Span::Data(&[]),
+        })
+    }
+
+    /// Returns up to num_frames frames from start_frame and the number of frames returned.
+    /// The frames of a wavl list end at the end of their segment, so fewer frames may be returned.
+    /// Returns None if the frames are not in the data.
+    fn frames_at(&self, start_frame: u64, num_frames: u64) -> Option<(Span<'a>, u64)> {
+        if self.segments.is_empty() {
+            let bytes = data_at(self.data, self.bytes_per_frame, start_frame, num_frames)?;
+            return Some((Span::Data(bytes), num_frames));
+        }
+        let mut first_frame = 0;
+        for segment in self.segments.iter() {
+            let end_frame = first_frame + u64::from(segment.num_frames);
+            if start_frame < end_frame {
+                let num_frames = num_frames.min(end_frame - start_frame);
+                if segment.is_silence {
+                    return Some((Span::Silence, num_frames));
                 }
-                64 => {
-                    //64bit float
-                    let (_remains, sample) =
-                        le_f64::<_, nom::error::Error<_>>(data).finish().unwrap();
-                    Ok(sample as f32) // TODO f32にダウンキャストするべきなのか検討
+                let offset = segment.offset as usize;
+                let data = self.input.get(offset..offset + segment.size as usize)?;
+                let frame = start_frame - first_frame;
+                let bytes = data_at(data, self.bytes_per_frame, frame, num_frames)?;
+                return Some((Span::Data(bytes), num_frames));
+            }
+            first_frame = end_frame;
+        }
+        None
+    }
+
+    /// Decode consecutive frames into an interleaved buffer.
+    /// Much faster than calling [PcmReader::read_sample] for each sample.
+    /// * 'start_frame' - Position of the first frame in samples.
+    /// * 'out' - Output buffer of interleaved samples. As many whole frames as fit are decoded.
+    ///
+    /// Returns the number of frames decoded, which is 0 when start_frame is at the end of the file.
+    /// Same as [PcmReader::read_frames] into f32.
+    pub fn decode_block_f32(
+        &self,
+        start_frame: u32,
+        out: &mut [f32],
+    ) -> Result<usize, PcmReaderError> {
+        self.read_frames(start_frame, out)
+    }
+
+    /// Decode consecutive frames into an interleaved buffer of f32 or f64, e.g. for the callback of an audio API.
+    /// The data is walked forward once, and the samples are decoded in a loop specialized for the format.
+    /// The frames of a wavl list are decoded segment by segment.
+    /// * 'start_frame' - Position of the first frame in samples.
+    /// * 'out' - Output buffer of interleaved samples. As many whole frames as fit are decoded, up to the end of the file.
+    ///
+    /// Returns the number of frames decoded, which is 0 when start_frame is at the end of the file.
+    /// Returns InvalidSample if start_frame is beyond the end of the file.
+    pub fn read_frames<T: Float>(
+        &self,
+        start_frame: u32,
+        out: &mut [T],
+    ) -> Result<usize, PcmReaderError> {
+        if start_frame > self.specs.num_samples {
+            return Err(PcmReaderError::InvalidSample);
+        }
+        if self.decode.is_none() {
+            // Returns the reason why the format is not supported.
+            return decode_sample(&self.specs, &[]).map(|_| 0);
+        }
+
+        let num_channels = self.specs.num_channels as usize;
+        let num_frames =
+            (out.len() / num_channels).min((self.specs.num_samples - start_frame) as usize);
+        let mut decoded = 0;
+        while decoded < num_frames {
+            let Some((span, n)) = self.frames_at(
+                u64::from(start_frame) + decoded as u64,
+                (num_frames - decoded) as u64,
+            ) else {
+                return Err(PcmReaderError::InvalidSample);
+            };
+            let out = &mut out[decoded * num_channels..(decoded + n as usize) * num_channels];
+            match span {
+                Span::Data(block) => decode_interleaved(&self.specs, block, out)?,
+                Span::Silence => out.fill(T::ZERO),
+            }
+            decoded += n as usize;
+        }
+        Ok(num_frames)
+    }
+
+    /// Decode consecutive frames into an interleaved buffer of 16bit integers.
+    /// 16bit sources are copied without going through floating point. Others are rounded to 16bit.
+    /// Returns the number of frames decoded.
+    pub(crate) fn decode_block_i16(
+        &self,
+        start_frame: u32,
+        out: &mut [i16],
+    ) -> Result<usize, PcmReaderError> {
+        let num_channels = self.specs.num_channels as usize;
+        let from_bytes: fn([u8; 2]) -> i16 = match (&self.specs.audio_format, self.specs.bit_depth)
+        {
+            (AudioFormat::LinearPcmLe, 16) => i16::from_le_bytes,
+            (AudioFormat::LinearPcmBe, 16) => i16::from_be_bytes,
+            _ => {
+                // Convert through a small f32 buffer.
+                let mut buffer = [0f32; 64];
+                let frames_per_chunk = buffer.len() / num_channels;
+                if frames_per_chunk == 0 {
+                    return Err(PcmReaderError::InvalidChannel);
                 }
-                _ => Err(PcmReaderError::UnsupportedBitDepth),
+                let mut num_frames = 0;
+                for chunk in out.chunks_mut(frames_per_chunk * num_channels) {
+                    let n = self.decode_block_f32(
+                        start_frame + num_frames as u32,
+                        &mut buffer[..chunk.len()],
+                    )?;
+                    for (o, s) in chunk.iter_mut().zip(&buffer[..n * num_channels]) {
+                        *o = writer::quantize(*s, 15) as i16;
+                    }
+                    num_frames += n;
+                    if n * num_channels < chunk.len() {
+                        break;
+                    }
+                }
+                return Ok(num_frames);
             }
+        };
+
+        if start_frame > self.specs.num_samples {
+            return Err(PcmReaderError::InvalidSample);
         }
-        AudioFormat::IeeeFloatBe => {
-            match specs.bit_depth {
-                32 => {
-                    //32bit float
-                    let (_remains, sample) =
-                        be_f32::<_, nom::error::Error<_>>(data).finish().unwrap();
-                    Ok(sample)
+        let num_frames =
+            (out.len() / num_channels).min((self.specs.num_samples - start_frame) as usize);
+        let mut decoded = 0;
+        while decoded < num_frames {
+            let Some((span, n)) = self.frames_at(
+                u64::from(start_frame) + decoded as u64,
+                (num_frames - decoded) as u64,
+            ) else {
+                return Err(PcmReaderError::InvalidSample);
+            };
+            let out = &mut out[decoded * num_channels..(decoded + n as usize) * num_channels];
+            match span {
+                Span::Data(block) => {
+                    for (bytes, o) in block.chunks_exact(2).zip(out.iter_mut()) {
+                        *o = from_bytes([bytes[0], bytes[1]]);
+                    }
                 }
-                64 => {
-                    //64bit float
-                    let (_remains, sample) =
-                        be_f64::<_, nom::error::Error<_>>(data).finish().unwrap();
-                    Ok(sample as f32) // TODO f32にダウンキャストするべきなのか検討
+                Span::Silence => out.fill(0),
+            }
+            decoded += n as usize;
+        }
+        Ok(num_frames)
+    }
+
+    /// Decode consecutive frames into one buffer per channel.
+    /// The source data is read strictly forward once, so interleaved data is not re-read for each channel.
+    /// * 'start_frame' - Position of the first frame in samples.
+    /// * 'out' - Output buffers of each channel. The number of buffers must be equal to or greater than the number of channels.
+    ///   As many frames as fit in the shortest buffer are decoded.
+    ///
+    /// Returns the number of frames decoded, which is 0 when start_frame is at the end of the file.
+    pub fn decode_block_planar_f32(
+        &self,
+        start_frame: u32,
+        out: &mut [&mut [f32]],
+    ) -> Result<usize, PcmReaderError> {
+        if start_frame > self.specs.num_samples {
+            return Err(PcmReaderError::InvalidSample);
+        }
+        let num_channels = self.specs.num_channels as usize;
+        if out.len() < num_channels {
+            return Err(PcmReaderError::InvalidChannel);
+        }
+        if self.decode.is_none() {
+            // Returns the reason why the format is not supported.
+            return decode_sample(&self.specs, &[]).map(|_| 0);
+        }
+
+        let out = &mut out[..num_channels];
+        let shortest = out.iter().map(|o| o.len()).min().unwrap_or(0);
+        let num_frames = shortest.min((self.specs.num_samples - start_frame) as usize);
+        let mut decoded = 0;
+        while decoded < num_frames {
+            let Some((span, n)) = self.frames_at(
+                u64::from(start_frame) + decoded as u64,
+                (num_frames - decoded) as u64,
+            ) else {
+                return Err(PcmReaderError::InvalidSample);
+            };
+            match span {
+                Span::Data(block) => decode_planar(&self.specs, block, out, decoded)?,
+                Span::Silence => {
+                    for channel in out.iter_mut() {
+                        channel[decoded..decoded + n as usize].fill(0.0);
+                    }
                 }
-                _ => Err(PcmReaderError::UnsupportedBitDepth),
+            }
+            decoded += n as usize;
+        }
+        Ok(num_frames)
+    }
+
+    /// Returns an iterator over the frames, e.g. `for frame in reader.frames::<f32>()?`.
+    ///
+    /// Returns UnsupportedAudioFormat for the compressed formats, e.g. IMA-ADPCM,
+    /// and InvalidChannel for more than [MAX_NUM_FRAME_CHANNELS] channels.
+    pub fn frames<T: Float>(&self) -> Result<Frames<'_, 'a, T, MAX_CHUNKS>, PcmReaderError> {
+        if self.decode.is_none() {
+            // Returns the reason why the format is not supported.
+            decode_sample(&self.specs, &[])?;
+        }
+        if self.specs.num_channels as usize > MAX_NUM_FRAME_CHANNELS {
+            return Err(PcmReaderError::InvalidChannel);
+        }
+        Ok(Frames {
+            reader: self,
+            position: 0,
+            _sample: core::marker::PhantomData,
+        })
+    }
+}
+
+/// Iterator over the frames of a [PcmReader], created by [PcmReader::frames].
+/// Skipped frames are not decoded, since the position of a frame is computed directly.
+/// The iteration ends early if the data of a frame is missing.
+pub struct Frames<'r, 'a, T, const MAX_CHUNKS: usize = MAX_NUM_CHUNKS> {
+    reader: &'r PcmReader<'a, MAX_CHUNKS>,
+    position: u32,
+    _sample: core::marker::PhantomData<T>,
+}
+
+impl<T: Float, const MAX_CHUNKS: usize> Frames<'_, '_, T, MAX_CHUNKS> {
+    /// Decode the next frame into out without copying it, e.g. into `[f32; 2]` for stereo.
+    /// * 'out' - Number of elements must be equal to or greater than the number of channels.
+    ///
+    /// Returns the number of channels written, or None at the end of the file or if out is too short.
+    pub fn next_into(&mut self, out: &mut [T]) -> Option<usize> {
+        if self.position >= self.reader.specs.num_samples {
+            return None;
+        }
+        match self.reader.read_frame(self.position, out) {
+            Ok(n) => {
+                self.position += 1;
+                Some(n)
+            }
+            Err(PcmReaderError::InvalidChannel) => None,
+            Err(_) => {
+                self.position = self.reader.specs.num_samples;
+                None
             }
         }
-        AudioFormat::ImaAdpcmLe => Err(PcmReaderError::UnsupportedAudioFormat),
+    }
+
+    /// Position of the next frame in samples.
+    #[must_use]
+    pub fn position(&self) -> u32 {
+        self.position
+    }
+}
+
+impl<T: Float, const MAX_CHUNKS: usize> Iterator for Frames<'_, '_, T, MAX_CHUNKS> {
+    type Item = Frame<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut frame = [T::ZERO; MAX_NUM_FRAME_CHANNELS];
+        let n = self.next_into(&mut frame)?;
+        Some(frame[..n].iter().copied().collect())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = (self.reader.specs.num_samples - self.position) as usize;
+        (len, Some(len))
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        let n = u32::try_from(n).unwrap_or(u32::MAX);
+        self.position = self
+            .position
+            .saturating_add(n)
+            .min(self.reader.specs.num_samples);
+        self.next()
+    }
+}
+
+impl<T: Float, const MAX_CHUNKS: usize> ExactSizeIterator for Frames<'_, '_, T, MAX_CHUNKS> {}
+
+impl<T: Float, const MAX_CHUNKS: usize> core::iter::FusedIterator
+    for Frames<'_, '_, T, MAX_CHUNKS>
+{
+}
+
+/// Decode a sample from a byte array.
+/// Returns a normalized value in the range +/-1.0 regardless of AudioFormat.
+/// TODO return not only f32 but also Q15, Q23, f64, etc.
+/// Or make it possible to select f32 or f64.
+/// It may be better to use a function like read_raw_sample() to get fixed-point numbers.
+fn decode_sample(specs: &PcmSpecs, data: &[u8]) -> Result<f32, PcmReaderError> {
+    match (&specs.audio_format, decoded_bit_depth(specs)) {
+        (AudioFormat::LinearPcmLe, 8) => Ok(le_u8_to_f32(first_bytes(data)?)),
+        (AudioFormat::LinearPcmLe, 16) => Ok(le_i16_to_f32(first_bytes(data)?)),
+        (AudioFormat::LinearPcmLe, 24) => Ok(le_i24_to_f32(first_bytes(data)?)),
+        (AudioFormat::LinearPcmLe, 32) => Ok(le_i32_to_f32(first_bytes(data)?)),
+        (AudioFormat::LinearPcmBe, 8) => Ok(be_i8_to_f32(first_bytes(data)?)),
+        (AudioFormat::LinearPcmBe, 16) => Ok(be_i16_to_f32(first_bytes(data)?)),
+        (AudioFormat::LinearPcmBe, 24) => Ok(be_i24_to_f32(first_bytes(data)?)),
+        (AudioFormat::LinearPcmBe, 32) => Ok(be_i32_to_f32(first_bytes(data)?)),
+        (AudioFormat::IeeeFloatLe, 32) => Ok(f32::from_le_bytes(first_bytes(data)?)),
+        #[cfg(feature = "f64")]
+        (AudioFormat::IeeeFloatLe, 64) => Ok(le_f64_to_f32(first_bytes(data)?)),
+        (AudioFormat::IeeeFloatBe, 32) => Ok(f32::from_be_bytes(first_bytes(data)?)),
+        #[cfg(feature = "f64")]
+        (AudioFormat::IeeeFloatBe, 64) => Ok(be_f64_to_f32(first_bytes(data)?)),
+        (AudioFormat::ALaw, 8) => Ok(alaw_to_f32(first_bytes(data)?)),
+        (AudioFormat::MuLaw, 8) => Ok(ulaw_to_f32(first_bytes(data)?)),
+        (AudioFormat::Unknown | AudioFormat::ImaAdpcmLe | AudioFormat::MsAdpcmLe, _) => {
+            Err(PcmReaderError::UnsupportedAudioFormat)
+        }
+        _ => Err(PcmReaderError::UnsupportedBitDepth),
+    }
+}
+
+/// Decode the first sample of the data to the stored integer, for read_sample_exact.
+fn decode_sample_exact(specs: &PcmSpecs, data: &[u8]) -> Result<i32, PcmReaderError> {
+    let container = decode_container_exact(specs, data)?;
+    // The samples are left-justified in the containers, and the padding bits are dropped.
+    Ok(container >> (decoded_bit_depth(specs) - specs.bit_depth))
+}
+
+/// Decode the first container of linear PCM of the data to a sign-extended integer.
+fn decode_container_exact(specs: &PcmSpecs, data: &[u8]) -> Result<i32, PcmReaderError> {
+    match (&specs.audio_format, decoded_bit_depth(specs)) {
+        // Unsigned, centered at 128
+        (AudioFormat::LinearPcmLe, 8) => {
+            let [b] = first_bytes(data)?;
+            Ok(i32::from(b) - 128)
+        }
+        (AudioFormat::LinearPcmLe, 16) => Ok(i16::from_le_bytes(first_bytes(data)?).into()),
+        (AudioFormat::LinearPcmLe, 24) => {
+            let [b0, b1, b2] = first_bytes(data)?;
+            Ok(i32::from_le_bytes([0, b0, b1, b2]) >> 8)
+        }
+        (AudioFormat::LinearPcmLe, 32) => Ok(i32::from_le_bytes(first_bytes(data)?)),
+        (AudioFormat::LinearPcmBe, 8) => Ok(i8::from_be_bytes(first_bytes(data)?).into()),
+        (AudioFormat::LinearPcmBe, 16) => Ok(i16::from_be_bytes(first_bytes(data)?).into()),
+        (AudioFormat::LinearPcmBe, 24) => {
+            let [b0, b1, b2] = first_bytes(data)?;
+            Ok(i32::from_be_bytes([b0, b1, b2, 0]) >> 8)
+        }
+        (AudioFormat::LinearPcmBe, 32) => Ok(i32::from_be_bytes(first_bytes(data)?)),
+        (AudioFormat::LinearPcmLe | AudioFormat::LinearPcmBe, _) => {
+            Err(PcmReaderError::UnsupportedBitDepth)
+        }
+        _ => Err(PcmReaderError::UnsupportedAudioFormat),
+    }
+}
+
+/// Bit depth of the containers which selects the decoder.
+/// Linear PCM of more than 8 bits is left-justified in its containers, so e.g. 12-bit in 2 bytes is decoded as 16-bit
+/// and 20-bit in 3 bytes as 24-bit. The padding bits are 0, so the values are normalized to the full scale of the bit depth.
+fn decoded_bit_depth(specs: &PcmSpecs) -> u16 {
+    let container_bits = specs.container_bytes_per_sample * 8;
+    match specs.audio_format {
+        AudioFormat::LinearPcmLe | AudioFormat::LinearPcmBe
+            if matches!(container_bits, 16 | 24 | 32)
+                && (9..container_bits).contains(&specs.bit_depth) =>
+        {
+            container_bits
+        }
+        _ => specs.bit_depth,
+    }
+}
+
+/// Select the decoder for read_sample.
+fn decode_fn(specs: &PcmSpecs) -> Option<DecodeFn> {
+    let decode: DecodeFn = match (&specs.audio_format, decoded_bit_depth(specs)) {
+        (AudioFormat::LinearPcmLe, 8) => |b| le_u8_to_f32([b[0]]),
+        (AudioFormat::LinearPcmLe, 16) => |b| le_i16_to_f32([b[0], b[1]]),
+        (AudioFormat::LinearPcmLe, 24) => |b| le_i24_to_f32([b[0], b[1], b[2]]),
+        (AudioFormat::LinearPcmLe, 32) => |b| le_i32_to_f32([b[0], b[1], b[2], b[3]]),
+        (AudioFormat::LinearPcmBe, 8) => |b| be_i8_to_f32([b[0]]),
+        (AudioFormat::LinearPcmBe, 16) => |b| be_i16_to_f32([b[0], b[1]]),
+        (AudioFormat::LinearPcmBe, 24) => |b| be_i24_to_f32([b[0], b[1], b[2]]),
+        (AudioFormat::LinearPcmBe, 32) => |b| be_i32_to_f32([b[0], b[1], b[2], b[3]]),
+        (AudioFormat::IeeeFloatLe, 32) => |b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+        #[cfg(feature = "f64")]
+        (AudioFormat::IeeeFloatLe, 64) => |b| le_f64_to_f32(b.try_into().unwrap()),
+        (AudioFormat::IeeeFloatBe, 32) => |b| f32::from_be_bytes([b[0], b[1], b[2], b[3]]),
+        #[cfg(feature = "f64")]
+        (AudioFormat::IeeeFloatBe, 64) => |b| be_f64_to_f32(b.try_into().unwrap()),
+        (AudioFormat::ALaw, 8) => |b| alaw_to_f32([b[0]]),
+        (AudioFormat::MuLaw, 8) => |b| ulaw_to_f32([b[0]]),
+        _ => return None,
+    };
+    Some(decode)
+}
+
+/// Decode interleaved samples in the format of specs.
+/// The format is matched once, and then the samples are decoded in a loop specialized for it.
+fn decode_interleaved<T: Float>(
+    specs: &PcmSpecs,
+    frame: &[u8],
+    out: &mut [T],
+) -> Result<(), PcmReaderError> {
+    match (&specs.audio_format, decoded_bit_depth(specs)) {
+        (AudioFormat::LinearPcmLe, 8) => decode_channels(frame, out, le_u8_to_f32),
+        (AudioFormat::LinearPcmLe, 16) => decode_channels(frame, out, le_i16_to_f32),
+        (AudioFormat::LinearPcmLe, 24) => decode_channels(frame, out, le_i24_to_f32),
+        (AudioFormat::LinearPcmLe, 32) => decode_channels(frame, out, le_i32_to_f32),
+        (AudioFormat::LinearPcmBe, 8) => decode_channels(frame, out, be_i8_to_f32),
+        (AudioFormat::LinearPcmBe, 16) => decode_channels(frame, out, be_i16_to_f32),
+        (AudioFormat::LinearPcmBe, 24) => decode_channels(frame, out, be_i24_to_f32),
+        (AudioFormat::LinearPcmBe, 32) => decode_channels(frame, out, be_i32_to_f32),
+        (AudioFormat::IeeeFloatLe, 32) => decode_channels(frame, out, f32::from_le_bytes),
+        #[cfg(feature = "f64")]
+        (AudioFormat::IeeeFloatLe, 64) => decode_channels(frame, out, le_f64_to_f32),
+        (AudioFormat::IeeeFloatBe, 32) => decode_channels(frame, out, f32::from_be_bytes),
+        #[cfg(feature = "f64")]
+        (AudioFormat::IeeeFloatBe, 64) => decode_channels(frame, out, be_f64_to_f32),
+        (AudioFormat::ALaw, 8) => decode_channels(frame, out, alaw_to_f32),
+        (AudioFormat::MuLaw, 8) => decode_channels(frame, out, ulaw_to_f32),
+        // Returns the same error as decode_sample.
+        _ => return decode_sample(specs, frame).map(|_| ()),
+    }
+    Ok(())
+}
+
+/// Returns num_frames frames of data from the frame, if they are in the data.
+/// Offsets are computed in u64 so that they do not overflow on 32bit targets.
+fn data_at(data: &[u8], bytes_per_frame: usize, frame: u64, num_frames: u64) -> Option<&[u8]> {
+    let bytes_per_frame = bytes_per_frame as u64;
+    let start = usize::try_from(bytes_per_frame * frame).ok()?;
+    let end = usize::try_from(bytes_per_frame * (frame + num_frames)).ok()?;
+    data.get(start..end)
+}
+
+/// Decode interleaved frames into one buffer per channel in the format of specs.
+/// The samples are written from the offset. Every buffer must hold at least offset + block.len() / (bytes per frame) samples.
+fn decode_planar(
+    specs: &PcmSpecs,
+    block: &[u8],
+    out: &mut [&mut [f32]],
+    offset: usize,
+) -> Result<(), PcmReaderError> {
+    match (&specs.audio_format, decoded_bit_depth(specs)) {
+        (AudioFormat::LinearPcmLe, 8) => decode_frames_planar(block, out, offset, le_u8_to_f32),
+        (AudioFormat::LinearPcmLe, 16) => decode_frames_planar(block, out, offset, le_i16_to_f32),
+        (AudioFormat::LinearPcmLe, 24) => decode_frames_planar(block, out, offset, le_i24_to_f32),
+        (AudioFormat::LinearPcmLe, 32) => decode_frames_planar(block, out, offset, le_i32_to_f32),
+        (AudioFormat::LinearPcmBe, 8) => decode_frames_planar(block, out, offset, be_i8_to_f32),
+        (AudioFormat::LinearPcmBe, 16) => decode_frames_planar(block, out, offset, be_i16_to_f32),
+        (AudioFormat::LinearPcmBe, 24) => decode_frames_planar(block, out, offset, be_i24_to_f32),
+        (AudioFormat::LinearPcmBe, 32) => decode_frames_planar(block, out, offset, be_i32_to_f32),
+        (AudioFormat::IeeeFloatLe, 32) => {
+            decode_frames_planar(block, out, offset, f32::from_le_bytes)
+        }
+        #[cfg(feature = "f64")]
+        (AudioFormat::IeeeFloatLe, 64) => decode_frames_planar(block, out, offset, le_f64_to_f32),
+        (AudioFormat::IeeeFloatBe, 32) => {
+            decode_frames_planar(block, out, offset, f32::from_be_bytes)
+        }
+        #[cfg(feature = "f64")]
+        (AudioFormat::IeeeFloatBe, 64) => decode_frames_planar(block, out, offset, be_f64_to_f32),
+        (AudioFormat::ALaw, 8) => decode_frames_planar(block, out, offset, alaw_to_f32),
+        (AudioFormat::MuLaw, 8) => decode_frames_planar(block, out, offset, ulaw_to_f32),
+        // Returns the same error as decode_sample.
+        _ => return decode_sample(specs, block).map(|_| ()),
+    }
+    Ok(())
+}
+
+/// Walk the frames forward once and scatter the samples of each frame to the buffers of the channels.
+#[inline]
+fn decode_frames_planar<const N: usize>(
+    block: &[u8],
+    out: &mut [&mut [f32]],
+    offset: usize,
+    decode: fn([u8; N]) -> f32,
+) {
+    let frame_size = N * out.len();
+    for (i, frame) in block.chunks_exact(frame_size).enumerate() {
+        for (bytes, channel) in frame.chunks_exact(N).zip(out.iter_mut()) {
+            channel[offset + i] = decode(bytes.try_into().unwrap());
+        }
+    }
+}
+
+/// Decode min(frame.len() / N, out.len()) samples.
+#[cfg(not(feature = "unchecked-decode"))]
+#[inline]
+fn decode_channels<T: Float, const N: usize>(
+    frame: &[u8],
+    out: &mut [T],
+    decode: fn([u8; N]) -> f32,
+) {
+    for (bytes, out) in frame.chunks_exact(N).zip(out.iter_mut()) {
+        *out = T::from_f32(decode(bytes.try_into().unwrap()));
+    }
+}
+
+/// Decode min(frame.len() / N, out.len()) samples without bounds checks in the loop.
+/// The number of samples is computed once from the lengths of both slices,
+/// so every index in the loop is in range.
+#[cfg(feature = "unchecked-decode")]
+#[inline]
+fn decode_channels<T: Float, const N: usize>(
+    frame: &[u8],
+    out: &mut [T],
+    decode: fn([u8; N]) -> f32,
+) {
+    let num_samples = (frame.len() / N).min(out.len());
+    for i in 0..num_samples {
+        // SAFETY: i < num_samples <= frame.len() / N, so (i + 1) * N <= frame.len().
+        // The read does not assume any alignment of the data.
+        let bytes = unsafe { frame.as_ptr().add(i * N).cast::<[u8; N]>().read_unaligned() };
+        // SAFETY: i < num_samples <= out.len()
+        unsafe { *out.get_unchecked_mut(i) = T::from_f32(decode(bytes)) };
+    }
+}
+
+// Conversions from the bytes of a sample to a normalized value.
+// The normalize factor is 2^(BitDepth-1). Its reciprocal is exact in f32,
+// so the multiplication gives the same values as the division.
+
+const SCALE_16: f32 = 1.0 / 32768.0;
+const SCALE_24: f32 = 1.0 / 8388608.0;
+const SCALE_32: f32 = 1.0 / 2147483648.0;
+
+/// 8bit samples of WAV are unsigned. See [g711::u8_to_f32].
+#[inline]
+fn le_u8_to_f32([b]: [u8; 1]) -> f32 {
+    g711::u8_to_f32(b)
+}
+
+/// 8bit samples of AIFF are signed. See [g711::i8_to_f32].
+#[inline]
+fn be_i8_to_f32([b]: [u8; 1]) -> f32 {
+    g711::i8_to_f32(b)
+}
+
+#[inline]
+fn le_i16_to_f32(bytes: [u8; 2]) -> f32 {
+    i16::from_le_bytes(bytes) as f32 * SCALE_16
+}
+
+#[inline]
+fn be_i16_to_f32(bytes: [u8; 2]) -> f32 {
+    i16::from_be_bytes(bytes) as f32 * SCALE_16
+}
+
+#[inline]
+fn le_i24_to_f32([b0, b1, b2]: [u8; 3]) -> f32 {
+    // Sign extension by the arithmetic shift
+    (i32::from_le_bytes([0, b0, b1, b2]) >> 8) as f32 * SCALE_24
+}
+
+#[inline]
+fn be_i24_to_f32([b0, b1, b2]: [u8; 3]) -> f32 {
+    // Sign extension by the arithmetic shift
+    (i32::from_be_bytes([b0, b1, b2, 0]) >> 8) as f32 * SCALE_24
+}
+
+#[inline]
+fn le_i32_to_f32(bytes: [u8; 4]) -> f32 {
+    i32::from_le_bytes(bytes) as f32 * SCALE_32
+}
+
+#[inline]
+fn be_i32_to_f32(bytes: [u8; 4]) -> f32 {
+    i32::from_be_bytes(bytes) as f32 * SCALE_32
+}
+
+#[inline]
+#[cfg(feature = "f64")]
+fn le_f64_to_f32(bytes: [u8; 8]) -> f32 {
+    f64::from_le_bytes(bytes) as f32 // TODO f32にダウンキャストするべきなのか検討
+}
+
+#[inline]
+#[cfg(feature = "f64")]
+fn be_f64_to_f32(bytes: [u8; 8]) -> f32 {
+    f64::from_be_bytes(bytes) as f32 // TODO f32にダウンキャストするべきなのか検討
+}
+
+/// Whether the ID of a chunk is printable ASCII, as required by RIFF and IFF.
+fn is_plausible_fourcc(id: &[u8; 4]) -> bool {
+    id.iter().all(|b| (0x20..=0x7E).contains(b))
+}
+
+#[inline]
+fn alaw_to_f32([b]: [u8; 1]) -> f32 {
+    g711::alaw_to_f32(b)
+}
+
+#[inline]
+fn ulaw_to_f32([b]: [u8; 1]) -> f32 {
+    g711::ulaw_to_f32(b)
+}
+
+/// Returns the first N bytes of the data.
+/// Returns InvalidSample if the data is shorter than N bytes.
+#[inline]
+fn first_bytes<const N: usize>(data: &[u8]) -> Result<[u8; N], PcmReaderError> {
+    match data.get(..N) {
+        Some(bytes) => Ok(bytes.try_into().unwrap()),
+        None => Err(PcmReaderError::InvalidSample),
     }
 }
 
@@ -394,34 +2354,53 @@ pub enum PcmPlayerError {
     InvalidPosition,
     #[error("Finish playing")]
     FinishPlaying,
+    #[error("Reader error")]
+    Reader(#[from] PcmReaderError),
 }
 
 /// High level of organized players for LinearPCM (WAVE or AIFF) file.
-#[derive(Default)]
-pub struct PcmPlayer<'a> {
+pub struct PcmPlayer<'a, const MAX_CHUNKS: usize = MAX_NUM_CHUNKS> {
     /// A reader to access basic information about the PCM file.
-    pub reader: PcmReader<'a>,
-    playback_position: u32,
+    pub reader: PcmReader<'a, MAX_CHUNKS>,
+    /// Kept in u64 so that the position arithmetic does not overflow.
+    playback_position: u64,
     loop_playing: bool,
+    /// Set by set_note. 1 plays at the original pitch.
+    speed_ratio: pitch::U16F16,
 }
 
-impl<'a> PcmPlayer<'a> {
+impl<const MAX_CHUNKS: usize> Default for PcmPlayer<'_, MAX_CHUNKS> {
+    fn default() -> Self {
+        Self::new(PcmReader::default())
+    }
+}
+
+impl<'a, const MAX_CHUNKS: usize> PcmPlayer<'a, MAX_CHUNKS> {
     /// * 'input' - PCM data byte array
-    pub fn new(reader: PcmReader<'a>) -> Self {
+    pub fn new(reader: PcmReader<'a, MAX_CHUNKS>) -> Self {
         PcmPlayer {
             reader,
             playback_position: 0,
             loop_playing: false,
+            speed_ratio: pitch::U16F16::ONE,
         }
     }
 
+    /// Returns a reference to basic information about the PCM file.
+    #[must_use]
+    pub fn specs(&self) -> &PcmSpecs {
+        &self.reader.specs
+    }
+
     /// Move the playback position to the desired position.
     /// * 'sample' - Playback position in samples.
+    ///
+    /// Position 0 is always accepted, so that a file without samples can be rewound.
     pub fn set_position(&mut self, sample: u32) -> Result<(), PcmPlayerError> {
-        if self.reader.specs.num_samples <= sample {
+        if sample != 0 && self.reader.specs.num_samples <= sample {
             return Err(PcmPlayerError::InvalidPosition);
         }
-        self.playback_position = sample;
+        self.playback_position = u64::from(sample);
         Ok(())
     }
 
@@ -432,6 +2411,145 @@ impl<'a> PcmPlayer<'a> {
         self.loop_playing = en;
     }
 
+    /// Set the playback speed to play a MIDI note, as the ratio of [pitch::note_ratio].
+    /// The root can be taken from the metadata of the file with [PcmReader::root_note].
+    ///
+    /// The ratio is only stored for now. The player has no varispeed engine yet, so the frames are still played at the original speed.
+    pub fn set_note(&mut self, root: u8, note: u8, fine_cents: i8) {
+        self.speed_ratio = pitch::note_ratio(root, note, fine_cents);
+    }
+
+    /// Returns the playback speed ratio set by [PcmPlayer::set_note], 1 by default.
+    #[must_use]
+    pub fn speed_ratio(&self) -> pitch::U16F16 {
+        self.speed_ratio
+    }
+
+    /// Fill the buffer with the next frames, wrapping around when loop playback is enabled.
+    /// * 'out' - Output buffer of interleaved samples. As many whole frames as fit are written.
+    ///
+    /// Returns the number of frames written. It is less than the buffer holds only when playback finishes.
+    pub fn fill_interleaved(&mut self, out: &mut [f32]) -> Result<usize, PcmPlayerError> {
+        self.fill_with(out, PcmReader::decode_block_f32)
+    }
+
+    /// Same as [PcmPlayer::fill_interleaved], for async code.
+    /// The data is in memory, so this completes without waiting.
+    #[cfg(feature = "async")]
+    pub async fn fill_interleaved_async(
+        &mut self,
+        out: &mut [f32],
+    ) -> Result<usize, PcmPlayerError> {
+        self.fill_interleaved(out)
+    }
+
+    /// Refill one half of a DMA double buffer (e.g. for I2S) with 16bit samples.
+    /// Samples are interleaved in the channel order of the file.
+    /// When playback finishes, the rest of the buffer is filled with silence so it can always be handed to the DMA.
+    /// * 'out' - Half of the DMA buffer.
+    ///
+    /// Returns the number of frames taken from the file.
+    pub fn refill_half_buffer(&mut self, out: &mut [i16]) -> Result<usize, PcmPlayerError> {
+        let num_frames = match self.fill_with(out, PcmReader::decode_block_i16) {
+            Ok(n) => n,
+            Err(PcmPlayerError::FinishPlaying) => 0,
+            Err(e) => return Err(e),
+        };
+        let num_channels = self.reader.specs.num_channels as usize;
+        out[num_frames * num_channels..].fill(0);
+        Ok(num_frames)
+    }
+
+    /// Fill the buffer with the next frames formatted for the 32bit slots of an I2S peripheral.
+    /// See [i2s::format_frames].
+    /// * 'out' - Output buffer of interleaved slots. As many whole frames as fit are written.
+    /// * 'fmt' - Position of the sample bits in the slot.
+    /// * 'bits' - Bit depth of the codec, from 8 to 32.
+    ///
+    /// Returns the number of frames written. It is less than the buffer holds only when playback finishes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if bits is not in the range 8..=32.
+    pub fn fill_formatted(
+        &mut self,
+        out: &mut [u32],
+        fmt: i2s::FrameFormat,
+        bits: u8,
+    ) -> Result<usize, PcmPlayerError> {
+        let num_channels = self.reader.specs.num_channels as usize;
+        let mut scratch = [0f32; 256];
+        let frames_per_chunk = scratch.len() / num_channels.max(1);
+        if num_channels == 0 || out.len() < num_channels || frames_per_chunk == 0 {
+            return Err(PcmPlayerError::OutputBufferTooShort);
+        }
+
+        let num_frames = out.len() / num_channels;
+        let mut filled = 0;
+        for chunk in out[..num_frames * num_channels].chunks_mut(frames_per_chunk * num_channels) {
+            let n = match self.fill_interleaved(&mut scratch[..chunk.len()]) {
+                Ok(n) => n,
+                Err(PcmPlayerError::FinishPlaying) => break,
+                Err(e) => return Err(e),
+            };
+            i2s::format_frames(&scratch[..n * num_channels], chunk, fmt, bits);
+            filled += n;
+            if n * num_channels < chunk.len() {
+                break;
+            }
+        }
+
+        if filled == 0 {
+            return Err(PcmPlayerError::FinishPlaying);
+        }
+        Ok(filled)
+    }
+
+    /// Fill the buffer by repeatedly decoding blocks from the playback position.
+    fn fill_with<T>(
+        &mut self,
+        out: &mut [T],
+        decode_block: fn(
+            &PcmReader<'a, MAX_CHUNKS>,
+            u32,
+            &mut [T],
+        ) -> Result<usize, PcmReaderError>,
+    ) -> Result<usize, PcmPlayerError> {
+        let num_channels = self.reader.specs.num_channels as usize;
+        if num_channels == 0 || out.len() < num_channels {
+            return Err(PcmPlayerError::OutputBufferTooShort);
+        }
+
+        let num_samples = u64::from(self.reader.specs.num_samples);
+        let num_frames = out.len() / num_channels;
+        let mut filled = 0;
+        while filled < num_frames {
+            if self.playback_position >= num_samples {
+                if self.loop_playing && num_samples > 0 {
+                    self.playback_position = 0;
+                } else {
+                    break;
+                }
+            }
+            // playback_position < num_samples, so it fits in u32.
+            let n = decode_block(
+                &self.reader,
+                self.playback_position as u32,
+                &mut out[filled * num_channels..num_frames * num_channels],
+            )?;
+            if n == 0 {
+                return Err(PcmPlayerError::InvalidPosition);
+            }
+            filled += n;
+            self.playback_position += n as u64;
+        }
+
+        if filled == 0 {
+            return Err(PcmPlayerError::FinishPlaying);
+        }
+        Ok(filled)
+    }
+
     /// Return samples value of the next frame.
     /// * ‘out’ - Output buffer which the sample values are written. Number of elements must be equal to or greater than the number of channels in the PCM file.
     pub fn get_next_frame(&mut self, out: &mut [f32]) -> Result<(), PcmPlayerError> {
@@ -439,22 +2557,18 @@ impl<'a> PcmPlayer<'a> {
             return Err(PcmPlayerError::OutputBufferTooShort);
         }
 
-        let num_samples = self.reader.specs.num_samples;
+        // The position may be beyond the end if the reader has been reloaded with a shorter file.
+        let num_samples = u64::from(self.reader.specs.num_samples);
         if self.playback_position >= num_samples {
-            if self.loop_playing {
-                self.set_position(0)?;
+            if self.loop_playing && num_samples > 0 {
+                self.playback_position = 0;
             } else {
                 return Err(PcmPlayerError::FinishPlaying);
             }
         }
 
-        let num_chennels = self.reader.specs.num_channels;
-        for ch in 0..num_chennels {
-            let Ok(sample) = self.reader.read_sample(ch, self.playback_position) else {
-                return Err(PcmPlayerError::InvalidPosition);
-            };
-            out[ch as usize] = sample;
-        }
+        // The position is less than the number of samples, so it fits in u32.
+        self.reader.read_frame(self.playback_position as u32, out)?;
 
         // Update the playback position.
         self.playback_position += 1;
@@ -462,3 +2576,333 @@ impl<'a> PcmPlayer<'a> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn specs(audio_format: AudioFormat, bit_depth: u16) -> PcmSpecs {
+        PcmSpecs {
+            audio_format,
+            bit_depth,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn duration() {
+        let mut specs = PcmSpecs::new(AudioFormat::LinearPcmLe, 1, 48000, 16);
+        specs.num_samples = 240000;
+        assert_eq!(specs.duration_secs(), 5.0);
+        assert_eq!(specs.duration_millis(), 5000);
+        assert_eq!(specs.duration(), core::time::Duration::from_secs(5));
+
+        // 1001 frames at 44.1 kHz are 22.698... ms.
+        specs.sample_rate = 44100;
+        specs.num_samples = 1001;
+        assert_eq!(specs.duration_millis(), 23);
+        assert_eq!(specs.duration().as_nanos(), 22_698_412);
+        assert!((specs.duration_secs() - 0.022_698_412).abs() < 1e-7);
+        // 22 frames are 0.4988... ms.
+        specs.num_samples = 22;
+        assert_eq!(specs.duration_millis(), 0);
+        specs.num_samples = 23;
+        assert_eq!(specs.duration_millis(), 1);
+
+        assert_eq!(specs.frames_for_duration(1000), 44100);
+        assert_eq!(specs.frames_for_duration(1), 44);
+        assert_eq!(specs.frames_for_duration(3), 132);
+        assert_eq!(specs.frames_for_duration(0), 0);
+        specs.sample_rate = 44110;
+        // 44.11 frames per millisecond, and 22.055 frames in 0.5 ms.
+        assert_eq!(specs.frames_for_duration(1), 44);
+        assert_eq!(specs.frames_for_duration(10), 441);
+        specs.sample_rate = 192000;
+        assert_eq!(specs.frames_for_duration(u32::MAX), u32::MAX);
+
+        // The sample rate of 1 Hz does not overflow.
+        specs.sample_rate = 1;
+        specs.num_samples = u32::MAX;
+        assert_eq!(specs.duration_millis(), u32::MAX);
+        assert_eq!(specs.duration().as_secs(), u64::from(u32::MAX));
+
+        specs.sample_rate = 0;
+        assert_eq!(specs.duration_secs(), 0.0);
+        assert_eq!(specs.duration_millis(), 0);
+        assert_eq!(specs.duration(), core::time::Duration::ZERO);
+        assert_eq!(specs.frames_for_duration(1000), 0);
+    }
+
+    #[test]
+    fn scale_is_exact() {
+        // The multiplication by the reciprocal gives the same values as the division.
+        for v in i16::MIN..=i16::MAX {
+            assert_eq!(le_i16_to_f32(v.to_le_bytes()), v as f32 / 32768.0);
+        }
+        for v in (-8388608i32..=8388607).step_by(7) {
+            let [b0, b1, b2, _] = v.to_le_bytes();
+            assert_eq!(le_i24_to_f32([b0, b1, b2]), v as f32 / 8388608.0);
+        }
+        for v in [i32::MIN, -1, 0, 1, 12345678, i32::MAX] {
+            assert_eq!(be_i32_to_f32(v.to_be_bytes()), v as f32 / 2147483648.0);
+        }
+    }
+
+    #[test]
+    fn decode_sample_test() {
+        let le = |bit_depth| specs(AudioFormat::LinearPcmLe, bit_depth);
+        let be = |bit_depth| specs(AudioFormat::LinearPcmBe, bit_depth);
+        assert_eq!(decode_sample(&le(16), &[0x00, 0x80]).unwrap(), -1.0);
+        assert_eq!(decode_sample(&be(16), &[0x40, 0x00]).unwrap(), 0.5);
+        assert_eq!(decode_sample(&le(24), &[0x00, 0x00, 0x80]).unwrap(), -1.0);
+        assert_eq!(
+            decode_sample(&le(24), &[0xFF, 0xFF, 0xFF]).unwrap(),
+            -1.0 / 8388608.0
+        );
+        assert_eq!(decode_sample(&be(24), &[0xC0, 0x00, 0x00]).unwrap(), -0.5);
+        assert_eq!(decode_sample(&le(32), &[0, 0, 0, 0x40]).unwrap(), 0.5);
+        assert_eq!(decode_sample(&be(32), &[0x80, 0, 0, 0]).unwrap(), -1.0);
+
+        let f32le = specs(AudioFormat::IeeeFloatLe, 32);
+        assert_eq!(decode_sample(&f32le, &0.25f32.to_le_bytes()).unwrap(), 0.25);
+
+        // Trailing bytes are ignored.
+        assert_eq!(decode_sample(&le(16), &[0x00, 0x40, 0xFF]).unwrap(), 0.5);
+        // Short input
+        assert!(matches!(
+            decode_sample(&le(24), &[0x00, 0x00]),
+            Err(PcmReaderError::InvalidSample)
+        ));
+    }
+
+    #[test]
+    fn decode_sample_f64() {
+        let f64be = specs(AudioFormat::IeeeFloatBe, 64);
+        #[cfg(feature = "f64")]
+        {
+            assert_eq!(
+                decode_sample(&f64be, &(-0.75f64).to_be_bytes()).unwrap(),
+                -0.75
+            );
+            assert!(matches!(
+                decode_sample(&f64be, &[0; 7]),
+                Err(PcmReaderError::InvalidSample)
+            ));
+        }
+        #[cfg(not(feature = "f64"))]
+        assert!(matches!(
+            decode_sample(&f64be, &[0; 8]),
+            Err(PcmReaderError::UnsupportedBitDepth)
+        ));
+    }
+
+    #[test]
+    fn decode_interleaved_test() {
+        let specs = specs(AudioFormat::LinearPcmBe, 24);
+        let frame = [0x40, 0x00, 0x00, 0xC0, 0x00, 0x00, 0xFF, 0xFF, 0xFF];
+        let mut out = [0f32; 3];
+        decode_interleaved(&specs, &frame, &mut out).unwrap();
+        for (bytes, value) in frame.chunks(3).zip(out) {
+            assert_eq!(decode_sample(&specs, bytes).unwrap(), value);
+        }
+
+        // 20bit in 24bit containers is decoded as 24bit.
+        let specs20 = PcmSpecs {
+            container_bytes_per_sample: 3,
+            ..self::specs(AudioFormat::LinearPcmBe, 20)
+        };
+        let mut out20 = [0f32; 3];
+        decode_interleaved(&specs20, &frame, &mut out20).unwrap();
+        assert_eq!(out20, out);
+        assert_eq!(
+            decode_sample_exact(&specs20, &frame[3..]).unwrap(),
+            -0x40000
+        );
+        assert_eq!(decode_sample_exact(&specs, &frame[3..]).unwrap(), -0x400000);
+
+        let specs = self::specs(AudioFormat::LinearPcmLe, 20);
+        assert!(matches!(
+            decode_interleaved(&specs, &frame, &mut out),
+            Err(PcmReaderError::UnsupportedBitDepth)
+        ));
+    }
+
+    /// Small enough to run under Miri, with and without the `unchecked-decode` feature.
+    #[test]
+    fn decode_channels_lengths() {
+        let bytes: [u8; 7] = [0x00, 0x40, 0x00, 0xC0, 0x00, 0x80, 0xFF];
+
+        // The trailing byte is not a whole sample.
+        let mut out = [9f32; 4];
+        decode_channels(&bytes, &mut out, le_i16_to_f32);
+        assert_eq!(out, [0.5, -0.5, -1.0, 9.0]);
+
+        // The output is shorter than the input.
+        let mut out = [9f32; 2];
+        decode_channels(&bytes, &mut out, le_i16_to_f32);
+        assert_eq!(out, [0.5, -0.5]);
+
+        let mut out = [9f32; 2];
+        decode_channels(&bytes[..5], &mut out, le_i24_to_f32);
+        assert_eq!(out, [le_i24_to_f32([0x00, 0x40, 0x00]), 9.0]);
+
+        decode_channels(&[], &mut out, le_i16_to_f32);
+        decode_channels(&bytes, &mut [0f32; 0], le_i16_to_f32);
+    }
+
+    /// The decoders read the same values from every offset of an 8-byte aligned buffer.
+    /// Small enough to run under Miri, which rejects misaligned loads.
+    #[test]
+    fn decode_misaligned() {
+        #[repr(C, align(8))]
+        struct Aligned([u8; 56]);
+
+        let mut data = [0u8; 48];
+        for (i, b) in data.iter_mut().enumerate() {
+            *b = (i as u8).wrapping_mul(73).wrapping_add(5);
+        }
+        let formats = [
+            (AudioFormat::LinearPcmLe, 8),
+            (AudioFormat::LinearPcmLe, 16),
+            (AudioFormat::LinearPcmLe, 24),
+            (AudioFormat::LinearPcmLe, 32),
+            (AudioFormat::LinearPcmBe, 8),
+            (AudioFormat::LinearPcmBe, 16),
+            (AudioFormat::LinearPcmBe, 24),
+            (AudioFormat::LinearPcmBe, 32),
+            (AudioFormat::IeeeFloatLe, 32),
+            (AudioFormat::IeeeFloatBe, 32),
+            #[cfg(feature = "f64")]
+            (AudioFormat::IeeeFloatLe, 64),
+            #[cfg(feature = "f64")]
+            (AudioFormat::IeeeFloatBe, 64),
+            (AudioFormat::ALaw, 8),
+            (AudioFormat::MuLaw, 8),
+        ];
+        for (audio_format, bit_depth) in formats {
+            let specs = PcmSpecs {
+                num_channels: 2,
+                ..specs(audio_format, bit_depth)
+            };
+            let mut expected = [0f32; 48];
+            decode_interleaved(&specs, &data, &mut expected).unwrap();
+
+            for offset in 1..8 {
+                let mut buffer = Aligned([0; 56]);
+                buffer.0[offset..offset + 48].copy_from_slice(&data);
+                let bytes = &buffer.0[offset..offset + 48];
+                assert_eq!(bytes.as_ptr() as usize % 8, offset);
+
+                let mut out = [0f32; 48];
+                decode_interleaved(&specs, bytes, &mut out).unwrap();
+                assert_eq!(out.map(f32::to_bits), expected.map(f32::to_bits));
+
+                let (mut left, mut right) = ([0f32; 24], [0f32; 24]);
+                decode_planar(&specs, bytes, &mut [&mut left, &mut right], 0).unwrap();
+                let num_samples = 48 / (bit_depth as usize / 8);
+                for i in 0..num_samples {
+                    let planar = if i % 2 == 0 {
+                        left[i / 2]
+                    } else {
+                        right[i / 2]
+                    };
+                    assert_eq!(planar.to_bits(), expected[i].to_bits());
+                }
+
+                let size = bit_depth as usize / 8;
+                let decode = decode_fn(&specs).unwrap();
+                for (i, sample) in bytes.chunks_exact(size).enumerate() {
+                    assert_eq!(decode(sample).to_bits(), expected[i].to_bits());
+                    let value = decode_sample(&specs, sample).unwrap();
+                    assert_eq!(value.to_bits(), expected[i].to_bits());
+                }
+            }
+        }
+    }
+
+    /// Synthetic specs claiming far more samples than the data holds, with positions near u32::MAX.
+    #[test]
+    fn positions_near_u32_max() {
+        let data = [0u8; 256];
+        let mut reader: PcmReader = PcmReader {
+            specs: PcmSpecs {
+                audio_format: AudioFormat::LinearPcmLe,
+                num_channels: 16,
+                bit_depth: 32,
+                container_bytes_per_sample: 4,
+                num_samples: u32::MAX,
+                ..Default::default()
+            },
+            data: &data,
+            ..Default::default()
+        };
+        reader.update_cache();
+
+        assert_eq!(reader.read_sample(15, 3).unwrap(), 0.0);
+        assert!(matches!(
+            reader.read_sample(15, u32::MAX - 1),
+            Err(PcmReaderError::InvalidSample)
+        ));
+        let mut out = [0f32; 16];
+        assert!(matches!(
+            reader.decode_block_f32(u32::MAX - 1, &mut out),
+            Err(PcmReaderError::InvalidSample)
+        ));
+        assert!(matches!(
+            reader.read_frame(u32::MAX - 1, &mut out),
+            Err(PcmReaderError::InvalidSample)
+        ));
+
+        let mut player = PcmPlayer::new(reader);
+        player.set_position(u32::MAX - 1).unwrap();
+        assert!(matches!(
+            player.get_next_frame(&mut out),
+            Err(PcmPlayerError::Reader(PcmReaderError::InvalidSample))
+        ));
+        assert!(matches!(
+            player.set_position(u32::MAX),
+            Err(PcmPlayerError::InvalidPosition)
+        ));
+    }
+
+    /// All error types implement core::error::Error, and wrapped errors are returned by source().
+    #[test]
+    fn error_source_chain() {
+        use crate::imaadpcm::ImaAdpcmError;
+        use crate::msadpcm::MsAdpcmError;
+        use crate::stream::StreamError;
+        use crate::transcode::TranscodeError;
+        use crate::writer::PcmWriterError;
+        use core::error::Error;
+
+        fn assert_error<E: Error + Send + Sync + 'static>() {}
+        assert_error::<PcmReaderError>();
+        assert_error::<PcmPlayerError>();
+        assert_error::<ImaAdpcmError>();
+        assert_error::<MsAdpcmError>();
+        assert_error::<PcmWriterError>();
+        assert_error::<TranscodeError>();
+        assert_error::<StreamError<core::convert::Infallible>>();
+
+        let e = PcmPlayerError::from(PcmReaderError::InvalidSample);
+        assert!(matches!(
+            e.source().unwrap().downcast_ref(),
+            Some(PcmReaderError::InvalidSample)
+        ));
+        let e = TranscodeError::from(ImaAdpcmError::BlockLengthMismatch);
+        assert!(matches!(
+            e.source().unwrap().downcast_ref(),
+            Some(ImaAdpcmError::BlockLengthMismatch)
+        ));
+        let e = StreamError::<core::convert::Infallible>::from(PcmPlayerError::from(
+            PcmReaderError::UnsupportedBitDepth,
+        ));
+        let source = e.source().unwrap();
+        assert!(source.downcast_ref::<PcmPlayerError>().is_some());
+        assert!(matches!(
+            source.source().unwrap().downcast_ref(),
+            Some(PcmReaderError::UnsupportedBitDepth)
+        ));
+        assert!(PcmPlayerError::FinishPlaying.source().is_none());
+    }
+}
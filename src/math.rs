@@ -0,0 +1,49 @@
+//! Math routines which are not in core, so that no floating point transcendental function is required.
+//!
+//! The default backends are integer or fixed-point approximations, which suit targets without an FPU.
+//! With the `libm` feature, the routines call libm instead.
+
+use fixed::types::U16F16;
+
+/// 2^(k/12) for k = 0..12 in Q2.30.
+#[cfg(not(feature = "libm"))]
+const SEMITONE_RATIOS: [u64; 12] = [
+    1073741824, 1137589835, 1205234447, 1276901417, 1352829926, 1433273380, 1518500250, 1608794974,
+    1704458901, 1805811301, 1913190429, 2026954652,
+];
+
+/// ln(2) / 1200 in Q0.40. The extra bits keep the error of x = c * ln(2) / 1200 below 2^-30.
+#[cfg(not(feature = "libm"))]
+const LN2_PER_CENT: u64 = 635102821;
+
+/// 2^(cents / 1200). cents is within ±12828, so the result fits in U16F16.
+#[cfg(not(feature = "libm"))]
+pub(crate) fn pow2_cents(cents: i32) -> U16F16 {
+    let octave = cents.div_euclid(1200);
+    let rest = cents.rem_euclid(1200);
+    let semitone = SEMITONE_RATIOS[(rest / 100) as usize];
+
+    // 2^(c/1200) = e^x with x = c * ln(2) / 1200 < 0.058. The Taylor series up to x^5 is exact to 2^-30.
+    let x = ((rest % 100) as u64 * LN2_PER_CENT + (1 << 9)) >> 10;
+    let x2 = (x * x) >> 30;
+    let x3 = (x2 * x) >> 30;
+    let x4 = (x3 * x) >> 30;
+    let x5 = (x4 * x) >> 30;
+    let fine = (1 << 30) + x + x2 / 2 + x3 / 6 + x4 / 24 + x5 / 120;
+    let mantissa = (semitone * fine) >> 30;
+
+    // Q2.30 to Q16.16, rounded.
+    let shift = 14 - octave;
+    let bits = if shift > 0 {
+        (mantissa + (1 << (shift - 1))) >> shift
+    } else {
+        mantissa << -shift
+    };
+    U16F16::from_bits(bits as u32)
+}
+
+/// 2^(cents / 1200).
+#[cfg(feature = "libm")]
+pub(crate) fn pow2_cents(cents: i32) -> U16F16 {
+    U16F16::saturating_from_num(libm::exp2f(cents as f32 / 1200.0))
+}
@@ -0,0 +1,514 @@
+//! Metadata of the files, e.g. the tags in the LIST chunk of type INFO, the loops of the smpl chunk and the cue points of WAV, the INST chunk of AIFF, the comments of AIFF, the bext chunk of BWF and the cart chunk.
+//!
+//! Text fields are often Latin-1, padded with NULs or broken, so they are returned as byte slices and the caller decides how to decode them.
+//! [trim_text] cuts a field at its NUL terminator. With the `alloc` feature, [text_lossy] converts it to a string, replacing invalid UTF-8.
+//! [crate::PcmReader::info_metadata] returns the tags of the LIST chunk of a file, and [crate::PcmReader::sampler_info] the loops of the smpl chunk.
+//!
+//! # Examples
+//!
+//! ```
+//! use pacmog::metadata::info_tags;
+//!
+//! // LIST payload of type INFO with an odd-sized INAM tag, which is followed by a pad byte.
+//! let list = b"INFOINAM\x05\0\0\0Sine\0\0IART\x04\0\0\0caf\xE9";
+//! let mut tags = info_tags(list);
+//! assert_eq!(tags.next(), Some((*b"INAM", &b"Sine"[..])));
+//! // Latin-1 is not decoded.
+//! assert_eq!(tags.next(), Some((*b"IART", &b"caf\xE9"[..])));
+//! assert_eq!(tags.next(), None);
+//! ```
+
+use crate::table::Table;
+#[cfg(feature = "alloc")]
+use alloc::{borrow::Cow, string::String};
+
+/// Cut a text field at the first NUL.
+/// Fixed-width fields are padded with NULs, and the bytes after the terminator may be garbage.
+pub fn trim_text(text: &[u8]) -> &[u8] {
+    match text.iter().position(|&b| b == 0) {
+        Some(end) => &text[..end],
+        None => text,
+    }
+}
+
+/// Convert a text field to a string after [trim_text]. Invalid UTF-8, e.g. Latin-1, is replaced with U+FFFD.
+#[cfg(feature = "alloc")]
+pub fn text_lossy(text: &[u8]) -> Cow<'_, str> {
+    String::from_utf8_lossy(trim_text(text))
+}
+
+/// Iterator over the tags of a LIST chunk of type INFO, e.g. INAM or IART. See [info_tags].
+#[derive(Debug, Clone)]
+pub struct InfoTags<'a> {
+    rest: &'a [u8],
+}
+
+/// Iterate over the tags of the payload of a LIST chunk.
+/// Yields the ID and the text of each tag, trimmed by [trim_text].
+/// A list of another type, e.g. adtl, yields nothing. A tag larger than the rest of the list is cut at the end of the list.
+pub fn info_tags(list: &[u8]) -> InfoTags<'_> {
+    let rest = match list.split_first_chunk::<4>() {
+        Some((b"INFO", rest)) => rest,
+        _ => &[],
+    };
+    InfoTags { rest }
+}
+
+impl<'a> Iterator for InfoTags<'a> {
+    type Item = ([u8; 4], &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (header, rest) = self.rest.split_first_chunk::<8>()?;
+        let id = [header[0], header[1], header[2], header[3]];
+        let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        let size = usize::try_from(size).unwrap_or(usize::MAX).min(rest.len());
+        let (text, rest) = rest.split_at(size);
+        // Odd-sized tags are followed by a pad byte.
+        self.rest = if size % 2 == 1 {
+            rest.get(1..).unwrap_or(rest)
+        } else {
+            rest
+        };
+        Some((id, trim_text(text)))
+    }
+}
+
+/// Maximum number of loops of [SamplerInfo]. The loops beyond are dropped. Ignored with the `alloc` feature.
+pub const MAX_NUM_LOOPS: usize = 8;
+
+/// Direction of a loop of the smpl chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LoopType {
+    /// Play forward.
+    Forward,
+    /// Play forward and backward in turn.
+    Alternating,
+    /// Play backward.
+    Backward,
+    /// Type defined by the manufacturer of the sampler.
+    Other(u32),
+}
+
+impl From<u32> for LoopType {
+    fn from(v: u32) -> Self {
+        match v {
+            0 => LoopType::Forward,
+            1 => LoopType::Alternating,
+            2 => LoopType::Backward,
+            _ => LoopType::Other(v),
+        }
+    }
+}
+
+/// A loop of the smpl chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SampleLoop {
+    /// Direction of the loop.
+    pub loop_type: LoopType,
+    /// First frame of the loop.
+    pub start: u32,
+    /// Last frame of the loop, which is played.
+    pub end: u32,
+    /// Number of times to play the loop. 0 is infinite.
+    pub play_count: u32,
+}
+
+/// Sampler information of the smpl chunk of WAV. See [crate::PcmReader::sampler_info].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SamplerInfo {
+    /// MIDI note which plays the samples at their original pitch, e.g. 60 for middle C.
+    pub midi_unity_note: u32,
+    /// Fraction of a semitone above the unity note, in 1/2^32 semitones.
+    pub midi_pitch_fraction: u32,
+    pub(crate) loops: Table<SampleLoop, MAX_NUM_LOOPS>,
+}
+
+impl SamplerInfo {
+    /// Returns the loops in the order of the chunk, up to [MAX_NUM_LOOPS] or all of them with the `alloc` feature.
+    pub fn loops(&self) -> &[SampleLoop] {
+        &self.loops
+    }
+}
+
+/// A cue point of the cue chunk of WAV. See [crate::PcmReader::cue_points].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CuePoint {
+    /// Unique ID of the cue point, referred to by the loops of the smpl chunk and the labels of the adtl list.
+    pub id: u32,
+    /// Position of the cue point in the order of playback, in frames.
+    pub position: u32,
+    /// ID of the chunk which holds the cue point, usually `data`.
+    pub data_chunk_id: [u8; 4],
+    /// Offset of the chunk in a wavl list. 0 for a data chunk.
+    pub chunk_start: u32,
+    /// Offset of the block which holds the cue point, for compressed formats. 0 for linear PCM.
+    pub block_start: u32,
+    /// Position of the cue point in the chunk, in frames.
+    pub sample_offset: u32,
+}
+
+/// Instrument information of the inst chunk of WAV.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Instrument {
+    /// MIDI note which plays the samples at their original pitch.
+    pub unshifted_note: u8,
+    /// Pitch shift to apply in cents, -50 to 50.
+    pub fine_tune: i8,
+    /// Gain to apply in dB.
+    pub gain: i8,
+    /// Lowest MIDI note to play the samples.
+    pub low_note: u8,
+    /// Highest MIDI note to play the samples.
+    pub high_note: u8,
+    /// Lowest MIDI velocity to play the samples.
+    pub low_velocity: u8,
+    /// Highest MIDI velocity to play the samples.
+    pub high_velocity: u8,
+}
+
+/// Instrument information of the INST chunk of AIFF. See [crate::PcmReader::aiff_instrument].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AiffInstrument {
+    /// MIDI note which plays the samples at their original pitch.
+    pub base_note: u8,
+    /// Pitch shift to apply in cents, -50 to 50.
+    pub detune: i8,
+    /// Lowest MIDI note to play the samples.
+    pub low_note: u8,
+    /// Highest MIDI note to play the samples.
+    pub high_note: u8,
+    /// Lowest MIDI velocity to play the samples.
+    pub low_velocity: u8,
+    /// Highest MIDI velocity to play the samples.
+    pub high_velocity: u8,
+    /// Gain to apply in dB.
+    pub gain: i16,
+    /// Loop while the note is held, as the first frame and the frame after the loop.
+    pub sustain_loop: Option<(u32, u32)>,
+    /// Loop after the note is released, as the first frame and the frame after the loop.
+    pub release_loop: Option<(u32, u32)>,
+}
+
+/// Maximum number of comments of [Comments]. The comments beyond are dropped. Ignored with the `alloc` feature.
+pub const MAX_NUM_COMMENTS: usize = 8;
+
+/// A comment of the COMT chunk of AIFF.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Comment<'a> {
+    /// Time of the creation in seconds since 1904-01-01 00:00:00.
+    pub timestamp: u32,
+    /// ID of the marker of the MARK chunk which the comment refers to, or 0 for none.
+    pub marker_id: i16,
+    /// Text of the comment. Not decoded, as it may be in a Mac encoding.
+    pub text: &'a [u8],
+}
+
+/// Comments of the COMT chunk of AIFF, in the order of the chunk, up to [MAX_NUM_COMMENTS] or all of them with the `alloc` feature.
+/// Dereferences to a slice of [Comment]. See [crate::PcmReader::aiff_comments].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Comments<'a> {
+    pub(crate) comments: Table<Comment<'a>, MAX_NUM_COMMENTS>,
+}
+
+impl<'a> core::ops::Deref for Comments<'a> {
+    type Target = [Comment<'a>];
+
+    fn deref(&self) -> &Self::Target {
+        &self.comments
+    }
+}
+
+/// Layout of the speakers of the channels, from the CHAN chunk of AIFF-C or the chna chunk of BW64.
+/// The channels are in the order of the speakers in the description of each layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ChannelLayout {
+    /// C
+    Mono,
+    /// L R
+    Stereo,
+    /// L R Ls Rs
+    Quad,
+    /// L R C Ls Rs
+    Surround50,
+    /// L R C LFE Ls Rs
+    Surround51,
+    /// L R C LFE Ls Rs, followed by a pair of rear or side surround channels.
+    Surround71,
+    /// Another layout, another order of the channels, or a layout which does not match the number of channels.
+    Unknown,
+}
+
+// Channel labels of Core Audio, to which the speakers of the chunks are converted.
+pub(crate) const LABEL_LEFT: u32 = 1;
+pub(crate) const LABEL_RIGHT: u32 = 2;
+pub(crate) const LABEL_CENTER: u32 = 3;
+pub(crate) const LABEL_LFE: u32 = 4;
+pub(crate) const LABEL_LEFT_SURROUND: u32 = 5;
+pub(crate) const LABEL_RIGHT_SURROUND: u32 = 6;
+pub(crate) const LABEL_LEFT_SURROUND_DIRECT: u32 = 10;
+pub(crate) const LABEL_RIGHT_SURROUND_DIRECT: u32 = 11;
+pub(crate) const LABEL_REAR_SURROUND_LEFT: u32 = 33;
+pub(crate) const LABEL_REAR_SURROUND_RIGHT: u32 = 34;
+
+impl ChannelLayout {
+    /// Layout of the speakers of the channels in order.
+    pub(crate) fn from_labels(labels: &[u32]) -> Self {
+        const L: u32 = LABEL_LEFT;
+        const R: u32 = LABEL_RIGHT;
+        const C: u32 = LABEL_CENTER;
+        const LFE: u32 = LABEL_LFE;
+        const LS: u32 = LABEL_LEFT_SURROUND;
+        const RS: u32 = LABEL_RIGHT_SURROUND;
+        match labels {
+            [C] => ChannelLayout::Mono,
+            [L, R] => ChannelLayout::Stereo,
+            [L, R, LS, RS] => ChannelLayout::Quad,
+            [L, R, C, LS, RS] => ChannelLayout::Surround50,
+            [L, R, C, LFE, LS, RS] => ChannelLayout::Surround51,
+            [L, R, C, LFE, LS, RS, LABEL_REAR_SURROUND_LEFT, LABEL_REAR_SURROUND_RIGHT]
+            | [L, R, C, LFE, LS, RS, LABEL_LEFT_SURROUND_DIRECT, LABEL_RIGHT_SURROUND_DIRECT] => {
+                ChannelLayout::Surround71
+            }
+            _ => ChannelLayout::Unknown,
+        }
+    }
+
+    /// Number of channels of the layout. None for [ChannelLayout::Unknown].
+    pub fn num_channels(&self) -> Option<u16> {
+        match self {
+            ChannelLayout::Mono => Some(1),
+            ChannelLayout::Stereo => Some(2),
+            ChannelLayout::Quad => Some(4),
+            ChannelLayout::Surround50 => Some(5),
+            ChannelLayout::Surround51 => Some(6),
+            ChannelLayout::Surround71 => Some(8),
+            ChannelLayout::Unknown => None,
+        }
+    }
+}
+
+/// Broadcast extension of the bext chunk of BWF, e.g. the time reference to align takes of recorders.
+/// The text fields are borrowed from the input and trimmed by [trim_text].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BroadcastExtension<'a> {
+    /// Description of the sound, up to 256 characters.
+    pub description: &'a [u8],
+    /// Name of the originator, e.g. the recorder, up to 32 characters.
+    pub originator: &'a [u8],
+    /// Reference of the originator, e.g. USID, up to 32 characters.
+    pub originator_reference: &'a [u8],
+    /// Date of the creation, yyyy-mm-dd.
+    pub origination_date: &'a [u8],
+    /// Time of the creation, hh:mm:ss.
+    pub origination_time: &'a [u8],
+    /// Samples from midnight to the first sample, in the sample rate of the file.
+    pub time_reference: u64,
+    /// Version of the chunk.
+    pub version: u16,
+    /// Loudness of the sound. Read from a chunk of version 2 or later, which is long enough.
+    pub loudness: Option<LoudnessInfo>,
+}
+
+/// Loudness of the bext chunk of version 2, in 0.01 LU, LUFS or dBTP.
+/// 0x7FFF is a value which is not set, and the conversions to f32 return None for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LoudnessInfo {
+    /// Integrated loudness in 0.01 LUFS.
+    pub loudness_value: i16,
+    /// Loudness range in 0.01 LU.
+    pub loudness_range: i16,
+    /// Maximum true peak level in 0.01 dBTP.
+    pub max_true_peak_level: i16,
+    /// Highest momentary loudness in 0.01 LUFS.
+    pub max_momentary_loudness: i16,
+    /// Highest short-term loudness in 0.01 LUFS.
+    pub max_short_term_loudness: i16,
+}
+
+impl LoudnessInfo {
+    /// Value of a field which is not set.
+    const NOT_SET: i16 = 0x7FFF;
+
+    /// Integrated loudness in LUFS.
+    pub fn integrated_loudness(&self) -> Option<f32> {
+        Self::to_f32(self.loudness_value)
+    }
+
+    /// Loudness range in LU.
+    pub fn range(&self) -> Option<f32> {
+        Self::to_f32(self.loudness_range)
+    }
+
+    /// Maximum true peak level in dBTP.
+    pub fn true_peak(&self) -> Option<f32> {
+        Self::to_f32(self.max_true_peak_level)
+    }
+
+    /// Highest momentary loudness in LUFS.
+    pub fn max_momentary(&self) -> Option<f32> {
+        Self::to_f32(self.max_momentary_loudness)
+    }
+
+    /// Highest short-term loudness in LUFS.
+    pub fn max_short_term(&self) -> Option<f32> {
+        Self::to_f32(self.max_short_term_loudness)
+    }
+
+    fn to_f32(v: i16) -> Option<f32> {
+        (v != Self::NOT_SET).then(|| v as f32 / 100.0)
+    }
+}
+
+/// Maximum number of timers of [CartInfo], as in the post timer array of the cart chunk.
+pub const MAX_NUM_CART_TIMERS: usize = 8;
+
+/// A timer of the cart chunk, e.g. the start of the intro.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CartTimer {
+    /// Usage of the timer, e.g. `SEG1` or `INT1`.
+    pub usage: [u8; 4],
+    /// Position in samples from the start of the sound.
+    pub value: u32,
+}
+
+/// Cart chunk of radio automation systems, AES46 of version 1.
+/// The text fields are borrowed from the input and trimmed by [trim_text].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CartInfo<'a> {
+    /// Version of the chunk, e.g. `0101`.
+    pub version: &'a [u8],
+    /// Title of the cut.
+    pub title: &'a [u8],
+    /// Artist of the cut.
+    pub artist: &'a [u8],
+    /// ID of the cut.
+    pub cut_id: &'a [u8],
+    /// ID of the client.
+    pub client_id: &'a [u8],
+    /// Category, e.g. `SPOT` for commercials.
+    pub category: &'a [u8],
+    /// Classification, e.g. a format of music.
+    pub classification: &'a [u8],
+    /// Text of the out cue.
+    pub out_cue: &'a [u8],
+    /// First day to play, yyyy-mm-dd.
+    pub start_date: &'a [u8],
+    /// Time of the first day to play, hh:mm:ss.
+    pub start_time: &'a [u8],
+    /// Last day to play, yyyy-mm-dd.
+    pub end_date: &'a [u8],
+    /// Time of the last day to play, hh:mm:ss.
+    pub end_time: &'a [u8],
+    /// Application which created the chunk.
+    pub producer_app_id: &'a [u8],
+    /// Version of the application.
+    pub producer_app_version: &'a [u8],
+    /// Definitions of the user.
+    pub user_def: &'a [u8],
+    /// Sample value of 0 dB reference level.
+    pub level_reference: i32,
+    /// URL of the cut.
+    pub url: &'a [u8],
+    /// Text after the fixed fields, e.g. CR LF separated tags.
+    pub tag_text: &'a [u8],
+    pub(crate) timers: heapless::Vec<CartTimer, MAX_NUM_CART_TIMERS>,
+}
+
+impl CartInfo<'_> {
+    /// Returns the timers of the post timer array which are used, i.e. whose usage is not zero.
+    pub fn timers(&self) -> &[CartTimer] {
+        &self.timers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{info_tags, trim_text, ChannelLayout, LoudnessInfo};
+
+    #[test]
+    fn text_fields() {
+        assert_eq!(trim_text(b"caf\xE9\0\0"), b"caf\xE9");
+        assert_eq!(trim_text(b"\0\0\0\0"), b"");
+        assert_eq!(trim_text(b""), b"");
+        // The bytes after the terminator are not part of the text.
+        assert_eq!(trim_text(b"name\0garbage"), b"name");
+        assert_eq!(trim_text(b"no terminator"), b"no terminator");
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn text_lossy() {
+        assert_eq!(super::text_lossy(b"Sine\0"), "Sine");
+        assert_eq!(super::text_lossy(b"caf\xE9"), "caf\u{FFFD}");
+        assert_eq!(super::text_lossy(b"caf\xC3\xA9\0\0"), "café");
+        assert_eq!(super::text_lossy(&[0; 16]), "");
+    }
+
+    #[test]
+    fn info_list() {
+        let list = b"INFOIART\x05\0\0\0caf\xE9\0\0ICMT\x04\0\0\0\0\0\0\0ISFT\x08\0\0\0pacmog";
+        let tags: Vec<_> = info_tags(list).collect();
+        assert_eq!(
+            tags,
+            [
+                (*b"IART", &b"caf\xE9"[..]),
+                // A field of NULs is empty.
+                (*b"ICMT", &b""[..]),
+                // Cut at the end of the list.
+                (*b"ISFT", &b"pacmog"[..]),
+            ]
+        );
+
+        assert_eq!(info_tags(b"adtlnote\x04\0\0\0text").count(), 0);
+        assert_eq!(info_tags(b"INF").count(), 0);
+        // A header cut short ends the list.
+        assert_eq!(info_tags(b"INFOINAM\x04\0").count(), 0);
+    }
+
+    #[test]
+    fn loudness() {
+        let loudness = LoudnessInfo {
+            loudness_value: -2300,
+            loudness_range: 450,
+            max_true_peak_level: -1,
+            max_momentary_loudness: 0x7FFF,
+            max_short_term_loudness: 0,
+        };
+        assert_eq!(loudness.integrated_loudness(), Some(-23.0));
+        assert_eq!(loudness.range(), Some(4.5));
+        assert_eq!(loudness.true_peak(), Some(-0.01));
+        assert_eq!(loudness.max_momentary(), None);
+        assert_eq!(loudness.max_short_term(), Some(0.0));
+    }
+
+    #[test]
+    fn channel_layout_from_labels() {
+        for (labels, layout) in [
+            (&[3][..], ChannelLayout::Mono),
+            (&[1, 2], ChannelLayout::Stereo),
+            (&[1, 2, 5, 6], ChannelLayout::Quad),
+            (&[1, 2, 3, 5, 6], ChannelLayout::Surround50),
+            (&[1, 2, 3, 4, 5, 6], ChannelLayout::Surround51),
+            (&[1, 2, 3, 4, 5, 6, 33, 34], ChannelLayout::Surround71),
+            (&[1, 2, 3, 4, 5, 6, 10, 11], ChannelLayout::Surround71),
+            // Another order of the channels
+            (&[2, 1], ChannelLayout::Unknown),
+            (&[1, 2, 3, 4, 5, 6, 10, 34], ChannelLayout::Unknown),
+            (&[], ChannelLayout::Unknown),
+        ] {
+            assert_eq!(ChannelLayout::from_labels(labels), layout);
+            assert_eq!(
+                layout.num_channels(),
+                (layout != ChannelLayout::Unknown).then_some(labels.len() as u16)
+            );
+        }
+    }
+}
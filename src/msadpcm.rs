@@ -0,0 +1,452 @@
+//! Microsoft ADPCM
+//!
+//! # Examples
+//!
+//! Play a MS-ADPCM file.
+//! ```
+//! use pacmog::msadpcm::{MsAdpcmPlayer, I1F15};
+//!
+//! let data = include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_4bit_MSADPCM.wav");
+//! let mut player = MsAdpcmPlayer::new(data).unwrap();
+//! let mut buffer: [I1F15; 2] = [I1F15::ZERO, I1F15::ZERO];
+//! let b = buffer.as_mut_slice();
+//!
+//! for _ in 0..player.specs().num_samples {
+//!     player.get_next_frame(b).unwrap();
+//! }
+//! ```
+
+use crate::{AudioFormat, PcmReader, PcmReaderError, PcmSpecs, SpecsField, MAX_NUM_CHUNKS};
+use arbitrary_int::u4;
+use nom::number::complete::{le_i16, u8};
+use nom::IResult;
+
+pub use fixed::types::I1F15;
+
+/// Number of coefficient pairs of the predictors. The fmt chunk may have more, which are not used by standard encoders.
+pub(crate) const NUM_COEFFICIENTS: usize = 7;
+
+/// Scale of the quantizer step for the next sample, in 1/256.
+const ADAPTATION_TABLE: [i32; 16] = [
+    230, 230, 230, 230, 307, 409, 512, 614, 768, 614, 512, 409, 307, 230, 230, 230,
+];
+
+/// The quantizer step does not go below this.
+const MIN_DELTA: i32 = 16;
+
+const MAX_NUM_CHANNELS: usize = 2;
+
+/// Error type for MS-ADPCM.
+#[derive(Debug, thiserror::Error)]
+pub enum MsAdpcmError {
+    #[error("The audio format is not MS-ADPCM.")]
+    NotMsAdpcm,
+    #[error("The number of elements in the output buffer must be at least equal to the number of MS-ADPCM channels.")]
+    InsufficientOutputBufferChannels,
+    #[error("Finish playing.")]
+    FinishPlaying,
+    #[error("Block length does not match block align")]
+    BlockLengthMismatch,
+    #[error("The predictor of the block is not in the coefficient table.")]
+    InvalidPredictor,
+    #[error("Only mono and stereo MS-ADPCM are supported.")]
+    UnsupportedChannels,
+    #[error("Reader error")]
+    Reader(#[from] PcmReaderError),
+}
+
+/// State of the predictor of a channel.
+/// * 'coefficients' - Coefficients of the last two samples, in 1/256.
+/// * 'delta' - Quantizer step.
+/// * 'sample1' - The last sample.
+/// * 'sample2' - The sample before the last one.
+#[derive(Default, Debug, Clone, Copy)]
+struct ChannelState {
+    coefficients: [i16; 2],
+    delta: i16,
+    sample1: i16,
+    sample2: i16,
+}
+
+/// MS-ADPCMのBlockのHeaderをパースする
+/// Each field is stored for every channel before the next field: bPredictor, iDelta, iSamp1 and iSamp2.
+/// The coefficients are not looked up here, so the predictor indices are returned separately.
+fn parse_block_header(
+    input: &[u8],
+    num_channels: usize,
+) -> IResult<&[u8], ([u8; MAX_NUM_CHANNELS], [ChannelState; MAX_NUM_CHANNELS])> {
+    let mut predictors = [0; MAX_NUM_CHANNELS];
+    let mut states = [ChannelState::default(); MAX_NUM_CHANNELS];
+    let mut input = input;
+    for predictor in &mut predictors[..num_channels] {
+        (input, *predictor) = u8(input)?;
+    }
+    for state in &mut states[..num_channels] {
+        (input, state.delta) = le_i16(input)?;
+    }
+    for state in &mut states[..num_channels] {
+        (input, state.sample1) = le_i16(input)?;
+    }
+    for state in &mut states[..num_channels] {
+        (input, state.sample2) = le_i16(input)?;
+    }
+    Ok((input, (predictors, states)))
+}
+
+/// Decode a nibble and update the state of the channel.
+/// * 'nibble' - 4bit two's complement code
+fn decode_sample(nibble: u4, state: &mut ChannelState) -> i16 {
+    let n = nibble.value();
+    let signed = if n & 8 == 8 { n as i32 - 16 } else { n as i32 };
+    let [coefficient1, coefficient2] = state.coefficients;
+    // The spec divides by 256, which truncates towards zero unlike a shift.
+    let prediction = (i32::from(state.sample1) * i32::from(coefficient1)
+        + i32::from(state.sample2) * i32::from(coefficient2))
+        / 256;
+    let sample = (prediction + signed * i32::from(state.delta))
+        .clamp(i16::MIN.into(), i16::MAX.into()) as i16;
+    state.sample2 = state.sample1;
+    state.sample1 = sample;
+    let delta = (ADAPTATION_TABLE[n as usize] * i32::from(state.delta)) / 256;
+    // The step never exceeds i16 in valid files. Broken headers are saturated.
+    state.delta = delta.clamp(MIN_DELTA, i16::MAX.into()) as i16;
+    sample
+}
+
+/// Calculate the number of samples per channel for MS-ADPCM files.
+/// The final block may be cut short by the encoder. It holds the 2 samples of its header and 2 samples per byte of the data.
+///
+/// Never panics. Returns UnsupportedAudioFormat if the specs are not MS-ADPCM or lack the fields of the fmt extension,
+/// and InconsistentSpecs for a block align or a number of channels of 0, or too many samples to count in u32.
+pub(crate) fn calc_num_samples_per_channel(
+    data_chunk_size_in_bytes: u64,
+    spec: &PcmSpecs,
+) -> Result<u32, PcmReaderError> {
+    if spec.audio_format != AudioFormat::MsAdpcmLe {
+        return Err(PcmReaderError::UnsupportedAudioFormat);
+    }
+
+    let (Some(num_block_align), Some(num_samples_per_block)) = (
+        spec.ms_adpcm_num_block_align,
+        spec.ms_adpcm_num_samples_per_block,
+    ) else {
+        return Err(PcmReaderError::UnsupportedAudioFormat);
+    };
+    let Some(num_blocks) = data_chunk_size_in_bytes.checked_div(num_block_align as u64) else {
+        return Err(PcmReaderError::InconsistentSpecs(SpecsField::BlockAlign));
+    };
+    // The header of a block is 7 bytes per channel.
+    let num_channels = spec.num_channels as u64;
+    if num_channels == 0 {
+        return Err(PcmReaderError::InconsistentSpecs(SpecsField::NumChannels));
+    }
+    let rest = data_chunk_size_in_bytes % num_block_align as u64;
+    let short_block = match rest.checked_sub(7 * num_channels) {
+        Some(data_size) => 2 + data_size * 2 / num_channels,
+        None => 0,
+    };
+    num_blocks
+        .checked_mul(num_samples_per_block as u64)
+        .and_then(|n| n.checked_add(short_block))
+        .and_then(|n| u32::try_from(n).ok())
+        .ok_or(PcmReaderError::InconsistentSpecs(SpecsField::NumSamples))
+}
+
+/// High level of organized players for MS-ADPCM playback.
+#[derive(Default)]
+pub struct MsAdpcmPlayer<'a, const MAX_CHUNKS: usize = MAX_NUM_CHUNKS> {
+    /// A reader to access basic information about the PCM file.
+    pub reader: PcmReader<'a, MAX_CHUNKS>,
+    /// Index of the next frame. Kept in u64 so that the offset arithmetic does not overflow.
+    frame_index: u64,
+    /// Number of samples per block. Taken from the specs at construction.
+    samples_per_block: u32,
+    /// Number of bytes per block. Taken from the specs at construction.
+    block_align: u32,
+    /// Coefficient pairs of the predictors. Taken from the specs at construction.
+    coefficients: [[i16; 2]; NUM_COEFFICIENTS],
+    /// State of the predictor of each channel.
+    states: [ChannelState; MAX_NUM_CHANNELS],
+    /// The nibbles of the current block after the header.
+    reading_block: &'a [u8],
+}
+
+impl<'a> MsAdpcmPlayer<'a> {
+    /// * 'input' - PCM data byte array.
+    ///
+    /// Returns an error if the file cannot be parsed or is not a mono or stereo MS-ADPCM file.
+    pub fn new(input: &'a [u8]) -> Result<Self, MsAdpcmError> {
+        let reader = PcmReader::new(input)?;
+        Self::from_reader(reader)
+    }
+}
+
+impl<'a, const MAX_CHUNKS: usize> MsAdpcmPlayer<'a, MAX_CHUNKS> {
+    /// Create a player whose reader records up to MAX_CHUNKS chunks. See [PcmReader::new_with_chunk_capacity].
+    /// * 'input' - PCM data byte array.
+    pub fn new_with_chunk_capacity(input: &'a [u8]) -> Result<Self, MsAdpcmError> {
+        let reader = PcmReader::new_with_chunk_capacity(input)?;
+        Self::from_reader(reader)
+    }
+
+    /// Create a player from a reader which has already parsed a MS-ADPCM file.
+    pub(crate) fn from_reader(reader: PcmReader<'a, MAX_CHUNKS>) -> Result<Self, MsAdpcmError> {
+        let specs = &reader.specs;
+        let (Some(samples_per_block), Some(block_align), Some(coefficients)) = (
+            specs.ms_adpcm_num_samples_per_block,
+            specs.ms_adpcm_num_block_align,
+            specs.ms_adpcm_coefficients,
+        ) else {
+            return Err(MsAdpcmError::NotMsAdpcm);
+        };
+        if specs.audio_format != AudioFormat::MsAdpcmLe || samples_per_block < 2 {
+            return Err(MsAdpcmError::NotMsAdpcm);
+        }
+        if specs.num_channels == 0 || specs.num_channels as usize > MAX_NUM_CHANNELS {
+            return Err(MsAdpcmError::UnsupportedChannels);
+        }
+
+        Ok(MsAdpcmPlayer {
+            reader,
+            frame_index: 0,
+            samples_per_block: samples_per_block as u32,
+            block_align: block_align as u32,
+            coefficients,
+            ..Default::default()
+        })
+    }
+
+    /// Returns a reference to basic information about the MS-ADPCM file.
+    #[must_use]
+    pub fn specs(&self) -> &PcmSpecs {
+        &self.reader.specs
+    }
+
+    /// Return samples value of the next frame.
+    /// * 'out' - Output buffer which the sample values are written. Number of elements must be equal to or greater than the number of channels in the PCM file.
+    pub fn get_next_frame(&mut self, out: &mut [I1F15]) -> Result<(), MsAdpcmError> {
+        let mut frame = [0i16; MAX_NUM_CHANNELS];
+        let num_channels = self.reader.specs.num_channels as usize;
+        if out.len() < num_channels {
+            return Err(MsAdpcmError::InsufficientOutputBufferChannels);
+        }
+        self.get_next_frame_i16(&mut frame[..num_channels])?;
+        for (output_value, sample) in out.iter_mut().zip(&frame[..num_channels]) {
+            *output_value = I1F15::from_bits(*sample);
+        }
+        Ok(())
+    }
+
+    /// Same as [MsAdpcmPlayer::get_next_frame], with 16bit integer samples.
+    pub fn get_next_frame_i16(&mut self, out: &mut [i16]) -> Result<(), MsAdpcmError> {
+        let num_channels = self.reader.specs.num_channels as usize;
+
+        // outバッファーのチャンネル数が不足している場合はエラーを返す
+        if out.len() < num_channels {
+            return Err(MsAdpcmError::InsufficientOutputBufferChannels);
+        }
+
+        // 再生終了している場合はエラーを返す
+        if self.frame_index >= u64::from(self.reader.specs.num_samples) {
+            return Err(MsAdpcmError::FinishPlaying);
+        }
+
+        // The first two samples of a block are stored in its header, the older one first.
+        let position = self.frame_index % u64::from(self.samples_per_block);
+        match position {
+            0 => {
+                self.update_block()?;
+                for (ch, output_value) in out.iter_mut().enumerate().take(num_channels) {
+                    *output_value = self.states[ch].sample2;
+                }
+            }
+            1 => {
+                for (ch, output_value) in out.iter_mut().enumerate().take(num_channels) {
+                    *output_value = self.states[ch].sample1;
+                }
+            }
+            _ => {
+                // The nibbles are interleaved by channel, the high nibble of a byte first.
+                let first_nibble = (position - 2) as usize * num_channels;
+                for (ch, output_value) in out.iter_mut().enumerate().take(num_channels) {
+                    let index = first_nibble + ch;
+                    let Some(&byte) = self.reading_block.get(index / 2) else {
+                        return Err(MsAdpcmError::BlockLengthMismatch);
+                    };
+                    let nibble = if index % 2 == 0 {
+                        byte >> 4
+                    } else {
+                        byte & 0x0F
+                    };
+                    *output_value = decode_sample(u4::new(nibble), &mut self.states[ch]);
+                }
+            }
+        }
+
+        self.frame_index += 1;
+        Ok(())
+    }
+
+    /// MS-ADPCMのブロック更新.
+    /// The final block may be shorter than the block align. Returns BlockLengthMismatch if its header is cut short by the end of the data.
+    fn update_block(&mut self) -> Result<(), MsAdpcmError> {
+        let offset = (self.frame_index / self.samples_per_block as u64) * self.block_align as u64;
+        let end = offset + self.block_align as u64;
+        let (Ok(offset), Ok(end)) = (usize::try_from(offset), usize::try_from(end)) else {
+            return Err(MsAdpcmError::BlockLengthMismatch);
+        };
+        let end = end.min(self.reader.data.len());
+        let Some(block) = self.reader.data.get(offset..end) else {
+            return Err(MsAdpcmError::BlockLengthMismatch);
+        };
+
+        let num_channels = self.reader.specs.num_channels as usize;
+        let Ok((remains, (predictors, mut states))) = parse_block_header(block, num_channels)
+        else {
+            return Err(MsAdpcmError::BlockLengthMismatch);
+        };
+        for (state, predictor) in states.iter_mut().zip(predictors).take(num_channels) {
+            let Some(&coefficients) = self.coefficients.get(predictor as usize) else {
+                return Err(MsAdpcmError::InvalidPredictor);
+            };
+            state.coefficients = coefficients;
+        }
+        self.states = states;
+        self.reading_block = remains;
+        Ok(())
+    }
+
+    /// Move the playback position back to the beginning.
+    pub fn rewind(&mut self) {
+        self.frame_index = 0;
+        self.reading_block = &[];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::msadpcm::{
+        calc_num_samples_per_channel, decode_sample, ChannelState, MsAdpcmError, MsAdpcmPlayer,
+        I1F15,
+    };
+    use crate::{AudioFormat, PcmReaderError, PcmSpecs, SpecsField};
+    use arbitrary_int::u4;
+
+    #[test]
+    fn ms_adpcm_decode() {
+        let mut state = ChannelState {
+            coefficients: [460, -208],
+            delta: 100,
+            sample1: 1000,
+            sample2: 900,
+        };
+        // (1000 * 460 - 900 * 208) / 256 = 1065, + 3 * 100
+        assert_eq!(decode_sample(u4::new(3), &mut state), 1365);
+        assert_eq!(state.sample1, 1365);
+        assert_eq!(state.sample2, 1000);
+        assert_eq!(state.delta, 100 * 230 / 256);
+
+        // -8 * delta. The prediction is truncated towards zero.
+        let mut state = ChannelState {
+            coefficients: [256, 0],
+            delta: 16,
+            sample1: -32700,
+            sample2: 0,
+        };
+        assert_eq!(decode_sample(u4::new(8), &mut state), -32768);
+        assert_eq!(state.delta, 16 * 768 / 256);
+
+        // The step does not go below 16.
+        let mut state = ChannelState {
+            coefficients: [192, 64],
+            delta: 16,
+            sample1: -3,
+            sample2: 0,
+        };
+        assert_eq!(decode_sample(u4::new(0), &mut state), -2);
+        assert_eq!(state.delta, 16);
+    }
+
+    #[test]
+    fn short_final_block() {
+        let data = include_bytes!("../tests/resources/Sine440Hz_2ch_48000Hz_4bit_MSADPCM.wav");
+        let mut player = MsAdpcmPlayer::new(data).unwrap();
+        let num_samples = player.specs().num_samples;
+        // Pretend there is one more block than the data holds.
+        player.reader.specs.num_samples = num_samples + player.samples_per_block;
+
+        let mut buffer = [I1F15::ZERO; 2];
+        for _ in 0..num_samples {
+            player.get_next_frame(&mut buffer).unwrap();
+        }
+        assert!(matches!(
+            player.get_next_frame(&mut buffer),
+            Err(MsAdpcmError::BlockLengthMismatch)
+        ));
+    }
+
+    #[test]
+    fn invalid_predictor() {
+        let data = include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_4bit_MSADPCM.wav");
+        let mut file = data.to_vec();
+        let offset = file.windows(4).position(|w| w == b"data").unwrap() + 8;
+        file[offset] = 7;
+        let mut player = MsAdpcmPlayer::new(&file).unwrap();
+        let mut buffer = [I1F15::ZERO; 1];
+        assert!(matches!(
+            player.get_next_frame(&mut buffer),
+            Err(MsAdpcmError::InvalidPredictor)
+        ));
+    }
+
+    #[test]
+    fn calc_num_samples_checked() {
+        let spec = |num_channels, block_align, samples_per_block| PcmSpecs {
+            audio_format: AudioFormat::MsAdpcmLe,
+            bit_depth: 4,
+            num_channels,
+            ms_adpcm_num_block_align: Some(block_align),
+            ms_adpcm_num_samples_per_block: Some(samples_per_block),
+            ..Default::default()
+        };
+        assert_eq!(
+            calc_num_samples_per_channel(256 * 10, &spec(1, 256, 500)).unwrap(),
+            5000
+        );
+        // The final block is cut in the middle: the header and 10 bytes.
+        assert_eq!(
+            calc_num_samples_per_channel(256 * 10 + 17, &spec(1, 256, 500)).unwrap(),
+            5000 + 22
+        );
+        assert_eq!(
+            calc_num_samples_per_channel(512 * 10 + 24, &spec(2, 512, 500)).unwrap(),
+            5000 + 12
+        );
+        // A data chunk of RF64 larger than 4 GiB.
+        assert_eq!(
+            calc_num_samples_per_channel(1 << 32, &spec(8, 2048, 500)).unwrap(),
+            (1 << 21) * 500
+        );
+        // Only a part of the header is left.
+        assert_eq!(
+            calc_num_samples_per_channel(256 * 10 + 6, &spec(1, 256, 500)).unwrap(),
+            5000
+        );
+        assert!(matches!(
+            calc_num_samples_per_channel(1024, &spec(1, 0, 500)),
+            Err(PcmReaderError::InconsistentSpecs(SpecsField::BlockAlign))
+        ));
+        assert!(matches!(
+            calc_num_samples_per_channel(u32::MAX.into(), &spec(1, 8, 500)),
+            Err(PcmReaderError::InconsistentSpecs(SpecsField::NumSamples))
+        ));
+        let not_ms = PcmSpecs {
+            audio_format: AudioFormat::ImaAdpcmLe,
+            ..spec(1, 256, 500)
+        };
+        assert!(matches!(
+            calc_num_samples_per_channel(1024, &not_ms),
+            Err(PcmReaderError::UnsupportedAudioFormat)
+        ));
+    }
+}
@@ -0,0 +1,173 @@
+//! Playback ratios of MIDI notes for samplers, in fixed point so that no FPU is needed.
+//! With the `libm` feature, the ratio is computed with `libm::exp2f` instead.
+//!
+//! ```
+//! use pacmog::pitch::{note_ratio, U16F16};
+//!
+//! // A sample recorded at C4 (60) played at C5 (72) is played twice as fast.
+//! assert_eq!(note_ratio(60, 72, 0), U16F16::from_num(2));
+//! ```
+//!
+//! The root of a sample can be taken from its metadata with [crate::PcmReader::root_note],
+//! and a note is set on a player with [crate::PcmPlayer::set_note].
+
+use crate::math::pow2_cents;
+use crate::metadata::{AiffInstrument, Instrument, SamplerInfo};
+pub use fixed::types::U16F16;
+
+/// Playback speed ratio of a note relative to the root note of the sample, 2^((note - root + fine_cents / 100) / 12).
+/// * 'root' - MIDI note at which the sample plays at its original pitch.
+/// * 'note' - MIDI note to play.
+/// * 'fine_cents' - Detune in cents.
+///
+/// The ratio is rounded to the nearest U16F16. Far below the root the resolution is coarse, e.g. 0.1% at 6 octaves down.
+pub fn note_ratio(root: u8, note: u8, fine_cents: i8) -> U16F16 {
+    let cents = (i32::from(note) - i32::from(root)) * 100 + i32::from(fine_cents);
+    pow2_cents(cents)
+}
+
+/// Root note of a sample and the detune to apply when it is played, as the root and fine_cents of [note_ratio].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Root {
+    /// MIDI note at which the sample plays at its original pitch.
+    pub note: u8,
+    /// Detune in cents.
+    pub fine_cents: i8,
+}
+
+impl Root {
+    /// Playback speed ratio of a note relative to the root. See [note_ratio].
+    pub fn ratio(&self, note: u8) -> U16F16 {
+        note_ratio(self.note, note, self.fine_cents)
+    }
+}
+
+/// The unity note of the smpl chunk, clamped to 127.
+/// The pitch fraction is the amount the sample is above the unity note, so it is rounded to cents and negated.
+impl From<&SamplerInfo> for Root {
+    fn from(info: &SamplerInfo) -> Self {
+        let fraction = (u64::from(info.midi_pitch_fraction) * 100 + (1 << 31)) >> 32;
+        Root {
+            note: info.midi_unity_note.min(127) as u8,
+            fine_cents: -(fraction as i8),
+        }
+    }
+}
+
+/// The unshifted note and the fine tune of the inst chunk.
+impl From<&Instrument> for Root {
+    fn from(inst: &Instrument) -> Self {
+        Root {
+            note: inst.unshifted_note,
+            fine_cents: inst.fine_tune,
+        }
+    }
+}
+
+/// The base note and the detune of the INST chunk of AIFF.
+impl From<&AiffInstrument> for Root {
+    fn from(inst: &AiffInstrument) -> Self {
+        Root {
+            note: inst.base_note,
+            fine_cents: inst.detune,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{note_ratio, Root, U16F16};
+    use crate::metadata::{Instrument, SamplerInfo};
+    use crate::table::Table;
+
+    #[test]
+    fn octaves_and_semitones() {
+        assert_eq!(note_ratio(60, 60, 0), U16F16::ONE);
+        assert_eq!(note_ratio(60, 72, 0), U16F16::from_num(2));
+        assert_eq!(note_ratio(60, 48, 0), U16F16::from_num(0.5));
+        let semitone = note_ratio(60, 61, 0).to_num::<f64>();
+        assert!((semitone - 2f64.powf(1.0 / 12.0)).abs() < 1.0 / 65536.0);
+        assert_eq!(note_ratio(60, 60, 100), note_ratio(60, 61, 0));
+        assert_eq!(note_ratio(60, 61, -100), U16F16::ONE);
+    }
+
+    #[test]
+    fn root_from_metadata() {
+        let smpl = |midi_unity_note, midi_pitch_fraction| SamplerInfo {
+            midi_unity_note,
+            midi_pitch_fraction,
+            loops: Table::new(),
+        };
+        let root = Root::from(&smpl(60, 0));
+        assert_eq!(
+            root,
+            Root {
+                note: 60,
+                fine_cents: 0
+            }
+        );
+        assert_eq!(root.ratio(72), U16F16::from_num(2));
+        assert_eq!(root.ratio(48), U16F16::from_num(0.5));
+        assert_eq!(root.ratio(61), note_ratio(60, 61, 0));
+        // A quarter semitone above the unity note is played 25 cents lower.
+        let root = Root::from(&smpl(69, 1 << 30));
+        assert_eq!(
+            root,
+            Root {
+                note: 69,
+                fine_cents: -25
+            }
+        );
+        let root = Root::from(&smpl(69, u32::MAX));
+        assert_eq!(
+            root,
+            Root {
+                note: 69,
+                fine_cents: -100
+            }
+        );
+        assert_eq!(root.ratio(70), U16F16::ONE);
+        assert_eq!(Root::from(&smpl(300, 0)).note, 127);
+
+        let inst = Instrument {
+            unshifted_note: 48,
+            fine_tune: -12,
+            gain: 0,
+            low_note: 0,
+            high_note: 127,
+            low_velocity: 1,
+            high_velocity: 127,
+        };
+        let root = Root::from(&inst);
+        assert_eq!(
+            root,
+            Root {
+                note: 48,
+                fine_cents: -12
+            }
+        );
+        assert_eq!(root.ratio(60), note_ratio(48, 60, -12));
+    }
+
+    #[test]
+    fn matches_float() {
+        for root in [0u8, 60, 127] {
+            for note in 0..=127u8 {
+                for cents in [-128i8, -50, -1, 0, 1, 37, 99, 127] {
+                    let expected = 2f64.powf(
+                        (f64::from(note) - f64::from(root) + f64::from(cents) / 100.0) / 12.0,
+                    );
+                    let ratio = note_ratio(root, note, cents).to_num::<f64>();
+                    // Rounding to U16F16 plus the error of the approximation.
+                    let error = if cfg!(feature = "libm") { 1e-6 } else { 4e-9 };
+                    let tolerance = 0.5 / 65536.0 + expected * error;
+                    assert!(
+                        (ratio - expected).abs() <= tolerance,
+                        "{root} {note} {cents}: {ratio} {expected}"
+                    );
+                }
+            }
+        }
+    }
+}
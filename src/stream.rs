@@ -0,0 +1,641 @@
+//! Streaming playback of files which do not fit in memory, e.g. files on an SD card.
+//!
+//! [StreamingPlayer] reads the header and then the samples through a [BlockSource],
+//! using a scratch buffer supplied by the caller. A buffer of 512 bytes, the size of an SD card block, is enough.
+//! Only linear PCM and IEEE float are supported. IMA-ADPCM needs the whole block in memory.
+//!
+//! With the `async` feature, the reads can be awaited with an [AsyncBlockSource],
+//! so the executor is not blocked while the storage is read.
+//!
+//! With the `embedded-sdmmc` feature, [BlockSource] is implemented for `embedded_sdmmc::File`.
+//!
+//! # Examples
+//!
+//! ```
+//! use pacmog::stream::StreamingPlayer;
+//!
+//! let wav: &[u8] = include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_24.wav");
+//! let mut scratch = [0u8; 512];
+//! let mut player = StreamingPlayer::new(wav, &mut scratch).unwrap();
+//! assert_eq!(player.specs().sample_rate, 48000);
+//!
+//! let mut buffer = [0f32; 256];
+//! let n = player.fill_interleaved(&mut buffer).unwrap();
+//! assert_eq!(n, 256);
+//! ```
+
+use core::future::Future;
+use core::pin::pin;
+use core::ptr;
+use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+use crate::{
+    aiff, decode_fn, decode_interleaved, decode_sample, wav, AudioFormat, PcmPlayerError,
+    PcmReaderError, PcmSpecs,
+};
+
+/// Random access to the bytes of a file.
+pub trait BlockSource {
+    type Error: core::fmt::Debug;
+
+    /// Read bytes from the offset into the buffer.
+    /// Fewer bytes than the buffer holds may be read, e.g. at a cluster boundary.
+    ///
+    /// Returns the number of bytes read. 0 means the end of the file.
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<usize, Self::Error>;
+}
+
+impl BlockSource for &[u8] {
+    type Error = core::convert::Infallible;
+
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let Some(rest) = usize::try_from(offset).ok().and_then(|o| self.get(o..)) else {
+            return Ok(0);
+        };
+        let n = rest.len().min(buf.len());
+        buf[..n].copy_from_slice(&rest[..n]);
+        Ok(n)
+    }
+}
+
+/// Random access to the bytes of a file, where reads complete asynchronously, e.g. with DMA.
+/// Every [BlockSource] is an AsyncBlockSource whose reads complete immediately.
+/// `StreamingPlayer::new_async` and `StreamingPlayer::fill_interleaved_async` require the `async` feature.
+pub trait AsyncBlockSource {
+    type Error: core::fmt::Debug;
+
+    /// Same as [BlockSource::read_at], but the read can be awaited.
+    fn read_at(
+        &mut self,
+        offset: u64,
+        buf: &mut [u8],
+    ) -> impl Future<Output = Result<usize, Self::Error>>;
+}
+
+impl<S: BlockSource> AsyncBlockSource for S {
+    type Error = S::Error;
+
+    fn read_at(
+        &mut self,
+        offset: u64,
+        buf: &mut [u8],
+    ) -> impl Future<Output = Result<usize, Self::Error>> {
+        core::future::ready(BlockSource::read_at(self, offset, buf))
+    }
+}
+
+#[cfg(feature = "embedded-sdmmc")]
+impl<D, T, const MAX_DIRS: usize, const MAX_FILES: usize, const MAX_VOLUMES: usize> BlockSource
+    for embedded_sdmmc::File<'_, D, T, MAX_DIRS, MAX_FILES, MAX_VOLUMES>
+where
+    D: embedded_sdmmc::BlockDevice,
+    T: embedded_sdmmc::TimeSource,
+{
+    type Error = embedded_sdmmc::Error<D::Error>;
+
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        // Files on FAT are smaller than 4GiB.
+        let Ok(offset) = u32::try_from(offset) else {
+            return Ok(0);
+        };
+        if offset >= self.length() {
+            return Ok(0);
+        }
+        self.seek_from_start(offset)?;
+        self.read(buf)
+    }
+}
+
+/// Error type for [StreamingPlayer].
+#[derive(Debug, thiserror::Error)]
+pub enum StreamError<E: core::fmt::Debug> {
+    #[error("Read error of the block source: {0:?}")]
+    Source(E),
+    #[error("Reader error")]
+    Reader(#[from] PcmReaderError),
+    #[error("Player error")]
+    Player(#[from] PcmPlayerError),
+    #[error("The scratch buffer is too short")]
+    ScratchTooShort,
+}
+
+/// Player which reads the samples from a [BlockSource] while playing.
+pub struct StreamingPlayer<'b, S> {
+    source: S,
+    scratch: &'b mut [u8],
+    specs: PcmSpecs,
+    /// Offset of the first sample from the beginning of the file in bytes.
+    data_offset: u64,
+    bytes_per_frame: usize,
+    playback_position: u64,
+    loop_playing: bool,
+}
+
+impl<'b, S: BlockSource> StreamingPlayer<'b, S> {
+    /// Read the header of the file and create a new StreamingPlayer.
+    /// The size in the RIFF or FORM header is not checked, because the length of the source is not known.
+    /// * 'source' - WAV or AIFF file.
+    /// * 'scratch' - Buffer for the header and the samples. It must hold at least one frame and the fmt or COMM chunk.
+    pub fn new(source: S, scratch: &'b mut [u8]) -> Result<Self, StreamError<S::Error>> {
+        poll_ready(Self::open(source, scratch))
+    }
+
+    /// Fill the buffer with the next frames, wrapping around when loop playback is enabled.
+    /// The samples are read in pieces of at most the size of the scratch buffer.
+    /// If the file ends before the number of samples in the header, playback finishes there.
+    /// * 'out' - Output buffer of interleaved samples. As many whole frames as fit are written.
+    ///
+    /// Returns the number of frames written. It is less than the buffer holds only when playback finishes.
+    pub fn fill_interleaved(&mut self, out: &mut [f32]) -> Result<usize, StreamError<S::Error>> {
+        poll_ready(self.fill(out))
+    }
+}
+
+#[cfg(feature = "async")]
+impl<'b, S: AsyncBlockSource> StreamingPlayer<'b, S> {
+    /// Same as [StreamingPlayer::new], but the reads of the source are awaited.
+    pub async fn new_async(
+        source: S,
+        scratch: &'b mut [u8],
+    ) -> Result<Self, StreamError<S::Error>> {
+        Self::open(source, scratch).await
+    }
+
+    /// Same as [StreamingPlayer::fill_interleaved], but the reads of the source are awaited.
+    pub async fn fill_interleaved_async(
+        &mut self,
+        out: &mut [f32],
+    ) -> Result<usize, StreamError<S::Error>> {
+        self.fill(out).await
+    }
+}
+
+impl<S> StreamingPlayer<'_, S> {
+    /// Returns a reference to basic information about the PCM file.
+    #[must_use]
+    pub fn specs(&self) -> &PcmSpecs {
+        &self.specs
+    }
+
+    /// Move the playback position to the desired position.
+    /// * 'sample' - Playback position in samples.
+    ///
+    /// Position 0 is always accepted, so that a file without samples can be rewound.
+    pub fn set_position(&mut self, sample: u32) -> Result<(), PcmPlayerError> {
+        if sample != 0 && self.specs.num_samples <= sample {
+            return Err(PcmPlayerError::InvalidPosition);
+        }
+        self.playback_position = u64::from(sample);
+        Ok(())
+    }
+
+    /// Enable loop playback.
+    /// true: Enable loop playback
+    /// false: Disable loop playback
+    pub fn set_loop_playing(&mut self, en: bool) {
+        self.loop_playing = en;
+    }
+
+    /// Returns the source, e.g. to close the file.
+    pub fn into_source(self) -> S {
+        self.source
+    }
+}
+
+// The implementation is shared by the blocking and the async API.
+// A BlockSource completes every read immediately, so the blocking API polls the futures only once.
+impl<'b, S: AsyncBlockSource> StreamingPlayer<'b, S> {
+    async fn open(mut source: S, scratch: &'b mut [u8]) -> Result<Self, StreamError<S::Error>> {
+        let (specs, data_offset) = parse_header(&mut source, scratch).await?;
+        if decode_fn(&specs).is_none() {
+            // Returns the same error as PcmReader::read_sample.
+            return Err(decode_sample(&specs, &[])
+                .err()
+                .unwrap_or(PcmReaderError::UnsupportedAudioFormat)
+                .into());
+        }
+        let bytes_per_frame =
+            specs.container_bytes_per_sample as usize * specs.num_channels as usize;
+        if bytes_per_frame == 0 {
+            return Err(PcmReaderError::InvalidChannel.into());
+        }
+        if scratch.len() < bytes_per_frame {
+            return Err(StreamError::ScratchTooShort);
+        }
+        Ok(StreamingPlayer {
+            source,
+            scratch,
+            specs,
+            data_offset,
+            bytes_per_frame,
+            playback_position: 0,
+            loop_playing: false,
+        })
+    }
+
+    async fn fill(&mut self, out: &mut [f32]) -> Result<usize, StreamError<S::Error>> {
+        let num_channels = self.specs.num_channels as usize;
+        if out.len() < num_channels {
+            return Err(PcmPlayerError::OutputBufferTooShort.into());
+        }
+
+        let frames_per_read = self.scratch.len() / self.bytes_per_frame;
+        let num_frames = out.len() / num_channels;
+        let mut filled = 0;
+        while filled < num_frames {
+            let num_samples = u64::from(self.specs.num_samples);
+            if self.playback_position >= num_samples {
+                if self.loop_playing && num_samples > 0 {
+                    self.playback_position = 0;
+                } else {
+                    break;
+                }
+            }
+            let n = (num_frames - filled)
+                .min(frames_per_read)
+                .min((num_samples - self.playback_position) as usize);
+            let len = n * self.bytes_per_frame;
+            let offset = self.data_offset + self.playback_position * self.bytes_per_frame as u64;
+            let read = read_full(&mut self.source, offset, &mut self.scratch[..len]).await?;
+            let n = read / self.bytes_per_frame;
+            decode_interleaved(
+                &self.specs,
+                &self.scratch[..n * self.bytes_per_frame],
+                &mut out[filled * num_channels..(filled + n) * num_channels],
+            )?;
+            filled += n;
+            self.playback_position += n as u64;
+            if read < len {
+                // The file is shorter than the header claims. It ends here.
+                self.specs.num_samples = self.playback_position as u32;
+            }
+        }
+
+        if filled == 0 {
+            return Err(PcmPlayerError::FinishPlaying.into());
+        }
+        Ok(filled)
+    }
+}
+
+/// Poll a future which is known to complete without waiting, i.e. one which only reads from a [BlockSource].
+fn poll_ready<F: Future>(future: F) -> F::Output {
+    const VTABLE: RawWakerVTable = RawWakerVTable::new(
+        |_| RawWaker::new(ptr::null(), &VTABLE),
+        |_| {},
+        |_| {},
+        |_| {},
+    );
+    // SAFETY: The functions of the vtable do nothing, so any data pointer is valid.
+    let waker = unsafe { Waker::from_raw(RawWaker::new(ptr::null(), &VTABLE)) };
+    match pin!(future).poll(&mut Context::from_waker(&waker)) {
+        Poll::Ready(output) => output,
+        Poll::Pending => unreachable!("BlockSource::read_at is synchronous"),
+    }
+}
+
+/// Read until the buffer is full or the end of the file.
+/// Returns the number of bytes read.
+async fn read_full<S: AsyncBlockSource>(
+    source: &mut S,
+    offset: u64,
+    buf: &mut [u8],
+) -> Result<usize, StreamError<S::Error>> {
+    let mut read = 0;
+    while read < buf.len() {
+        let n = source
+            .read_at(offset + read as u64, &mut buf[read..])
+            .await
+            .map_err(StreamError::Source)?;
+        if n == 0 {
+            break;
+        }
+        read += n;
+    }
+    Ok(read)
+}
+
+/// Chunks needed to play, common to WAV and AIFF.
+enum HeaderChunk {
+    /// fmt or COMM
+    Format,
+    /// data
+    Data,
+    /// SSND, which starts with the offset and the block size.
+    SoundData,
+    /// fact of WAV, which counts the samples of float files.
+    Fact,
+    Other,
+}
+
+/// Read the chunk headers until both the format and the position of the samples are known.
+/// Returns the specs and the offset of the first sample.
+async fn parse_header<S: AsyncBlockSource>(
+    source: &mut S,
+    scratch: &mut [u8],
+) -> Result<(PcmSpecs, u64), StreamError<S::Error>> {
+    if scratch.len() < 12 {
+        return Err(StreamError::ScratchTooShort);
+    }
+    if read_full(source, 0, &mut scratch[..12]).await? < 12 {
+        return Err(PcmReaderError::UnsupportedAudioFormat.into());
+    }
+    let is_wav = wav::parse_riff_header(&scratch[..12]).is_ok();
+    if !is_wav && aiff::parse_aiff_header(&scratch[..12]).is_err() {
+        return Err(PcmReaderError::UnsupportedAudioFormat.into());
+    }
+
+    let mut specs = None;
+    let mut data = None;
+    // Only a fact chunk before the data chunk is read.
+    let mut fact = None;
+    let mut offset = 12u64;
+    while specs.is_none() || data.is_none() {
+        if read_full(source, offset, &mut scratch[..8]).await? < 8 {
+            break;
+        }
+        let (chunk, size) = if is_wav {
+            let (_, header) = wav::parse_chunk_header(&scratch[..8]).unwrap();
+            let chunk = match header.id {
+                wav::ChunkId::Fmt => HeaderChunk::Format,
+                wav::ChunkId::Data => HeaderChunk::Data,
+                wav::ChunkId::Fact => HeaderChunk::Fact,
+                _ => HeaderChunk::Other,
+            };
+            (chunk, header.size)
+        } else {
+            let (_, header) = aiff::parse_chunk_header(&scratch[..8]).unwrap();
+            let chunk = match header.id {
+                aiff::ChunkId::Common => HeaderChunk::Format,
+                aiff::ChunkId::SoundData => HeaderChunk::SoundData,
+                _ => HeaderChunk::Other,
+            };
+            (chunk, header.size)
+        };
+        let payload_offset = offset + 8;
+        let payload_size = size as usize;
+
+        match chunk {
+            // The first chunk is used, as in PcmReader.
+            HeaderChunk::Format if specs.is_some() => {}
+            HeaderChunk::Data | HeaderChunk::SoundData if data.is_some() => {}
+            HeaderChunk::Format => {
+                let Some(payload) = scratch.get_mut(..payload_size) else {
+                    return Err(StreamError::ScratchTooShort);
+                };
+                if read_full(source, payload_offset, payload).await? < payload_size {
+                    return Err(PcmReaderError::UnsupportedAudioFormat.into());
+                }
+                specs = Some(if is_wav {
+                    let Ok((_, fmt)) = wav::parse_fmt(payload) else {
+                        return Err(PcmReaderError::UnsupportedAudioFormat.into());
+                    };
+                    PcmSpecs {
+                        container_bytes_per_sample: fmt.container_bytes_per_sample(),
+                        bit_depth: fmt.valid_bit_depth(),
+                        audio_format: fmt.audio_format,
+                        num_channels: fmt.num_channels,
+                        sample_rate: fmt.sample_rate,
+                        ..Default::default()
+                    }
+                } else {
+                    let Ok((_, comm)) = aiff::parse_comm(payload) else {
+                        return Err(PcmReaderError::UnsupportedAudioFormat.into());
+                    };
+                    comm
+                });
+            }
+            HeaderChunk::Data => data = Some((payload_offset, size)),
+            HeaderChunk::SoundData => {
+                if read_full(source, payload_offset, &mut scratch[..8]).await? < 8 {
+                    return Err(PcmReaderError::UnsupportedAudioFormat.into());
+                }
+                let Ok((_, ssnd)) = aiff::parse_ssnd(&scratch[..8]) else {
+                    return Err(PcmReaderError::UnsupportedAudioFormat.into());
+                };
+                let data_size = size.saturating_sub(8);
+                let Some(first_frame) = ssnd.first_frame(data_size.into()) else {
+                    return Err(PcmReaderError::UnsupportedAudioFormat.into());
+                };
+                // Less than the size of the chunk, so it fits in u32.
+                data = Some((
+                    payload_offset + 8 + first_frame,
+                    data_size - first_frame as u32,
+                ));
+            }
+            HeaderChunk::Fact => {
+                if read_full(source, payload_offset, &mut scratch[..4]).await? == 4 {
+                    fact = wav::parse_fact(&scratch[..4]).ok().map(|(_, n)| n);
+                }
+            }
+            HeaderChunk::Other => {}
+        }
+        // Odd-sized chunks are followed by a pad byte.
+        offset = payload_offset + u64::from(size) + u64::from(size % 2);
+    }
+
+    let (format_id, data_id) = if is_wav {
+        (*b"fmt ", *b"data")
+    } else {
+        (*b"COMM", *b"SSND")
+    };
+    let Some(mut specs) = specs else {
+        return Err(PcmReaderError::MissingChunk(format_id).into());
+    };
+    let Some((data_offset, data_size)) = data else {
+        return Err(PcmReaderError::MissingChunk(data_id).into());
+    };
+    if is_wav {
+        specs.num_samples = match specs.audio_format {
+            AudioFormat::LinearPcmLe
+            | AudioFormat::IeeeFloatLe
+            | AudioFormat::ALaw
+            | AudioFormat::MuLaw => wav::calc_num_samples_per_channel(data_size.into(), &specs)?,
+            _ => return Err(PcmReaderError::UnsupportedAudioFormat.into()),
+        };
+        wav::limit_to_fact(&mut specs, fact);
+    } else {
+        aiff::count_missing_frames(&mut specs, data_size.into());
+    }
+    Ok((specs, data_offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BlockSource, StreamError, StreamingPlayer};
+    use crate::{PcmPlayer, PcmPlayerError, PcmReader};
+
+    /// Reads at most up to the next 512-byte boundary, like a file on FAT at the end of a block or cluster,
+    /// and fails at the offset if given.
+    struct BlockBoundarySource<'a> {
+        data: &'a [u8],
+        fail_at: Option<u64>,
+    }
+
+    impl BlockSource for BlockBoundarySource<'_> {
+        type Error = u64;
+
+        fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            let len = buf.len().min(512 - (offset % 512) as usize);
+            if let Some(fail_at) = self.fail_at {
+                if offset + len as u64 > fail_at {
+                    return Err(offset);
+                }
+            }
+            self.data
+                .read_at(offset, &mut buf[..len])
+                .map_err(|e| match e {})
+        }
+    }
+
+    #[test]
+    fn matches_pcm_player() {
+        let files: [&[u8]; 7] = [
+            include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_24.wav"),
+            include_bytes!("../tests/resources/MLKDream.wav"),
+            include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_16.aif"),
+            include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_32FP.aif"),
+            include_bytes!("../tests/resources/Empty_1ch_48000Hz_16.wav"),
+            include_bytes!("../tests/resources/Empty_1ch_48000Hz_16.aif"),
+            include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_32FP_padded.wav"),
+        ];
+        for data in files {
+            let source = BlockBoundarySource {
+                data,
+                fail_at: None,
+            };
+            // A scratch buffer which is not a multiple of the frame size or the block size.
+            let mut scratch = [0u8; 500];
+            let mut streaming = StreamingPlayer::new(source, &mut scratch).unwrap();
+            let mut player = PcmPlayer::new(PcmReader::new(data).unwrap());
+            assert_eq!(streaming.specs().num_samples, player.specs().num_samples);
+
+            let mut expected = [0f32; 1000];
+            let mut actual = [0f32; 1000];
+            while let Ok(n) = player.fill_interleaved(&mut expected) {
+                assert_eq!(streaming.fill_interleaved(&mut actual).unwrap(), n);
+                let num_channels = player.specs().num_channels as usize;
+                assert_eq!(actual[..n * num_channels], expected[..n * num_channels]);
+            }
+            assert!(matches!(
+                streaming.fill_interleaved(&mut actual),
+                Err(StreamError::Player(PcmPlayerError::FinishPlaying))
+            ));
+        }
+    }
+
+    #[test]
+    fn loop_and_errors() {
+        let data: &[u8] = include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_16.wav");
+        let num_samples = PcmReader::new(data).unwrap().specs().num_samples;
+        let mut scratch = [0u8; 512];
+        let mut player = StreamingPlayer::new(data, &mut scratch).unwrap();
+        player.set_loop_playing(true);
+        player.set_position(num_samples - 10).unwrap();
+        let mut buffer = [0f32; 20];
+        assert_eq!(player.fill_interleaved(&mut buffer).unwrap(), 20);
+        let mut scratch = [0u8; 512];
+        let mut from_start = StreamingPlayer::new(data, &mut scratch).unwrap();
+        let mut expected = [0f32; 10];
+        from_start.fill_interleaved(&mut expected).unwrap();
+        assert_eq!(buffer[10..], expected);
+
+        // A read error while playing.
+        let source = BlockBoundarySource {
+            data,
+            fail_at: Some(4096),
+        };
+        let mut scratch = [0u8; 512];
+        let mut player = StreamingPlayer::new(source, &mut scratch).unwrap();
+        let mut buffer = [0f32; 4096];
+        assert!(matches!(
+            player.fill_interleaved(&mut buffer),
+            Err(StreamError::Source(_))
+        ));
+
+        // A truncated file finishes where the data ends.
+        let truncated = &data[..data.len() - 100];
+        let mut scratch = [0u8; 512];
+        let mut player = StreamingPlayer::new(truncated, &mut scratch).unwrap();
+        let mut total = 0;
+        while let Ok(n) = player.fill_interleaved(&mut buffer) {
+            total += n;
+        }
+        assert_eq!(total as u32, num_samples - 50);
+
+        // IMA-ADPCM and too short scratch buffers are rejected.
+        let adpcm: &[u8] =
+            include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_4bit_IMAADPCM.wav");
+        assert!(StreamingPlayer::new(adpcm, &mut [0u8; 512]).is_err());
+        assert!(matches!(
+            StreamingPlayer::new(data, &mut [0u8; 8]),
+            Err(StreamError::ScratchTooShort)
+        ));
+    }
+
+    /// Completes every other poll, like a read through DMA.
+    #[cfg(feature = "async")]
+    struct PendingSource<'a> {
+        data: &'a [u8],
+        num_polls: usize,
+    }
+
+    #[cfg(feature = "async")]
+    impl super::AsyncBlockSource for PendingSource<'_> {
+        type Error = core::convert::Infallible;
+
+        async fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            let mut pending = true;
+            core::future::poll_fn(|cx| {
+                self.num_polls += 1;
+                if core::mem::take(&mut pending) {
+                    cx.waker().wake_by_ref();
+                    return core::task::Poll::Pending;
+                }
+                core::task::Poll::Ready(())
+            })
+            .await;
+            BlockSource::read_at(&mut self.data, offset, buf)
+        }
+    }
+
+    #[cfg(feature = "async")]
+    fn block_on<F: core::future::Future>(future: F) -> F::Output {
+        use std::sync::Arc;
+        use std::task::{Context, Poll, Wake, Waker};
+
+        struct NoopWake;
+        impl Wake for NoopWake {
+            fn wake(self: Arc<Self>) {}
+        }
+        let waker = Waker::from(Arc::new(NoopWake));
+        let mut future = core::pin::pin!(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut Context::from_waker(&waker)) {
+                return output;
+            }
+        }
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn fill_async() {
+        let data: &[u8] = include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_24.aif");
+        let source = PendingSource { data, num_polls: 0 };
+        let mut scratch = [0u8; 512];
+        let mut streaming = block_on(StreamingPlayer::new_async(source, &mut scratch)).unwrap();
+        let mut player = PcmPlayer::new(PcmReader::new(data).unwrap());
+
+        let mut expected = [0f32; 300];
+        let mut actual = [0f32; 300];
+        while let Ok(n) = block_on(player.fill_interleaved_async(&mut expected)) {
+            assert_eq!(
+                block_on(streaming.fill_interleaved_async(&mut actual)).unwrap(),
+                n
+            );
+            assert_eq!(actual[..n], expected[..n]);
+        }
+        assert!(block_on(streaming.fill_interleaved_async(&mut actual)).is_err());
+        assert!(streaming.into_source().num_polls > 0);
+    }
+}
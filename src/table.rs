@@ -0,0 +1,81 @@
+//! Storage of the metadata tables, e.g. the chunk table of [crate::PcmReader].
+//!
+//! Without the `alloc` feature, a table holds up to N entries and the entries beyond are dropped.
+//! With it, the table grows on the heap and N is ignored.
+
+use core::ops::{Deref, DerefMut};
+
+#[cfg(not(feature = "alloc"))]
+type Storage<T, const N: usize> = heapless::Vec<T, N>;
+#[cfg(feature = "alloc")]
+type Storage<T, const N: usize> = alloc::vec::Vec<T>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Table<T, const N: usize>(Storage<T, N>);
+
+impl<T, const N: usize> Table<T, N> {
+    pub(crate) const fn new() -> Self {
+        Table(Storage::new())
+    }
+
+    /// Append the entry. Returns false if the table is full and the entry was dropped.
+    pub(crate) fn push(&mut self, value: T) -> bool {
+        #[cfg(not(feature = "alloc"))]
+        return self.0.push(value).is_ok();
+        #[cfg(feature = "alloc")]
+        {
+            self.0.push(value);
+            true
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    /// Keep only the entries for which f returns true, in their order.
+    pub(crate) fn retain(&mut self, f: impl FnMut(&T) -> bool) {
+        self.0.retain(f);
+    }
+}
+
+impl<T, const N: usize> Default for Table<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Deref for Table<T, N> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.0
+    }
+}
+
+impl<T, const N: usize> DerefMut for Table<T, N> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        &mut self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Table;
+
+    #[test]
+    fn push_beyond_capacity() {
+        let mut table = Table::<u32, 2>::new();
+        assert!(table.push(1));
+        assert!(table.push(2));
+        if cfg!(feature = "alloc") {
+            assert!(table.push(3));
+            assert_eq!(&table[..], [1, 2, 3]);
+        } else {
+            assert!(!table.push(3));
+            assert_eq!(&table[..], [1, 2]);
+        }
+        table.clear();
+        assert!(table.is_empty());
+    }
+}
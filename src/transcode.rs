@@ -0,0 +1,348 @@
+//! Format conversion from [PcmReader] to [PcmWriter].
+//!
+//! Frames are converted in blocks of a fixed size, so no buffer for the whole file is needed.
+//! When the bit depth is reduced, TPDF dither can be applied with [TranscodeOptions::dither].
+//! Channels can be extracted or reordered with [TranscodeOptions::channel_map],
+//! and several files can be merged into one with [transcode_merge].
+//!
+//! # Examples
+//!
+//! Convert a 24bit AIFF file into a 16bit WAV file.
+//! ```
+//! use pacmog::transcode::{transcode, TranscodeOptions};
+//! use pacmog::writer::PcmWriterBuilder;
+//! use pacmog::PcmReader;
+//!
+//! let data = include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_24.aif");
+//! let reader = PcmReader::new(data).unwrap();
+//! let mut buffer = vec![0u8; 500_000];
+//! let mut writer = PcmWriterBuilder::new(1, 48000).build(&mut buffer).unwrap();
+//! let num_frames = transcode(&reader, &mut writer, &TranscodeOptions::default()).unwrap();
+//! writer.finalize().unwrap();
+//! assert_eq!(num_frames, 240000);
+//! ```
+
+use crate::imaadpcm::{ImaAdpcmError, ImaAdpcmPlayer, I1F15};
+use crate::msadpcm::{MsAdpcmError, MsAdpcmPlayer};
+use crate::writer::{PcmWriter, PcmWriterError};
+use crate::{AudioFormat, PcmReader, PcmReaderError, PcmSpecs};
+
+/// Number of samples converted at once.
+/// This is also the maximum total number of source channels.
+const BLOCK_SIZE: usize = 256;
+
+/// Maximum number of readers for [transcode_merge].
+pub const MAX_NUM_SOURCES: usize = 8;
+
+/// Error type for transcode.
+#[derive(Debug, thiserror::Error)]
+pub enum TranscodeError {
+    #[error("Reader error")]
+    Reader(#[from] PcmReaderError),
+    #[error("Writer error")]
+    Writer(#[from] PcmWriterError),
+    #[error("IMA-ADPCM error")]
+    ImaAdpcm(#[from] ImaAdpcmError),
+    #[error("MS-ADPCM error")]
+    MsAdpcm(#[from] MsAdpcmError),
+    #[error("The number of channels of the reader and the writer do not match")]
+    ChannelMismatch,
+    #[error("The sample rates of the reader and the writer do not match")]
+    SampleRateMismatch,
+    #[error("The channel map does not match the writer or refers to a nonexistent channel")]
+    InvalidChannelMap,
+    #[error("Too many readers")]
+    TooManySources,
+}
+
+/// Dither applied when samples are quantized to integers.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DitherMode {
+    /// Round to the nearest integer.
+    #[default]
+    None,
+    /// Add triangular probability density function noise of +/-1 LSB before rounding.
+    Tpdf,
+}
+
+/// How [transcode_merge] handles readers with different numbers of samples.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LengthMismatch {
+    /// Output the length of the longest reader. Shorter readers are padded with silence.
+    #[default]
+    PadWithSilence,
+    /// Output the length of the shortest reader.
+    Truncate,
+}
+
+/// Options for [transcode].
+#[derive(Debug, Default, Clone)]
+pub struct TranscodeOptions<'a> {
+    /// Dither applied when the writer's format is linear PCM and the bit depth is reduced.
+    /// Ignored for IEEE float, and for sources of linear PCM which are not deeper than the writer.
+    pub dither: DitherMode,
+    /// Seed of the pseudo random number generator for the dither.
+    pub dither_seed: u32,
+    /// Source channel of each output channel. Output channel `i` is taken from source channel `channel_map[i]`.
+    /// The length must be equal to the number of channels of the writer.
+    /// For [transcode_merge], the source channels are the channels of all readers in order.
+    /// If empty, the channels are copied as they are.
+    pub channel_map: &'a [u16],
+    /// Only used by [transcode_merge].
+    pub length_mismatch: LengthMismatch,
+}
+
+/// Convert all frames of the reader into the format of the writer.
+/// Bit depth and endianness are converted; the sample rate must match.
+/// Without [TranscodeOptions::channel_map], the number of channels must match too.
+/// The writer buffer must be large enough to hold the output. Use [transcode_streaming] otherwise.
+/// The writer is not finalized.
+/// Returns the number of frames written.
+pub fn transcode<const MAX_CHUNKS: usize>(
+    reader: &PcmReader<'_, MAX_CHUNKS>,
+    writer: &mut PcmWriter,
+    opts: &TranscodeOptions,
+) -> Result<u32, TranscodeError> {
+    run(&[reader], writer, opts, None::<fn(&[u8])>)
+}
+
+/// Same as [transcode], but flushes the writer whenever its buffer becomes full.
+/// * 'on_flush' - Receives the bytes returned by [PcmWriter::flush], e.g. to write them to a file.
+pub fn transcode_streaming<F: FnMut(&[u8]), const MAX_CHUNKS: usize>(
+    reader: &PcmReader<'_, MAX_CHUNKS>,
+    writer: &mut PcmWriter,
+    opts: &TranscodeOptions,
+    on_flush: F,
+) -> Result<u32, TranscodeError> {
+    run(&[reader], writer, opts, Some(on_flush))
+}
+
+/// Merge the channels of several readers into the writer, e.g. two mono files into one stereo file.
+/// The source channels are the channels of all readers in order, mapped by [TranscodeOptions::channel_map].
+/// The sample rates of all readers must match the writer.
+/// Returns the number of frames written, which depends on [TranscodeOptions::length_mismatch].
+pub fn transcode_merge<const MAX_CHUNKS: usize>(
+    readers: &[&PcmReader<'_, MAX_CHUNKS>],
+    writer: &mut PcmWriter,
+    opts: &TranscodeOptions,
+) -> Result<u32, TranscodeError> {
+    run(readers, writer, opts, None::<fn(&[u8])>)
+}
+
+fn run<F: FnMut(&[u8]), const MAX_CHUNKS: usize>(
+    readers: &[&PcmReader<'_, MAX_CHUNKS>],
+    writer: &mut PcmWriter,
+    opts: &TranscodeOptions,
+    mut on_flush: Option<F>,
+) -> Result<u32, TranscodeError> {
+    let out_specs = writer.get_pcm_specs();
+    let mut sources: heapless::Vec<Source<MAX_CHUNKS>, MAX_NUM_SOURCES> = heapless::Vec::new();
+    let mut num_source_channels = 0;
+    for reader in readers {
+        if reader.specs.sample_rate != out_specs.sample_rate {
+            return Err(TranscodeError::SampleRateMismatch);
+        }
+        num_source_channels += reader.specs.num_channels as usize;
+        sources
+            .push(Source::new(reader)?)
+            .map_err(|_| TranscodeError::TooManySources)?;
+    }
+    if num_source_channels > BLOCK_SIZE {
+        return Err(TranscodeError::ChannelMismatch);
+    }
+
+    let num_channels = out_specs.num_channels as usize;
+    if opts.channel_map.is_empty() {
+        if num_source_channels != num_channels {
+            return Err(TranscodeError::ChannelMismatch);
+        }
+    } else if opts.channel_map.len() != num_channels
+        || opts
+            .channel_map
+            .iter()
+            .any(|&ch| ch as usize >= num_source_channels)
+    {
+        return Err(TranscodeError::InvalidChannelMap);
+    }
+    let frames_per_block = BLOCK_SIZE / num_channels;
+    if frames_per_block == 0 {
+        return Err(TranscodeError::ChannelMismatch);
+    }
+
+    let lengths = readers.iter().map(|r| r.specs.num_samples);
+    let num_samples = match opts.length_mismatch {
+        LengthMismatch::PadWithSilence => lengths.max(),
+        LengthMismatch::Truncate => lengths.min(),
+    }
+    .unwrap_or(0);
+
+    // Size of 1 LSB of the output in the normalized scale.
+    // Sources which fit in the output are copied exactly, so they are not dithered.
+    let reduces_depth = readers
+        .iter()
+        .any(|r| precision(&r.specs).map_or(true, |bits| bits > out_specs.bit_depth));
+    let lsb = match (opts.dither, out_specs.audio_format) {
+        (DitherMode::Tpdf, AudioFormat::LinearPcmLe) if reduces_depth => {
+            Some(1.0 / (1u64 << (out_specs.bit_depth - 1)) as f32)
+        }
+        _ => None,
+    };
+    let mut tpdf = Tpdf::new(opts.dither_seed);
+
+    let mut source_frame = [0f32; BLOCK_SIZE];
+    let mut block = [0f32; BLOCK_SIZE];
+    let mut frame_index = 0;
+    while frame_index < num_samples {
+        let num_frames = frames_per_block.min((num_samples - frame_index) as usize);
+        let block = &mut block[..num_frames * num_channels];
+        for frame in block.chunks_exact_mut(num_channels) {
+            let mut offset = 0;
+            for (source, reader) in sources.iter_mut().zip(readers) {
+                let channels = reader.specs.num_channels as usize;
+                let out = &mut source_frame[offset..offset + channels];
+                if frame_index < reader.specs.num_samples {
+                    source.read_frame(frame_index, out)?;
+                } else {
+                    out.fill(0.0);
+                }
+                offset += channels;
+            }
+            if opts.channel_map.is_empty() {
+                frame.copy_from_slice(&source_frame[..num_channels]);
+            } else {
+                for (sample, &ch) in frame.iter_mut().zip(opts.channel_map) {
+                    *sample = source_frame[ch as usize];
+                }
+            }
+            frame_index += 1;
+        }
+        if let Some(lsb) = lsb {
+            // Each sample gets its own draw, so the noise is independent between channels.
+            for sample in block.iter_mut() {
+                *sample += tpdf.next_noise() * lsb;
+            }
+        }
+
+        for frame in block.chunks_exact(num_channels) {
+            match (writer.write_frame(frame), on_flush.as_mut()) {
+                (Ok(()), _) => {}
+                (Err(PcmWriterError::OutputBufferTooShort), Some(f)) => {
+                    f(writer.flush());
+                    writer.write_frame(frame)?;
+                }
+                (Err(e), _) => return Err(e.into()),
+            }
+        }
+    }
+    Ok(num_samples)
+}
+
+/// Number of bits of the decoded samples of the source, or None if it is not on an integer grid, e.g. IEEE float and ADPCM.
+fn precision(specs: &PcmSpecs) -> Option<u16> {
+    match specs.audio_format {
+        AudioFormat::LinearPcmLe | AudioFormat::LinearPcmBe => Some(specs.bit_depth),
+        // G.711 expands to 13bit (A-law) and 14bit (µ-law) linear samples.
+        AudioFormat::ALaw => Some(13),
+        AudioFormat::MuLaw => Some(14),
+        _ => None,
+    }
+}
+
+/// TPDF noise generator based on xorshift32.
+struct Tpdf {
+    state: u32,
+}
+
+impl Tpdf {
+    fn new(seed: u32) -> Self {
+        // xorshift must not start from 0.
+        let state = if seed == 0 { 0x9E37_79B9 } else { seed };
+        Tpdf { state }
+    }
+
+    /// Returns a uniformly distributed value in [0, 1).
+    fn next_uniform(&mut self) -> f32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        (x >> 8) as f32 / (1u32 << 24) as f32
+    }
+
+    /// Returns triangularly distributed noise in (-1, 1).
+    fn next_noise(&mut self) -> f32 {
+        self.next_uniform() - self.next_uniform()
+    }
+}
+
+/// Sequential access to the frames of a reader regardless of the format.
+// Boxing is not available in no_std. The sources are created once per transcode.
+#[allow(clippy::large_enum_variant)]
+enum Source<'r, 'a, const MAX_CHUNKS: usize> {
+    Pcm(&'r PcmReader<'a, MAX_CHUNKS>),
+    ImaAdpcm(ImaAdpcmPlayer<'a, MAX_CHUNKS>),
+    MsAdpcm(MsAdpcmPlayer<'a, MAX_CHUNKS>),
+}
+
+impl<'r, 'a, const MAX_CHUNKS: usize> Source<'r, 'a, MAX_CHUNKS> {
+    fn new(reader: &'r PcmReader<'a, MAX_CHUNKS>) -> Result<Self, TranscodeError> {
+        match reader.specs.audio_format {
+            AudioFormat::ImaAdpcmLe => Ok(Source::ImaAdpcm(ImaAdpcmPlayer::from_reader(
+                reader.clone(),
+            )?)),
+            AudioFormat::MsAdpcmLe => {
+                Ok(Source::MsAdpcm(MsAdpcmPlayer::from_reader(reader.clone())?))
+            }
+            _ => Ok(Source::Pcm(reader)),
+        }
+    }
+
+    /// Read the frame. Frames must be read in order.
+    fn read_frame(&mut self, frame_index: u32, out: &mut [f32]) -> Result<(), TranscodeError> {
+        match self {
+            Source::Pcm(reader) => {
+                reader.read_frame(frame_index, out)?;
+            }
+            Source::ImaAdpcm(player) => {
+                let mut frame = [I1F15::ZERO; 2];
+                player.get_next_frame(&mut frame)?;
+                for (sample, value) in out.iter_mut().zip(frame) {
+                    *sample = value.to_num::<f32>();
+                }
+            }
+            Source::MsAdpcm(player) => {
+                let mut frame = [I1F15::ZERO; 2];
+                player.get_next_frame(&mut frame)?;
+                for (sample, value) in out.iter_mut().zip(frame) {
+                    *sample = value.to_num::<f32>();
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Tpdf;
+
+    #[test]
+    fn tpdf_distribution() {
+        let mut tpdf = Tpdf::new(1234);
+        let n = 100_000;
+        let mut sum = 0f64;
+        let mut sum_sq = 0f64;
+        for _ in 0..n {
+            let v = tpdf.next_noise();
+            assert!(v > -1.0 && v < 1.0);
+            sum += v as f64;
+            sum_sq += (v * v) as f64;
+        }
+        let mean = sum / n as f64;
+        let variance = sum_sq / n as f64 - mean * mean;
+        assert!(mean.abs() < 0.01);
+        // The variance of TPDF over (-1, 1) is 1/6.
+        assert!((variance - 1.0 / 6.0).abs() < 0.01);
+    }
+}
@@ -0,0 +1,117 @@
+//! Sony Wave64, a 64-bit variant of WAV.
+//! Chunks are identified by a GUID instead of a FourCC, and their sizes are 64-bit and include the chunk header.
+//! The payloads of the fmt, fact and data chunks are the same as WAV.
+
+use nom::bytes::complete::{tag, take};
+use nom::number::complete::le_u64;
+use nom::IResult;
+
+/// GUID of the riff chunk, which starts the file. 66666972-912E-11CF-A5D6-28DB04C10000
+const RIFF_GUID: [u8; 16] = [
+    0x72, 0x69, 0x66, 0x66, 0x2E, 0x91, 0xCF, 0x11, 0xA5, 0xD6, 0x28, 0xDB, 0x04, 0xC1, 0x00, 0x00,
+];
+
+/// The bytes of the other GUIDs following the FourCC, e.g. 20746D66-ACF3-11D3-8CD1-00C04F8EDB8A for "fmt ".
+const GUID_TAIL: [u8; 12] = [
+    0xF3, 0xAC, 0xD3, 0x11, 0x8C, 0xD1, 0x00, 0xC0, 0x4F, 0x8E, 0xDB, 0x8A,
+];
+
+/// Size of the riff GUID, the file size and the wave GUID.
+pub(super) const HEADER_LEN: usize = 40;
+
+/// Chunks start at a multiple of 8 bytes.
+pub(super) const ALIGN: u64 = 8;
+
+/// Size of the GUID and the size of a chunk header.
+const CHUNK_HEADER_LEN: u64 = 24;
+
+/// Wave64のchunkの種類
+#[derive(Debug, PartialEq)]
+pub(super) enum ChunkId {
+    Fmt,
+    Fact,
+    Data,
+    Unknown,
+}
+
+/// Header of a chunk.
+#[derive(Debug)]
+pub(super) struct ChunkHeader {
+    pub id: ChunkId,
+    /// The first 4 bytes of the GUID, e.g. "fmt ".
+    pub fourcc: [u8; 4],
+    /// Size of the payload in bytes, without the chunk header.
+    pub size: u64,
+}
+
+/// Check the riff and wave GUIDs at the start of the file.
+/// Returns the size of the file, which includes the header.
+pub(super) fn parse_w64_header(input: &[u8]) -> IResult<&[u8], u64> {
+    let (input, _) = tag(&RIFF_GUID[..])(input)?;
+    let (input, size) = le_u64(input)?;
+    let (input, _) = tag(&b"wave"[..])(input)?;
+    let (input, _) = tag(&GUID_TAIL[..])(input)?;
+    Ok((input, size))
+}
+
+/// Parse the GUID and the size of a chunk.
+/// A size less than the chunk header is an error.
+pub(super) fn parse_chunk_header(input: &[u8]) -> IResult<&[u8], ChunkHeader> {
+    let (input, guid) = take(16usize)(input)?;
+    let (rest, size) = le_u64(input)?;
+    let Some(size) = size.checked_sub(CHUNK_HEADER_LEN) else {
+        return Err(nom::Err::Error(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Verify,
+        )));
+    };
+    let fourcc = [guid[0], guid[1], guid[2], guid[3]];
+    let id = if guid[4..] != GUID_TAIL {
+        ChunkId::Unknown
+    } else {
+        match &fourcc {
+            b"fmt " => ChunkId::Fmt,
+            b"fact" => ChunkId::Fact,
+            b"data" => ChunkId::Data,
+            _ => ChunkId::Unknown,
+        }
+    };
+    Ok((rest, ChunkHeader { id, fourcc, size }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_chunk_header, parse_w64_header, ChunkId, GUID_TAIL, RIFF_GUID};
+
+    #[test]
+    fn chunk_headers() {
+        let mut file = RIFF_GUID.to_vec();
+        file.extend_from_slice(&64u64.to_le_bytes());
+        file.extend_from_slice(b"wave");
+        file.extend_from_slice(&GUID_TAIL);
+        let (rest, size) = parse_w64_header(&file).unwrap();
+        assert_eq!(size, 64);
+        assert!(rest.is_empty());
+
+        let mut chunk = b"data".to_vec();
+        chunk.extend_from_slice(&GUID_TAIL);
+        chunk.extend_from_slice(&28u64.to_le_bytes());
+        let (_, header) = parse_chunk_header(&chunk).unwrap();
+        assert_eq!(header.id, ChunkId::Data);
+        assert_eq!(header.size, 4);
+
+        // The size includes the chunk header.
+        chunk[16..24].copy_from_slice(&23u64.to_le_bytes());
+        assert!(parse_chunk_header(&chunk).is_err());
+
+        // The FourCC of another GUID is not a known chunk.
+        chunk[15] ^= 1;
+        chunk[16..24].copy_from_slice(&24u64.to_le_bytes());
+        let (_, header) = parse_chunk_header(&chunk).unwrap();
+        assert_eq!(header.id, ChunkId::Unknown);
+        assert_eq!(&header.fourcc, b"data");
+
+        // A RIFF file is not Wave64.
+        assert!(parse_w64_header(b"RIFF\x24\0\0\0WAVEfmt ").is_err());
+    }
+}
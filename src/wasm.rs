@@ -0,0 +1,175 @@
+//! Owned wrapper of [PcmReader] for JavaScript through wasm-bindgen.
+//!
+//! [WasmPcm] parses the file once and copies its samples, so JS does not have to keep the bytes alive.
+//! The samples are returned as a `Float32Array`, e.g. to draw a waveform or to fill an `AudioBuffer` of WebAudio.
+//!
+//! ```js
+//! import { WasmPcm } from "pacmog";
+//!
+//! const pcm = new WasmPcm(new Uint8Array(await file.arrayBuffer()));
+//! const specs = JSON.parse(pcm.specs_json());
+//! const samples = pcm.decode_range_f32(0, specs.num_samples);
+//! ```
+//!
+//! This module requires the `wasm` feature.
+
+use crate::{PcmReader, PcmSpecs};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::Write;
+use wasm_bindgen::prelude::*;
+
+/// PCM file owned by the wrapper.
+#[wasm_bindgen]
+pub struct WasmPcm {
+    specs: PcmSpecs,
+    samples: Samples,
+}
+
+/// Samples of the file, kept from [WasmPcm::new].
+enum Samples {
+    /// Interleaved frames in the format of the specs. Empty for the formats which are stored in blocks, e.g. IMA-ADPCM.
+    Raw(Vec<u8>),
+    /// Frames of a wavl list, decoded up front since the segments are not contiguous in the file.
+    Decoded(Vec<f32>),
+}
+
+#[wasm_bindgen]
+impl WasmPcm {
+    /// Parse the file and copy its samples. Throws if the file is not supported.
+    #[wasm_bindgen(constructor)]
+    pub fn new(bytes: &[u8]) -> Result<WasmPcm, JsError> {
+        let reader = PcmReader::new(bytes).map_err(|e| JsError::new(&e.to_string()))?;
+        let specs = reader.specs().clone();
+        let samples = if reader.is_segmented() {
+            let mut out =
+                alloc::vec![0f32; specs.num_samples as usize * specs.num_channels as usize];
+            reader
+                .read_frames(0, &mut out)
+                .map_err(|e| JsError::new(&e.to_string()))?;
+            Samples::Decoded(out)
+        } else {
+            let data = reader.raw_data();
+            let len = data
+                .len()
+                .min(specs.num_samples as usize * reader.bytes_per_frame());
+            Samples::Raw(data[..len].to_vec())
+        };
+        Ok(WasmPcm { specs, samples })
+    }
+
+    /// Returns the specs as JSON, e.g.
+    /// `{"audio_format":"LinearPcmLe","num_channels":1,"sample_rate":48000,"bit_depth":16,"num_samples":240000}`.
+    pub fn specs_json(&self) -> String {
+        let specs = &self.specs;
+        let mut json = String::new();
+        // Writing to a String does not fail.
+        let _ = write!(
+            json,
+            r#"{{"audio_format":"{:?}","num_channels":{},"sample_rate":{},"bit_depth":{},"num_samples":{}}}"#,
+            specs.audio_format,
+            specs.num_channels,
+            specs.sample_rate,
+            specs.bit_depth,
+            specs.num_samples
+        );
+        json
+    }
+
+    /// Decode interleaved frames as normalized samples.
+    /// * 'start' - First frame.
+    /// * 'len' - Number of frames. Frames beyond the end of the file are not returned.
+    ///
+    /// Returns `len * num_channels` samples, or fewer at the end of the file.
+    /// Throws if the format cannot be decoded to f32, e.g. IMA-ADPCM.
+    pub fn decode_range_f32(&self, start: u32, len: u32) -> Result<Vec<f32>, JsError> {
+        let num_channels = self.specs.num_channels as usize;
+        let len = len.min(self.specs.num_samples.saturating_sub(start)) as usize;
+        let data = match &self.samples {
+            Samples::Decoded(samples) => {
+                let start = start as usize * num_channels;
+                return Ok(samples[start..start + len * num_channels].to_vec());
+            }
+            Samples::Raw(data) => data,
+        };
+        let mut out = alloc::vec![0f32; len * num_channels];
+        if out.is_empty() {
+            return Ok(out);
+        }
+        // Only wraps the cached data and specs; the file is not parsed again.
+        let n = PcmReader::new_raw(data, self.specs.clone())
+            .and_then(|reader| reader.decode_block_f32(start, &mut out))
+            .map_err(|e| JsError::new(&e.to_string()))?;
+        out.truncate(n * num_channels);
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WasmPcm;
+    use crate::PcmReader;
+
+    #[test]
+    fn specs_and_range() {
+        let wav = include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_16.wav");
+        let pcm = WasmPcm::new(wav).unwrap();
+        assert_eq!(
+            pcm.specs_json(),
+            r#"{"audio_format":"LinearPcmLe","num_channels":1,"sample_rate":48000,"bit_depth":16,"num_samples":240000}"#
+        );
+
+        let reader = PcmReader::new(wav).unwrap();
+        let samples = pcm.decode_range_f32(1000, 64).unwrap();
+        assert_eq!(samples.len(), 64);
+        for (i, sample) in samples.iter().enumerate() {
+            assert_eq!(*sample, reader.read_sample(0, 1000 + i as u32).unwrap());
+        }
+        assert_eq!(pcm.decode_range_f32(239990, 64).unwrap().len(), 10);
+        assert!(pcm.decode_range_f32(240000, 64).unwrap().is_empty());
+    }
+
+    /// The cached samples decode like the reader of the file.
+    #[test]
+    fn matches_reader() {
+        let chunk = |id: &[u8; 4], payload: &[u8]| {
+            let mut chunk = id.to_vec();
+            chunk.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+            chunk.extend_from_slice(payload);
+            chunk
+        };
+        let wav = include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_16.wav");
+        let original = PcmReader::new(wav).unwrap();
+        // 1000 frames, 500 frames of silence and 1000 frames in a wavl list.
+        let data = original.raw_data();
+        let mut list = b"wavl".to_vec();
+        list.extend(chunk(b"data", &data[..2000]));
+        list.extend(chunk(b"slnt", &500u32.to_le_bytes()));
+        list.extend(chunk(b"data", &data[2000..4000]));
+        let mut body = b"WAVE".to_vec();
+        body.extend(chunk(b"fmt ", original.chunk_data(b"fmt ").unwrap()));
+        body.extend(chunk(b"LIST", &list));
+        let wavl = chunk(b"RIFF", &body);
+        assert!(PcmReader::new(&wavl).unwrap().is_segmented());
+
+        let files: [&[u8]; 7] = [
+            wav,
+            include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_8.wav"),
+            include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_20.wav"),
+            include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_24.aif"),
+            include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_32FP_padded.wav"),
+            include_bytes!("../tests/resources/Sine440Hz_1ch_48000Hz_8_MuLaw.aifc"),
+            &wavl,
+        ];
+        for file in files {
+            let reader = PcmReader::new(file).unwrap();
+            let pcm = WasmPcm::new(file).unwrap();
+            let num_samples = reader.specs().num_samples;
+            for start in [0, 990, num_samples - 100] {
+                let mut expected = [0f32; 600];
+                let n = reader.decode_block_f32(start, &mut expected).unwrap();
+                assert_eq!(pcm.decode_range_f32(start, 600).unwrap(), expected[..n]);
+            }
+        }
+    }
+}
@@ -1,6 +1,12 @@
-use crate::{AudioFormat, PcmReaderError, PcmSpecs};
+use crate::metadata::{
+    self, trim_text, BroadcastExtension, CartInfo, CartTimer, ChannelLayout, CuePoint, Instrument,
+    LoudnessInfo, SampleLoop, SamplerInfo, MAX_NUM_CART_TIMERS,
+};
+use crate::table::Table;
+use crate::{msadpcm, AudioFormat, NumSamplesSource, PcmReaderError, PcmSpecs, SpecsField};
 use nom::bytes::complete::{tag, take};
-use nom::number::complete::{le_u16, le_u32};
+use nom::number::complete::{i8, le_i16, le_i32, le_u16, le_u32, le_u64, u8};
+use nom::sequence::tuple;
 use nom::IResult;
 
 /// WAVのchunkの種類
@@ -12,7 +18,9 @@ pub(super) enum ChunkId {
     Data, // b"data"
     Junk,
     List,
-    IDv3,
+    IDv3, // b"id3 ", b"ID3 " or b"IDv3" ID3v2 tags
+    Ds64, // b"ds64" RF64 only
+    Slnt, // b"slnt" Silence in a wavl list
     #[default]
     Unknown,
 }
@@ -32,19 +40,22 @@ impl TryFrom<&[u8]> for ChunkId {
             b"data" => Ok(ChunkId::Data),
             b"junk" => Ok(ChunkId::Junk),
             b"JUNK" => Ok(ChunkId::Junk),
-            b"IDv3" => Ok(ChunkId::IDv3),
+            b"id3 " | b"ID3 " | b"IDv3" => Ok(ChunkId::IDv3),
             b"LIST" => Ok(ChunkId::List),
+            b"ds64" => Ok(ChunkId::Ds64),
+            b"slnt" => Ok(ChunkId::Slnt),
             _ => Ok(ChunkId::Unknown),
         }
     }
 }
 
+/// Header of a chunk. The payload is not sliced until it is needed.
 #[derive(Debug, Default)]
-pub(super) struct Chunk<'a> {
+pub(super) struct ChunkHeader {
     pub id: ChunkId,
-    #[allow(dead_code)]
+    pub fourcc: [u8; 4],
+    /// Size of the payload in bytes.
     pub size: u32,
-    pub data: &'a [u8],
 }
 
 /// Waveの形式
@@ -54,17 +65,31 @@ pub(super) struct Chunk<'a> {
 enum WaveFormatTag {
     LinearPcm = 0x01, //1
     IeeeFloat = 0x03, //3
-    ImaAdpcm = 0x11,  //0x11 aka DVI ADPCM
+    ALaw = 0x06,
+    MuLaw = 0x07,
+    MsAdpcm = 0x02,
+    ImaAdpcm = 0x11, //0x11 aka DVI ADPCM
+    Extensible = 0xFFFE,
 }
 
+/// The bytes of the SubFormat GUID of WAVE_FORMAT_EXTENSIBLE following the format tag.
+/// KSDATAFORMAT_SUBTYPE_PCM is 00000001-0000-0010-8000-00aa00389b71 for example.
+const SUBFORMAT_GUID_TAIL: [u8; 14] = [
+    0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71,
+];
+
 impl TryFrom<u16> for WaveFormatTag {
     type Error = ();
 
     fn try_from(v: u16) -> Result<Self, Self::Error> {
         match v {
             x if x == WaveFormatTag::LinearPcm as u16 => Ok(WaveFormatTag::LinearPcm),
+            x if x == WaveFormatTag::MsAdpcm as u16 => Ok(WaveFormatTag::MsAdpcm),
             x if x == WaveFormatTag::IeeeFloat as u16 => Ok(WaveFormatTag::IeeeFloat),
+            x if x == WaveFormatTag::ALaw as u16 => Ok(WaveFormatTag::ALaw),
+            x if x == WaveFormatTag::MuLaw as u16 => Ok(WaveFormatTag::MuLaw),
             x if x == WaveFormatTag::ImaAdpcm as u16 => Ok(WaveFormatTag::ImaAdpcm),
+            x if x == WaveFormatTag::Extensible as u16 => Ok(WaveFormatTag::Extensible),
             _ => Err(()),
         }
     }
@@ -85,12 +110,63 @@ pub(super) fn parse_riff_header(input: &[u8]) -> IResult<&[u8], RiffHeader> {
     Ok((input, RiffHeader { size }))
 }
 
-pub(super) fn parse_chunk(input: &[u8]) -> IResult<&[u8], Chunk> {
+/// Check that the file starts with RF64 and the form type is WAVE.
+/// The size in the header is 0xFFFFFFFF, and the real size is in the ds64 chunk.
+pub(super) fn parse_rf64_header(input: &[u8]) -> IResult<&[u8], ()> {
+    let (input, _) = tag(b"RF64")(input)?;
+    let (input, _size) = le_u32(input)?;
+    let (input, _) = tag(b"WAVE")(input)?;
+    Ok((input, ()))
+}
+
+/// Sizes of an RF64 file which do not fit in 32 bits.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Ds64 {
+    /// Size of the file - 8, which replaces the size in the RF64 header.
+    pub riff_size: u64,
+    /// Size of the data chunk, which replaces a size of 0xFFFFFFFF in the data chunk header.
+    pub data_size: u64,
+    /// Number of samples per channel, as in the fact chunk.
+    pub sample_count: u64,
+}
+
+/// Parse the ds64 chunk, which must be the first chunk of an RF64 file.
+/// The table of the sizes of other chunks is ignored.
+pub(super) fn parse_ds64(input: &[u8]) -> IResult<&[u8], Ds64> {
+    let (input, _) = tag(b"ds64")(input)?;
+    let (input, _size) = le_u32(input)?;
+    let (input, riff_size) = le_u64(input)?;
+    let (input, data_size) = le_u64(input)?;
+    let (input, sample_count) = le_u64(input)?;
+    Ok((
+        input,
+        Ds64 {
+            riff_size,
+            data_size,
+            sample_count,
+        },
+    ))
+}
+
+/// Parse the ID and the size of a chunk. The returned input starts at the payload.
+pub(super) fn parse_chunk_header(input: &[u8]) -> IResult<&[u8], ChunkHeader> {
     let (input, chunk_id) = take(4usize)(input)?;
     let id: ChunkId = chunk_id.try_into().unwrap();
+    let fourcc = chunk_id.try_into().unwrap();
     let (input, size) = le_u32(input)?;
-    let (input, data) = take(size)(input)?;
-    Ok((input, Chunk { id, size, data }))
+    Ok((input, ChunkHeader { id, fourcc, size }))
+}
+
+/// Find the next known chunk after a corrupt one, whose payload fits in the input.
+/// A data chunk may be cut off, as in a truncated file. Returns the input from the chunk header.
+pub(super) fn find_chunk_header(input: &[u8]) -> Option<&[u8]> {
+    (0..input.len()).map(|i| &input[i..]).find(|candidate| {
+        parse_chunk_header(candidate).is_ok_and(|(payload, header)| match header.id {
+            ChunkId::Unknown => false,
+            ChunkId::Data => true,
+            _ => header.size as usize <= payload.len(),
+        })
+    })
 }
 
 /// WAVのfmtチャンクから取得できる情報の構造体
@@ -98,64 +174,263 @@ pub(super) fn parse_chunk(input: &[u8]) -> IResult<&[u8], Chunk> {
 /// * 'num_channels' - Mono: 1, Stereo: 2, and so on.
 /// * 'sample_rate' - Sample rate in Hz (44100, 48000, etc...).
 /// * 'bit_depth' - Bit depth (16, 24, 32, etc...).
+/// * 'block_align' - nBlockAlign. Bytes per frame.
+/// * 'byte_rate' - nAvgBytesPerSec.
 /// * 'ima_adpcm_num_block_align' - IMA-ADPCM only. IMA-ADPCMの1ブロックが何byteで構成されているか。
 /// * 'ima_adpcm_num_samples_per_block' - IMA-ADPCM only. IMA-ADPCMの1ブロックに何サンプル記録されているか。
+/// * 'ms_adpcm_num_block_align' - MS-ADPCM only. MS-ADPCMの1ブロックが何byteで構成されているか。
+/// * 'ms_adpcm_num_samples_per_block' - MS-ADPCM only. MS-ADPCMの1ブロックに何サンプル記録されているか。
+/// * 'ms_adpcm_coefficients' - MS-ADPCM only. The first 7 coefficient pairs of the predictors.
 #[derive(Debug, Default)]
 pub(super) struct WavFmtSpecs {
     pub audio_format: AudioFormat,
     pub num_channels: u16,
     pub sample_rate: u32,
     pub bit_depth: u16,
+    pub block_align: u16,
+    pub byte_rate: u32,
+    /// wValidBitsPerSample of WAVE_FORMAT_EXTENSIBLE, if it is used as the bit depth.
+    pub valid_bits_per_sample: Option<u16>,
     pub ima_adpcm_num_block_align: Option<u16>,
     pub ima_adpcm_num_samples_per_block: Option<u16>,
+    pub ms_adpcm_num_block_align: Option<u16>,
+    pub ms_adpcm_num_samples_per_block: Option<u16>,
+    pub ms_adpcm_coefficients: Option<[[i16; 2]; msadpcm::NUM_COEFFICIENTS]>,
+}
+
+impl WavFmtSpecs {
+    /// IMA-ADPCM and MS-ADPCM, whose nBlockAlign is the size of a block.
+    fn is_adpcm(&self) -> bool {
+        matches!(
+            self.audio_format,
+            AudioFormat::ImaAdpcmLe | AudioFormat::MsAdpcmLe
+        )
+    }
+
+    /// Bit depth of the samples, which may be narrower than wBitsPerSample for WAVE_FORMAT_EXTENSIBLE.
+    /// e.g. 24 for 24-bit samples in 32-bit containers.
+    pub(super) fn valid_bit_depth(&self) -> u16 {
+        self.valid_bits_per_sample
+            .filter(|&bits| bits <= self.bit_depth)
+            .unwrap_or(self.bit_depth)
+    }
+
+    /// Bytes in which a sample is stored. 0 for ADPCM.
+    /// Bit depths which are not a multiple of 8, e.g. 20-bit, are stored in nBlockAlign / nChannels bytes if it is wide enough,
+    /// because some files pad them to a wider container than the bit depth rounded up, e.g. 20-bit in 4 bytes.
+    pub(super) fn container_bytes_per_sample(&self) -> u16 {
+        if self.is_adpcm() {
+            return 0;
+        }
+        let min = self.bit_depth.div_ceil(8);
+        if self.bit_depth % 8 != 0
+            && self.num_channels != 0
+            && self.block_align % self.num_channels == 0
+        {
+            let bytes = self.block_align / self.num_channels;
+            if (min..=4).contains(&bytes) {
+                return bytes;
+            }
+        }
+        min
+    }
+
+    /// Cross-check nBlockAlign and nAvgBytesPerSec with the number of channels, the sample rate and the bit depth.
+    /// Returns the first field which does not match. ADPCM is not checked, because nBlockAlign is the size of a block.
+    /// 0 channels is reported by the validation of the specs.
+    pub(super) fn inconsistent_field(&self) -> Option<SpecsField> {
+        if self.is_adpcm() || self.num_channels == 0 {
+            return None;
+        }
+        let block_align =
+            u32::from(self.container_bytes_per_sample()) * u32::from(self.num_channels);
+        if u32::from(self.block_align) != block_align {
+            return Some(SpecsField::BlockAlign);
+        }
+        if u64::from(self.byte_rate) != u64::from(block_align) * u64::from(self.sample_rate) {
+            return Some(SpecsField::ByteRate);
+        }
+        None
+    }
+
+    /// Bit depth implied by nBlockAlign, if nAvgBytesPerSec agrees with it but wBitsPerSample does not.
+    /// e.g. 16 for a stereo file of 4 bytes per frame which declares 8 bits.
+    pub(super) fn implied_bit_depth(&self) -> Option<u16> {
+        if self.is_adpcm() || self.num_channels == 0 {
+            return None;
+        }
+        let bytes_per_sample = self.block_align / self.num_channels;
+        let consistent = self.block_align % self.num_channels == 0
+            && u64::from(self.block_align) * u64::from(self.sample_rate)
+                == u64::from(self.byte_rate);
+        if !consistent
+            || bytes_per_sample == 0
+            || bytes_per_sample == self.container_bytes_per_sample()
+        {
+            return None;
+        }
+        bytes_per_sample.checked_mul(8)
+    }
 }
 
 /// WAVはLittleEndianしか使わないのでAudioFormat::LinearPcmBe (Be = BigEndian)にはならない.
 /// fmtチャンクはwFormatTagによって拡張属性が追加される場合がある.
 /// https://www.mmsp.ece.mcgill.ca/Documents/AudioFormats/WAVE/Docs/RIFFNEW.pdf
+///
+/// The chunk is parsed by its size: WAVEFORMAT of 14 bytes without wBitsPerSample, PCMWAVEFORMAT of 16 bytes,
+/// or WAVEFORMATEX with cbSize and the extension. Extension bytes which the format does not need, and bytes after the extension, are ignored.
 pub(super) fn parse_fmt(input: &[u8]) -> IResult<&[u8], WavFmtSpecs> {
-    let (input, wave_format_tag) = le_u16(input)?;
-    let audio_format = match wave_format_tag.try_into().unwrap() {
-        WaveFormatTag::LinearPcm => AudioFormat::LinearPcmLe,
-        WaveFormatTag::IeeeFloat => AudioFormat::IeeeFloatLe,
-        WaveFormatTag::ImaAdpcm => AudioFormat::ImaAdpcmLe,
-    };
-
+    let (input, format_tag) = le_u16(input)?;
     let (input, num_channels) = le_u16(input)?;
     let (input, sample_rate) = le_u32(input)?;
-    let (input, _bytes_per_seconds) = le_u32(input)?;
+    let (input, byte_rate) = le_u32(input)?;
     let (input, block_size) = le_u16(input)?;
-    let (input, bit_depth) = le_u16(input)?;
+    let (input, bit_depth) = if input.is_empty() {
+        // WAVEFORMAT. The samples fill nBlockAlign.
+        let bit_depth = block_size
+            .checked_div(num_channels)
+            .unwrap_or(0)
+            .saturating_mul(8);
+        debug!(
+            "fmt chunk without wBitsPerSample, {} bits from the block align",
+            bit_depth
+        );
+        (input, bit_depth)
+    } else {
+        le_u16(input)?
+    };
+    let (input, extension) = parse_extension(input)?;
+
+    let (wave_format_tag, valid_bits_per_sample) = match format_tag.try_into() {
+        Ok(WaveFormatTag::Extensible) => parse_extensible(extension, bit_depth)?.1,
+        tag => (tag, None),
+    };
+    let audio_format = match wave_format_tag {
+        Ok(WaveFormatTag::LinearPcm) => AudioFormat::LinearPcmLe,
+        Ok(WaveFormatTag::IeeeFloat) => AudioFormat::IeeeFloatLe,
+        Ok(WaveFormatTag::ALaw) => AudioFormat::ALaw,
+        Ok(WaveFormatTag::MuLaw) => AudioFormat::MuLaw,
+        Ok(WaveFormatTag::ImaAdpcm) => AudioFormat::ImaAdpcmLe,
+        Ok(WaveFormatTag::MsAdpcm) => AudioFormat::MsAdpcmLe,
+        Ok(WaveFormatTag::Extensible) | Err(()) => {
+            debug!("unsupported WAV format tag {:#x}", format_tag);
+            return Err(nom::Err::Error(nom::error::Error::new(
+                input,
+                nom::error::ErrorKind::Tag,
+            )));
+        }
+    };
+    debug!(
+        "WAV format tag {:#x} -> {:?}, {} bits, {} channels, {} Hz",
+        format_tag, audio_format, bit_depth, num_channels, sample_rate
+    );
 
     if audio_format == AudioFormat::ImaAdpcmLe {
         //IMA-ADPCMの拡張属性の取得
         let num_block_align = block_size;
 
         if block_size % 4 != 0 {
+            debug!(
+                "IMA-ADPCM block align {} is not a multiple of 4",
+                block_size
+            );
             return Err(nom::Err::Error(nom::error::Error::new(
                 input,
                 nom::error::ErrorKind::LengthValue,
             )));
         }
-        if input.len() < 4 {
+        //wSamplesPerBlock = (((nBlockAlign - (4*nChannels))) * 8) / (wBitPerSample * nChannels) + 1
+        // Computed in u32 and checked, so that broken headers do not overflow or divide by zero.
+        let Some(expected) = (block_size as u32)
+            .checked_sub(4 * num_channels as u32)
+            .and_then(|data_size| data_size.checked_mul(8))
+            .and_then(|bits| bits.checked_div(bit_depth as u32 * num_channels as u32))
+            .and_then(|n| u16::try_from(n + 1).ok())
+        else {
+            debug!(
+                "IMA-ADPCM block align {} does not hold a block of {} channels",
+                block_size, num_channels
+            );
             return Err(nom::Err::Error(nom::error::Error::new(
                 input,
-                nom::error::ErrorKind::Eof,
+                nom::error::ErrorKind::Verify,
             )));
-        }
-        let (input, cb_size) = le_u16(input)?;
-        if cb_size != 2 {
+        };
+        // Some encoders omit the extension or write cbSize 0, so the value is derived from the block align.
+        // A value which the block can hold is used as is. The rest of the block is not decoded.
+        let num_samples_per_block = if extension.len() < 2 {
+            debug!(
+                "IMA-ADPCM fmt chunk without the extension, {} samples per block",
+                expected
+            );
+            expected
+        } else {
+            let (_, stored) = le_u16(extension)?; //2041
+            if stored != expected {
+                debug!(
+                    "IMA-ADPCM samples per block {} does not match the block align {}",
+                    stored, block_size
+                );
+            }
+            if (1..=expected).contains(&stored) {
+                stored
+            } else {
+                expected
+            }
+        };
+
+        return Ok((
+            input,
+            WavFmtSpecs {
+                audio_format,
+                num_channels,
+                sample_rate,
+                bit_depth,
+                block_align: block_size,
+                byte_rate,
+                ima_adpcm_num_block_align: Some(num_block_align),
+                ima_adpcm_num_samples_per_block: Some(num_samples_per_block),
+                ..Default::default()
+            },
+        ));
+    }
+
+    if audio_format == AudioFormat::MsAdpcmLe {
+        //MS-ADPCMの拡張属性の取得
+        let (coefficient_pairs, num_samples_per_block) = le_u16(extension)?;
+        let (coefficient_pairs, num_coefficients) = le_u16(coefficient_pairs)?;
+        // The extension covers wSamplesPerBlock, wNumCoef and the coefficient pairs.
+        if extension.len() < 4 + 4 * usize::from(num_coefficients)
+            || usize::from(num_coefficients) < msadpcm::NUM_COEFFICIENTS
+        {
+            debug!(
+                "MS-ADPCM extension of {} bytes with {} coefficients",
+                extension.len(),
+                num_coefficients
+            );
             return Err(nom::Err::Error(nom::error::Error::new(
                 input,
                 nom::error::ErrorKind::Verify,
             )));
         }
+        let mut coefficients = [[0i16; 2]; msadpcm::NUM_COEFFICIENTS];
+        let mut coefficient_pairs = coefficient_pairs;
+        for pair in &mut coefficients {
+            (coefficient_pairs, pair[0]) = le_i16(coefficient_pairs)?;
+            (coefficient_pairs, pair[1]) = le_i16(coefficient_pairs)?;
+        }
 
-        //wSamplesPerBlock = (((nBlockAlign - (4*nChannels))) * 8) / (wBitPerSample * nChannels) + 1
-        let (input, num_samples_per_block) = le_u16(input)?; //2041
-        if num_samples_per_block
-            != ((block_size - (4 * num_channels)) * 8) / (bit_depth * num_channels) + 1
-        {
+        //wSamplesPerBlock = (nBlockAlign - 7 * nChannels) * 8 / (wBitsPerSample * nChannels) + 2
+        let expected = (block_size as u32)
+            .checked_sub(7 * num_channels as u32)
+            .and_then(|data_size| data_size.checked_mul(8))
+            .and_then(|bits| bits.checked_div(bit_depth as u32 * num_channels as u32))
+            .map(|n| n + 2);
+        if expected != Some(num_samples_per_block as u32) {
+            debug!(
+                "MS-ADPCM samples per block {} does not match the block align {}",
+                num_samples_per_block, block_size
+            );
             return Err(nom::Err::Error(nom::error::Error::new(
                 input,
                 nom::error::ErrorKind::Verify,
@@ -169,12 +444,19 @@ pub(super) fn parse_fmt(input: &[u8]) -> IResult<&[u8], WavFmtSpecs> {
                 num_channels,
                 sample_rate,
                 bit_depth,
-                ima_adpcm_num_block_align: Some(num_block_align),
-                ima_adpcm_num_samples_per_block: Some(num_samples_per_block),
+                block_align: block_size,
+                byte_rate,
+                ms_adpcm_num_block_align: Some(block_size),
+                ms_adpcm_num_samples_per_block: Some(num_samples_per_block),
+                ms_adpcm_coefficients: Some(coefficients),
+                ..Default::default()
             },
         ));
     }
 
+    // Only linear PCM is decoded from the left-justified samples.
+    let valid_bits_per_sample =
+        valid_bits_per_sample.filter(|_| audio_format == AudioFormat::LinearPcmLe);
     Ok((
         input,
         WavFmtSpecs {
@@ -182,45 +464,430 @@ pub(super) fn parse_fmt(input: &[u8]) -> IResult<&[u8], WavFmtSpecs> {
             num_channels,
             sample_rate,
             bit_depth,
-            ima_adpcm_num_block_align: None,
-            ima_adpcm_num_samples_per_block: None,
+            block_align: block_size,
+            byte_rate,
+            valid_bits_per_sample,
+            ..Default::default()
+        },
+    ))
+}
+
+/// Parse cbSize of WAVEFORMATEX and return the extension of cbSize bytes, which is cut at the end of the chunk.
+/// The extension is empty if the chunk ends before cbSize, e.g. PCMWAVEFORMAT.
+fn parse_extension(input: &[u8]) -> IResult<&[u8], &[u8]> {
+    if input.len() < 2 {
+        return Ok((input, &[]));
+    }
+    let (input, cb_size) = le_u16(input)?;
+    if usize::from(cb_size) > input.len() {
+        debug!(
+            "fmt cbSize {} exceeds the {} bytes of the chunk",
+            cb_size,
+            input.len()
+        );
+    }
+    let (extension, rest) = input.split_at(usize::from(cb_size).min(input.len()));
+    Ok((rest, extension))
+}
+
+/// Format tag of the SubFormat of WAVE_FORMAT_EXTENSIBLE and wValidBitsPerSample.
+type SubFormat = (Result<WaveFormatTag, ()>, Option<u16>);
+
+/// Parse the extension of WAVE_FORMAT_EXTENSIBLE and return the format tag of the SubFormat and wValidBitsPerSample.
+/// wBitsPerSample is the container size. wValidBitsPerSample is returned if it is from 9 to wBitsPerSample,
+/// the range of the left-justified samples which are decoded in a wider container. dwChannelMask is not used.
+fn parse_extensible(input: &[u8], bits_per_sample: u16) -> IResult<&[u8], SubFormat> {
+    if input.len() < 22 {
+        debug!(
+            "WAVE_FORMAT_EXTENSIBLE extension of {} bytes is less than 22",
+            input.len()
+        );
+        return Err(nom::Err::Error(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Verify,
+        )));
+    }
+    let (input, valid_bits_per_sample) = le_u16(input)?;
+    let (input, _channel_mask) = le_u32(input)?;
+    let (input, sub_format_tag) = le_u16(input)?;
+    let (input, _) = tag(SUBFORMAT_GUID_TAIL.as_slice())(input)
+        .inspect_err(|_| debug!("unsupported WAVE_FORMAT_EXTENSIBLE SubFormat GUID"))?;
+    debug!("WAVE_FORMAT_EXTENSIBLE SubFormat {:#x}", sub_format_tag);
+    let format_tag = match sub_format_tag.try_into() {
+        // IMA-ADPCM needs the extension of its own, which EXTENSIBLE does not have.
+        Ok(WaveFormatTag::ImaAdpcm | WaveFormatTag::Extensible) => Err(()),
+        tag => tag,
+    };
+    let valid_bits_per_sample = if (9..=bits_per_sample).contains(&valid_bits_per_sample) {
+        Some(valid_bits_per_sample)
+    } else {
+        if valid_bits_per_sample != 0 && valid_bits_per_sample != bits_per_sample {
+            debug!(
+                "wValidBitsPerSample {} of {} bits is not used",
+                valid_bits_per_sample, bits_per_sample
+            );
+        }
+        None
+    };
+    Ok((input, (format_tag, valid_bits_per_sample)))
+}
+
+/// factチャンクをパースする
+/// Returns dwSampleLength, the number of samples per channel.
+pub(super) fn parse_fact(input: &[u8]) -> IResult<&[u8], u32> {
+    le_u32(input)
+}
+
+/// slntチャンクをパースする
+/// Returns dwSamples, the number of frames of silence in a LIST chunk of type wavl.
+pub(super) fn parse_slnt(input: &[u8]) -> IResult<&[u8], u32> {
+    le_u32(input)
+}
+
+/// smplチャンクをパースする
+/// The loops are read up to the count in the chunk and the end of the chunk. Loops beyond [crate::metadata::MAX_NUM_LOOPS] are dropped without the `alloc` feature.
+pub(super) fn parse_smpl(input: &[u8]) -> IResult<&[u8], SamplerInfo> {
+    let (input, _manufacturer) = le_u32(input)?;
+    let (input, _product) = le_u32(input)?;
+    let (input, _sample_period) = le_u32(input)?;
+    let (input, midi_unity_note) = le_u32(input)?;
+    let (input, midi_pitch_fraction) = le_u32(input)?;
+    let (input, _smpte_format) = le_u32(input)?;
+    let (input, _smpte_offset) = le_u32(input)?;
+    let (input, num_loops) = le_u32(input)?;
+    let (mut input, _sampler_data) = le_u32(input)?;
+
+    let mut loops = Table::new();
+    for _ in 0..num_loops {
+        let parsed: IResult<&[u8], _> =
+            tuple((le_u32, le_u32, le_u32, le_u32, le_u32, le_u32))(input);
+        // The chunk may hold fewer loops than num_loops.
+        let Ok((rest, (_cue_point_id, loop_type, start, end, _fraction, play_count))) = parsed
+        else {
+            break;
+        };
+        input = rest;
+        let sample_loop = SampleLoop {
+            loop_type: loop_type.into(),
+            start,
+            end,
+            play_count,
+        };
+        if !loops.push(sample_loop) {
+            debug!(
+                "more than {} loops in the smpl chunk",
+                crate::metadata::MAX_NUM_LOOPS
+            );
+            break;
+        }
+    }
+    Ok((
+        input,
+        SamplerInfo {
+            midi_unity_note,
+            midi_pitch_fraction,
+            loops,
+        },
+    ))
+}
+
+/// Parse the number of cue points of a cue chunk. The cue points follow it.
+pub(super) fn parse_cue_header(input: &[u8]) -> IResult<&[u8], u32> {
+    le_u32(input)
+}
+
+/// Parse a cue point of 24 bytes of a cue chunk.
+pub(super) fn parse_cue_point(input: &[u8]) -> IResult<&[u8], CuePoint> {
+    let (input, id) = le_u32(input)?;
+    let (input, position) = le_u32(input)?;
+    let (input, data_chunk_id) = take(4usize)(input)?;
+    let (input, chunk_start) = le_u32(input)?;
+    let (input, block_start) = le_u32(input)?;
+    let (input, sample_offset) = le_u32(input)?;
+    Ok((
+        input,
+        CuePoint {
+            id,
+            position,
+            data_chunk_id: [
+                data_chunk_id[0],
+                data_chunk_id[1],
+                data_chunk_id[2],
+                data_chunk_id[3],
+            ],
+            chunk_start,
+            block_start,
+            sample_offset,
+        },
+    ))
+}
+
+/// instチャンクをパースする
+/// The chunk is 7 bytes of notes, velocities, fine tune and gain.
+pub(super) fn parse_inst(input: &[u8]) -> IResult<&[u8], Instrument> {
+    let (input, unshifted_note) = u8(input)?;
+    let (input, fine_tune) = i8(input)?;
+    let (input, gain) = i8(input)?;
+    let (input, low_note) = u8(input)?;
+    let (input, high_note) = u8(input)?;
+    let (input, low_velocity) = u8(input)?;
+    let (input, high_velocity) = u8(input)?;
+    Ok((
+        input,
+        Instrument {
+            unshifted_note,
+            fine_tune,
+            gain,
+            low_note,
+            high_note,
+            low_velocity,
+            high_velocity,
+        },
+    ))
+}
+
+/// bextチャンクをパースする
+/// The loudness of version 2 is read if the chunk is long enough. UMID and the coding history are not read.
+pub(super) fn parse_bext(input: &[u8]) -> IResult<&[u8], BroadcastExtension<'_>> {
+    let (input, description) = take(256usize)(input)?;
+    let (input, originator) = take(32usize)(input)?;
+    let (input, originator_reference) = take(32usize)(input)?;
+    let (input, origination_date) = take(10usize)(input)?;
+    let (input, origination_time) = take(8usize)(input)?;
+    // TimeReferenceLow and TimeReferenceHigh
+    let (input, time_reference) = le_u64(input)?;
+    let (input, version) = le_u16(input)?;
+    let loudness = if version >= 2 {
+        let parsed: IResult<&[u8], _> = tuple((
+            take(64usize), // UMID
+            le_i16,
+            le_i16,
+            le_i16,
+            le_i16,
+            le_i16,
+        ))(input);
+        match parsed {
+            Ok((_, (_umid, value, range, true_peak, momentary, short_term))) => {
+                Some(LoudnessInfo {
+                    loudness_value: value,
+                    loudness_range: range,
+                    max_true_peak_level: true_peak,
+                    max_momentary_loudness: momentary,
+                    max_short_term_loudness: short_term,
+                })
+            }
+            Err(_) => {
+                debug!("bext version {} without the loudness", version);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    Ok((
+        input,
+        BroadcastExtension {
+            description: trim_text(description),
+            originator: trim_text(originator),
+            originator_reference: trim_text(originator_reference),
+            origination_date: trim_text(origination_date),
+            origination_time: trim_text(origination_time),
+            time_reference,
+            version,
+            loudness,
+        },
+    ))
+}
+
+/// cartチャンクをパースする
+/// The fixed fields are 2048 bytes, and the rest of the chunk is the tag text.
+pub(super) fn parse_cart(input: &[u8]) -> IResult<&[u8], CartInfo<'_>> {
+    let (input, version) = take(4usize)(input)?;
+    let (input, title) = take(64usize)(input)?;
+    let (input, artist) = take(64usize)(input)?;
+    let (input, cut_id) = take(64usize)(input)?;
+    let (input, client_id) = take(64usize)(input)?;
+    let (input, category) = take(64usize)(input)?;
+    let (input, classification) = take(64usize)(input)?;
+    let (input, out_cue) = take(64usize)(input)?;
+    let (input, start_date) = take(10usize)(input)?;
+    let (input, start_time) = take(8usize)(input)?;
+    let (input, end_date) = take(10usize)(input)?;
+    let (input, end_time) = take(8usize)(input)?;
+    let (input, producer_app_id) = take(64usize)(input)?;
+    let (input, producer_app_version) = take(64usize)(input)?;
+    let (input, user_def) = take(64usize)(input)?;
+    let (mut input, level_reference) = le_i32(input)?;
+    let mut timers = heapless::Vec::new();
+    for _ in 0..MAX_NUM_CART_TIMERS {
+        let (rest, usage) = take(4usize)(input)?;
+        let (rest, value) = le_u32(rest)?;
+        input = rest;
+        if usage != [0; 4] {
+            // At most MAX_NUM_CART_TIMERS are pushed.
+            let _ = timers.push(CartTimer {
+                usage: [usage[0], usage[1], usage[2], usage[3]],
+                value,
+            });
+        }
+    }
+    let (input, _reserved) = take(276usize)(input)?;
+    let (tag_text, url) = take(1024usize)(input)?;
+    Ok((
+        &[],
+        CartInfo {
+            version: trim_text(version),
+            title: trim_text(title),
+            artist: trim_text(artist),
+            cut_id: trim_text(cut_id),
+            client_id: trim_text(client_id),
+            category: trim_text(category),
+            classification: trim_text(classification),
+            out_cue: trim_text(out_cue),
+            start_date: trim_text(start_date),
+            start_time: trim_text(start_time),
+            end_date: trim_text(end_date),
+            end_time: trim_text(end_time),
+            producer_app_id: trim_text(producer_app_id),
+            producer_app_version: trim_text(producer_app_version),
+            user_def: trim_text(user_def),
+            level_reference,
+            url: trim_text(url),
+            tag_text: trim_text(tag_text),
+            timers,
         },
     ))
 }
 
+/// chnaチャンクをパースする
+/// The tracks are matched to the speakers of the common definitions of ADM by their audioTrackFormatID.
+pub(super) fn parse_chna(input: &[u8]) -> IResult<&[u8], ChannelLayout> {
+    let (input, num_tracks) = le_u16(input)?;
+    let (mut input, num_uids) = le_u16(input)?;
+    let mut labels = [0u32; 8];
+    let Some(labels) = labels.get_mut(..num_tracks as usize) else {
+        return Ok((input, ChannelLayout::Unknown));
+    };
+    for _ in 0..num_uids {
+        let (rest, track_index) = le_u16(input)?;
+        let (rest, _uid) = take(12usize)(rest)?;
+        let (rest, track_ref) = take(14usize)(rest)?;
+        // audioPackFormatID and a pad byte
+        let (rest, _) = take(12usize)(rest)?;
+        input = rest;
+        // trackIndex starts at 1.
+        let Some(label) = (track_index as usize)
+            .checked_sub(1)
+            .and_then(|i| labels.get_mut(i))
+        else {
+            return Ok((input, ChannelLayout::Unknown));
+        };
+        *label = match &track_ref[..11] {
+            b"AT_00010001" => metadata::LABEL_LEFT,
+            b"AT_00010002" => metadata::LABEL_RIGHT,
+            b"AT_00010003" => metadata::LABEL_CENTER,
+            b"AT_00010004" => metadata::LABEL_LFE,
+            b"AT_00010005" => metadata::LABEL_LEFT_SURROUND,
+            b"AT_00010006" => metadata::LABEL_RIGHT_SURROUND,
+            _ => 0,
+        };
+    }
+    Ok((input, ChannelLayout::from_labels(labels)))
+}
+
+/// Use the number of samples of the fact chunk if it is less than the data chunk holds.
+/// Encoders of float and compressed formats may pad the data chunk, e.g. to a sector, and count the real samples in the fact chunk.
+/// Linear PCM does not need the fact chunk, so it is ignored.
+pub(super) fn limit_to_fact(spec: &mut PcmSpecs, fact: Option<u32>) {
+    let Some(fact) = fact else {
+        return;
+    };
+    let plausible = match spec.audio_format {
+        AudioFormat::IeeeFloatLe | AudioFormat::ALaw | AudioFormat::MuLaw => true,
+        // Only the last block is partly filled. Some encoders write a fact chunk which does not count the samples, e.g. half of them in stereo.
+        AudioFormat::ImaAdpcmLe => spec
+            .ima_adpcm_num_samples_per_block
+            .is_some_and(|n| fact > spec.num_samples.saturating_sub(u32::from(n))),
+        AudioFormat::MsAdpcmLe => spec
+            .ms_adpcm_num_samples_per_block
+            .is_some_and(|n| fact > spec.num_samples.saturating_sub(u32::from(n))),
+        _ => false,
+    };
+    if plausible && fact < spec.num_samples {
+        debug!(
+            "fact chunk: {} samples, the data chunk holds {}",
+            fact, spec.num_samples
+        );
+        spec.num_samples = fact;
+        spec.num_samples_source = NumSamplesSource::FactChunk;
+    }
+}
+
 /// dataチャンクのサイズ情報からサンプル数を求める
 /// IMA-ADPCMは非対応。fmtチャンクの拡張属性から取得する必要がある。
 /// * 'data_chunk_size_in_bytes' - dataチャンクのlength (byte)
 /// * 'spec' - PCMファイルの情報
+///
+/// Never panics. Returns UnsupportedAudioFormat for IMA-ADPCM, InconsistentSpecs for 0 channels
+/// and UnsupportedBitDepth for bit depths less than 8, e.g. 0.
+/// The size is 64-bit for RF64. More samples than fit in u32 are InconsistentSpecs(NumSamples).
 pub(super) fn calc_num_samples_per_channel(
-    data_chunk_size_in_bytes: u32,
+    data_chunk_size_in_bytes: u64,
     spec: &PcmSpecs,
 ) -> Result<u32, PcmReaderError> {
-    // IMA-ADPCMは非対応
-    if spec.audio_format == AudioFormat::ImaAdpcmLe {
+    // ADPCMは非対応
+    if matches!(
+        spec.audio_format,
+        AudioFormat::ImaAdpcmLe | AudioFormat::MsAdpcmLe
+    ) {
         return Err(PcmReaderError::UnsupportedAudioFormat);
     }
 
-    Ok(data_chunk_size_in_bytes / (spec.bit_depth / 8u16 * spec.num_channels) as u32)
+    if spec.num_channels == 0 {
+        return Err(PcmReaderError::InconsistentSpecs(SpecsField::NumChannels));
+    }
+    // Bit depths less than 8 are not supported.
+    if spec.bit_depth < 8 {
+        return Err(PcmReaderError::UnsupportedBitDepth);
+    }
+    let bytes_per_frame = spec.container_bytes_per_sample as u64 * spec.num_channels as u64;
+    let num_samples = data_chunk_size_in_bytes
+        .checked_div(bytes_per_frame)
+        .ok_or(PcmReaderError::UnsupportedBitDepth)?;
+    u32::try_from(num_samples)
+        .map_err(|_| PcmReaderError::InconsistentSpecs(SpecsField::NumSamples))
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{wav::calc_num_samples_per_channel, wav::ChunkId, PcmSpecs};
+    use crate::{
+        wav::calc_num_samples_per_channel, wav::ChunkId, AudioFormat, PcmReaderError, PcmSpecs,
+        SpecsField,
+    };
 
-    use super::WaveFormatTag;
+    use super::{parse_fmt, WaveFormatTag, SUBFORMAT_GUID_TAIL};
 
     #[test]
     fn calc_num_samples() {
         let spec = PcmSpecs {
             audio_format: crate::AudioFormat::LinearPcmLe,
             bit_depth: 16,
+            container_bytes_per_sample: 2,
             num_channels: 2,
             ..Default::default()
         };
         let n = calc_num_samples_per_channel(192000, &spec).unwrap();
         assert_eq!(n, 48000);
 
+        // 20-bit in 24-bit containers
+        let spec = PcmSpecs {
+            bit_depth: 20,
+            container_bytes_per_sample: 3,
+            ..spec
+        };
+        let n = calc_num_samples_per_channel(192000, &spec).unwrap();
+        assert_eq!(n, 32000);
+
         // IMA-ADPCMのときにErrになるかtest
         let spec = PcmSpecs {
             audio_format: crate::AudioFormat::ImaAdpcmLe,
@@ -230,6 +897,39 @@ mod tests {
         };
         let e = calc_num_samples_per_channel(2041, &spec);
         assert!(e.is_err());
+
+        let spec = |bit_depth, num_channels| PcmSpecs {
+            audio_format: AudioFormat::LinearPcmLe,
+            bit_depth,
+            container_bytes_per_sample: bit_depth.div_ceil(8),
+            num_channels,
+            ..Default::default()
+        };
+        assert!(matches!(
+            calc_num_samples_per_channel(192000, &spec(16, 0)),
+            Err(PcmReaderError::InconsistentSpecs(SpecsField::NumChannels))
+        ));
+        assert!(matches!(
+            calc_num_samples_per_channel(192000, &spec(4, 2)),
+            Err(PcmReaderError::UnsupportedBitDepth)
+        ));
+        assert_eq!(
+            calc_num_samples_per_channel(u32::MAX.into(), &spec(8, 1)).unwrap(),
+            u32::MAX
+        );
+        assert_eq!(
+            calc_num_samples_per_channel(u32::MAX.into(), &spec(64, u16::MAX)).unwrap(),
+            u32::MAX / (8 * 65535)
+        );
+        // The data chunk of RF64 may hold more samples than fit in u32.
+        assert_eq!(
+            calc_num_samples_per_channel(u64::from(u32::MAX) * 2, &spec(16, 2)).unwrap(),
+            u32::MAX / 2
+        );
+        assert!(matches!(
+            calc_num_samples_per_channel(u64::from(u32::MAX) + 1, &spec(8, 1)),
+            Err(PcmReaderError::InconsistentSpecs(SpecsField::NumSamples))
+        ));
     }
 
     #[test]
@@ -246,11 +946,120 @@ mod tests {
         let tag: WaveFormatTag = b.try_into().unwrap();
         assert_eq!(tag, WaveFormatTag::ImaAdpcm);
 
+        let b = 0xFFFE;
+        let tag: WaveFormatTag = b.try_into().unwrap();
+        assert_eq!(tag, WaveFormatTag::Extensible);
+
         let b = 0xFF;
         let e: Result<WaveFormatTag, ()> = b.try_into();
         assert_eq!(e, Err(()));
     }
 
+    #[test]
+    fn parse_fmt_extensible() {
+        // 2ch 48kHz 24bit in 32bit containers
+        let mut fmt = vec![
+            0xFE, 0xFF, 2, 0, 0x80, 0xBB, 0, 0, 0, 0xDC, 0x05, 0, 8, 0, 32, 0,
+        ];
+        fmt.extend_from_slice(&[22, 0, 24, 0, 3, 0, 0, 0, 0x01, 0x00]);
+        fmt.extend_from_slice(&SUBFORMAT_GUID_TAIL);
+        let (_, spec) = parse_fmt(&fmt).unwrap();
+        assert_eq!(spec.audio_format, AudioFormat::LinearPcmLe);
+        assert_eq!(spec.num_channels, 2);
+        assert_eq!(spec.sample_rate, 48000);
+        assert_eq!(spec.bit_depth, 32);
+        assert_eq!(spec.valid_bit_depth(), 24);
+        assert_eq!(spec.container_bytes_per_sample(), 4);
+        assert_eq!(spec.inconsistent_field(), None);
+
+        // KSDATAFORMAT_SUBTYPE_IEEE_FLOAT
+        fmt[24] = 0x03;
+        let (_, spec) = parse_fmt(&fmt).unwrap();
+        assert_eq!(spec.audio_format, AudioFormat::IeeeFloatLe);
+        assert_eq!(spec.valid_bit_depth(), 32);
+
+        // Unknown SubFormat GUID
+        fmt[39] = 0x72;
+        assert!(parse_fmt(&fmt).is_err());
+        // Extension too short
+        assert!(parse_fmt(&fmt[..30]).is_err());
+    }
+
+    #[test]
+    fn parse_fmt_container() {
+        // 2ch 48kHz 20bit in 24bit containers
+        let mut fmt = [1, 0, 2, 0, 0x80, 0xBB, 0, 0, 0, 0x65, 0x04, 0, 6, 0, 20, 0];
+        let (_, spec) = parse_fmt(&fmt).unwrap();
+        assert_eq!(spec.bit_depth, 20);
+        assert_eq!(spec.container_bytes_per_sample(), 3);
+        assert_eq!(spec.inconsistent_field(), None);
+
+        // 20bit in 32bit containers
+        fmt[8..12].copy_from_slice(&384000u32.to_le_bytes());
+        fmt[12] = 8;
+        let (_, spec) = parse_fmt(&fmt).unwrap();
+        assert_eq!(spec.container_bytes_per_sample(), 4);
+        assert_eq!(spec.inconsistent_field(), None);
+
+        // Too narrow for 20bit
+        fmt[8..12].copy_from_slice(&192000u32.to_le_bytes());
+        fmt[12] = 4;
+        let (_, spec) = parse_fmt(&fmt).unwrap();
+        assert_eq!(spec.container_bytes_per_sample(), 3);
+        assert_eq!(spec.inconsistent_field(), Some(SpecsField::BlockAlign));
+
+        // Whole bytes are not padded, so 8bit in 2 bytes is inconsistent.
+        let fmt = [1, 0, 1, 0, 0x80, 0xBB, 0, 0, 0, 0x77, 0x01, 0, 2, 0, 8, 0];
+        let (_, spec) = parse_fmt(&fmt).unwrap();
+        assert_eq!(spec.container_bytes_per_sample(), 1);
+        assert_eq!(spec.inconsistent_field(), Some(SpecsField::BlockAlign));
+    }
+
+    #[test]
+    fn parse_fmt_sizes() {
+        // 2ch 48kHz 16bit
+        let pcm = [1, 0, 2, 0, 0x80, 0xBB, 0, 0, 0, 0xEE, 0x02, 0, 4, 0, 16, 0];
+
+        // WAVEFORMAT without wBitsPerSample
+        let (_, spec) = parse_fmt(&pcm[..14]).unwrap();
+        assert_eq!(spec.bit_depth, 16);
+        assert_eq!(spec.inconsistent_field(), None);
+        assert!(parse_fmt(&pcm[..15]).is_err());
+
+        // WAVEFORMATEX of 18 bytes with cbSize 0, and bytes after the extension
+        let mut fmt = pcm.to_vec();
+        fmt.extend_from_slice(&[0, 0]);
+        let (rest, spec) = parse_fmt(&fmt).unwrap();
+        assert_eq!(spec.bit_depth, 16);
+        assert!(rest.is_empty());
+        fmt.extend_from_slice(&[0xAA; 6]);
+        let (rest, _) = parse_fmt(&fmt).unwrap();
+        assert_eq!(rest.len(), 6);
+
+        // 40 bytes for plain PCM. The extension is not used.
+        let mut fmt = pcm.to_vec();
+        fmt.extend_from_slice(&[22, 0]);
+        fmt.extend_from_slice(&[0xAA; 22]);
+        let (rest, spec) = parse_fmt(&fmt).unwrap();
+        assert_eq!(spec.audio_format, AudioFormat::LinearPcmLe);
+        assert_eq!(spec.bit_depth, 16);
+        assert!(rest.is_empty());
+
+        // IMA-ADPCM with an extension of 4 bytes
+        let mut ima = [0x11, 0, 1, 0, 0x80, 0xBB, 0, 0, 0, 0, 0, 0, 0, 1, 4, 0].to_vec();
+        ima.extend_from_slice(&[4, 0, 0xF9, 0x01, 0xAA, 0xAA]);
+        let (rest, spec) = parse_fmt(&ima).unwrap();
+        assert_eq!(spec.ima_adpcm_num_samples_per_block, Some(505));
+        assert!(rest.is_empty());
+        // cbSize beyond the chunk is cut at its end.
+        ima[16] = 8;
+        let (_, spec) = parse_fmt(&ima).unwrap();
+        assert_eq!(spec.ima_adpcm_num_samples_per_block, Some(505));
+        // Without wSamplesPerBlock, it is derived from the block align.
+        let (_, spec) = parse_fmt(&ima[..19]).unwrap();
+        assert_eq!(spec.ima_adpcm_num_samples_per_block, Some(505));
+    }
+
     #[test]
     fn chunk_id_test() {
         let b = b"fmt ";
@@ -277,9 +1086,10 @@ mod tests {
         let chunk: ChunkId = b.as_slice().try_into().unwrap();
         assert_eq!(chunk, ChunkId::Junk);
 
-        let b = b"IDv3";
-        let chunk: ChunkId = b.as_slice().try_into().unwrap();
-        assert_eq!(chunk, ChunkId::IDv3);
+        for b in [b"id3 ", b"ID3 ", b"IDv3"] {
+            let chunk: ChunkId = b.as_slice().try_into().unwrap();
+            assert_eq!(chunk, ChunkId::IDv3);
+        }
 
         let b = b"LIST";
         let chunk: ChunkId = b.as_slice().try_into().unwrap();
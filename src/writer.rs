@@ -0,0 +1,666 @@
+//! WAV writer.
+//!
+//! The writer encodes normalized samples into a caller-provided byte buffer.
+//! The sizes in the header are not known until recording stops, so the header is written with placeholder sizes
+//! and [PcmWriter::finalize] returns a [HeaderPatch] describing the regions of the file that must be rewritten.
+//! When the whole file fits in the buffer, the patch is also applied in place.
+//!
+//! # Examples
+//!
+//! Write a WAV file into memory and read it back.
+//! ```
+//! use pacmog::writer::PcmWriterBuilder;
+//! use pacmog::PcmReader;
+//!
+//! let mut buffer = [0u8; 1024];
+//! let mut writer = PcmWriterBuilder::new(1, 48000).build(&mut buffer).unwrap();
+//! for i in 0..100 {
+//!     writer.write_frame(&[i as f32 / 100.0]).unwrap();
+//! }
+//! writer.finalize().unwrap();
+//!
+//! let wav = writer.flush();
+//! let reader = PcmReader::new(wav).unwrap();
+//! assert_eq!(reader.specs().num_samples, 100);
+//! ```
+//!
+//! Streaming to storage (e.g. an SD card) works by flushing the buffer regularly and applying the [HeaderPatch] at the end.
+//! ```
+//! use pacmog::writer::PcmWriterBuilder;
+//! use pacmog::AudioFormat;
+//!
+//! let mut file = Vec::new(); // Stands in for the file on the storage.
+//! let mut buffer = [0u8; 512];
+//! let mut writer = PcmWriterBuilder::new(2, 48000)
+//!     .with_format(AudioFormat::LinearPcmLe, 24)
+//!     .build(&mut buffer)
+//!     .unwrap();
+//! for _ in 0..1000 {
+//!     if writer.write_frame(&[0.5, -0.5]).is_err() {
+//!         file.extend_from_slice(writer.flush());
+//!         writer.write_frame(&[0.5, -0.5]).unwrap();
+//!     }
+//! }
+//! let patch = writer.finalize().unwrap();
+//! file.extend_from_slice(writer.flush());
+//!
+//! for region in patch.regions() {
+//!     // Seek to region.offset and write region.bytes.
+//!     let offset = region.offset as usize;
+//!     file[offset..offset + region.bytes.len()].copy_from_slice(&region.bytes);
+//! }
+//! ```
+
+use crate::{AudioFormat, PcmSpecs};
+use heapless::Vec;
+
+/// Size of the fmt chunk payload for linear PCM.
+const FMT_CHUNK_SIZE_PCM: u32 = 16;
+/// Size of the fmt chunk payload for IEEE float, including cbSize.
+const FMT_CHUNK_SIZE_FLOAT: u32 = 18;
+/// Maximum number of regions in a HeaderPatch: RIFF size, fact sample length and data size.
+const MAX_NUM_PATCH_REGIONS: usize = 3;
+/// Size of the smpl chunk payload without sample loops.
+const SMPL_CHUNK_SIZE_BASE: u32 = 36;
+/// Size of a sample loop in the smpl chunk.
+const SMPL_LOOP_SIZE: u32 = 24;
+/// Size of a cue point in the cue chunk.
+const CUE_POINT_SIZE: u32 = 24;
+/// MIDI unity note written to the smpl chunk. 60 is middle C.
+const DEFAULT_MIDI_UNITY_NOTE: u32 = 60;
+/// Sample length of the fact chunk until finalize. It is not less than the samples in the data chunk,
+/// so a reader does not limit a file cut off before finalize to it.
+const FACT_PLACEHOLDER: u32 = u32::MAX;
+
+/// Error type for PcmWriter.
+#[derive(Debug, thiserror::Error)]
+pub enum PcmWriterError {
+    #[error("Unsupported bit-depth")]
+    UnsupportedBitDepth,
+    #[error("Unsupported audio format")]
+    UnsupportedAudioFormat,
+    #[error("Invalid number of channels")]
+    InvalidChannels,
+    /// The sample rate is 0, or the byte rate of the fmt chunk does not fit in u32.
+    #[error("Invalid sample rate")]
+    InvalidSampleRate,
+    #[error("Output buffer too short")]
+    OutputBufferTooShort,
+    #[error("The number of elements in the frame is less than the number of channels")]
+    FrameTooShort,
+    #[error("The number of samples is not a multiple of the number of channels")]
+    IncompleteFrame,
+    #[error("The file exceeds the maximum size of WAV")]
+    FileTooLarge,
+    #[error("The writer has already been finalized")]
+    Finalized,
+    #[error("Loop start must not exceed loop end and loop end must be within the written frames")]
+    InvalidLoopPoints,
+}
+
+/// A region of the file that must be rewritten after recording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PatchRegion {
+    /// Byte offset from the beginning of the file.
+    pub offset: u32,
+    /// Bytes to be written at the offset.
+    pub bytes: [u8; 4],
+}
+
+/// Regions of the header which hold sizes that were unknown when the header was written.
+/// Returned by [PcmWriter::finalize].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct HeaderPatch {
+    regions: Vec<PatchRegion, MAX_NUM_PATCH_REGIONS>,
+}
+
+impl HeaderPatch {
+    /// Returns the regions to be rewritten, in ascending order of offset.
+    pub fn regions(&self) -> &[PatchRegion] {
+        &self.regions
+    }
+
+    /// Apply the patch to a whole file held in memory.
+    /// * 'file' - Byte array of the file, starting at the RIFF header.
+    pub fn apply(&self, file: &mut [u8]) -> Result<(), PcmWriterError> {
+        for region in &self.regions {
+            let offset = region.offset as usize;
+            let Some(dst) = file.get_mut(offset..offset + region.bytes.len()) else {
+                return Err(PcmWriterError::OutputBufferTooShort);
+            };
+            dst.copy_from_slice(&region.bytes);
+        }
+        Ok(())
+    }
+}
+
+/// Builder of [PcmWriter].
+#[derive(Debug, Default, Clone)]
+pub struct PcmWriterBuilder<'c> {
+    specs: PcmSpecs,
+    loop_points: Option<(u32, u32)>,
+    cue_points: &'c [u32],
+}
+
+impl<'c> PcmWriterBuilder<'c> {
+    /// Create a builder for 16bit linear PCM.
+    /// * 'num_channels' - Number of channels.
+    /// * 'sample_rate' - Sample rate in Hz.
+    pub fn new(num_channels: u16, sample_rate: u32) -> Self {
+        PcmWriterBuilder {
+            specs: PcmSpecs {
+                audio_format: AudioFormat::LinearPcmLe,
+                num_channels,
+                sample_rate,
+                bit_depth: 16,
+                container_bytes_per_sample: 2,
+                ..Default::default()
+            },
+            loop_points: None,
+            cue_points: &[],
+        }
+    }
+
+    /// Create a builder with the same format as the given specs, e.g. from [crate::PcmReader::specs].
+    /// num_samples is ignored.
+    pub fn from_specs(specs: &PcmSpecs) -> Self {
+        PcmWriterBuilder::new(specs.num_channels, specs.sample_rate)
+            .with_format(specs.audio_format.clone(), specs.bit_depth)
+    }
+
+    /// Set the audio format and the bit depth.
+    /// Supported formats are [AudioFormat::LinearPcmLe] (16, 24, 32bit) and [AudioFormat::IeeeFloatLe] (32, 64bit).
+    /// 64bit requires the `f64` feature.
+    pub fn with_format(mut self, audio_format: AudioFormat, bit_depth: u16) -> Self {
+        self.specs.audio_format = audio_format;
+        self.specs.bit_depth = bit_depth;
+        self.specs.container_bytes_per_sample = bit_depth.div_ceil(8);
+        self
+    }
+
+    /// Write a smpl chunk with one forward loop after the data chunk.
+    /// * 'start' - First frame of the loop.
+    /// * 'end' - Last frame of the loop (inclusive), as stored in the smpl chunk.
+    pub fn with_loop(mut self, start: u32, end: u32) -> Self {
+        self.loop_points = Some((start, end));
+        self
+    }
+
+    /// Write a cue chunk after the data chunk.
+    /// * 'positions' - Positions of the cue points in frames.
+    pub fn with_cue_points(mut self, positions: &'c [u32]) -> Self {
+        self.cue_points = positions;
+        self
+    }
+
+    /// Create a PcmWriter and write the header into the buffer.
+    /// * 'buffer' - Output buffer. It must be large enough to hold at least the header.
+    pub fn build<'a>(self, buffer: &'a mut [u8]) -> Result<PcmWriter<'a>, PcmWriterError>
+    where
+        'c: 'a,
+    {
+        let specs = self.specs;
+        match specs.audio_format {
+            AudioFormat::LinearPcmLe => {
+                if !matches!(specs.bit_depth, 16 | 24 | 32) {
+                    return Err(PcmWriterError::UnsupportedBitDepth);
+                }
+            }
+            AudioFormat::IeeeFloatLe => {
+                let f64_supported = cfg!(feature = "f64") && specs.bit_depth == 64;
+                if specs.bit_depth != 32 && !f64_supported {
+                    return Err(PcmWriterError::UnsupportedBitDepth);
+                }
+            }
+            _ => return Err(PcmWriterError::UnsupportedAudioFormat),
+        }
+        // nBlockAlign and nAvgBytesPerSec of the fmt chunk must fit in their fields.
+        let Some(block_align) = (specs.bit_depth / 8)
+            .checked_mul(specs.num_channels)
+            .filter(|&b| b != 0)
+        else {
+            return Err(PcmWriterError::InvalidChannels);
+        };
+        if specs.sample_rate == 0
+            || specs
+                .sample_rate
+                .checked_mul(u32::from(block_align))
+                .is_none()
+        {
+            return Err(PcmWriterError::InvalidSampleRate);
+        }
+
+        let mut writer = PcmWriter {
+            specs,
+            buffer,
+            pending: 0,
+            flushed: 0,
+            fact_offset: None,
+            data_size_offset: 0,
+            data_offset: 0,
+            num_frames: 0,
+            loop_points: self.loop_points,
+            cue_points: self.cue_points,
+            finalized: false,
+        };
+        writer.write_header()?;
+        Ok(writer)
+    }
+}
+
+/// Writes a WAV file into a byte buffer.
+pub struct PcmWriter<'a> {
+    specs: PcmSpecs,
+    buffer: &'a mut [u8],
+    /// Number of bytes written to the buffer since the last flush.
+    pending: usize,
+    /// Number of bytes handed out by flush so far.
+    flushed: u32,
+    /// Offset of the sample length field in the fact chunk.
+    fact_offset: Option<u32>,
+    /// Offset of the size field of the data chunk.
+    data_size_offset: u32,
+    /// Offset of the first byte of the data chunk payload.
+    data_offset: u32,
+    /// Number of frames written so far.
+    num_frames: u32,
+    /// Start and end of the loop written to the smpl chunk.
+    loop_points: Option<(u32, u32)>,
+    /// Positions written to the cue chunk.
+    cue_points: &'a [u32],
+    finalized: bool,
+}
+
+impl PcmWriter<'_> {
+    /// Returns the format of the file being written.
+    pub fn get_pcm_specs(&self) -> PcmSpecs {
+        let mut specs = self.specs.clone();
+        specs.num_samples = self.num_frames;
+        specs
+    }
+
+    /// Write one frame.
+    /// * 'frame' - Sample values of each channel, normalized to +/-1.0. Values outside the range are clipped.
+    pub fn write_frame(&mut self, frame: &[f32]) -> Result<(), PcmWriterError> {
+        if self.finalized {
+            return Err(PcmWriterError::Finalized);
+        }
+        let num_channels = self.specs.num_channels as usize;
+        if frame.len() < num_channels {
+            return Err(PcmWriterError::FrameTooShort);
+        }
+
+        let byte_depth = (self.specs.bit_depth / 8) as usize;
+        let frame_size = byte_depth * num_channels;
+        if self.data_bytes(self.num_frames + 1).is_none() {
+            return Err(PcmWriterError::FileTooLarge);
+        }
+        let Some(dst) = self.buffer.get_mut(self.pending..self.pending + frame_size) else {
+            return Err(PcmWriterError::OutputBufferTooShort);
+        };
+
+        for (sample, out) in frame.iter().zip(dst.chunks_exact_mut(byte_depth)) {
+            encode_sample(&self.specs, *sample, out);
+        }
+        self.pending += frame_size;
+        self.num_frames += 1;
+        Ok(())
+    }
+
+    /// Append interleaved frames.
+    /// Can be called any number of times before [PcmWriter::finalize]. No audio is kept after it is flushed.
+    /// Until finalize, the header holds placeholder sizes, so a file cut off by a crash still has its data
+    /// but needs a reader which tolerates wrong sizes.
+    /// * 'frames' - Interleaved sample values, normalized to +/-1.0. The length must be a multiple of the number of channels.
+    ///
+    /// Returns the number of frames appended. It is less than the number of given frames when the buffer becomes full,
+    /// and 0 if it is already full; call [PcmWriter::flush] and append the rest.
+    pub fn append_frames(&mut self, frames: &[f32]) -> Result<u32, PcmWriterError> {
+        let num_channels = self.specs.num_channels as usize;
+        if frames.len() % num_channels != 0 {
+            return Err(PcmWriterError::IncompleteFrame);
+        }
+        let mut num_appended = 0;
+        for frame in frames.chunks_exact(num_channels) {
+            match self.write_frame(frame) {
+                Ok(()) => num_appended += 1,
+                Err(PcmWriterError::OutputBufferTooShort) => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(num_appended)
+    }
+
+    /// Number of frames written so far.
+    pub fn frames_written(&self) -> u32 {
+        self.num_frames
+    }
+
+    /// Returns the bytes written since the previous flush and makes the whole buffer available again.
+    /// The returned bytes must be stored in order to form the file.
+    pub fn flush(&mut self) -> &[u8] {
+        let len = self.pending;
+        self.flushed += len as u32;
+        self.pending = 0;
+        &self.buffer[..len]
+    }
+
+    /// Finish writing and fill in the sizes in the header.
+    /// The smpl and cue chunks are written after the data chunk, so the buffer must have room for them.
+    /// The header is patched in place if it has not been flushed yet.
+    /// Returns the regions which must be rewritten when the header has already been flushed.
+    pub fn finalize(&mut self) -> Result<HeaderPatch, PcmWriterError> {
+        if self.finalized {
+            return Err(PcmWriterError::Finalized);
+        }
+
+        let data_bytes = self
+            .data_bytes(self.num_frames)
+            .ok_or(PcmWriterError::FileTooLarge)?;
+        let trailing_bytes = self.write_trailing_chunks(data_bytes)?;
+        let riff_size = (self.data_offset - 8 + data_bytes)
+            .checked_add(trailing_bytes)
+            .ok_or(PcmWriterError::FileTooLarge)?;
+
+        let mut patch = HeaderPatch::default();
+        let mut add = |offset: u32, value: u32| {
+            patch
+                .regions
+                .push(PatchRegion {
+                    offset,
+                    bytes: value.to_le_bytes(),
+                })
+                .unwrap();
+        };
+        add(4, riff_size);
+        if let Some(offset) = self.fact_offset {
+            add(offset, self.num_frames);
+        }
+        add(self.data_size_offset, data_bytes);
+
+        // Patch the regions which are still in the buffer.
+        for region in patch.regions() {
+            let Some(start) = region.offset.checked_sub(self.flushed) else {
+                continue;
+            };
+            let start = start as usize;
+            if start + region.bytes.len() <= self.pending {
+                self.buffer[start..start + region.bytes.len()].copy_from_slice(&region.bytes);
+            }
+        }
+
+        self.finalized = true;
+        Ok(patch)
+    }
+
+    /// Write RIFF header, fmt chunk, fact chunk (IEEE float only) and the header of data chunk.
+    /// The sizes are left as 0 until finalize.
+    fn write_header(&mut self) -> Result<(), PcmWriterError> {
+        let specs = &self.specs;
+        let is_float = specs.audio_format == AudioFormat::IeeeFloatLe;
+        let byte_depth = specs.bit_depth / 8;
+        let block_align = byte_depth * specs.num_channels;
+        let byte_rate = specs.sample_rate * block_align as u32;
+        let (format_tag, fmt_size) = if is_float {
+            (0x03u16, FMT_CHUNK_SIZE_FLOAT)
+        } else {
+            (0x01u16, FMT_CHUNK_SIZE_PCM)
+        };
+
+        let mut header: Vec<u8, 64> = Vec::new();
+        let mut put = |bytes: &[u8]| header.extend_from_slice(bytes).unwrap();
+        put(b"RIFF");
+        put(&0u32.to_le_bytes());
+        put(b"WAVE");
+        put(b"fmt ");
+        put(&fmt_size.to_le_bytes());
+        put(&format_tag.to_le_bytes());
+        put(&specs.num_channels.to_le_bytes());
+        put(&specs.sample_rate.to_le_bytes());
+        put(&byte_rate.to_le_bytes());
+        put(&block_align.to_le_bytes());
+        put(&specs.bit_depth.to_le_bytes());
+        if is_float {
+            put(&0u16.to_le_bytes()); //cbSize
+            put(b"fact");
+            put(&4u32.to_le_bytes());
+            put(&FACT_PLACEHOLDER.to_le_bytes());
+        }
+        put(b"data");
+        put(&0u32.to_le_bytes());
+
+        let len = header.len();
+        if is_float {
+            self.fact_offset = Some(len as u32 - 12);
+        }
+        self.data_size_offset = len as u32 - 4;
+        self.data_offset = len as u32;
+
+        let Some(dst) = self.buffer.get_mut(..len) else {
+            return Err(PcmWriterError::OutputBufferTooShort);
+        };
+        dst.copy_from_slice(&header);
+        self.pending = len;
+        Ok(())
+    }
+
+    /// Write the pad byte of the data chunk, smpl chunk and cue chunk.
+    /// Returns the number of bytes written.
+    fn write_trailing_chunks(&mut self, data_bytes: u32) -> Result<u32, PcmWriterError> {
+        if let Some((start, end)) = self.loop_points {
+            if start > end || end >= self.num_frames {
+                return Err(PcmWriterError::InvalidLoopPoints);
+            }
+        }
+
+        // Chunks are word aligned.
+        let pad_size = data_bytes % 2;
+        let smpl_size = match self.loop_points {
+            Some(_) => 8 + SMPL_CHUNK_SIZE_BASE + SMPL_LOOP_SIZE,
+            None => 0,
+        };
+        let cue_size = match self.cue_points.len() {
+            0 => 0,
+            n => 8 + 4 + CUE_POINT_SIZE * n as u32,
+        };
+        let total = pad_size + smpl_size + cue_size;
+        let Some(dst) = self
+            .buffer
+            .get_mut(self.pending..self.pending + total as usize)
+        else {
+            return Err(PcmWriterError::OutputBufferTooShort);
+        };
+
+        let mut cursor = 0;
+        let mut put = |bytes: &[u8]| {
+            dst[cursor..cursor + bytes.len()].copy_from_slice(bytes);
+            cursor += bytes.len();
+        };
+        if pad_size == 1 {
+            put(&[0]);
+        }
+        if let Some((start, end)) = self.loop_points {
+            let sample_period = 1_000_000_000 / self.specs.sample_rate.max(1); //nanoseconds
+            put(b"smpl");
+            put(&(SMPL_CHUNK_SIZE_BASE + SMPL_LOOP_SIZE).to_le_bytes());
+            put(&0u32.to_le_bytes()); //manufacturer
+            put(&0u32.to_le_bytes()); //product
+            put(&sample_period.to_le_bytes());
+            put(&DEFAULT_MIDI_UNITY_NOTE.to_le_bytes());
+            put(&0u32.to_le_bytes()); //MIDI pitch fraction
+            put(&0u32.to_le_bytes()); //SMPTE format
+            put(&0u32.to_le_bytes()); //SMPTE offset
+            put(&1u32.to_le_bytes()); //number of sample loops
+            put(&0u32.to_le_bytes()); //sampler data
+            put(&0u32.to_le_bytes()); //cue point ID
+            put(&0u32.to_le_bytes()); //type: loop forward
+            put(&start.to_le_bytes());
+            put(&end.to_le_bytes());
+            put(&0u32.to_le_bytes()); //fraction
+            put(&0u32.to_le_bytes()); //play count: infinite
+        }
+        if !self.cue_points.is_empty() {
+            put(b"cue ");
+            put(&(cue_size - 8).to_le_bytes());
+            put(&(self.cue_points.len() as u32).to_le_bytes());
+            for (i, position) in self.cue_points.iter().enumerate() {
+                put(&(i as u32 + 1).to_le_bytes()); //ID
+                put(&position.to_le_bytes());
+                put(b"data");
+                put(&0u32.to_le_bytes()); //chunk start
+                put(&0u32.to_le_bytes()); //block start
+                put(&position.to_le_bytes()); //sample offset
+            }
+        }
+
+        self.pending += total as usize;
+        Ok(total)
+    }
+
+    /// Size of the data chunk payload holding `num_frames` frames, if it fits in a WAV file.
+    fn data_bytes(&self, num_frames: u32) -> Option<u32> {
+        let frame_size = (self.specs.bit_depth / 8) as u32 * self.specs.num_channels as u32;
+        let data_bytes = num_frames.checked_mul(frame_size)?;
+        // RIFF size must also fit in 32 bit.
+        data_bytes.checked_add(self.data_offset)?;
+        Some(data_bytes)
+    }
+}
+
+/// Encode a normalized sample into the byte array.
+/// * 'out' - Destination. The length must be bit_depth / 8.
+fn encode_sample(specs: &PcmSpecs, sample: f32, out: &mut [u8]) {
+    match (&specs.audio_format, specs.bit_depth) {
+        (AudioFormat::LinearPcmLe, 16) => {
+            let v = quantize(sample, 15) as i16;
+            out.copy_from_slice(&v.to_le_bytes());
+        }
+        (AudioFormat::LinearPcmLe, 24) => {
+            let v = quantize(sample, 23);
+            out.copy_from_slice(&v.to_le_bytes()[..3]);
+        }
+        (AudioFormat::LinearPcmLe, 32) => {
+            let v = quantize(sample, 31);
+            out.copy_from_slice(&v.to_le_bytes());
+        }
+        (AudioFormat::IeeeFloatLe, 32) => {
+            out.copy_from_slice(&sample.to_le_bytes());
+        }
+        #[cfg(feature = "f64")]
+        (AudioFormat::IeeeFloatLe, 64) => {
+            out.copy_from_slice(&(sample as f64).to_le_bytes());
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Convert a normalized sample to an integer with rounding and clipping.
+/// * 'shift' - BitDepth - 1. The normalize factor is 2^shift.
+pub(crate) fn quantize(sample: f32, shift: u32) -> i32 {
+    let max = (1i64 << shift) - 1;
+    let min = -(1i64 << shift);
+    let v = sample * (1u64 << shift) as f32;
+    // Round half away from zero. `as` saturates and maps NaN to 0.
+    let v = if v >= 0.0 { v + 0.5 } else { v - 0.5 } as i64;
+    v.clamp(min, max) as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{quantize, PcmWriterBuilder, PcmWriterError};
+    use crate::AudioFormat;
+
+    #[test]
+    fn quantize_test() {
+        assert_eq!(quantize(0.0, 15), 0);
+        assert_eq!(quantize(0.5, 15), 16384);
+        assert_eq!(quantize(-1.0, 15), -32768);
+        assert_eq!(quantize(1.0, 15), 32767); //clip
+        assert_eq!(quantize(2.0, 23), 8388607); //clip
+        assert_eq!(quantize(-2.0, 31), i32::MIN); //clip
+        assert_eq!(quantize(1.4 / 32768.0, 15), 1);
+        assert_eq!(quantize(-1.6 / 32768.0, 15), -2);
+        assert_eq!(quantize(f32::NAN, 15), 0);
+    }
+
+    #[test]
+    fn header_layout() {
+        let mut buffer = [0u8; 64];
+        let mut writer = PcmWriterBuilder::new(2, 48000).build(&mut buffer).unwrap();
+        writer.write_frame(&[0.0, 0.0]).unwrap();
+        let patch = writer.finalize().unwrap();
+
+        let regions = patch.regions();
+        assert_eq!(regions.len(), 2);
+        assert_eq!(regions[0].offset, 4);
+        assert_eq!(regions[0].bytes, 40u32.to_le_bytes());
+        assert_eq!(regions[1].offset, 40);
+        assert_eq!(regions[1].bytes, 4u32.to_le_bytes());
+
+        let wav = writer.flush();
+        assert_eq!(wav.len(), 48);
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(&wav[4..8], &40u32.to_le_bytes());
+        assert_eq!(&wav[8..16], b"WAVEfmt ");
+        assert_eq!(&wav[28..32], &192000u32.to_le_bytes()); //byte rate
+        assert_eq!(&wav[32..34], &4u16.to_le_bytes()); //block align
+        assert_eq!(&wav[36..40], b"data");
+        assert_eq!(&wav[40..44], &4u32.to_le_bytes());
+    }
+
+    #[test]
+    fn float_header_has_fact() {
+        let mut buffer = [0u8; 128];
+        let mut writer = PcmWriterBuilder::new(1, 44100)
+            .with_format(AudioFormat::IeeeFloatLe, 32)
+            .build(&mut buffer)
+            .unwrap();
+        for _ in 0..3 {
+            writer.write_frame(&[0.25]).unwrap();
+        }
+        let patch = writer.finalize().unwrap();
+        let regions = patch.regions();
+        assert_eq!(regions.len(), 3);
+        assert_eq!(regions[1].offset, 46);
+        assert_eq!(regions[1].bytes, 3u32.to_le_bytes());
+        assert_eq!(regions[2].offset, 54);
+        assert_eq!(regions[2].bytes, 12u32.to_le_bytes());
+
+        let wav = writer.flush();
+        assert_eq!(&wav[38..42], b"fact");
+        assert_eq!(&wav[58..62], &0.25f32.to_le_bytes());
+    }
+
+    #[test]
+    fn build_errors() {
+        let mut buffer = [0u8; 64];
+        let e = PcmWriterBuilder::new(1, 48000)
+            .with_format(AudioFormat::LinearPcmBe, 16)
+            .build(&mut buffer);
+        assert!(matches!(e, Err(PcmWriterError::UnsupportedAudioFormat)));
+
+        let e = PcmWriterBuilder::new(1, 48000)
+            .with_format(AudioFormat::LinearPcmLe, 12)
+            .build(&mut buffer);
+        assert!(matches!(e, Err(PcmWriterError::UnsupportedBitDepth)));
+
+        let e = PcmWriterBuilder::new(0, 48000).build(&mut buffer);
+        assert!(matches!(e, Err(PcmWriterError::InvalidChannels)));
+
+        // nBlockAlign of 4 bytes * 16384 channels does not fit in u16.
+        let e = PcmWriterBuilder::new(16384, 48000)
+            .with_format(AudioFormat::IeeeFloatLe, 32)
+            .build(&mut buffer);
+        assert!(matches!(e, Err(PcmWriterError::InvalidChannels)));
+
+        // nAvgBytesPerSec does not fit in u32.
+        let e = PcmWriterBuilder::new(8, u32::MAX / 2).build(&mut buffer);
+        assert!(matches!(e, Err(PcmWriterError::InvalidSampleRate)));
+        let e = PcmWriterBuilder::new(1, 0).build(&mut buffer);
+        assert!(matches!(e, Err(PcmWriterError::InvalidSampleRate)));
+
+        let mut small = [0u8; 16];
+        let e = PcmWriterBuilder::new(1, 48000).build(&mut small);
+        assert!(matches!(e, Err(PcmWriterError::OutputBufferTooShort)));
+    }
+}
@@ -0,0 +1,65 @@
+/*
+ * Smoke test of the C API. Reads a 16bit mono WAV file and plays it to the end.
+ * See "C API" in README.md for the build commands.
+ */
+#include <pacmog.h>
+#include <stdio.h>
+
+#define CHECK(cond)                                                            \
+  do {                                                                         \
+    if (!(cond)) {                                                             \
+      fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #cond); \
+      return 1;                                                                \
+    }                                                                          \
+  } while (0)
+
+static uint8_t file[1 << 20];
+
+int main(int argc, char **argv) {
+  CHECK(argc == 2);
+  CHECK(sizeof(pacmog_reader_t) == PACMOG_READER_SIZE);
+  CHECK(sizeof(pacmog_player_t) == PACMOG_PLAYER_SIZE);
+
+  FILE *fp = fopen(argv[1], "rb");
+  CHECK(fp != NULL);
+  size_t len = fread(file, 1, sizeof(file), fp);
+  fclose(fp);
+
+  pacmog_reader_t reader;
+  CHECK(pacmog_reader_new(file, len, &reader) == PACMOG_OK);
+  CHECK(pacmog_reader_new(file, 12, &reader) == PACMOG_ERROR_TRUNCATED_DATA);
+  CHECK(pacmog_reader_new(NULL, 0, &reader) == PACMOG_ERROR_NULL_POINTER);
+  CHECK(pacmog_reader_new(file, len, &reader) == PACMOG_OK);
+
+  pacmog_specs_t specs;
+  CHECK(pacmog_reader_specs(&reader, &specs) == PACMOG_OK);
+  CHECK(specs.audio_format == PACMOG_AUDIO_FORMAT_LINEAR_PCM_LE);
+  CHECK(specs.num_channels == 1);
+  CHECK(specs.bit_depth == 16);
+  CHECK(specs.sample_rate == 48000);
+
+  float sample;
+  CHECK(pacmog_read_sample_f32(&reader, 0, 10, &sample) == PACMOG_OK);
+  CHECK(sample > 0.0f && sample < 1.0f);
+  CHECK(pacmog_read_sample_f32(&reader, 1, 10, &sample) == PACMOG_ERROR_INVALID_CHANNEL);
+  CHECK(pacmog_read_sample_f32(&reader, 0, specs.num_samples, &sample) ==
+        PACMOG_ERROR_INVALID_SAMPLE);
+
+  pacmog_player_t player;
+  CHECK(pacmog_player_new(&reader, &player) == PACMOG_OK);
+  pacmog_reader_drop(&reader);
+  CHECK(pacmog_player_set_loop_playing(&player, false) == PACMOG_OK);
+
+  int16_t buffer[256];
+  size_t num_frames = 0;
+  uint32_t total = 0;
+  do {
+    CHECK(pacmog_player_fill_i16(&player, buffer, 256, &num_frames) == PACMOG_OK);
+    total += (uint32_t)num_frames;
+  } while (num_frames == 256);
+  CHECK(total == specs.num_samples);
+  pacmog_player_drop(&player);
+
+  printf("%u frames\n", (unsigned)total);
+  return 0;
+}
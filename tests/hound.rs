@@ -0,0 +1,115 @@
+//! Reference tests against hound.
+//! Random samples are written to WAV files by hound, and read back through PcmReader.
+use hound::{SampleFormat, WavSpec, WavWriter};
+use pacmog::{PcmReader, PcmReaderError};
+use std::io::Cursor;
+
+const NUM_FRAMES: usize = 257;
+
+/// xorshift32, so that the test does not depend on a random number crate and is reproducible.
+struct Xorshift(u32);
+
+impl Xorshift {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+}
+
+/// Formats which pacmog does not read yet.
+fn unsupported(spec: &WavSpec) -> bool {
+    spec.bits_per_sample == 8
+}
+
+/// Write random samples with hound and return the file with the expected normalized values.
+fn write_random(spec: WavSpec, seed: u32) -> (Vec<u8>, Vec<f32>) {
+    let mut rng = Xorshift(seed);
+    let mut file = Cursor::new(Vec::new());
+    let mut writer = WavWriter::new(&mut file, spec).unwrap();
+    let mut expected = Vec::new();
+    // The extremes are always included.
+    let num_samples = NUM_FRAMES * spec.channels as usize;
+    for i in 0..num_samples {
+        let r = match i {
+            0 => 0x8000_0000,
+            1 => 0x7FFF_FFFF,
+            _ => rng.next(),
+        };
+        match spec.sample_format {
+            SampleFormat::Float => {
+                let value = (r as i32 as f64 / 2f64.powi(31)) as f32;
+                writer.write_sample(value).unwrap();
+                expected.push(value);
+            }
+            SampleFormat::Int => {
+                let bits = spec.bits_per_sample as i32;
+                let value = r as i32 >> (32 - bits);
+                writer.write_sample(value).unwrap();
+                expected.push((value as f64 / 2f64.powi(bits - 1)) as f32);
+            }
+        }
+    }
+    writer.finalize().unwrap();
+    (file.into_inner(), expected)
+}
+
+#[test]
+fn read_back_hound_files() {
+    let formats = [
+        (SampleFormat::Int, 8),
+        (SampleFormat::Int, 16),
+        (SampleFormat::Int, 24),
+        (SampleFormat::Int, 32),
+        (SampleFormat::Float, 32),
+    ];
+    let mut seed = 1;
+    for (sample_format, bits_per_sample) in formats {
+        for channels in 1..=4 {
+            seed += 1;
+            let spec = WavSpec {
+                channels,
+                sample_rate: 44100,
+                bits_per_sample,
+                sample_format,
+            };
+            let (wav, expected) = write_random(spec, seed);
+
+            let reader = match PcmReader::new(&wav) {
+                Ok(reader) => reader,
+                Err(e) => {
+                    assert!(unsupported(&spec), "{spec:?}: {e:?}");
+                    continue;
+                }
+            };
+            let specs = reader.specs();
+            assert_eq!(specs.num_channels, channels, "{spec:?}");
+            assert_eq!(specs.sample_rate, 44100, "{spec:?}");
+            assert_eq!(specs.bit_depth, bits_per_sample, "{spec:?}");
+            assert_eq!(specs.num_samples, NUM_FRAMES as u32, "{spec:?}");
+
+            for (i, expected) in expected.iter().enumerate() {
+                let channel = (i % channels as usize) as u16;
+                let frame = (i / channels as usize) as u32;
+                match reader.read_sample(channel, frame) {
+                    Ok(sample) => assert_eq!(sample, *expected, "{spec:?} {channel} {frame}"),
+                    Err(e) => {
+                        assert!(unsupported(&spec), "{spec:?}: {e:?}");
+                        break;
+                    }
+                }
+            }
+            for channel in 0..channels {
+                assert!(matches!(
+                    reader.read_sample(channel, NUM_FRAMES as u32),
+                    Err(PcmReaderError::InvalidSample)
+                ));
+            }
+            assert!(matches!(
+                reader.read_sample(channels, 0),
+                Err(PcmReaderError::InvalidChannel)
+            ));
+        }
+    }
+}
@@ -1,8 +1,18 @@
 use approx::assert_relative_eq;
 use pacmog::{
-    imaadpcm::{ImaAdpcmPlayer, I1F15},
-    AudioFormat, PcmPlayer, PcmReader,
+    i2s::{format_frames, FrameFormat},
+    imaadpcm::{ImaAdpcmError, ImaAdpcmPlayer, I1F15},
+    msadpcm::{MsAdpcmError, MsAdpcmPlayer},
+    stream::StreamingPlayer,
+    transcode::{
+        transcode, transcode_merge, transcode_streaming, DitherMode, LengthMismatch,
+        TranscodeError, TranscodeOptions,
+    },
+    writer::{PcmWriterBuilder, PcmWriterError},
+    AudioFormat, NumSamplesSource, ParseMode, PcmPlayer, PcmPlayerError, PcmReader, PcmReaderError,
+    PcmSpecs, ReaderOptions, SpecsField,
 };
+use std::ops::Range;
 
 const SINEWAVE: [f32; 3000] = [
     0f32,
@@ -3053,11 +3063,7 @@ fn decode_with_symphonia(data: &'static [u8]) -> (Vec<f32>, u32, usize) {
     let mut symphonia_decoded_samples = Vec::<f32>::new();
     let mut sample_rate = None;
     let mut num_channels = None;
-    loop {
-        let Ok(packet) = format.next_packet() else {
-            break;
-        };
-
+    while let Ok(packet) = format.next_packet() {
         // Consume any new metadata that has been read since the last packet.
         while !format.metadata().is_latest() {
             // Pop the old head of the metadata queue.
@@ -3332,6 +3338,7 @@ fn wav_compare_with_symphonia() {
     }
 
     // 1ch 48kHz 64bit float
+    #[cfg(feature = "f64")]
     {
         let data = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_64FP.wav");
         reader.reload(data).unwrap();
@@ -3515,6 +3522,7 @@ fn aiff_32bit_float() {
 
 // TODO SymphoniaがAIFF 64bit floatをサポートしたら比較テストに置き換える
 #[test]
+#[cfg(feature = "f64")]
 fn aiff_64bit_float() {
     let aiff = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_64FP.aif");
     let reader = PcmReader::new(aiff).unwrap();
@@ -3587,12 +3595,124 @@ fn wav_player_32bit() {
     }
 }
 
+#[test]
+fn ms_adpcm_4bit() {
+    let data = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_4bit_MSADPCM.wav");
+    let mut player = MsAdpcmPlayer::new(data).unwrap();
+    let spec = player.reader.get_pcm_specs();
+    assert_eq!(spec.num_samples, 5000);
+    assert_eq!(spec.sample_rate, 48000);
+    assert_eq!(spec.num_channels, 1);
+    assert_eq!(spec.audio_format, AudioFormat::MsAdpcmLe);
+    assert_eq!(spec.bit_depth, 4);
+
+    // Decoded bit-exactly like symphonia.
+    let (symphonia_buf, sample_rate, num_channels) = decode_with_symphonia(data);
+    assert_eq!(sample_rate, 48000);
+    assert_eq!(num_channels, 1);
+    assert_eq!(symphonia_buf.len(), 5000);
+    let mut buffer = [I1F15::ZERO; 1];
+    for expected in &symphonia_buf {
+        player.get_next_frame(&mut buffer).unwrap();
+        assert_eq!(buffer[0].to_num::<f32>(), *expected);
+    }
+    assert!(matches!(
+        player.get_next_frame(&mut buffer),
+        Err(MsAdpcmError::FinishPlaying)
+    ));
+
+    // 再生位置を先頭に戻す
+    player.rewind();
+    let mut frame = [0i16; 1];
+    for expected in &symphonia_buf {
+        player.get_next_frame_i16(&mut frame).unwrap();
+        assert_eq!(f32::from(frame[0]) / 32768.0, *expected);
+    }
+    assert!(matches!(
+        player.get_next_frame_i16(&mut frame),
+        Err(MsAdpcmError::FinishPlaying)
+    ));
+
+    // The reader does not decode MS-ADPCM.
+    assert!(matches!(
+        player.reader.read_sample(0, 0),
+        Err(PcmReaderError::UnsupportedAudioFormat)
+    ));
+
+    // Transcoded to 16-bit linear PCM without loss.
+    let reader = PcmReader::new(data).unwrap();
+    let mut buffer = vec![0u8; 20_000];
+    let mut writer = PcmWriterBuilder::new(1, 48000).build(&mut buffer).unwrap();
+    let n = transcode(&reader, &mut writer, &TranscodeOptions::default()).unwrap();
+    assert_eq!(n, 5000);
+    writer.finalize().unwrap();
+    let output = PcmReader::new(writer.flush()).unwrap();
+    for (i, expected) in symphonia_buf.iter().enumerate() {
+        assert_eq!(output.read_sample(0, i as u32).unwrap(), *expected);
+    }
+}
+
+#[test]
+fn ms_adpcm_4bit_2ch() {
+    let data = include_bytes!("./resources/Sine440Hz_2ch_48000Hz_4bit_MSADPCM.wav");
+    let mut player = MsAdpcmPlayer::new(data).unwrap();
+    let spec = player.reader.get_pcm_specs();
+    assert_eq!(spec.num_samples, 5000);
+    assert_eq!(spec.num_channels, 2);
+    assert_eq!(spec.audio_format, AudioFormat::MsAdpcmLe);
+
+    let (symphonia_buf, _, num_channels) = decode_with_symphonia(data);
+    assert_eq!(num_channels, 2);
+    assert_eq!(symphonia_buf.len(), 10000);
+    let mut buffer = [I1F15::ZERO; 2];
+    for expected in symphonia_buf.chunks_exact(2) {
+        player.get_next_frame(&mut buffer).unwrap();
+        assert_eq!(buffer[0].to_num::<f32>(), expected[0]);
+        assert_eq!(buffer[1].to_num::<f32>(), expected[1]);
+    }
+    assert!(matches!(
+        player.get_next_frame(&mut buffer),
+        Err(MsAdpcmError::FinishPlaying)
+    ));
+    assert!(matches!(
+        player.get_next_frame(&mut buffer[..1]),
+        Err(MsAdpcmError::InsufficientOutputBufferChannels)
+    ));
+
+    // The fact chunk limits the number of samples.
+    let mut file = data.to_vec();
+    let fact = file.windows(4).position(|w| w == b"fact").unwrap();
+    file[fact + 8..fact + 12].copy_from_slice(&4990u32.to_le_bytes());
+    let reader = PcmReader::new(&file).unwrap();
+    assert_eq!(reader.specs().num_samples, 4990);
+    assert_eq!(
+        reader.specs().num_samples_source,
+        NumSamplesSource::FactChunk
+    );
+
+    // wSamplesPerBlock which does not match nBlockAlign
+    let mut file = data.to_vec();
+    let fmt = file.windows(4).position(|w| w == b"fmt ").unwrap();
+    file[fmt + 26..fmt + 28].copy_from_slice(&499u16.to_le_bytes());
+    assert!(matches!(
+        PcmReader::new(&file),
+        Err(PcmReaderError::UnsupportedAudioFormat)
+    ));
+
+    // IMA-ADPCM is not MS-ADPCM.
+    let ima = include_bytes!("./resources/Sine440Hz_2ch_48000Hz_4bit_IMAADPCM.wav");
+    assert!(matches!(
+        MsAdpcmPlayer::new(ima),
+        Err(MsAdpcmError::NotMsAdpcm)
+    ));
+}
+
 #[test]
 fn ima_adpcm_4bit() {
     let data = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_4bit_IMAADPCM.wav");
-    let mut player = ImaAdpcmPlayer::new(data);
+    let mut player = ImaAdpcmPlayer::new(data).unwrap();
     let spec = player.reader.get_pcm_specs();
-    assert_eq!(spec.num_samples, 240838);
+    assert_eq!(spec.num_samples, 240000);
     assert_eq!(spec.sample_rate, 48000);
     assert_eq!(spec.num_channels, 1);
     assert_eq!(spec.audio_format, AudioFormat::ImaAdpcmLe);
@@ -3610,9 +3730,9 @@ fn ima_adpcm_4bit() {
 #[test]
 fn ima_adpcm_4bit_play_to_end() {
     let data = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_4bit_IMAADPCM.wav");
-    let mut player = ImaAdpcmPlayer::new(data);
+    let mut player = ImaAdpcmPlayer::new(data).unwrap();
     let spec = player.reader.get_pcm_specs();
-    assert_eq!(spec.num_samples, 240838);
+    assert_eq!(spec.num_samples, 240000);
     assert_eq!(spec.sample_rate, 48000);
     assert_eq!(spec.num_channels, 1);
     assert_eq!(spec.audio_format, AudioFormat::ImaAdpcmLe);
@@ -3646,7 +3766,7 @@ fn ima_adpcm_4bit_play_to_end() {
 #[test]
 fn ima_adpcm_4bit_2ch() {
     let data = include_bytes!("./resources/Sine440Hz_2ch_48000Hz_4bit_IMAADPCM.wav");
-    let mut player = ImaAdpcmPlayer::new(data);
+    let mut player = ImaAdpcmPlayer::new(data).unwrap();
     let spec = player.reader.get_pcm_specs();
     assert_eq!(spec.num_samples, 240838);
     assert_eq!(spec.sample_rate, 48000);
@@ -3669,7 +3789,7 @@ fn ima_adpcm_4bit_2ch() {
 #[test]
 fn ima_adpcm_4bit_2ch_play_to_end() {
     let data = include_bytes!("./resources/Sine440Hz_2ch_48000Hz_4bit_IMAADPCM.wav");
-    let mut player = ImaAdpcmPlayer::new(data);
+    let mut player = ImaAdpcmPlayer::new(data).unwrap();
     let spec = player.reader.get_pcm_specs();
     assert_eq!(spec.num_samples, 240838);
     assert_eq!(spec.sample_rate, 48000);
@@ -3701,3 +3821,4352 @@ fn ima_adpcm_4bit_2ch_play_to_end() {
     let e = player.get_next_frame(buf);
     assert!(e.is_err());
 }
+
+#[test]
+fn wav_writer_round_trip() {
+    let data = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_24.wav");
+    let reader = PcmReader::new(data).unwrap();
+    let spec = reader.get_pcm_specs();
+
+    let mut formats = vec![
+        (AudioFormat::LinearPcmLe, 16),
+        (AudioFormat::LinearPcmLe, 24),
+        (AudioFormat::LinearPcmLe, 32),
+        (AudioFormat::IeeeFloatLe, 32),
+    ];
+    if cfg!(feature = "f64") {
+        formats.push((AudioFormat::IeeeFloatLe, 64));
+    } else {
+        assert!(matches!(
+            PcmWriterBuilder::from_specs(&spec)
+                .with_format(AudioFormat::IeeeFloatLe, 64)
+                .build(&mut [0u8; 100]),
+            Err(PcmWriterError::UnsupportedBitDepth)
+        ));
+    }
+
+    for (audio_format, bit_depth) in formats {
+        let mut buffer = vec![0u8; 100 + SINEWAVE.len() * 8];
+        let mut writer = PcmWriterBuilder::from_specs(&spec)
+            .with_format(audio_format.clone(), bit_depth)
+            .build(&mut buffer)
+            .unwrap();
+        for i in 0..SINEWAVE.len() as u32 {
+            writer
+                .write_frame(&[reader.read_sample(0, i).unwrap()])
+                .unwrap();
+        }
+        writer.finalize().unwrap();
+        let wav = writer.flush();
+
+        let written = PcmReader::new(wav).unwrap();
+        let written_spec = written.get_pcm_specs();
+        assert_eq!(written_spec.audio_format, audio_format);
+        assert_eq!(written_spec.bit_depth, bit_depth);
+        assert_eq!(written_spec.num_channels, 1);
+        assert_eq!(written_spec.sample_rate, 48000);
+        assert_eq!(written_spec.num_samples, SINEWAVE.len() as u32);
+        for i in 0..SINEWAVE.len() as u32 {
+            assert_relative_eq!(
+                written.read_sample(0, i).unwrap(),
+                reader.read_sample(0, i).unwrap(),
+                epsilon = 1.0 / 32768.0
+            );
+        }
+    }
+}
+
+#[test]
+fn wav_writer_header_patch() {
+    // Record through a small staging buffer into a simulated file on an SD card.
+    let mut sd_file = Vec::new();
+    let mut staging = [0u8; 512];
+    let mut writer = PcmWriterBuilder::new(2, 48000)
+        .with_format(AudioFormat::IeeeFloatLe, 32)
+        .build(&mut staging)
+        .unwrap();
+    for chunk in SINEWAVE.chunks(32) {
+        for s in chunk {
+            writer.write_frame(&[*s, -*s]).unwrap();
+        }
+        sd_file.extend_from_slice(writer.flush());
+    }
+    let patch = writer.finalize().unwrap();
+    sd_file.extend_from_slice(writer.flush());
+
+    // The header was flushed before finalize, so the sizes are still placeholders.
+    assert_eq!(&sd_file[4..8], &[0, 0, 0, 0]);
+    assert!(PcmReader::new(&sd_file).is_err());
+
+    // RIFF size, fact sample length and data size.
+    assert_eq!(patch.regions().len(), 3);
+    for region in patch.regions() {
+        let offset = region.offset as usize;
+        sd_file[offset..offset + region.bytes.len()].copy_from_slice(&region.bytes);
+    }
+
+    let reader = PcmReader::new(&sd_file).unwrap();
+    let spec = reader.get_pcm_specs();
+    assert_eq!(spec.num_samples, SINEWAVE.len() as u32);
+    assert_eq!(spec.num_channels, 2);
+    for (i, s) in SINEWAVE.iter().enumerate() {
+        assert_eq!(reader.read_sample(0, i as u32).unwrap(), *s);
+        assert_eq!(reader.read_sample(1, i as u32).unwrap(), -*s);
+    }
+}
+
+#[test]
+fn wav_writer_append_frames() {
+    let interleaved: Vec<f32> = SINEWAVE.iter().flat_map(|s| [*s, -*s * 0.5]).collect();
+
+    // Single-shot write
+    let mut buffer = vec![0u8; 100 + interleaved.len() * 2];
+    let mut writer = PcmWriterBuilder::new(2, 48000).build(&mut buffer).unwrap();
+    for frame in interleaved.chunks(2) {
+        writer.write_frame(frame).unwrap();
+    }
+    writer.finalize().unwrap();
+    let expected = writer.flush().to_vec();
+
+    // Append in irregular chunk sizes through a small staging buffer.
+    let mut file = Vec::new();
+    let mut staging = [0u8; 300];
+    let mut writer = PcmWriterBuilder::new(2, 48000).build(&mut staging).unwrap();
+    let chunk_sizes = [1usize, 7, 64, 3, 250, 31, 2];
+    let mut rest = &interleaved[..];
+    for size in chunk_sizes.iter().cycle() {
+        if rest.is_empty() {
+            break;
+        }
+        let (mut frames, next) = rest.split_at((size * 2).min(rest.len()));
+        rest = next;
+        // Flush only when nothing fits, so that a full buffer is hit both mid-call and at the first frame.
+        while !frames.is_empty() {
+            let n = writer.append_frames(frames).unwrap() as usize;
+            if n == 0 {
+                file.extend_from_slice(writer.flush());
+            }
+            frames = &frames[n * 2..];
+        }
+    }
+    assert_eq!(writer.frames_written(), SINEWAVE.len() as u32);
+    file.extend_from_slice(writer.flush());
+
+    // Before finalize, the data is in the file while the sizes are still placeholders.
+    assert_eq!(file.len(), expected.len());
+    assert_eq!(&file[44..], &expected[44..]);
+    assert_eq!(&file[40..44], &[0, 0, 0, 0]);
+
+    let patch = writer.finalize().unwrap();
+    file.extend_from_slice(writer.flush());
+    for region in patch.regions() {
+        let offset = region.offset as usize;
+        file[offset..offset + region.bytes.len()].copy_from_slice(&region.bytes);
+    }
+    assert_eq!(file, expected);
+
+    assert!(matches!(
+        writer.append_frames(&[0.0, 0.0]),
+        Err(PcmWriterError::Finalized)
+    ));
+    let mut writer = PcmWriterBuilder::new(2, 48000).build(&mut staging).unwrap();
+    assert!(matches!(
+        writer.append_frames(&[0.0, 0.0, 0.0]),
+        Err(PcmWriterError::IncompleteFrame)
+    ));
+    assert_eq!(writer.frames_written(), 0);
+
+    // Not even the first frame fits.
+    let mut staging = [0u8; 46];
+    let mut writer = PcmWriterBuilder::new(2, 48000).build(&mut staging).unwrap();
+    assert_eq!(writer.append_frames(&[0.0, 0.0]).unwrap(), 0);
+    assert_eq!(writer.frames_written(), 0);
+    assert_eq!(writer.flush().len(), 44);
+    assert_eq!(writer.append_frames(&[0.0, 0.0, 0.5, 0.5]).unwrap(), 2);
+}
+
+#[test]
+fn wav_writer_recover_before_finalize() {
+    let lenient = ReaderOptions {
+        mode: ParseMode::Lenient,
+        ..Default::default()
+    };
+    for (audio_format, bit_depth) in [
+        (AudioFormat::LinearPcmLe, 16),
+        (AudioFormat::IeeeFloatLe, 32),
+    ] {
+        let mut buffer = [0u8; 1024];
+        let mut writer = PcmWriterBuilder::new(2, 48000)
+            .with_format(audio_format.clone(), bit_depth)
+            .build(&mut buffer)
+            .unwrap();
+        let frames: Vec<f32> = SINEWAVE[..200].to_vec();
+        assert_eq!(writer.append_frames(&frames).unwrap(), 100);
+        // Cut off before finalize, e.g. by a power loss while recording.
+        let file = writer.flush();
+
+        let reader = PcmReader::<16>::new_with_options(file, lenient).unwrap();
+        assert_eq!(reader.specs().audio_format, audio_format);
+        assert_eq!(reader.specs().num_samples, 100);
+        for (i, s) in frames.iter().enumerate() {
+            let value = reader.read_sample(i as u16 % 2, i as u32 / 2).unwrap();
+            assert_relative_eq!(value, *s, epsilon = 1.0 / 32768.0);
+        }
+    }
+}
+
+/// Returns the payload of the first chunk with the given ID in a RIFF WAVE file.
+/// Odd-sized chunks are followed by a pad byte.
+fn find_riff_chunk<'a>(wav: &'a [u8], id: &[u8; 4]) -> Option<&'a [u8]> {
+    let mut input = &wav[12..];
+    while input.len() >= 8 {
+        let size = u32::from_le_bytes(input[4..8].try_into().unwrap()) as usize;
+        let payload = &input[8..8 + size];
+        if &input[0..4] == id {
+            return Some(payload);
+        }
+        input = &input[(8 + size + size % 2).min(input.len())..];
+    }
+    None
+}
+
+#[test]
+fn wav_writer_smpl_and_cue() {
+    use pacmog::metadata::{CuePoint, LoopType, SampleLoop};
+
+    let cue_points = [0u32, 1000, 2999];
+    let mut buffer = vec![0u8; 100 + SINEWAVE.len() * 3 + 200];
+    let mut writer = PcmWriterBuilder::new(1, 48000)
+        .with_format(AudioFormat::LinearPcmLe, 24)
+        .with_loop(109, 2289)
+        .with_cue_points(&cue_points)
+        .build(&mut buffer)
+        .unwrap();
+    // An odd number of 24bit frames makes the data chunk odd-sized.
+    for s in SINEWAVE.iter().take(2999) {
+        writer.write_frame(&[*s]).unwrap();
+    }
+    writer.finalize().unwrap();
+    let wav = writer.flush();
+    assert_eq!(wav.len(), 44 + 2999 * 3 + 1 + 68 + 84);
+
+    let riff_size = u32::from_le_bytes(wav[4..8].try_into().unwrap()) as usize;
+    assert_eq!(riff_size, wav.len() - 8);
+
+    // Round trip through the smpl and cue readers
+    let reader = PcmReader::new(wav).unwrap();
+    let info = reader.sampler_info().unwrap();
+    assert_eq!(info.midi_unity_note, 60);
+    assert_eq!(
+        info.loops(),
+        [SampleLoop {
+            loop_type: LoopType::Forward,
+            start: 109,
+            end: 2289,
+            play_count: 0,
+        }]
+    );
+    let cues: Vec<CuePoint> = reader.cue_points().collect();
+    assert_eq!(cues.len(), cue_points.len());
+    for (i, (cue, position)) in cues.iter().zip(cue_points).enumerate() {
+        assert_eq!(cue.id, i as u32 + 1);
+        assert_eq!(cue.position, position);
+        assert_eq!(&cue.data_chunk_id, b"data");
+        assert_eq!(cue.sample_offset, position);
+    }
+    // A cue chunk shorter than its count
+    let mut cue = 3u32.to_le_bytes().to_vec();
+    cue.extend(cues[..2].iter().flat_map(|c| {
+        [c.id, c.position]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .chain(*b"data")
+            .chain([0; 8])
+            .chain(c.sample_offset.to_le_bytes())
+            .collect::<Vec<u8>>()
+    }));
+    let fmt = reader.chunk_data(b"fmt ").unwrap();
+    let file = riff_wave(&[(b"fmt ", fmt), (b"data", &[0; 6]), (b"cue ", &cue)]);
+    let truncated = PcmReader::new(&file).unwrap();
+    assert_eq!(truncated.cue_points().collect::<Vec<_>>(), cues[..2]);
+    let file = riff_wave(&[(b"fmt ", fmt), (b"data", &[0; 6])]);
+    assert_eq!(PcmReader::new(&file).unwrap().cue_points().count(), 0);
+
+    // Audio is not affected by the trailing chunks.
+    assert_eq!(reader.get_pcm_specs().num_samples, 2999);
+    for (i, s) in SINEWAVE.iter().take(2999).enumerate() {
+        assert_relative_eq!(reader.read_sample(0, i as u32).unwrap(), *s, epsilon = 1e-6);
+    }
+}
+
+#[test]
+fn wav_writer_invalid_loop() {
+    let mut buffer = [0u8; 256];
+    let mut writer = PcmWriterBuilder::new(1, 48000)
+        .with_loop(10, 100)
+        .build(&mut buffer)
+        .unwrap();
+    for _ in 0..50 {
+        writer.write_frame(&[0.0]).unwrap();
+    }
+    assert!(writer.finalize().is_err());
+}
+
+#[test]
+fn transcode_to_wav() {
+    // (input, tolerance)
+    let mut inputs: Vec<(&[u8], f32)> = vec![
+        (
+            include_bytes!("./resources/Sine440Hz_1ch_48000Hz_24.aif"),
+            1.0 / 32768.0,
+        ),
+        (
+            include_bytes!("./resources/Sine440Hz_1ch_48000Hz_32.wav"),
+            1.0 / 32768.0,
+        ),
+        (
+            include_bytes!("./resources/Sine440Hz_1ch_48000Hz_4bit_IMAADPCM.wav"),
+            0.3,
+        ),
+    ];
+    if cfg!(feature = "f64") {
+        inputs.push((
+            include_bytes!("./resources/Sine440Hz_1ch_48000Hz_64FP.aif"),
+            1.0 / 32768.0,
+        ));
+    }
+
+    let mut buffer = vec![0u8; 600_000];
+    for (data, epsilon) in inputs {
+        let reader = PcmReader::new(data).unwrap();
+        let spec = reader.get_pcm_specs();
+        let mut writer = PcmWriterBuilder::new(1, 48000).build(&mut buffer).unwrap();
+        let n = transcode(&reader, &mut writer, &TranscodeOptions::default()).unwrap();
+        assert_eq!(n, spec.num_samples);
+        writer.finalize().unwrap();
+
+        let output = PcmReader::new(writer.flush()).unwrap();
+        let output_spec = output.get_pcm_specs();
+        assert_eq!(output_spec.audio_format, AudioFormat::LinearPcmLe);
+        assert_eq!(output_spec.bit_depth, 16);
+        assert_eq!(output_spec.num_samples, spec.num_samples);
+        for (i, s) in SINEWAVE.iter().enumerate() {
+            assert_relative_eq!(
+                output.read_sample(0, i as u32).unwrap(),
+                *s,
+                epsilon = epsilon
+            );
+        }
+    }
+}
+
+#[test]
+fn transcode_streaming_matches_in_memory() {
+    let data = include_bytes!("./resources/Sine440Hz_2ch_48000Hz_4bit_IMAADPCM.wav");
+    let reader = PcmReader::new(data).unwrap();
+    let opts = TranscodeOptions::default();
+
+    let mut buffer = vec![0u8; 2_000_000];
+    let mut writer = PcmWriterBuilder::new(2, 48000)
+        .with_format(AudioFormat::IeeeFloatLe, 32)
+        .build(&mut buffer)
+        .unwrap();
+    transcode(&reader, &mut writer, &opts).unwrap();
+    writer.finalize().unwrap();
+    let in_memory = writer.flush().to_vec();
+
+    let mut file = Vec::new();
+    let mut staging = [0u8; 1024];
+    let mut writer = PcmWriterBuilder::new(2, 48000)
+        .with_format(AudioFormat::IeeeFloatLe, 32)
+        .build(&mut staging)
+        .unwrap();
+    transcode_streaming(&reader, &mut writer, &opts, |bytes| {
+        file.extend_from_slice(bytes)
+    })
+    .unwrap();
+    let patch = writer.finalize().unwrap();
+    file.extend_from_slice(writer.flush());
+    patch.apply(&mut file).unwrap();
+    assert_eq!(file, in_memory);
+
+    // The number of channels must match.
+    let mut writer = PcmWriterBuilder::new(1, 48000).build(&mut staging).unwrap();
+    assert!(transcode(&reader, &mut writer, &opts).is_err());
+}
+
+#[test]
+fn transcode_dither() {
+    let data = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_24.wav");
+    let reader = PcmReader::new(data).unwrap();
+    let num_samples = reader.get_pcm_specs().num_samples;
+
+    let mut buffer = vec![0u8; 600_000];
+    let mut transcode_to_16bit = |opts: &TranscodeOptions| -> Vec<i16> {
+        let mut writer = PcmWriterBuilder::new(1, 48000).build(&mut buffer).unwrap();
+        transcode(&reader, &mut writer, opts).unwrap();
+        writer.finalize().unwrap();
+        let data = find_riff_chunk(writer.flush(), b"data").unwrap();
+        data.chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]))
+            .collect()
+    };
+
+    // Without dither, samples are rounded to the nearest integer.
+    let rounded = transcode_to_16bit(&TranscodeOptions::default());
+    for (i, v) in rounded.iter().enumerate() {
+        let expected = (reader.read_sample(0, i as u32).unwrap() * 32768.0).round();
+        assert_eq!(*v as f32, expected.clamp(-32768.0, 32767.0));
+    }
+
+    // TPDF dither adds zero-mean noise within +/-1 LSB.
+    let opts = TranscodeOptions {
+        dither: DitherMode::Tpdf,
+        dither_seed: 42,
+        ..Default::default()
+    };
+    let dithered = transcode_to_16bit(&opts);
+    assert_eq!(dithered.len(), num_samples as usize);
+    let mut sum = 0i64;
+    for (d, r) in dithered.iter().zip(rounded.iter()) {
+        let diff = *d as i64 - *r as i64;
+        assert!(diff.abs() <= 1);
+        sum += diff;
+    }
+    let mean = sum as f64 / num_samples as f64;
+    assert!(mean.abs() < 0.01);
+    assert_ne!(dithered, rounded);
+
+    // The same seed gives the same output.
+    assert_eq!(transcode_to_16bit(&opts), dithered);
+
+    // Without a reduction of the bit depth, the samples are copied as they are.
+    let data = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav");
+    let reader = PcmReader::new(data).unwrap();
+    let mut writer = PcmWriterBuilder::new(1, 48000).build(&mut buffer).unwrap();
+    transcode(&reader, &mut writer, &opts).unwrap();
+    writer.finalize().unwrap();
+    assert_eq!(
+        find_riff_chunk(writer.flush(), b"data").unwrap(),
+        reader.chunk_data(b"data").unwrap()
+    );
+}
+
+#[test]
+fn transcode_channel_map_and_merge() {
+    let data = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav");
+    let sine = PcmReader::new(data).unwrap();
+
+    // Short mono file of a ramp.
+    let mut short_buffer = vec![0u8; 4096];
+    let mut writer = PcmWriterBuilder::new(1, 48000)
+        .build(&mut short_buffer)
+        .unwrap();
+    for i in 0..1000 {
+        writer.write_frame(&[(i - 500) as f32 / 1024.0]).unwrap();
+    }
+    writer.finalize().unwrap();
+    let short_len = writer.flush().len();
+    let short = PcmReader::new(&short_buffer[..short_len]).unwrap();
+
+    // Merge two mono files into one stereo file, padding the shorter with silence.
+    let mut stereo_buffer = vec![0u8; 1_000_000];
+    let mut writer = PcmWriterBuilder::new(2, 48000)
+        .build(&mut stereo_buffer)
+        .unwrap();
+    let opts = TranscodeOptions::default();
+    let num_frames = transcode_merge(&[&sine, &short], &mut writer, &opts).unwrap();
+    assert_eq!(num_frames, 240000);
+    writer.finalize().unwrap();
+    let stereo_len = writer.flush().len();
+    let stereo = PcmReader::new(&stereo_buffer[..stereo_len]).unwrap();
+    assert_eq!(stereo.get_pcm_specs().num_channels, 2);
+    for i in 0..num_frames {
+        let expected_right = if i < 1000 {
+            short.read_sample(0, i).unwrap()
+        } else {
+            0.0
+        };
+        assert_eq!(
+            stereo.read_sample(0, i).unwrap(),
+            sine.read_sample(0, i).unwrap()
+        );
+        assert_eq!(stereo.read_sample(1, i).unwrap(), expected_right);
+    }
+
+    // Truncate to the shorter file, with the channels swapped.
+    let mut buffer = vec![0u8; 8192];
+    let mut writer = PcmWriterBuilder::new(2, 48000).build(&mut buffer).unwrap();
+    let opts = TranscodeOptions {
+        channel_map: &[1, 0],
+        length_mismatch: LengthMismatch::Truncate,
+        ..Default::default()
+    };
+    let num_frames = transcode_merge(&[&sine, &short], &mut writer, &opts).unwrap();
+    assert_eq!(num_frames, 1000);
+    writer.finalize().unwrap();
+    let len = writer.flush().len();
+    let swapped = PcmReader::new(&buffer[..len]).unwrap();
+    for i in 0..num_frames {
+        assert_eq!(
+            swapped.read_sample(0, i).unwrap(),
+            short.read_sample(0, i).unwrap()
+        );
+        assert_eq!(
+            swapped.read_sample(1, i).unwrap(),
+            sine.read_sample(0, i).unwrap()
+        );
+    }
+
+    // Extract the right channel of the stereo file into a mono file.
+    let mut buffer = vec![0u8; 500_000];
+    let mut writer = PcmWriterBuilder::new(1, 48000).build(&mut buffer).unwrap();
+    let opts = TranscodeOptions {
+        channel_map: &[1],
+        ..Default::default()
+    };
+    assert_eq!(transcode(&stereo, &mut writer, &opts).unwrap(), 240000);
+    writer.finalize().unwrap();
+    let len = writer.flush().len();
+    let right = PcmReader::new(&buffer[..len]).unwrap();
+    for i in 0..240000 {
+        assert_eq!(
+            right.read_sample(0, i).unwrap(),
+            stereo.read_sample(1, i).unwrap()
+        );
+    }
+
+    // Errors
+    let mut writer = PcmWriterBuilder::new(1, 48000).build(&mut buffer).unwrap();
+    let opts = TranscodeOptions {
+        channel_map: &[2],
+        ..Default::default()
+    };
+    assert!(matches!(
+        transcode(&stereo, &mut writer, &opts),
+        Err(TranscodeError::InvalidChannelMap)
+    ));
+    assert!(matches!(
+        transcode(&stereo, &mut writer, &TranscodeOptions::default()),
+        Err(TranscodeError::ChannelMismatch)
+    ));
+}
+
+#[test]
+fn reader_reload_updates_geometry() {
+    // Stereo 32bit float WAV
+    let mut buffer = vec![0u8; 1024];
+    let mut writer = PcmWriterBuilder::new(2, 48000)
+        .with_format(AudioFormat::IeeeFloatLe, 32)
+        .build(&mut buffer)
+        .unwrap();
+    for i in 0..10 {
+        writer.write_frame(&[i as f32 / 10.0, -0.5]).unwrap();
+    }
+    writer.finalize().unwrap();
+    let len = writer.flush().len();
+    let stereo = &buffer[..len];
+
+    let mono = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav");
+    let mut reader = PcmReader::new(mono).unwrap();
+    let expected = reader.read_sample(0, 100).unwrap();
+
+    reader.reload(stereo).unwrap();
+    for i in 0..10 {
+        assert_eq!(reader.read_sample(0, i).unwrap(), i as f32 / 10.0);
+        assert_eq!(reader.read_sample(1, i).unwrap(), -0.5);
+    }
+    assert!(reader.read_sample(0, 10).is_err());
+
+    reader.reload(mono).unwrap();
+    assert_eq!(reader.read_sample(0, 100).unwrap(), expected);
+
+    // A failed reload leaves no stale geometry behind.
+    assert!(reader.reload(&stereo[..20]).is_err());
+    assert!(reader.read_sample(0, 0).is_err());
+}
+
+#[test]
+fn decode_block_f32() {
+    let mut files: Vec<&[u8]> = vec![
+        include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav"),
+        include_bytes!("./resources/Sine440Hz_1ch_48000Hz_24.wav"),
+        include_bytes!("./resources/Sine440Hz_1ch_48000Hz_32.wav"),
+        include_bytes!("./resources/Sine440Hz_1ch_48000Hz_32FP.wav"),
+        include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.aif"),
+        include_bytes!("./resources/Sine440Hz_1ch_48000Hz_24.aif"),
+        include_bytes!("./resources/Sine440Hz_1ch_48000Hz_32FP.aif"),
+        include_bytes!("./resources/MLKDream.wav"),
+    ];
+    if cfg!(feature = "f64") {
+        files.push(include_bytes!("./resources/Sine440Hz_1ch_48000Hz_64FP.wav"));
+    }
+    for data in files {
+        let reader = PcmReader::new(data).unwrap();
+        let spec = reader.get_pcm_specs();
+        let num_channels = spec.num_channels as usize;
+        let mut buffer = [0f32; 1000];
+        let mut frame = 0;
+        // Irregular block sizes, including one which is not a multiple of the number of channels.
+        for size in [1usize, 999, 64, 257].iter().cycle() {
+            let n = reader
+                .decode_block_f32(frame, &mut buffer[..*size])
+                .unwrap();
+            if n == 0 {
+                break;
+            }
+            for (i, samples) in buffer[..n * num_channels].chunks(num_channels).enumerate() {
+                for (ch, sample) in samples.iter().enumerate() {
+                    let expected = reader.read_sample(ch as u16, frame + i as u32).unwrap();
+                    assert_eq!(*sample, expected);
+                }
+            }
+            frame += n as u32;
+        }
+        assert_eq!(frame, spec.num_samples);
+        assert!(reader.decode_block_f32(frame + 1, &mut buffer).is_err());
+    }
+
+    let data = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_4bit_IMAADPCM.wav");
+    let reader = PcmReader::new(data).unwrap();
+    assert!(reader.decode_block_f32(0, &mut [0f32; 16]).is_err());
+}
+
+/// Insert chunks right after the RIFF/FORM header and fix up the header size.
+fn insert_chunks(file: &[u8], id: &[u8; 4], count: usize, big_endian: bool) -> Vec<u8> {
+    let mut out = file[..12].to_vec();
+    for i in 0..count {
+        let payload = [i as u8; 6];
+        out.extend_from_slice(id);
+        if big_endian {
+            out.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        } else {
+            out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        }
+        out.extend_from_slice(&payload);
+    }
+    out.extend_from_slice(&file[12..]);
+    let size = out.len() as u32 - 8;
+    if big_endian {
+        out[4..8].copy_from_slice(&size.to_be_bytes());
+    } else {
+        out[4..8].copy_from_slice(&size.to_le_bytes());
+    }
+    out
+}
+
+#[test]
+fn many_chunks() {
+    let wav = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav");
+    let expected = PcmReader::new(wav).unwrap();
+    let wav = insert_chunks(wav, b"JUNK", 40, false);
+    let reader = PcmReader::new(&wav).unwrap();
+    assert_eq!(reader.get_pcm_specs().num_samples, 240000);
+    assert_eq!(
+        reader.read_sample(0, 1000).unwrap(),
+        expected.read_sample(0, 1000).unwrap()
+    );
+
+    let aiff = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_24.aif");
+    let expected = PcmReader::new(aiff).unwrap();
+    let aiff = insert_chunks(aiff, b"ABCD", 40, true);
+    let reader = PcmReader::new(&aiff).unwrap();
+    assert_eq!(reader.get_pcm_specs().num_samples, 240000);
+    assert_eq!(
+        reader.read_sample(0, 1000).unwrap(),
+        expected.read_sample(0, 1000).unwrap()
+    );
+
+    // Wave64 chunks are named by GUIDs, which share the tail of the fmt chunk's.
+    let w64 = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.w64");
+    let expected = PcmReader::new(w64).unwrap();
+    let mut file = w64[..40].to_vec();
+    for _ in 0..40 {
+        file.extend_from_slice(b"abcd");
+        file.extend_from_slice(&w64[44..56]);
+        file.extend_from_slice(&32u64.to_le_bytes());
+        file.extend_from_slice(&[0; 8]);
+    }
+    file.extend_from_slice(&w64[40..]);
+    let size = file.len() as u64;
+    file[16..24].copy_from_slice(&size.to_le_bytes());
+    let reader = PcmReader::new(&file).unwrap();
+    assert_eq!(reader.specs().num_samples, expected.specs().num_samples);
+    assert_eq!(
+        reader.read_sample(0, 100).unwrap(),
+        expected.read_sample(0, 100).unwrap()
+    );
+
+    let caf = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.caf");
+    let expected = PcmReader::new(caf).unwrap();
+    let mut file = caf[..8].to_vec();
+    for _ in 0..40 {
+        file.extend_from_slice(b"free");
+        file.extend_from_slice(&6i64.to_be_bytes());
+        file.extend_from_slice(&[0; 6]);
+    }
+    file.extend_from_slice(&caf[8..]);
+    let reader = PcmReader::new(&file).unwrap();
+    assert_eq!(reader.specs().num_samples, expected.specs().num_samples);
+    assert_eq!(
+        reader.read_sample(0, 100).unwrap(),
+        expected.read_sample(0, 100).unwrap()
+    );
+}
+
+#[test]
+fn chunk_table() {
+    let wav = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav");
+    let wav = insert_chunks(wav, b"iXML", 1, false);
+    let reader = PcmReader::new(&wav).unwrap();
+    let ids: Vec<[u8; 4]> = reader.chunks().iter().map(|c| c.id).collect();
+    assert_eq!(ids, [*b"iXML", *b"fmt ", *b"junk", *b"data"]);
+
+    let ixml = reader.chunks()[0];
+    assert_eq!(ixml.offset, 20);
+    assert_eq!(ixml.size, 6);
+    assert_eq!(reader.chunk_data(b"iXML").unwrap(), &[0u8; 6]);
+    assert_eq!(reader.chunk_data(b"fmt ").unwrap().len(), 16);
+    assert_eq!(reader.chunk_data(b"data").unwrap().len(), 240000 * 2);
+    assert!(reader.chunk_data(b"bext").is_none());
+
+    // Chunks beyond the table are skipped but do not prevent reading.
+    let wav = insert_chunks(wav.as_slice(), b"JUNK", 20, false);
+    let reader = PcmReader::new(&wav).unwrap();
+    if cfg!(feature = "alloc") {
+        assert_eq!(reader.chunks().len(), 24);
+    } else {
+        assert_eq!(reader.chunks().len(), pacmog::MAX_NUM_CHUNKS);
+        assert!(reader.chunk_data(b"data").is_none());
+    }
+    assert_eq!(reader.get_pcm_specs().num_samples, 240000);
+
+    let aiff = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_24.aif");
+    let reader = PcmReader::new(aiff).unwrap();
+    assert!(reader.chunks().iter().any(|c| &c.id == b"COMM"));
+    assert!(reader.chunk_data(b"SSND").is_some());
+}
+
+#[test]
+fn chunk_capacity() {
+    let wav = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav");
+    let expected = PcmReader::new(wav).unwrap();
+    // fmt, junk and data of the fixture, plus 17 chunks.
+    let wav = insert_chunks(wav, b"JUNK", 17, false);
+
+    let reader = PcmReader::<32>::new_with_chunk_capacity(&wav).unwrap();
+    assert_eq!(reader.chunks().len(), 20);
+    assert_eq!(reader.chunk_data(b"data").unwrap().len(), 240000 * 2);
+    let reader = PcmReader::new(&wav).unwrap();
+    let reader_8 = PcmReader::<8>::new_with_chunk_capacity(&wav).unwrap();
+    if cfg!(feature = "alloc") {
+        assert_eq!(reader.chunks().len(), 20);
+        assert_eq!(reader_8.chunks().len(), 20);
+    } else {
+        assert_eq!(reader.chunks().len(), pacmog::MAX_NUM_CHUNKS);
+        assert!(reader.chunk_data(b"data").is_none());
+        assert_eq!(reader_8.chunks().len(), 8);
+    }
+    let reader = reader_8;
+    let mut player = PcmPlayer::new(reader);
+    let mut frame = [0f32; 1];
+    player.get_next_frame(&mut frame).unwrap();
+    assert_eq!(frame[0], expected.read_sample(0, 0).unwrap());
+
+    let data = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_4bit_IMAADPCM.wav");
+    let reader: PcmReader<64> = PcmReader::new_with_chunk_capacity(data).unwrap();
+    let mut buffer = vec![0u8; 600_000];
+    let mut writer = PcmWriterBuilder::new(1, 48000).build(&mut buffer).unwrap();
+    let n = transcode(&reader, &mut writer, &TranscodeOptions::default()).unwrap();
+    assert_eq!(n, reader.specs().num_samples);
+}
+
+/// Build a RIFF WAVE file from chunks. Odd-sized chunks get a pad byte.
+fn riff_wave(chunks: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+    let mut file = b"RIFF\0\0\0\0WAVE".to_vec();
+    for (id, payload) in chunks {
+        file.extend_from_slice(*id);
+        file.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        file.extend_from_slice(payload);
+        if payload.len() % 2 == 1 {
+            file.push(0);
+        }
+    }
+    let riff_size = file.len() as u32 - 8;
+    file[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    file
+}
+
+/// Build a FORM file of the form type (AIFF or AIFC) from chunks. Odd-sized chunks get a pad byte.
+fn aiff_form(form_type: &[u8; 4], chunks: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+    let mut file = b"FORM\0\0\0\0".to_vec();
+    file.extend_from_slice(form_type);
+    for (id, payload) in chunks {
+        file.extend_from_slice(*id);
+        file.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        file.extend_from_slice(payload);
+        if payload.len() % 2 == 1 {
+            file.push(0);
+        }
+    }
+    let form_size = file.len() as u32 - 8;
+    file[4..8].copy_from_slice(&form_size.to_be_bytes());
+    file
+}
+
+#[test]
+fn ima_adpcm_truncated_final_block() {
+    let data = include_bytes!("./resources/Sine440Hz_2ch_48000Hz_4bit_IMAADPCM.wav");
+    let reader = PcmReader::new(data).unwrap();
+    let fmt = reader.chunk_data(b"fmt ").unwrap();
+    let samples = reader.chunk_data(b"data").unwrap();
+    let block_align = u16::from_le_bytes([fmt[12], fmt[13]]) as usize;
+    let num_blocks = samples.len() / block_align;
+    // The last block is cut in the middle of its data words.
+    let truncated = &samples[..(num_blocks - 1) * block_align + block_align / 2];
+    let wav = riff_wave(&[(b"fmt ", fmt), (b"data", truncated)]);
+
+    let mut player = ImaAdpcmPlayer::new(&wav).unwrap();
+    let num_samples = player.reader.get_pcm_specs().num_samples;
+    // The header and 127 data words of each channel are left in the last block.
+    assert_eq!(num_samples, (num_blocks as u32 - 1) * 2041 + 1 + 127 * 8);
+    let mut buffer = [I1F15::ZERO; 2];
+    for _ in 0..num_samples {
+        player.get_next_frame(&mut buffer).unwrap();
+    }
+    assert!(matches!(
+        player.get_next_frame(&mut buffer),
+        Err(ImaAdpcmError::FinishPlaying)
+    ));
+}
+
+#[test]
+fn ima_adpcm_short_final_block_fixture() {
+    // 10 blocks and a half
+    let data =
+        include_bytes!("./resources/Sine440Hz_1ch_48000Hz_4bit_IMAADPCM_short_final_block.wav");
+    let mut player = ImaAdpcmPlayer::new(data).unwrap();
+    assert_eq!(player.specs().num_samples, 10 * 2041 + 1 + 127 * 8);
+    let original = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_4bit_IMAADPCM.wav");
+    let mut expected_player = ImaAdpcmPlayer::new(original).unwrap();
+
+    let mut buffer = [I1F15::ZERO; 1];
+    let mut expected = [I1F15::ZERO; 1];
+    for _ in 0..player.specs().num_samples {
+        player.get_next_frame(&mut buffer).unwrap();
+        expected_player.get_next_frame(&mut expected).unwrap();
+        assert_eq!(buffer, expected);
+    }
+    assert!(matches!(
+        player.get_next_frame(&mut buffer),
+        Err(ImaAdpcmError::FinishPlaying)
+    ));
+}
+
+#[test]
+fn specs_accessor() {
+    let wav = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_24.wav");
+    let reader = PcmReader::new(wav).unwrap();
+    let specs = reader.specs();
+    let cloned = reader.get_pcm_specs();
+    assert_eq!(specs.num_samples, cloned.num_samples);
+    assert_eq!(specs.bit_depth, 24);
+
+    let player = PcmPlayer::new(reader.clone());
+    assert_eq!(player.specs().num_samples, 240000);
+
+    let data = include_bytes!("./resources/Sine440Hz_2ch_48000Hz_4bit_IMAADPCM.wav");
+    let player = ImaAdpcmPlayer::new(data).unwrap();
+    assert_eq!(player.specs().num_channels, 2);
+    assert_eq!(player.specs().audio_format, AudioFormat::ImaAdpcmLe);
+}
+
+#[test]
+fn decode_block_planar_f32() {
+    // 3ch 24bit WAV made from the mono sine wave
+    let data = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_24.aif");
+    let mono = PcmReader::new(data).unwrap();
+    let mut wav = vec![0u8; 2_000_000];
+    let mut writer = PcmWriterBuilder::new(3, 48000)
+        .with_format(AudioFormat::LinearPcmLe, 24)
+        .build(&mut wav)
+        .unwrap();
+    for i in 0..1000 {
+        let s = mono.read_sample(0, i).unwrap();
+        writer.write_frame(&[s, -s, s * 0.25]).unwrap();
+    }
+    writer.finalize().unwrap();
+    let len = writer.flush().len();
+    let reader = PcmReader::new(&wav[..len]).unwrap();
+
+    let mut left = [0f32; 300];
+    let mut right = [0f32; 300];
+    let mut center = [0f32; 200];
+    let mut frame = 0;
+    loop {
+        let mut out: [&mut [f32]; 3] = [&mut left, &mut right, &mut center];
+        let n = reader.decode_block_planar_f32(frame, &mut out).unwrap();
+        if n == 0 {
+            break;
+        }
+        // Limited by the shortest buffer
+        assert!(n <= 200);
+        for i in 0..n {
+            let f = frame + i as u32;
+            assert_eq!(left[i], reader.read_sample(0, f).unwrap());
+            assert_eq!(right[i], reader.read_sample(1, f).unwrap());
+            assert_eq!(center[i], reader.read_sample(2, f).unwrap());
+        }
+        frame += n as u32;
+    }
+    assert_eq!(frame, 1000);
+
+    let mut out: [&mut [f32]; 2] = [&mut left, &mut right];
+    assert!(reader.decode_block_planar_f32(0, &mut out).is_err());
+}
+
+#[test]
+fn player_fill_interleaved() {
+    let data = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_32FP.wav");
+    let reader = PcmReader::new(data).unwrap();
+    let mut expected_player = PcmPlayer::new(reader.clone());
+    let mut player = PcmPlayer::new(reader);
+    player.set_loop_playing(true);
+    expected_player.set_loop_playing(true);
+    player.set_position(239_900).unwrap();
+    expected_player.set_position(239_900).unwrap();
+
+    // Wraps around the end of the file.
+    let mut buffer = [0f32; 256];
+    for _ in 0..3 {
+        assert_eq!(player.fill_interleaved(&mut buffer).unwrap(), 256);
+        for sample in buffer {
+            let mut expected = [0f32];
+            expected_player.get_next_frame(&mut expected).unwrap();
+            assert_eq!(sample, expected[0]);
+        }
+    }
+
+    // Without loop playback, the last fill is short.
+    player.set_loop_playing(false);
+    player.set_position(239_900).unwrap();
+    assert_eq!(player.fill_interleaved(&mut buffer).unwrap(), 100);
+    assert!(player.fill_interleaved(&mut buffer).is_err());
+}
+
+#[test]
+fn player_refill_half_buffer() {
+    // 16bit samples are copied as they are.
+    let data = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav");
+    let reader = PcmReader::new(data).unwrap();
+    let samples = reader.chunk_data(b"data").unwrap();
+    let mut player = PcmPlayer::new(reader);
+    let mut half = [0i16; 256];
+    for block in 0..4 {
+        assert_eq!(player.refill_half_buffer(&mut half).unwrap(), 256);
+        for (i, s) in half.iter().enumerate() {
+            let offset = (block * 256 + i) * 2;
+            assert_eq!(
+                *s,
+                i16::from_le_bytes([samples[offset], samples[offset + 1]])
+            );
+        }
+    }
+
+    // Other bit depths are rounded to 16bit.
+    let data = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_24.aif");
+    let reader = PcmReader::new(data).unwrap();
+    let mut player = PcmPlayer::new(reader.clone());
+    player.set_position(239_800).unwrap();
+    assert_eq!(player.refill_half_buffer(&mut half).unwrap(), 200);
+    for (i, s) in half[..200].iter().enumerate() {
+        let expected = (reader.read_sample(0, 239_800 + i as u32).unwrap() * 32768.0)
+            .round()
+            .clamp(-32768.0, 32767.0);
+        assert_eq!(*s as f32, expected);
+    }
+    // The rest is silence.
+    assert!(half[200..].iter().all(|s| *s == 0));
+    half.fill(1);
+    assert_eq!(player.refill_half_buffer(&mut half).unwrap(), 0);
+    assert!(half.iter().all(|s| *s == 0));
+}
+
+#[test]
+fn player_fill_formatted() {
+    let data = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_24.wav");
+    let reader = PcmReader::new(data).unwrap();
+    let num_samples = reader.specs().num_samples;
+    let mut player = PcmPlayer::new(reader.clone());
+    let mut expected_player = PcmPlayer::new(reader);
+    player.set_position(num_samples - 1000).unwrap();
+    expected_player.set_position(num_samples - 1000).unwrap();
+
+    // Larger than the internal scratch buffer.
+    let mut slots = [0u32; 600];
+    let mut samples = [0f32; 600];
+    let mut expected = [0u32; 600];
+    for n in [600, 400] {
+        assert_eq!(
+            player
+                .fill_formatted(&mut slots, FrameFormat::Philips, 24)
+                .unwrap(),
+            n
+        );
+        expected_player.fill_interleaved(&mut samples).unwrap();
+        format_frames(&samples[..n], &mut expected, FrameFormat::Philips, 24);
+        assert_eq!(slots[..n], expected[..n]);
+    }
+    assert!(player
+        .fill_formatted(&mut slots, FrameFormat::Philips, 24)
+        .is_err());
+}
+
+#[test]
+fn player_reader_reloaded_with_shorter_file() {
+    let wav = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav");
+    let reader = PcmReader::new(wav).unwrap();
+    let fmt = reader.chunk_data(b"fmt ").unwrap();
+    let data = reader.chunk_data(b"data").unwrap();
+    let short = riff_wave(&[(b"fmt ", fmt), (b"data", &data[..2000])]);
+
+    let mut player = PcmPlayer::new(reader);
+    player.set_position(5000).unwrap();
+    player.reader.reload(&short).unwrap();
+    let mut out = [0f32; 1];
+    assert!(matches!(
+        player.get_next_frame(&mut out),
+        Err(PcmPlayerError::FinishPlaying)
+    ));
+    assert!(matches!(
+        player.fill_interleaved(&mut [0f32; 16]),
+        Err(PcmPlayerError::FinishPlaying)
+    ));
+    player.set_loop_playing(true);
+    player.get_next_frame(&mut out).unwrap();
+    assert_eq!(out[0], player.reader.read_sample(0, 0).unwrap());
+
+    let wav = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_4bit_IMAADPCM.wav");
+    let reader = PcmReader::new(wav).unwrap();
+    let fmt = reader.chunk_data(b"fmt ").unwrap();
+    let block_align = usize::from(u16::from_le_bytes([fmt[12], fmt[13]]));
+    let data = reader.chunk_data(b"data").unwrap();
+    let short = riff_wave(&[(b"fmt ", fmt), (b"data", &data[..block_align])]);
+    let mut player = ImaAdpcmPlayer::new(wav).unwrap();
+    let mut out = [I1F15::ZERO; 1];
+    for _ in 0..3000 {
+        player.get_next_frame(&mut out).unwrap();
+    }
+    player.reader.reload(&short).unwrap();
+    assert!(matches!(
+        player.get_next_frame(&mut out),
+        Err(ImaAdpcmError::FinishPlaying)
+    ));
+}
+
+/// fmt chunk payload of IMA-ADPCM.
+fn ima_adpcm_fmt(num_channels: u16, block_align: u16, samples_per_block: u16) -> Vec<u8> {
+    let mut fmt = Vec::new();
+    fmt.extend_from_slice(&0x11u16.to_le_bytes());
+    fmt.extend_from_slice(&num_channels.to_le_bytes());
+    fmt.extend_from_slice(&48000u32.to_le_bytes());
+    fmt.extend_from_slice(&24000u32.to_le_bytes());
+    fmt.extend_from_slice(&block_align.to_le_bytes());
+    fmt.extend_from_slice(&4u16.to_le_bytes());
+    fmt.extend_from_slice(&2u16.to_le_bytes());
+    fmt.extend_from_slice(&samples_per_block.to_le_bytes());
+    fmt
+}
+
+// Minimal inputs found by the fuzz targets. They must be rejected or played without panicking.
+#[test]
+fn fuzz_regressions_wav() {
+    // Unknown format tag
+    let fmt = [2, 0, 1, 0, 0x80, 0xBB, 0, 0, 0, 0, 0, 0, 2, 0, 16, 0];
+    assert!(PcmReader::new(&riff_wave(&[(b"fmt ", &fmt), (b"data", &[0; 4])])).is_err());
+    // No fmt chunk
+    assert!(PcmReader::new(&riff_wave(&[(b"data", &[0; 4])])).is_err());
+    // Linear PCM of 4bit and of 0 channels
+    let fmt = [1, 0, 1, 0, 0x80, 0xBB, 0, 0, 0x80, 0xBB, 0, 0, 1, 0, 4, 0];
+    assert!(PcmReader::new(&riff_wave(&[(b"fmt ", &fmt), (b"data", &[0; 4])])).is_err());
+    let fmt = [1, 0, 0, 0, 0x80, 0xBB, 0, 0, 0, 0, 0, 0, 2, 0, 16, 0];
+    assert!(PcmReader::new(&riff_wave(&[(b"fmt ", &fmt), (b"data", &[0; 4])])).is_err());
+    // IMA-ADPCM blocks smaller than their headers
+    for (num_channels, block_align) in [(2, 4), (1, 0)] {
+        let fmt = ima_adpcm_fmt(num_channels, block_align, 1);
+        let wav = riff_wave(&[(b"fmt ", &fmt), (b"data", &[0; 8])]);
+        assert!(PcmReader::new(&wav).is_err());
+        assert!(ImaAdpcmPlayer::new(&wav).is_err());
+    }
+}
+
+#[test]
+fn fuzz_regressions_aiff() {
+    // COMM chunk of 0 channels
+    let mut aiff = b"FORM\0\0\0\x30AIFFCOMM\0\0\0\x12\0\0\0\0\0\x01\0\x10".to_vec();
+    aiff.extend_from_slice(&[0x40, 0x0E, 0xBB, 0x80, 0, 0, 0, 0, 0, 0]);
+    aiff.extend_from_slice(b"SSND\0\0\0\x0A\0\0\0\0\0\0\0\0\0\0");
+    assert!(PcmReader::new(&aiff).is_err());
+    // The same file with 1 channel is valid.
+    aiff[21] = 1;
+    let reader = PcmReader::new(&aiff).unwrap();
+    assert_eq!(reader.read_sample(0, 0).unwrap(), 0.0);
+}
+
+#[test]
+fn fuzz_regressions_ima_adpcm() {
+    // Only mono and stereo are supported.
+    let fmt = ima_adpcm_fmt(3, 36, 17);
+    let wav = riff_wave(&[(b"fmt ", &fmt), (b"data", &[0; 36])]);
+    assert!(PcmReader::new(&wav).is_ok());
+    assert!(ImaAdpcmPlayer::new(&wav).is_err());
+
+    // Not IMA-ADPCM
+    let data = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav");
+    assert!(ImaAdpcmPlayer::new(data).is_err());
+
+    // Step index out of range in the block header
+    let fmt = ima_adpcm_fmt(1, 8, 9);
+    let wav = riff_wave(&[
+        (b"fmt ", &fmt),
+        (b"data", &[0, 0, 0x7F, 0, 0xFF, 0xFF, 0xFF, 0xFF]),
+    ]);
+    let mut player = ImaAdpcmPlayer::new(&wav).unwrap();
+    let mut buffer = [I1F15::ZERO; 1];
+    for _ in 0..9 {
+        player.get_next_frame(&mut buffer).unwrap();
+    }
+    assert!(player.get_next_frame(&mut buffer).is_err());
+
+    // Stereo blocks whose data is shorter than the declared samples per block
+    let fmt = ima_adpcm_fmt(2, 12, 17);
+    let wav = riff_wave(&[(b"fmt ", &fmt), (b"data", &[0; 12])]);
+    if let Ok(mut player) = ImaAdpcmPlayer::new(&wav) {
+        let mut buffer = [I1F15::ZERO; 2];
+        while player.get_next_frame(&mut buffer).is_ok() {}
+    }
+}
+
+#[test]
+fn aifc_unknown_compression_type() {
+    for compression_type in [b"ima4", b"MAC3", b"XXXX"] {
+        let mut aifc = b"FORM\0\0\0\x34AIFCCOMM\0\0\0\x16\0\x01\0\0\0\x01\0\x10".to_vec();
+        aifc.extend_from_slice(&[0x40, 0x0E, 0xBB, 0x80, 0, 0, 0, 0, 0, 0]);
+        aifc.extend_from_slice(compression_type);
+        aifc.extend_from_slice(b"SSND\0\0\0\x0A\0\0\0\0\0\0\0\0\0\0");
+        assert!(matches!(
+            PcmReader::new(&aifc),
+            Err(PcmReaderError::UnsupportedAudioFormat)
+        ));
+        // The same file is read as uncompressed.
+        aifc[38..42].copy_from_slice(b"NONE");
+        assert!(PcmReader::new(&aifc).is_ok());
+    }
+}
+
+#[test]
+fn wav_trailing_chunks() {
+    let fmt = [1, 0, 1, 0, 0x80, 0xBB, 0, 0, 0, 0x77, 1, 0, 2, 0, 16, 0];
+    let samples = [0x00, 0x40, 0x00, 0xC0];
+    let list = b"INFOISFT\x04\0\0\0test";
+    // Chunks after the data chunk, including a broken one at the end of the file
+    let mut wav = riff_wave(&[(b"fmt ", &fmt), (b"data", &samples), (b"LIST", list)]);
+    let lenient = ReaderOptions {
+        mode: ParseMode::Lenient,
+        ..Default::default()
+    };
+    for tail in [&b""[..], b"id3 \xFF\0\0\0", b"JU"] {
+        let mut wav = wav.clone();
+        wav.extend_from_slice(tail);
+        let riff_size = wav.len() as u32 - 8;
+        wav[4..8].copy_from_slice(&riff_size.to_le_bytes());
+        let reader = match PcmReader::new(&wav) {
+            Ok(reader) => reader,
+            Err(PcmReaderError::ChunkSizeExceedsFile {
+                id,
+                declared,
+                available,
+            }) => {
+                assert_eq!((&id, declared, available), (b"id3 ", 255, 0));
+                PcmReader::<16>::new_with_options(&wav, lenient).unwrap()
+            }
+            Err(e) => panic!("{e:?}"),
+        };
+        assert_eq!(reader.specs().num_samples, 2);
+        assert_eq!(reader.read_sample(0, 1).unwrap(), -0.5);
+        assert_eq!(reader.chunk_data(b"LIST").unwrap(), list);
+    }
+    // EXTENSIBLE format of the same samples
+    let mut extensible = fmt.to_vec();
+    extensible[..2].copy_from_slice(&0xFFFEu16.to_le_bytes());
+    extensible.extend_from_slice(&[22, 0, 16, 0, 4, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0x10, 0]);
+    extensible.extend_from_slice(&[0x80, 0, 0, 0xAA, 0, 0x38, 0x9B, 0x71]);
+    wav = riff_wave(&[(b"fmt ", &extensible), (b"data", &samples)]);
+    let reader = PcmReader::new(&wav).unwrap();
+    assert_eq!(reader.specs().audio_format, AudioFormat::LinearPcmLe);
+    assert_eq!(reader.read_sample(0, 0).unwrap(), 0.5);
+}
+
+#[test]
+fn extensible_valid_bits() {
+    // 1ch 48kHz, 24bit in 32bit containers
+    let mut fmt = vec![
+        0xFE, 0xFF, 1, 0, 0x80, 0xBB, 0, 0, 0, 0xEE, 0x02, 0, 4, 0, 32, 0,
+    ];
+    fmt.extend_from_slice(&[22, 0, 24, 0, 4, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0x10, 0]);
+    fmt.extend_from_slice(&[0x80, 0, 0, 0xAA, 0, 0x38, 0x9B, 0x71]);
+    let samples = [0, 0x56, 0x34, 0x12, 0, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0xC0];
+    let wav = riff_wave(&[(b"fmt ", &fmt), (b"data", &samples)]);
+    let reader = PcmReader::new(&wav).unwrap();
+    let specs = reader.specs();
+    assert_eq!(specs.bit_depth, 24);
+    assert_eq!(specs.container_bytes_per_sample, 4);
+    assert_eq!(specs.num_samples, 3);
+    let exact: Vec<i32> = (0..3)
+        .map(|i| reader.read_sample_exact(0, i).unwrap())
+        .collect();
+    assert_eq!(exact, [0x123456, -1, -0x400000]);
+    assert_eq!(reader.read_sample(0, 2).unwrap(), -0.5);
+
+    // wValidBitsPerSample 0 or wider than the container is not used.
+    for valid_bits in [0, 40] {
+        let mut fmt = fmt.clone();
+        fmt[18..20].copy_from_slice(&u16::to_le_bytes(valid_bits));
+        let wav = riff_wave(&[(b"fmt ", &fmt), (b"data", &samples)]);
+        let reader = PcmReader::new(&wav).unwrap();
+        assert_eq!(reader.specs().bit_depth, 32);
+        assert_eq!(reader.read_sample_exact(0, 0).unwrap(), 0x12345600);
+    }
+}
+
+/// Copy the file to a position `offset` bytes past an 8-byte boundary of the returned buffer, as in memory-mapped flash.
+/// Returns the buffer and the range of the copy.
+fn misaligned_copy(file: &[u8], offset: usize) -> (Vec<u8>, Range<usize>) {
+    let mut buffer = vec![0u8; file.len() + 16];
+    let start = buffer.as_ptr().align_offset(8) + offset;
+    buffer[start..start + file.len()].copy_from_slice(file);
+    (buffer, start..start + file.len())
+}
+
+#[test]
+fn decode_misaligned_fixtures() {
+    let files: &[&[u8]] = &[
+        include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav"),
+        include_bytes!("./resources/Sine440Hz_1ch_48000Hz_24.wav"),
+        include_bytes!("./resources/Sine440Hz_1ch_48000Hz_32.wav"),
+        include_bytes!("./resources/Sine440Hz_1ch_48000Hz_32FP.wav"),
+        #[cfg(feature = "f64")]
+        include_bytes!("./resources/Sine440Hz_1ch_48000Hz_64FP.wav"),
+        include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.aif"),
+        include_bytes!("./resources/Sine440Hz_1ch_48000Hz_24.aif"),
+        include_bytes!("./resources/Sine440Hz_1ch_48000Hz_32.aif"),
+        include_bytes!("./resources/Sine440Hz_1ch_48000Hz_32FP.aif"),
+        #[cfg(feature = "f64")]
+        include_bytes!("./resources/Sine440Hz_1ch_48000Hz_64FP.aif"),
+    ];
+    for file in files {
+        let reader = PcmReader::new(file).unwrap();
+        let mut expected = [0f32; 1000];
+        reader.decode_block_f32(0, &mut expected).unwrap();
+
+        for offset in 1..8 {
+            let (buffer, range) = misaligned_copy(file, offset);
+            let copy = &buffer[range];
+            assert_eq!(copy.as_ptr() as usize % 8, offset);
+            let reader = PcmReader::new(copy).unwrap();
+
+            let mut out = [0f32; 1000];
+            assert_eq!(reader.decode_block_f32(0, &mut out).unwrap(), 1000);
+            assert_eq!(out, expected);
+            let mut planar = [0f32; 1000];
+            reader
+                .decode_block_planar_f32(0, &mut [&mut planar])
+                .unwrap();
+            assert_eq!(planar, expected);
+            for (i, expected) in expected.iter().enumerate().step_by(7) {
+                assert_eq!(reader.read_sample(0, i as u32).unwrap(), *expected);
+            }
+        }
+    }
+
+    let files: [&[u8]; 2] = [
+        include_bytes!("./resources/Sine440Hz_1ch_48000Hz_4bit_IMAADPCM.wav"),
+        include_bytes!("./resources/Sine440Hz_2ch_48000Hz_4bit_IMAADPCM.wav"),
+    ];
+    for file in files {
+        let mut player = ImaAdpcmPlayer::new(file).unwrap();
+        let mut expected = vec![[I1F15::ZERO; 2]; 3000];
+        for frame in &mut expected {
+            player.get_next_frame(frame).unwrap();
+        }
+
+        for offset in 1..8 {
+            let (buffer, range) = misaligned_copy(file, offset);
+            let mut player = ImaAdpcmPlayer::new(&buffer[range]).unwrap();
+            let mut frame = [I1F15::ZERO; 2];
+            for expected in &expected {
+                player.get_next_frame(&mut frame).unwrap();
+                assert_eq!(frame, *expected);
+            }
+        }
+    }
+}
+
+#[test]
+fn chunk_size_exceeds_file() {
+    let fmt = [1, 0, 1, 0, 0x80, 0xBB, 0, 0, 0, 0x77, 1, 0, 2, 0, 16, 0];
+    let samples = [0x00, 0x40, 0x00, 0xC0];
+    let lenient = ReaderOptions {
+        mode: ParseMode::Lenient,
+        ..Default::default()
+    };
+
+    // A LIST chunk declaring 0xFFFFFF bytes before the data chunk
+    let mut wav = riff_wave(&[(b"fmt ", &fmt), (b"LIST", b"INFO"), (b"data", &samples)]);
+    let list = wav.windows(4).position(|w| w == b"LIST").unwrap();
+    wav[list + 4..list + 8].copy_from_slice(&0xFFFFFFu32.to_le_bytes());
+    let available = (wav.len() - list - 8) as u32;
+    assert!(matches!(
+        PcmReader::new(&wav),
+        Err(PcmReaderError::ChunkSizeExceedsFile { id, declared: 0xFFFFFF, available: a })
+            if &id == b"LIST" && a == available
+    ));
+    // The LIST chunk is skipped up to the data chunk.
+    let reader = PcmReader::<16>::new_with_options(&wav, lenient).unwrap();
+    assert_eq!(reader.specs().num_samples, 2);
+    assert!(reader.chunk_data(b"LIST").is_none());
+
+    // A LIST chunk at the end is clamped, because no chunk follows it.
+    let mut wav = riff_wave(&[(b"fmt ", &fmt), (b"data", &samples), (b"LIST", b"INFO")]);
+    let list = wav.windows(4).position(|w| w == b"LIST").unwrap();
+    wav[list + 4..list + 8].copy_from_slice(&0xFFFFFFu32.to_le_bytes());
+    let reader = PcmReader::<16>::new_with_options(&wav, lenient).unwrap();
+    assert_eq!(reader.specs().num_samples, 2);
+    assert_eq!(reader.chunk_data(b"LIST").unwrap(), b"INFO");
+
+    // A data chunk cut off by the end of the file
+    let wav = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav");
+    let mut truncated = wav[..1104].to_vec();
+    truncated[4..8].copy_from_slice(&1096u32.to_le_bytes());
+    assert!(matches!(
+        PcmReader::new(&truncated),
+        Err(PcmReaderError::ChunkSizeExceedsFile {
+            id: [b'd', b'a', b't', b'a'],
+            declared: 480000,
+            available: 1000
+        })
+    ));
+    let reader = PcmReader::<16>::new_with_options(&truncated, lenient).unwrap();
+    assert_eq!(reader.specs().num_samples, 500);
+    let original = PcmReader::new(wav).unwrap();
+    assert_eq!(
+        reader.read_sample(0, 499).unwrap(),
+        original.read_sample(0, 499).unwrap()
+    );
+
+    // The SSND chunk of AIFF
+    let aiff = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.aif");
+    let ssnd = aiff.windows(4).position(|w| w == b"SSND").unwrap();
+    let mut truncated = aiff[..ssnd + 116].to_vec();
+    let form_size = truncated.len() as u32 - 8;
+    truncated[4..8].copy_from_slice(&form_size.to_be_bytes());
+    assert!(matches!(
+        PcmReader::new(&truncated),
+        Err(PcmReaderError::ChunkSizeExceedsFile { id, available: 108, .. }) if &id == b"SSND"
+    ));
+    let reader = PcmReader::<16>::new_with_options(&truncated, lenient).unwrap();
+    assert!(reader.read_sample(0, 0).is_ok());
+}
+
+#[test]
+fn checked_sample_counts() {
+    let data = (b"data", &[0u8; 8][..]);
+    // 0 channels
+    let fmt = [1, 0, 0, 0, 0x80, 0xBB, 0, 0, 0, 0, 0, 0, 2, 0, 16, 0];
+    assert!(matches!(
+        PcmReader::new(&riff_wave(&[(b"fmt ", &fmt), data])),
+        Err(PcmReaderError::InconsistentSpecs(SpecsField::NumChannels))
+    ));
+    // 4bit routed to the linear PCM path
+    let fmt = [1, 0, 1, 0, 0x80, 0xBB, 0, 0, 0x80, 0xBB, 0, 0, 1, 0, 4, 0];
+    assert!(matches!(
+        PcmReader::new(&riff_wave(&[(b"fmt ", &fmt), data])),
+        Err(PcmReaderError::UnsupportedBitDepth)
+    ));
+    // IMA-ADPCM with a block align of 0 is rejected by the fmt chunk.
+    let wav = riff_wave(&[(b"fmt ", &ima_adpcm_fmt(0, 0, 1)), data]);
+    assert!(PcmReader::new(&wav).is_err());
+
+    // Maximal sample indices
+    let fmt = [1, 0, 2, 0, 0x80, 0xBB, 0, 0, 0, 0xEE, 2, 0, 4, 0, 16, 0];
+    let wav = riff_wave(&[(b"fmt ", &fmt), data]);
+    let reader = PcmReader::new(&wav).unwrap();
+    assert_eq!(reader.specs().num_samples, 2);
+    assert_eq!(reader.decode_block_f32(2, &mut [0f32; 4]).unwrap(), 0);
+    for sample in [3, u32::MAX - 1, u32::MAX] {
+        assert!(matches!(
+            reader.read_sample(1, sample),
+            Err(PcmReaderError::InvalidSample)
+        ));
+        assert!(matches!(
+            reader.decode_block_f32(sample, &mut [0f32; 4]),
+            Err(PcmReaderError::InvalidSample)
+        ));
+    }
+    assert!(matches!(
+        reader.read_sample(u16::MAX, 0),
+        Err(PcmReaderError::InvalidChannel)
+    ));
+    let mut player = PcmPlayer::new(reader);
+    assert!(player.set_position(u32::MAX).is_err());
+}
+
+#[test]
+fn inconsistent_specs() {
+    let data = (b"data", &[0u8; 8][..]);
+    // 0 Hz
+    let fmt = [1, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 16, 0];
+    assert!(matches!(
+        PcmReader::new(&riff_wave(&[(b"fmt ", &fmt), data])),
+        Err(PcmReaderError::InconsistentSpecs(SpecsField::SampleRate))
+    ));
+    // 16bit IEEE float
+    let fmt = [3, 0, 1, 0, 0x80, 0xBB, 0, 0, 0, 0x77, 1, 0, 2, 0, 16, 0];
+    assert!(matches!(
+        PcmReader::new(&riff_wave(&[(b"fmt ", &fmt), data])),
+        Err(PcmReaderError::InconsistentSpecs(SpecsField::BitDepth))
+    ));
+
+    let aiff = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.aif");
+    let comm = aiff.windows(4).position(|w| w == b"COMM").unwrap();
+    // 0 channels
+    let mut file = aiff.to_vec();
+    file[comm + 8..comm + 10].copy_from_slice(&[0, 0]);
+    assert!(matches!(
+        PcmReader::new(&file),
+        Err(PcmReaderError::InconsistentSpecs(SpecsField::NumChannels))
+    ));
+    // 0 Hz (80-bit extended 0.0)
+    let mut file = aiff.to_vec();
+    file[comm + 16..comm + 26].fill(0);
+    assert!(matches!(
+        PcmReader::new(&file),
+        Err(PcmReaderError::InconsistentSpecs(SpecsField::SampleRate))
+    ));
+
+    // More frames in COMM than in SSND
+    let mut file = aiff.to_vec();
+    let num_frames = u32::from_be_bytes(file[comm + 10..comm + 14].try_into().unwrap());
+    file[comm + 10..comm + 14].copy_from_slice(&(num_frames + 1).to_be_bytes());
+    assert!(matches!(
+        PcmReader::new(&file),
+        Err(PcmReaderError::InconsistentSpecs(SpecsField::NumSamples))
+    ));
+    let options = ReaderOptions {
+        mode: ParseMode::Lenient,
+        ..Default::default()
+    };
+    let reader = PcmReader::<16>::new_with_options(&file, options).unwrap();
+    assert_eq!(reader.specs().num_samples, num_frames);
+    let mut player = PcmPlayer::new(reader);
+    let mut out = [0f32; 256];
+    let mut total = 0;
+    while let Ok(n) = player.fill_interleaved(&mut out) {
+        total += n as u32;
+    }
+    assert_eq!(total, num_frames);
+}
+
+#[test]
+fn empty_files() {
+    let wav = include_bytes!("./resources/Empty_1ch_48000Hz_16.wav");
+    let aiff = include_bytes!("./resources/Empty_1ch_48000Hz_16.aif");
+    for file in [&wav[..], &aiff[..]] {
+        let reader = PcmReader::new(file).unwrap();
+        assert_eq!(reader.specs().num_samples, 0);
+        assert_eq!(reader.specs().num_channels, 1);
+        assert!(matches!(
+            reader.read_sample(0, 0),
+            Err(PcmReaderError::InvalidSample)
+        ));
+        assert_eq!(reader.decode_block_f32(0, &mut [0f32; 4]).unwrap(), 0);
+
+        for loop_playing in [false, true] {
+            let mut player = PcmPlayer::new(PcmReader::new(file).unwrap());
+            player.set_loop_playing(loop_playing);
+            player.set_position(0).unwrap();
+            assert!(matches!(
+                player.set_position(1),
+                Err(PcmPlayerError::InvalidPosition)
+            ));
+            assert!(matches!(
+                player.get_next_frame(&mut [0f32; 1]),
+                Err(PcmPlayerError::FinishPlaying)
+            ));
+            assert!(matches!(
+                player.fill_interleaved(&mut [0f32; 4]),
+                Err(PcmPlayerError::FinishPlaying)
+            ));
+            let mut half = [1i16; 4];
+            assert_eq!(player.refill_half_buffer(&mut half).unwrap(), 0);
+            assert_eq!(half, [0; 4]);
+        }
+    }
+
+    // IMA-ADPCM without blocks
+    let wav = riff_wave(&[(b"fmt ", &ima_adpcm_fmt(1, 1024, 2041)), (b"data", &[])]);
+    let mut player = ImaAdpcmPlayer::new(&wav).unwrap();
+    assert_eq!(player.specs().num_samples, 0);
+    assert!(matches!(
+        player.get_next_frame(&mut [I1F15::ZERO; 1]),
+        Err(ImaAdpcmError::FinishPlaying)
+    ));
+    player.rewind();
+    assert!(matches!(
+        player.get_next_frame(&mut [I1F15::ZERO; 1]),
+        Err(ImaAdpcmError::FinishPlaying)
+    ));
+}
+
+#[test]
+fn fact_chunk_limits_num_samples() {
+    // 1000 samples of 32bit float followed by 1024 bytes of zero padding
+    let data = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_32FP_padded.wav");
+    let reader = PcmReader::new(data).unwrap();
+    assert_eq!(reader.specs().num_samples, 1000);
+    assert_eq!(
+        reader.specs().num_samples_source,
+        NumSamplesSource::FactChunk
+    );
+    let original =
+        PcmReader::new(include_bytes!("./resources/Sine440Hz_1ch_48000Hz_32FP.wav")).unwrap();
+    assert_eq!(
+        original.specs().num_samples_source,
+        NumSamplesSource::DataSize
+    );
+    let mut player = PcmPlayer::new(reader);
+    let mut buffer = [0f32; 2000];
+    assert_eq!(player.fill_interleaved(&mut buffer).unwrap(), 1000);
+    for (i, sample) in buffer[..1000].iter().enumerate() {
+        assert_eq!(*sample, original.read_sample(0, i as u32).unwrap());
+    }
+
+    // A fact chunk with more samples than the data holds is ignored.
+    let mut file = data.to_vec();
+    let fact = file.windows(4).position(|w| w == b"fact").unwrap();
+    file[fact + 8..fact + 12].copy_from_slice(&5000u32.to_le_bytes());
+    let reader = PcmReader::new(&file).unwrap();
+    assert_eq!(reader.specs().num_samples, 1256);
+    assert_eq!(
+        reader.specs().num_samples_source,
+        NumSamplesSource::DataSize
+    );
+
+    // IMA-ADPCM: the last block is not full.
+    let data = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_4bit_IMAADPCM.wav");
+    let reader = PcmReader::new(data).unwrap();
+    assert_eq!(reader.specs().num_samples, 240000);
+    assert_eq!(
+        reader.specs().num_samples_source,
+        NumSamplesSource::FactChunk
+    );
+
+    // The fact chunk of this file reports half of the samples, which is not in the last block.
+    let data = include_bytes!("./resources/Sine440Hz_2ch_48000Hz_4bit_IMAADPCM.wav");
+    let reader = PcmReader::new(data).unwrap();
+    assert_eq!(reader.specs().num_samples, 240838);
+    assert_eq!(
+        reader.specs().num_samples_source,
+        NumSamplesSource::DataSize
+    );
+
+    // AIFF counts the sample frames in COMM.
+    let aiff = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.aif");
+    let reader = PcmReader::new(aiff).unwrap();
+    assert_eq!(
+        reader.specs().num_samples_source,
+        NumSamplesSource::CommChunk
+    );
+}
+
+#[test]
+fn fmt_consistency() {
+    let lenient = ReaderOptions {
+        mode: ParseMode::Lenient,
+        ..Default::default()
+    };
+    let original =
+        PcmReader::new(include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav")).unwrap();
+
+    // Stereo 16bit declared as 8bit. nBlockAlign and nAvgBytesPerSec say 4 bytes per frame.
+    let data = include_bytes!("./resources/Sine440Hz_2ch_48000Hz_16_declared_8bit.wav");
+    assert!(matches!(
+        PcmReader::new(data),
+        Err(PcmReaderError::InconsistentSpecs(SpecsField::BlockAlign))
+    ));
+    let reader = PcmReader::<16>::new_with_options(data, lenient).unwrap();
+    assert_eq!(reader.specs().bit_depth, 16);
+    assert_eq!(reader.specs().num_channels, 2);
+    assert_eq!(reader.specs().num_samples, 1000);
+    for sample in 0..1000 {
+        let expected = original.read_sample(0, sample).unwrap();
+        assert_eq!(reader.read_sample(0, sample).unwrap(), expected);
+        assert_eq!(reader.read_sample(1, sample).unwrap(), expected);
+    }
+
+    // nAvgBytesPerSec of 44.1kHz. The bit depth matches nBlockAlign, so it is not corrected.
+    let data = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16_wrong_byte_rate.wav");
+    assert!(matches!(
+        PcmReader::new(data),
+        Err(PcmReaderError::InconsistentSpecs(SpecsField::ByteRate))
+    ));
+    let reader = PcmReader::<16>::new_with_options(data, lenient).unwrap();
+    assert_eq!(reader.specs().bit_depth, 16);
+    assert_eq!(reader.specs().num_samples, 1000);
+    assert_eq!(
+        reader.read_sample(0, 999).unwrap(),
+        original.read_sample(0, 999).unwrap()
+    );
+
+    // nBlockAlign disagrees with both, so nothing is corrected.
+    let mut file = data.to_vec();
+    let fmt = file.windows(4).position(|w| w == b"fmt ").unwrap();
+    file[fmt + 20..fmt + 22].copy_from_slice(&3u16.to_le_bytes());
+    assert!(matches!(
+        PcmReader::new(&file),
+        Err(PcmReaderError::InconsistentSpecs(SpecsField::BlockAlign))
+    ));
+    let reader = PcmReader::<16>::new_with_options(&file, lenient).unwrap();
+    assert_eq!(reader.specs().bit_depth, 16);
+}
+
+#[test]
+fn aiff_chunk_orderings() {
+    let original =
+        PcmReader::new(include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.aif")).unwrap();
+    let comm = original.chunk_data(b"COMM").unwrap();
+    let ssnd = original.chunk_data(b"SSND").unwrap();
+    // AIFF-C COMM with the compression type NONE and its name as a pstring
+    let mut comm_aifc = comm.to_vec();
+    comm_aifc.extend_from_slice(b"NONE\x0enot compressed\0");
+    let fver = 0xA280_5140u32.to_be_bytes();
+    // Text chunks of odd and even sizes
+    let name = (b"NAME", &b"Sine440"[..]);
+    let anno = (b"ANNO", &b"pacmog"[..]);
+    let many: Vec<(&[u8; 4], &[u8])> = vec![(b"AUTH", &b"x"[..]); 20];
+
+    for (form_type, comm) in [(b"AIFF", comm), (b"AIFC", &comm_aifc[..])] {
+        let local = [(b"FVER", &fver[..]), (b"COMM", comm), (b"SSND", ssnd)];
+        for order in [
+            [0, 1, 2],
+            [0, 2, 1],
+            [1, 0, 2],
+            [1, 2, 0],
+            [2, 0, 1],
+            [2, 1, 0],
+        ] {
+            let [a, b, c] = order.map(|i| local[i]);
+            let files = [
+                aiff_form(form_type, &[a, b, c]),
+                aiff_form(form_type, &[name, a, anno, b, name, c, anno]),
+                // More chunks than the chunk table holds before the last one
+                aiff_form(form_type, &[[a, b].as_slice(), &many, &[c]].concat()),
+            ];
+            for file in &files {
+                let reader = PcmReader::new(file).unwrap_or_else(|e| {
+                    panic!("{:?} {order:?}: {e}", std::str::from_utf8(form_type))
+                });
+                assert_eq!(
+                    format!("{:?}", reader.specs()),
+                    format!("{:?}", original.specs())
+                );
+                for sample in [0, 1, 1000, original.specs().num_samples - 1] {
+                    assert_eq!(
+                        reader.read_sample(0, sample).unwrap(),
+                        original.read_sample(0, sample).unwrap()
+                    );
+                }
+
+                let mut scratch = [0u8; 64];
+                let mut streaming = StreamingPlayer::new(file.as_slice(), &mut scratch).unwrap();
+                assert_eq!(
+                    format!("{:?}", streaming.specs()),
+                    format!("{:?}", original.specs())
+                );
+                let mut buffer = [0f32; 16];
+                assert_eq!(streaming.fill_interleaved(&mut buffer).unwrap(), 16);
+                for (i, sample) in buffer.iter().enumerate() {
+                    assert_eq!(*sample, original.read_sample(0, i as u32).unwrap());
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn duplicate_chunks() {
+    let lenient = ReaderOptions {
+        mode: ParseMode::Lenient,
+        ..Default::default()
+    };
+    let original =
+        PcmReader::new(include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav")).unwrap();
+    let fmt = (b"fmt ", original.chunk_data(b"fmt ").unwrap());
+    let data = (b"data", &original.chunk_data(b"data").unwrap()[..2000]);
+    // Stereo 24bit, and data of another length
+    let fmt2 = [1, 0, 2, 0, 0x80, 0xBB, 0, 0, 0, 0x65, 4, 0, 6, 0, 24, 0];
+    let fmt2 = (b"fmt ", &fmt2[..]);
+    let data2 = (b"data", &[0x7F; 600][..]);
+    for chunks in [
+        [fmt, fmt2, data],
+        [fmt, data, fmt2],
+        [fmt, data, data2],
+        [data, fmt, data2],
+        [data, data2, fmt],
+    ] {
+        let file = riff_wave(&chunks);
+        assert!(matches!(
+            PcmReader::new(&file),
+            Err(PcmReaderError::DuplicateChunk(id)) if [fmt.0, data.0].contains(&&id)
+        ));
+        let reader = PcmReader::<16>::new_with_options(&file, lenient).unwrap();
+        assert_eq!(reader.specs().num_channels, 1);
+        assert_eq!(reader.specs().bit_depth, 16);
+        assert_eq!(reader.specs().num_samples, 1000);
+        assert_eq!(
+            reader.read_sample(0, 999).unwrap(),
+            original.read_sample(0, 999).unwrap()
+        );
+        // The streaming player uses the first chunks as well.
+        let mut scratch = [0u8; 64];
+        let streaming = StreamingPlayer::new(file.as_slice(), &mut scratch).unwrap();
+        assert_eq!(streaming.specs().num_channels, 1);
+        assert_eq!(streaming.specs().num_samples, 1000);
+    }
+
+    let original =
+        PcmReader::new(include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.aif")).unwrap();
+    let comm = (b"COMM", original.chunk_data(b"COMM").unwrap());
+    let ssnd = (b"SSND", original.chunk_data(b"SSND").unwrap());
+    // Stereo 24bit of 100 frames, and SSND of another length
+    let mut comm2 = comm.1.to_vec();
+    comm2[..8].copy_from_slice(&[0, 2, 0, 0, 0, 100, 0, 24]);
+    let comm2 = (b"COMM", &comm2[..]);
+    let ssnd2 = (b"SSND", &[0; 608][..]);
+    for chunks in [
+        [comm, comm2, ssnd],
+        [comm, ssnd, comm2],
+        [comm, ssnd, ssnd2],
+        [ssnd, comm, ssnd2],
+        [ssnd, ssnd2, comm],
+    ] {
+        let file = aiff_form(b"AIFF", &chunks);
+        assert!(matches!(
+            PcmReader::new(&file),
+            Err(PcmReaderError::DuplicateChunk(id)) if [comm.0, ssnd.0].contains(&&id)
+        ));
+        let reader = PcmReader::<16>::new_with_options(&file, lenient).unwrap();
+        assert_eq!(
+            format!("{:?}", reader.specs()),
+            format!("{:?}", original.specs())
+        );
+        let last = original.specs().num_samples - 1;
+        assert_eq!(
+            reader.read_sample(0, last).unwrap(),
+            original.read_sample(0, last).unwrap()
+        );
+        let mut scratch = [0u8; 64];
+        let streaming = StreamingPlayer::new(file.as_slice(), &mut scratch).unwrap();
+        assert_eq!(
+            format!("{:?}", streaming.specs()),
+            format!("{:?}", original.specs())
+        );
+    }
+}
+
+#[test]
+fn calc_num_samples_errors() {
+    let lenient = ReaderOptions {
+        mode: ParseMode::Lenient,
+        ..Default::default()
+    };
+    let data = (b"data", &[0u8; 64][..]);
+    // IMA-ADPCM fmt chunk whose block align does not hold the headers of a stereo block
+    let fmt = ima_adpcm_fmt(2, 4, 1);
+    let wav = riff_wave(&[(b"fmt ", &fmt), data]);
+    assert!(matches!(
+        PcmReader::new(&wav),
+        Err(PcmReaderError::UnsupportedAudioFormat)
+    ));
+    assert!(PcmReader::<16>::new_with_options(&wav, lenient).is_err());
+    assert!(ImaAdpcmPlayer::new(&wav).is_err());
+
+    // Linear PCM of 0 bits. nBlockAlign and nAvgBytesPerSec agree, so the fields are consistent.
+    let fmt = [1, 0, 1, 0, 0x80, 0xBB, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    let wav = riff_wave(&[(b"fmt ", &fmt), data]);
+    assert!(matches!(
+        PcmReader::new(&wav),
+        Err(PcmReaderError::UnsupportedBitDepth)
+    ));
+    assert!(matches!(
+        PcmReader::<16>::new_with_options(&wav, lenient),
+        Err(PcmReaderError::UnsupportedBitDepth)
+    ));
+    let mut scratch = [0u8; 64];
+    assert!(StreamingPlayer::new(wav.as_slice(), &mut scratch).is_err());
+}
+
+#[test]
+fn trust_declared_size() {
+    let options = ReaderOptions {
+        trust_declared_size: true,
+        ..Default::default()
+    };
+    let files: [&[u8]; 2] = [
+        include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav"),
+        include_bytes!("./resources/Sine440Hz_1ch_48000Hz_24.aif"),
+    ];
+    for file in files {
+        let expected = PcmReader::new(file).unwrap();
+        for padding in [0x00, 0xFF] {
+            let mut partition = file.to_vec();
+            partition.resize(file.len() + 1000, padding);
+            assert!(matches!(
+                PcmReader::new(&partition),
+                Err(PcmReaderError::HeaderSizeMismatch)
+            ));
+            let reader = PcmReader::<16>::new_with_options(&partition, options).unwrap();
+            assert_eq!(
+                format!("{:?}", reader.specs()),
+                format!("{:?}", expected.specs())
+            );
+            assert_eq!(reader.chunks(), expected.chunks());
+            let last = expected.specs().num_samples - 1;
+            assert_eq!(
+                reader.read_sample(0, last).unwrap(),
+                expected.read_sample(0, last).unwrap()
+            );
+        }
+        // Exactly the size of the file
+        assert!(PcmReader::<16>::new_with_options(file, options).is_ok());
+        // The input must not be shorter than the declared size.
+        assert!(matches!(
+            PcmReader::<16>::new_with_options(&file[..file.len() - 1], options),
+            Err(PcmReaderError::TruncatedData { .. })
+        ));
+    }
+
+    // A header size which does not cover the form type
+    let mut wav = b"RIFF\0\0\0\0WAVE".to_vec();
+    wav.resize(64, 0xFF);
+    assert!(PcmReader::<16>::new_with_options(&wav, options).is_err());
+}
+
+/// FNV-1a of the samples as little-endian i32.
+fn fnv1a_i32(samples: impl Iterator<Item = i32>) -> u64 {
+    samples
+        .flat_map(i32::to_le_bytes)
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+        })
+}
+
+#[test]
+fn read_sample_exact() {
+    // Reference hash of the samples of the data and SSND chunks, computed with Python's struct module.
+    const HASH_32BIT: u64 = 0xf9ed_ce69_d993_0b65;
+    let files: [&[u8]; 2] = [
+        include_bytes!("./resources/Sine440Hz_1ch_48000Hz_32.wav"),
+        include_bytes!("./resources/Sine440Hz_1ch_48000Hz_32.aif"),
+    ];
+    for file in files {
+        let reader = PcmReader::new(file).unwrap();
+        let num_samples = reader.specs().num_samples;
+        assert_eq!(num_samples, 240000);
+        let samples = (0..num_samples).map(|i| reader.read_sample_exact(0, i).unwrap());
+        assert_eq!(fnv1a_i32(samples), HASH_32BIT);
+    }
+
+    // The same values as hound
+    let files: [&[u8]; 3] = [
+        include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav"),
+        include_bytes!("./resources/Sine440Hz_1ch_48000Hz_24.wav"),
+        include_bytes!("./resources/Sine440Hz_1ch_48000Hz_32.wav"),
+    ];
+    for file in files {
+        let reader = PcmReader::new(file).unwrap();
+        let expected = hound::WavReader::new(file).unwrap();
+        for (i, sample) in expected.into_samples::<i32>().enumerate() {
+            assert_eq!(
+                reader.read_sample_exact(0, i as u32).unwrap(),
+                sample.unwrap()
+            );
+        }
+    }
+
+    // Big-endian 24bit
+    let wav = PcmReader::new(include_bytes!("./resources/Sine440Hz_1ch_48000Hz_24.wav")).unwrap();
+    let aiff = PcmReader::new(include_bytes!("./resources/Sine440Hz_1ch_48000Hz_24.aif")).unwrap();
+    for i in 0..wav.specs().num_samples {
+        assert_eq!(
+            aiff.read_sample_exact(0, i).unwrap(),
+            wav.read_sample_exact(0, i).unwrap()
+        );
+    }
+
+    let reader =
+        PcmReader::new(include_bytes!("./resources/Sine440Hz_1ch_48000Hz_32.wav")).unwrap();
+    assert!(matches!(
+        reader.read_sample_exact(1, 0),
+        Err(PcmReaderError::InvalidChannel)
+    ));
+    assert!(matches!(
+        reader.read_sample_exact(0, 240000),
+        Err(PcmReaderError::InvalidSample)
+    ));
+    let float =
+        PcmReader::new(include_bytes!("./resources/Sine440Hz_1ch_48000Hz_32FP.wav")).unwrap();
+    assert!(matches!(
+        float.read_sample_exact(0, 0),
+        Err(PcmReaderError::UnsupportedAudioFormat)
+    ));
+}
+
+#[test]
+#[cfg(feature = "f64")]
+fn read_sample_f64() {
+    let files: [&[u8]; 4] = [
+        include_bytes!("./resources/Sine440Hz_1ch_48000Hz_32FP.wav"),
+        include_bytes!("./resources/Sine440Hz_1ch_48000Hz_32FP.aif"),
+        include_bytes!("./resources/Sine440Hz_1ch_48000Hz_64FP.wav"),
+        include_bytes!("./resources/Sine440Hz_1ch_48000Hz_64FP.aif"),
+    ];
+    for file in files {
+        let reader = PcmReader::new(file).unwrap();
+        for i in 0..reader.specs().num_samples {
+            let sample = reader.read_sample_f64(0, i).unwrap();
+            assert_eq!(sample as f32, reader.read_sample(0, i).unwrap());
+        }
+    }
+
+    // 64bit samples are not rounded to f32.
+    let values = [0.1f64, -1.0 / 3.0, 1.0 - f64::EPSILON];
+    let fmt = [3, 0, 1, 0, 0x80, 0xBB, 0, 0, 0, 0xDC, 0x05, 0, 8, 0, 64, 0];
+    let data: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+    let wav = riff_wave(&[(b"fmt ", &fmt), (b"data", &data)]);
+    let data: Vec<u8> = values.iter().flat_map(|v| v.to_be_bytes()).collect();
+    let mut ssnd = vec![0; 8];
+    ssnd.extend_from_slice(&data);
+    let comm = [
+        0, 1, 0, 0, 0, 3, 0, 64, 0x40, 0x0E, 0xBB, 0x80, 0, 0, 0, 0, 0, 0, b'f', b'l', b'6', b'4',
+        0, 0,
+    ];
+    let aiff = aiff_form(b"AIFC", &[(b"COMM", &comm), (b"SSND", &ssnd)]);
+    for file in [wav, aiff] {
+        let reader = PcmReader::new(&file).unwrap();
+        for (i, value) in values.iter().enumerate() {
+            assert_eq!(reader.read_sample_f64(0, i as u32).unwrap(), *value);
+        }
+    }
+
+    let reader =
+        PcmReader::new(include_bytes!("./resources/Sine440Hz_1ch_48000Hz_32.wav")).unwrap();
+    assert!(matches!(
+        reader.read_sample_f64(0, 0),
+        Err(PcmReaderError::UnsupportedAudioFormat)
+    ));
+}
+
+#[test]
+fn corrupt_chunk_recovery() {
+    let lenient = ReaderOptions {
+        mode: ParseMode::Lenient,
+        ..Default::default()
+    };
+    let original =
+        PcmReader::new(include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav")).unwrap();
+
+    // The size of the LIST chunk before the data chunk is broken.
+    let wav = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16_corrupt_LIST.wav");
+    assert!(matches!(
+        PcmReader::new(wav),
+        Err(PcmReaderError::ChunkSizeExceedsFile { id, .. }) if &id == b"LIST"
+    ));
+    let reader = PcmReader::<16>::new_with_options(wav, lenient).unwrap();
+    assert_eq!(reader.specs().num_samples, 1000);
+    for i in 0..1000 {
+        assert_eq!(
+            reader.read_sample(0, i).unwrap(),
+            original.read_sample(0, i).unwrap()
+        );
+    }
+    // The corrupt chunk is not recorded.
+    let ids: Vec<_> = reader.chunks().iter().map(|c| c.id).collect();
+    assert_eq!(ids, [*b"fmt ", *b"data"]);
+
+    // Bytes which are not a chunk between the fmt and data chunks
+    let fmt = (b"fmt ", original.chunk_data(b"fmt ").unwrap());
+    let data = (b"data", &original.chunk_data(b"data").unwrap()[..2000]);
+    let junk = (b"JUNK", &[0u8; 6][..]);
+    let mut file = riff_wave(&[fmt]);
+    file.extend_from_slice(&[0xFF; 12]);
+    file.extend_from_slice(&riff_wave(&[junk, data])[12..]);
+    let riff_size = file.len() as u32 - 8;
+    file[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    assert!(PcmReader::new(&file).is_err());
+    let reader = PcmReader::<16>::new_with_options(&file, lenient).unwrap();
+    assert_eq!(reader.specs().num_samples, 1000);
+
+    // Corrupt chunks are skipped up to 8 times.
+    for (num_corrupt, num_samples) in [(8, 1000), (9, 0)] {
+        let mut file = riff_wave(&[fmt]);
+        for _ in 0..num_corrupt {
+            file.extend_from_slice(&[0xFF; 4]);
+            file.extend_from_slice(&riff_wave(&[junk])[12..]);
+        }
+        file.extend_from_slice(&riff_wave(&[data])[12..]);
+        let riff_size = file.len() as u32 - 8;
+        file[4..8].copy_from_slice(&riff_size.to_le_bytes());
+        let reader = PcmReader::<16>::new_with_options(&file, lenient).unwrap();
+        assert_eq!(reader.specs().num_samples, num_samples);
+    }
+
+    // AIFF with a broken ANNO chunk before the SSND chunk
+    let original =
+        PcmReader::new(include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.aif")).unwrap();
+    let comm = (b"COMM", original.chunk_data(b"COMM").unwrap());
+    let ssnd = (b"SSND", original.chunk_data(b"SSND").unwrap());
+    let mut aiff = aiff_form(b"AIFF", &[comm, (b"ANNO", b"pacmog"), ssnd]);
+    let anno = aiff.windows(4).position(|w| w == b"ANNO").unwrap();
+    aiff[anno + 4..anno + 8].copy_from_slice(&[0xFF; 4]);
+    assert!(matches!(
+        PcmReader::new(&aiff),
+        Err(PcmReaderError::ChunkSizeExceedsFile { id, .. }) if &id == b"ANNO"
+    ));
+    let reader = PcmReader::<16>::new_with_options(&aiff, lenient).unwrap();
+    assert_eq!(reader.specs().num_samples, 240000);
+    assert_eq!(
+        reader.read_sample(0, 239999).unwrap(),
+        original.read_sample(0, 239999).unwrap()
+    );
+}
+
+#[test]
+fn wav_8bit() {
+    let wav = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_8.wav");
+    let reader = PcmReader::new(wav).unwrap();
+    let spec = reader.get_pcm_specs();
+    assert_eq!(spec.audio_format, AudioFormat::LinearPcmLe);
+    assert_eq!(spec.bit_depth, 8);
+    assert_eq!(spec.num_channels, 1);
+    assert_eq!(spec.sample_rate, 48000);
+    assert_eq!(spec.num_samples, 1000);
+
+    // Unsigned bytes centered at 128
+    let first_bytes = [128u8, 135, 141, 148, 154, 160, 167, 173];
+    for (i, byte) in first_bytes.into_iter().enumerate() {
+        let expected = (byte as f32 - 128.0) / 128.0;
+        assert_eq!(reader.read_sample(0, i as u32).unwrap(), expected);
+        assert_eq!(
+            reader.read_sample_exact(0, i as u32).unwrap(),
+            byte as i32 - 128
+        );
+    }
+    // Quantized from the 16bit fixture
+    for i in 0..spec.num_samples {
+        let sample = reader.read_sample(0, i).unwrap();
+        assert_relative_eq!(sample, SINEWAVE[i as usize], epsilon = 1.0 / 128.0);
+    }
+    let expected = hound::WavReader::new(&wav[..]).unwrap();
+    for (i, sample) in expected.into_samples::<i8>().enumerate() {
+        assert_eq!(
+            reader.read_sample_exact(0, i as u32).unwrap(),
+            sample.unwrap() as i32
+        );
+    }
+
+    let mut player = PcmPlayer::new(reader);
+    let mut frame = [0f32; 1];
+    for i in 0..spec.num_samples {
+        player.get_next_frame(&mut frame).unwrap();
+        assert_eq!(frame[0], player.reader.read_sample(0, i).unwrap());
+    }
+    assert!(matches!(
+        player.get_next_frame(&mut frame),
+        Err(PcmPlayerError::FinishPlaying)
+    ));
+
+    let mut scratch = [0u8; 64];
+    let mut streaming = StreamingPlayer::new(&wav[..], &mut scratch).unwrap();
+    let mut streamed = [0f32; 1000];
+    assert_eq!(streaming.fill_interleaved(&mut streamed).unwrap(), 1000);
+    for (i, sample) in streamed.into_iter().enumerate() {
+        assert_eq!(sample, player.reader.read_sample(0, i as u32).unwrap());
+    }
+}
+
+#[test]
+fn aiff_8bit() {
+    let aiff = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_8.aif");
+    let reader = PcmReader::new(aiff).unwrap();
+    let spec = reader.get_pcm_specs();
+    assert_eq!(spec.audio_format, AudioFormat::LinearPcmBe);
+    assert_eq!(spec.bit_depth, 8);
+    assert_eq!(spec.num_channels, 1);
+    assert_eq!(spec.sample_rate, 48000);
+    assert_eq!(spec.num_samples, 1000);
+
+    // Signed bytes, which hold the same samples as the unsigned bytes of the 8bit WAV.
+    let wav = PcmReader::new(include_bytes!("./resources/Sine440Hz_1ch_48000Hz_8.wav")).unwrap();
+    for i in 0..spec.num_samples {
+        assert_eq!(
+            reader.read_sample(0, i).unwrap(),
+            wav.read_sample(0, i).unwrap()
+        );
+        assert_eq!(
+            reader.read_sample_exact(0, i).unwrap(),
+            wav.read_sample_exact(0, i).unwrap()
+        );
+    }
+    assert_eq!(reader.read_sample_exact(0, 1).unwrap(), 7);
+    assert_eq!(reader.read_sample(0, 1).unwrap(), 7.0 / 128.0);
+
+    let (expected, sample_rate, num_channels) = decode_with_symphonia(aiff);
+    assert_eq!(sample_rate, 48000);
+    assert_eq!(num_channels, 1);
+    assert_eq!(expected.len(), 1000);
+    let mut player = PcmPlayer::new(reader);
+    let mut frame = [0f32; 1];
+    for sample in expected {
+        player.get_next_frame(&mut frame).unwrap();
+        assert_relative_eq!(frame[0], sample);
+    }
+}
+
+#[test]
+fn wav_alaw() {
+    let wav = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_8_ALaw.wav");
+    let reader = PcmReader::new(wav).unwrap();
+    let spec = reader.get_pcm_specs();
+    assert_eq!(spec.audio_format, AudioFormat::ALaw);
+    assert_eq!(spec.bit_depth, 8);
+    assert_eq!(spec.num_channels, 1);
+    assert_eq!(spec.sample_rate, 48000);
+    assert_eq!(spec.num_samples, 1000);
+
+    // Decoded by Python's audioop.alaw2lin
+    for (i, expected) in [8, 1696, 3392, 4992].into_iter().enumerate() {
+        let sample = reader.read_sample(0, i as u32).unwrap();
+        assert_eq!(sample, expected as f32 / 32768.0);
+    }
+    assert!(matches!(
+        reader.read_sample_exact(0, 0),
+        Err(PcmReaderError::UnsupportedAudioFormat)
+    ));
+
+    let (expected, sample_rate, num_channels) = decode_with_symphonia(wav);
+    assert_eq!(sample_rate, 48000);
+    assert_eq!(num_channels, 1);
+    assert_eq!(expected.len(), 1000);
+    for (i, sample) in expected.iter().enumerate() {
+        assert_relative_eq!(reader.read_sample(0, i as u32).unwrap(), *sample);
+        // Within the quantization error of A-law
+        assert_relative_eq!(*sample, SINEWAVE[i], epsilon = 0.02);
+    }
+
+    let mut player = PcmPlayer::new(reader);
+    let mut frame = [0f32; 1];
+    for sample in &expected {
+        player.get_next_frame(&mut frame).unwrap();
+        assert_relative_eq!(frame[0], *sample);
+    }
+    assert!(matches!(
+        player.get_next_frame(&mut frame),
+        Err(PcmPlayerError::FinishPlaying)
+    ));
+
+    let mut scratch = [0u8; 64];
+    let mut streaming = StreamingPlayer::new(&wav[..], &mut scratch).unwrap();
+    assert_eq!(streaming.specs().audio_format, AudioFormat::ALaw);
+    let mut streamed = [0f32; 1000];
+    assert_eq!(streaming.fill_interleaved(&mut streamed).unwrap(), 1000);
+    for (streamed, sample) in streamed.iter().zip(&expected) {
+        assert_relative_eq!(*streamed, *sample);
+    }
+}
+
+#[test]
+fn wav_mulaw() {
+    let wav = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_8_MuLaw.wav");
+    let reader = PcmReader::new(wav).unwrap();
+    let spec = reader.get_pcm_specs();
+    assert_eq!(spec.audio_format, AudioFormat::MuLaw);
+    assert_eq!(spec.bit_depth, 8);
+    assert_eq!(spec.num_channels, 1);
+    assert_eq!(spec.sample_rate, 48000);
+    assert_eq!(spec.num_samples, 1000);
+
+    // Decoded by Python's audioop.ulaw2lin
+    for (i, expected) in [0, 1692, 3388, 5116].into_iter().enumerate() {
+        let sample = reader.read_sample(0, i as u32).unwrap();
+        assert_eq!(sample, expected as f32 / 32768.0);
+    }
+    assert!(matches!(
+        reader.read_sample_exact(0, 0),
+        Err(PcmReaderError::UnsupportedAudioFormat)
+    ));
+
+    let (expected, sample_rate, num_channels) = decode_with_symphonia(wav);
+    assert_eq!(sample_rate, 48000);
+    assert_eq!(num_channels, 1);
+    assert_eq!(expected.len(), 1000);
+    for (i, sample) in expected.iter().enumerate() {
+        assert_relative_eq!(reader.read_sample(0, i as u32).unwrap(), *sample);
+        // Within the quantization error of µ-law
+        assert_relative_eq!(*sample, SINEWAVE[i], epsilon = 0.02);
+    }
+
+    let mut player = PcmPlayer::new(reader);
+    let mut frame = [0f32; 1];
+    for sample in &expected {
+        player.get_next_frame(&mut frame).unwrap();
+        assert_relative_eq!(frame[0], *sample);
+    }
+    assert!(matches!(
+        player.get_next_frame(&mut frame),
+        Err(PcmPlayerError::FinishPlaying)
+    ));
+
+    let mut scratch = [0u8; 64];
+    let mut streaming = StreamingPlayer::new(&wav[..], &mut scratch).unwrap();
+    assert_eq!(streaming.specs().audio_format, AudioFormat::MuLaw);
+    let mut streamed = [0f32; 1000];
+    assert_eq!(streaming.fill_interleaved(&mut streamed).unwrap(), 1000);
+    for (streamed, sample) in streamed.iter().zip(&expected) {
+        assert_relative_eq!(*streamed, *sample);
+    }
+}
+
+#[test]
+fn aifc_alaw() {
+    // sampleSize of the COMM chunk is 16.
+    let aifc = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_8_ALaw.aifc");
+    let reader = PcmReader::new(aifc).unwrap();
+    let spec = reader.get_pcm_specs();
+    assert_eq!(spec.audio_format, AudioFormat::ALaw);
+    assert_eq!(spec.bit_depth, 8);
+    assert_eq!(spec.num_channels, 1);
+    assert_eq!(spec.sample_rate, 48000);
+    assert_eq!(spec.num_samples, 1000);
+
+    // The same bytes as the A-law WAV
+    let wav = PcmReader::new(include_bytes!(
+        "./resources/Sine440Hz_1ch_48000Hz_8_ALaw.wav"
+    ))
+    .unwrap();
+    for i in 0..spec.num_samples {
+        assert_eq!(
+            reader.read_sample(0, i).unwrap(),
+            wav.read_sample(0, i).unwrap()
+        );
+    }
+
+    let mut scratch = [0u8; 64];
+    let mut streaming = StreamingPlayer::new(&aifc[..], &mut scratch).unwrap();
+    assert_eq!(streaming.specs().bit_depth, 8);
+    let mut streamed = [0f32; 1000];
+    assert_eq!(streaming.fill_interleaved(&mut streamed).unwrap(), 1000);
+    for (i, sample) in streamed.into_iter().enumerate() {
+        assert_eq!(sample, wav.read_sample(0, i as u32).unwrap());
+    }
+
+    // Upper case
+    let mut aifc = aifc.to_vec();
+    let comm = aifc.windows(4).position(|w| w == b"COMM").unwrap();
+    aifc[comm + 26..comm + 30].copy_from_slice(b"ALAW");
+    let reader = PcmReader::new(&aifc).unwrap();
+    assert_eq!(reader.specs().audio_format, AudioFormat::ALaw);
+    assert_eq!(reader.specs().bit_depth, 8);
+}
+
+#[test]
+fn aifc_mulaw() {
+    // sampleSize of the COMM chunk is 16.
+    let aifc = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_8_MuLaw.aifc");
+    let reader = PcmReader::new(aifc).unwrap();
+    let spec = reader.get_pcm_specs();
+    assert_eq!(spec.audio_format, AudioFormat::MuLaw);
+    assert_eq!(spec.bit_depth, 8);
+    assert_eq!(spec.num_channels, 1);
+    assert_eq!(spec.sample_rate, 48000);
+    assert_eq!(spec.num_samples, 1000);
+
+    // The same bytes as the µ-law WAV
+    let wav = PcmReader::new(include_bytes!(
+        "./resources/Sine440Hz_1ch_48000Hz_8_MuLaw.wav"
+    ))
+    .unwrap();
+    for i in 0..spec.num_samples {
+        assert_eq!(
+            reader.read_sample(0, i).unwrap(),
+            wav.read_sample(0, i).unwrap()
+        );
+    }
+
+    let mut scratch = [0u8; 64];
+    let mut streaming = StreamingPlayer::new(&aifc[..], &mut scratch).unwrap();
+    assert_eq!(streaming.specs().bit_depth, 8);
+    let mut streamed = [0f32; 1000];
+    assert_eq!(streaming.fill_interleaved(&mut streamed).unwrap(), 1000);
+    for (i, sample) in streamed.into_iter().enumerate() {
+        assert_eq!(sample, wav.read_sample(0, i as u32).unwrap());
+    }
+
+    // Upper case
+    let mut aifc = aifc.to_vec();
+    let comm = aifc.windows(4).position(|w| w == b"COMM").unwrap();
+    aifc[comm + 26..comm + 30].copy_from_slice(b"ULAW");
+    let reader = PcmReader::new(&aifc).unwrap();
+    assert_eq!(reader.specs().audio_format, AudioFormat::MuLaw);
+    assert_eq!(reader.specs().bit_depth, 8);
+}
+
+#[test]
+fn rf64() {
+    let rf64 = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16_RF64.wav");
+    let original =
+        PcmReader::new(include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav")).unwrap();
+    let reader = PcmReader::new(rf64).unwrap();
+    let spec = reader.get_pcm_specs();
+    assert_eq!(spec.audio_format, AudioFormat::LinearPcmLe);
+    assert_eq!(spec.bit_depth, 16);
+    assert_eq!(spec.num_channels, 1);
+    assert_eq!(spec.sample_rate, 48000);
+    // The data chunk declares 0xFFFFFFFF bytes, and the ds64 chunk 2000.
+    assert_eq!(spec.num_samples, 1000);
+    assert_eq!(reader.chunk_data(b"data").unwrap().len(), 2000);
+    for i in 0..spec.num_samples {
+        assert_eq!(
+            reader.read_sample(0, i).unwrap(),
+            original.read_sample(0, i).unwrap()
+        );
+    }
+    let ids: Vec<_> = reader.chunks().iter().map(|c| c.id).collect();
+    assert_eq!(ids, [*b"ds64", *b"fmt ", *b"data"]);
+
+    // The riff size of the ds64 chunk is checked instead of the header.
+    let mut file = rf64.to_vec();
+    file[20..28].copy_from_slice(&(rf64.len() as u64 - 7).to_le_bytes());
+    assert!(matches!(
+        PcmReader::new(&file),
+        Err(PcmReaderError::TruncatedData { expected, available })
+            if expected == rf64.len() as u64 + 1 && available == rf64.len() as u64
+    ));
+    file[20..28].copy_from_slice(&(rf64.len() as u64 - 9).to_le_bytes());
+    assert!(matches!(
+        PcmReader::new(&file),
+        Err(PcmReaderError::HeaderSizeMismatch)
+    ));
+
+    // The data size of the ds64 chunk exceeds the file.
+    let mut file = rf64.to_vec();
+    file[28..36].copy_from_slice(&4000u64.to_le_bytes());
+    assert!(matches!(
+        PcmReader::new(&file),
+        Err(PcmReaderError::ChunkSizeExceedsFile { id, declared: 4000, available: 2000 })
+            if &id == b"data"
+    ));
+    let lenient = ReaderOptions {
+        mode: ParseMode::Lenient,
+        ..Default::default()
+    };
+    let reader = PcmReader::<16>::new_with_options(&file, lenient).unwrap();
+    assert_eq!(reader.specs().num_samples, 1000);
+
+    // RF64 without the ds64 chunk
+    let mut file = rf64[..12].to_vec();
+    file.extend_from_slice(&rf64[48..]);
+    assert!(matches!(
+        PcmReader::new(&file),
+        Err(PcmReaderError::UnsupportedAudioFormat)
+    ));
+}
+
+#[test]
+fn w64() {
+    let w64 = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.w64");
+    let original =
+        PcmReader::new(include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav")).unwrap();
+    let reader = PcmReader::new(w64).unwrap();
+    let spec = reader.get_pcm_specs();
+    assert_eq!(spec.audio_format, AudioFormat::LinearPcmLe);
+    assert_eq!(spec.bit_depth, 16);
+    assert_eq!(spec.num_channels, 1);
+    assert_eq!(spec.sample_rate, 48000);
+    assert_eq!(spec.num_samples, 1000);
+    for i in 0..spec.num_samples {
+        assert_eq!(
+            reader.read_sample(0, i).unwrap(),
+            original.read_sample(0, i).unwrap()
+        );
+    }
+    // The junk chunk of 5 bytes is padded to 8.
+    let ids: Vec<_> = reader.chunks().iter().map(|c| c.id).collect();
+    assert_eq!(ids, [*b"fmt ", *b"junk", *b"data"]);
+    assert_eq!(reader.chunk_data(b"junk").unwrap(), b"pacmo");
+    assert_eq!(reader.chunk_data(b"data").unwrap().len(), 2000);
+
+    // The file size in the header includes the 40-byte header.
+    let mut file = w64.to_vec();
+    file[16..24].copy_from_slice(&(w64.len() as u64 - 1).to_le_bytes());
+    assert!(matches!(
+        PcmReader::new(&file),
+        Err(PcmReaderError::HeaderSizeMismatch)
+    ));
+
+    // The size of the data chunk includes the 24-byte chunk header.
+    let mut file = w64.to_vec();
+    file[128..136].copy_from_slice(&(24u64 + 4000).to_le_bytes());
+    assert!(matches!(
+        PcmReader::new(&file),
+        Err(PcmReaderError::ChunkSizeExceedsFile { id, declared: 4000, available: 2000 })
+            if &id == b"data"
+    ));
+}
+
+#[test]
+fn caf() {
+    let files: &[(&[u8], &[u8], AudioFormat, u16)] = &[
+        (
+            include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.caf"),
+            include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav"),
+            AudioFormat::LinearPcmBe,
+            16,
+        ),
+        // The data chunk of size -1 extends to the end of the file.
+        (
+            include_bytes!("./resources/Sine440Hz_1ch_48000Hz_24.caf"),
+            include_bytes!("./resources/Sine440Hz_1ch_48000Hz_24.wav"),
+            AudioFormat::LinearPcmLe,
+            24,
+        ),
+        (
+            include_bytes!("./resources/Sine440Hz_1ch_48000Hz_32.caf"),
+            include_bytes!("./resources/Sine440Hz_1ch_48000Hz_32.wav"),
+            AudioFormat::LinearPcmBe,
+            32,
+        ),
+        (
+            include_bytes!("./resources/Sine440Hz_1ch_48000Hz_32FP.caf"),
+            include_bytes!("./resources/Sine440Hz_1ch_48000Hz_32FP.wav"),
+            AudioFormat::IeeeFloatLe,
+            32,
+        ),
+        #[cfg(feature = "f64")]
+        (
+            include_bytes!("./resources/Sine440Hz_1ch_48000Hz_64FP.caf"),
+            include_bytes!("./resources/Sine440Hz_1ch_48000Hz_64FP.wav"),
+            AudioFormat::IeeeFloatBe,
+            64,
+        ),
+    ];
+    for (caf, wav, audio_format, bit_depth) in files {
+        let original = PcmReader::new(wav).unwrap();
+        let reader = PcmReader::new(caf).unwrap();
+        let spec = reader.get_pcm_specs();
+        assert_eq!(&spec.audio_format, audio_format);
+        assert_eq!(spec.bit_depth, *bit_depth);
+        assert_eq!(spec.num_channels, 1);
+        assert_eq!(spec.sample_rate, 48000);
+        assert_eq!(spec.num_samples, 1000);
+        for i in 0..spec.num_samples {
+            assert_eq!(
+                reader.read_sample(0, i).unwrap(),
+                original.read_sample(0, i).unwrap()
+            );
+        }
+        let ids: Vec<_> = reader.chunks().iter().map(|c| c.id).collect();
+        assert_eq!(ids, [*b"desc", *b"chan", *b"data"]);
+    }
+
+    let caf = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.caf");
+    // mBytesPerPacket which does not match the channels and the bit depth
+    let mut file = caf.to_vec();
+    file[39] = 4;
+    assert!(matches!(
+        PcmReader::new(&file),
+        Err(PcmReaderError::InconsistentSpecs(SpecsField::BlockAlign))
+    ));
+    let lenient = ReaderOptions {
+        mode: ParseMode::Lenient,
+        ..Default::default()
+    };
+    let reader = PcmReader::<16>::new_with_options(&file, lenient).unwrap();
+    assert_eq!(reader.specs().num_samples, 1000);
+
+    // Compressed formats are not supported.
+    let mut file = caf.to_vec();
+    file[28..32].copy_from_slice(b"aac ");
+    assert!(matches!(
+        PcmReader::new(&file),
+        Err(PcmReaderError::UnsupportedAudioFormat)
+    ));
+}
+
+#[test]
+fn new_raw() {
+    let wav = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_24.wav");
+    let original = PcmReader::new(wav).unwrap();
+    let data = original.chunk_data(b"data").unwrap();
+    let mut specs = PcmSpecs::new(AudioFormat::LinearPcmLe, 1, 48000, 24);
+    // Computed from the data.
+    specs.num_samples = 1;
+    let reader = PcmReader::new_raw(data, specs.clone()).unwrap();
+    assert_eq!(reader.specs().num_samples, original.specs().num_samples);
+    assert!(reader.chunks().is_empty());
+    for i in 0..1000 {
+        assert_eq!(
+            reader.read_sample(0, i).unwrap(),
+            original.read_sample(0, i).unwrap()
+        );
+    }
+
+    let mut player = PcmPlayer::new(reader);
+    let mut out = [0f32; 64];
+    assert_eq!(player.fill_interleaved(&mut out).unwrap(), 64);
+    for (i, sample) in out.iter().enumerate() {
+        assert_eq!(*sample, original.read_sample(0, i as u32).unwrap());
+    }
+
+    // 4-bit samples are not decoded.
+    let result = PcmReader::new_raw(data, PcmSpecs::new(AudioFormat::LinearPcmLe, 1, 48000, 4));
+    assert!(matches!(result, Err(PcmReaderError::UnsupportedBitDepth)));
+    let result = PcmReader::new_raw(data, PcmSpecs::new(AudioFormat::LinearPcmLe, 0, 48000, 24));
+    assert!(matches!(
+        result,
+        Err(PcmReaderError::InconsistentSpecs(SpecsField::NumChannels))
+    ));
+    // A partial frame at the end
+    let result = PcmReader::new_raw(&data[..3001], specs.clone());
+    assert!(matches!(
+        result,
+        Err(PcmReaderError::InconsistentSpecs(SpecsField::NumSamples))
+    ));
+    let result = PcmReader::new_raw(data, PcmSpecs::new(AudioFormat::Unknown, 1, 48000, 24));
+    assert!(matches!(
+        result,
+        Err(PcmReaderError::UnsupportedAudioFormat)
+    ));
+}
+
+#[test]
+fn wav_20bit_in_24bit() {
+    let data = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_20.wav");
+    let reader = PcmReader::new(data).unwrap();
+    let spec = reader.specs();
+    assert_eq!(spec.audio_format, AudioFormat::LinearPcmLe);
+    assert_eq!(spec.bit_depth, 20);
+    assert_eq!(spec.container_bytes_per_sample, 3);
+    assert_eq!(spec.num_samples, 4800);
+
+    // The 24-bit sibling with the low nibble of each sample cleared
+    let original =
+        PcmReader::new(include_bytes!("./resources/Sine440Hz_1ch_48000Hz_24.wav")).unwrap();
+    assert_eq!(original.specs().container_bytes_per_sample, 3);
+    for i in 0..spec.num_samples {
+        let exact = original.read_sample_exact(0, i).unwrap();
+        assert_eq!(reader.read_sample_exact(0, i).unwrap(), exact >> 4);
+        assert_eq!(
+            reader.read_sample(0, i).unwrap(),
+            (exact & !0xF) as f32 / 8388608.0
+        );
+        assert_relative_eq!(
+            reader.read_sample(0, i).unwrap(),
+            original.read_sample(0, i).unwrap(),
+            epsilon = 16.0 / 8388608.0
+        );
+    }
+
+    let mut scratch = [0u8; 512];
+    let mut player = StreamingPlayer::new(data.as_slice(), &mut scratch).unwrap();
+    let mut out = [0f32; 64];
+    assert_eq!(player.fill_interleaved(&mut out).unwrap(), 64);
+    for (i, sample) in out.iter().enumerate() {
+        assert_eq!(*sample, reader.read_sample(0, i as u32).unwrap());
+    }
+}
+
+#[test]
+fn wav_12bit_in_16bit() {
+    // 2ch 48kHz 12bit in 16bit containers
+    let fmt = [1, 0, 2, 0, 0x80, 0xBB, 0, 0, 0, 0xEE, 0x02, 0, 4, 0, 12, 0];
+    // Full scale, mid-scale and the smallest step, left-justified
+    let values: [i16; 6] = [2047, -2048, 0, 1024, -1024, 1];
+    let data: Vec<u8> = values.iter().flat_map(|v| (v << 4).to_le_bytes()).collect();
+    let wav = riff_wave(&[(b"fmt ", &fmt), (b"data", &data)]);
+    let reader = PcmReader::new(&wav).unwrap();
+    let spec = reader.specs();
+    assert_eq!(spec.bit_depth, 12);
+    assert_eq!(spec.container_bytes_per_sample, 2);
+    assert_eq!(spec.num_samples, 3);
+
+    for (i, value) in values.iter().enumerate() {
+        let (channel, sample) = ((i % 2) as u16, (i / 2) as u32);
+        assert_eq!(
+            reader.read_sample_exact(channel, sample).unwrap(),
+            i32::from(*value)
+        );
+        assert_eq!(
+            reader.read_sample(channel, sample).unwrap(),
+            f32::from(*value) / 2048.0
+        );
+    }
+    assert_eq!(reader.read_sample(0, 0).unwrap(), 2047.0 / 2048.0);
+    assert_eq!(reader.read_sample(1, 0).unwrap(), -1.0);
+    assert_eq!(reader.read_sample(1, 1).unwrap(), 0.5);
+    assert!(matches!(
+        reader.read_sample(0, 3),
+        Err(PcmReaderError::InvalidSample)
+    ));
+
+    let mut player = PcmPlayer::new(reader);
+    let mut out = [0f32; 6];
+    assert_eq!(player.fill_interleaved(&mut out).unwrap(), 3);
+    assert_eq!(out, values.map(|v| f32::from(v) / 2048.0));
+
+    // Headerless data
+    let reader =
+        PcmReader::new_raw(&data, PcmSpecs::new(AudioFormat::LinearPcmLe, 2, 48000, 12)).unwrap();
+    assert_eq!(reader.specs().num_samples, 3);
+    assert_eq!(reader.read_sample_exact(1, 0).unwrap(), -2048);
+}
+
+#[test]
+fn aifc_sowt_twos_bit_depths() {
+    let values = [i32::MAX, i32::MIN, 0, 0x4000_0000, -0x4000_0000];
+    for bit_depth in [8u16, 16, 24, 32] {
+        let bytes = bit_depth as usize / 8;
+        for compression_type in [b"sowt", b"twos"] {
+            let mut comm = vec![0, 1, 0, 0, 0, values.len() as u8];
+            comm.extend_from_slice(&bit_depth.to_be_bytes());
+            comm.extend_from_slice(&[0x40, 0x0E, 0xBB, 0x80, 0, 0, 0, 0, 0, 0]);
+            comm.extend_from_slice(compression_type);
+            comm.extend_from_slice(&[0, 0]);
+            let mut ssnd = vec![0; 8];
+            for value in values {
+                // The most significant bytes of the value
+                if compression_type == b"sowt" {
+                    ssnd.extend_from_slice(&value.to_le_bytes()[4 - bytes..]);
+                } else {
+                    ssnd.extend_from_slice(&value.to_be_bytes()[..bytes]);
+                }
+            }
+            let aiff = aiff_form(b"AIFC", &[(b"COMM", &comm), (b"SSND", &ssnd)]);
+            let reader = PcmReader::new(&aiff).unwrap();
+            let spec = reader.specs();
+            assert_eq!(spec.bit_depth, bit_depth);
+            assert_eq!(spec.container_bytes_per_sample as usize, bytes);
+            assert_eq!(spec.num_samples, values.len() as u32);
+            for (i, value) in values.iter().enumerate() {
+                let expected = value >> (32 - bit_depth);
+                assert_eq!(reader.read_sample_exact(0, i as u32).unwrap(), expected);
+                assert_eq!(
+                    reader.read_sample(0, i as u32).unwrap(),
+                    expected as f32 / (1i64 << (bit_depth - 1)) as f32
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn aiff_zero_sample_frames() {
+    let original = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.aif");
+    let expected = PcmReader::new(original).unwrap();
+    // numSampleFrames of COMM
+    let mut aiff = original.to_vec();
+    aiff[22..26].copy_from_slice(&0u32.to_be_bytes());
+
+    let reader = PcmReader::new(&aiff).unwrap();
+    let spec = reader.specs();
+    assert_eq!(spec.num_samples, 240000);
+    assert_eq!(spec.num_samples_source, NumSamplesSource::DataSize);
+    for i in (0..spec.num_samples).step_by(97) {
+        assert_eq!(
+            reader.read_sample(0, i).unwrap(),
+            expected.read_sample(0, i).unwrap()
+        );
+    }
+
+    let mut scratch = [0u8; 64];
+    let player = StreamingPlayer::new(aiff.as_slice(), &mut scratch).unwrap();
+    assert_eq!(player.specs().num_samples, 240000);
+
+    // A non-zero count is kept.
+    aiff[22..26].copy_from_slice(&1000u32.to_be_bytes());
+    let reader = PcmReader::new(&aiff).unwrap();
+    assert_eq!(reader.specs().num_samples, 1000);
+    assert_eq!(
+        reader.specs().num_samples_source,
+        NumSamplesSource::CommChunk
+    );
+
+    // An empty SSND chunk holds no frames.
+    let empty = PcmReader::new(include_bytes!("./resources/Empty_1ch_48000Hz_16.aif")).unwrap();
+    assert_eq!(empty.specs().num_samples, 0);
+}
+
+#[test]
+fn ima_adpcm_headerless() {
+    let data = include_bytes!("./resources/Sine440Hz_1ch_8000Hz_4bit_IMAADPCM.ima");
+    // Decoded by the reference implementation of the IMA-ADPCM specification
+    let reference = PcmReader::new(include_bytes!(
+        "./resources/Sine440Hz_1ch_8000Hz_4bit_IMAADPCM_decoded.wav"
+    ))
+    .unwrap();
+    let mut player = ImaAdpcmPlayer::new_raw(data, 8000, 1).unwrap();
+    let spec = player.specs().clone();
+    assert_eq!(spec.audio_format, AudioFormat::ImaAdpcmLe);
+    assert_eq!(spec.sample_rate, 8000);
+    assert_eq!(spec.num_samples, reference.specs().num_samples);
+
+    let mut buffer = [I1F15::ZERO];
+    for _ in 0..2 {
+        for i in 0..spec.num_samples {
+            player.get_next_frame(&mut buffer).unwrap();
+            assert_eq!(
+                i32::from(buffer[0].to_bits()),
+                reference.read_sample_exact(0, i).unwrap()
+            );
+        }
+        assert!(matches!(
+            player.get_next_frame(&mut buffer),
+            Err(ImaAdpcmError::FinishPlaying)
+        ));
+        // The predictor starts over.
+        player.rewind();
+    }
+}
+
+#[test]
+fn odd_sized_chunks_before_data() {
+    let original =
+        PcmReader::new(include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav")).unwrap();
+    let fmt = original.chunk_data(b"fmt ").unwrap();
+    let data = original.chunk_data(b"data").unwrap();
+    // LIST of type INFO with an odd-sized INAM tag, so the LIST chunk is odd-sized too.
+    let list = b"INFOINAM\x05\0\0\0Sine\0";
+    assert_eq!(list.len() % 2, 1);
+    let wav = riff_wave(&[(b"fmt ", fmt), (b"LIST", list), (b"data", data)]);
+
+    let aiff_original =
+        PcmReader::new(include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.aif")).unwrap();
+    let comm = aiff_original.chunk_data(b"COMM").unwrap();
+    let ssnd = aiff_original.chunk_data(b"SSND").unwrap();
+    let aiff = aiff_form(
+        b"AIFF",
+        &[(b"COMM", comm), (b"ANNO", b"pacmog!"), (b"SSND", ssnd)],
+    );
+
+    for (file, id, meta) in [(&wav, b"data", b"LIST"), (&aiff, b"SSND", b"ANNO")] {
+        let reader = PcmReader::new(file).unwrap();
+        assert_eq!(reader.specs().num_samples, 240000);
+        let chunks = reader.chunks();
+        let meta = chunks.iter().find(|c| &c.id == meta).unwrap();
+        let data = chunks.iter().find(|c| &c.id == id).unwrap();
+        assert_eq!(meta.size % 2, 1);
+        // The pad byte is skipped before the next chunk header.
+        assert_eq!(data.offset, meta.offset + meta.size + 1 + 8);
+        for i in [0, 1, 1000, 239999] {
+            assert_eq!(
+                reader.read_sample(0, i).unwrap(),
+                original.read_sample(0, i).unwrap()
+            );
+        }
+
+        let mut scratch = [0u8; 64];
+        let mut player = StreamingPlayer::new(file.as_slice(), &mut scratch).unwrap();
+        let mut out = [0f32; 16];
+        assert_eq!(player.fill_interleaved(&mut out).unwrap(), 16);
+        for (i, sample) in out.iter().enumerate() {
+            assert_eq!(*sample, original.read_sample(0, i as u32).unwrap());
+        }
+    }
+}
+
+#[test]
+fn data_before_fmt() {
+    for file in [
+        &include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav")[..],
+        include_bytes!("./resources/Sine440Hz_2ch_48000Hz_4bit_IMAADPCM.wav"),
+        include_bytes!("./resources/Sine440Hz_1ch_48000Hz_4bit_MSADPCM.wav"),
+    ] {
+        let original = PcmReader::new(file).unwrap();
+        let fmt = original.chunk_data(b"fmt ").unwrap();
+        let data = original.chunk_data(b"data").unwrap();
+        let mut chunks = vec![(b"data", data), (b"fmt ", fmt)];
+        if let Some(fact) = original.chunk_data(b"fact") {
+            chunks.insert(0, (b"fact", fact));
+        }
+        let wav = riff_wave(&chunks);
+        let reader = PcmReader::new(&wav).unwrap();
+        assert_eq!(
+            format!("{:?}", reader.specs()),
+            format!("{:?}", original.specs())
+        );
+        assert_eq!(reader.chunks()[chunks.len() - 1].id, *b"fmt ");
+
+        // Without the fmt chunk
+        chunks.retain(|(id, _)| *id != b"fmt ");
+        let wav = riff_wave(&chunks);
+        assert!(matches!(
+            PcmReader::new(&wav),
+            Err(PcmReaderError::MissingChunk(id)) if id == *b"fmt "
+        ));
+        let lenient = ReaderOptions {
+            mode: ParseMode::Lenient,
+            ..Default::default()
+        };
+        assert!(matches!(
+            PcmReader::<16>::new_with_options(&wav, lenient),
+            Err(PcmReaderError::MissingChunk(_))
+        ));
+        assert!(ImaAdpcmPlayer::new(&wav).is_err());
+        assert!(MsAdpcmPlayer::new(&wav).is_err());
+    }
+
+    let original =
+        PcmReader::new(include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav")).unwrap();
+    let fmt = original.chunk_data(b"fmt ").unwrap();
+    let data = original.chunk_data(b"data").unwrap();
+    let wav = riff_wave(&[(b"data", data), (b"fmt ", fmt)]);
+    let mut scratch = [0u8; 64];
+    let mut player = StreamingPlayer::new(wav.as_slice(), &mut scratch).unwrap();
+    let mut out = [0f32; 16];
+    assert_eq!(player.fill_interleaved(&mut out).unwrap(), 16);
+    assert_eq!(out[1], original.read_sample(0, 1).unwrap());
+    let wav = riff_wave(&[(b"data", data)]);
+    assert!(StreamingPlayer::new(wav.as_slice(), &mut scratch).is_err());
+
+    // AIFF without COMM
+    let aiff = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.aif");
+    let ssnd = PcmReader::new(aiff).unwrap().chunk_data(b"SSND").unwrap();
+    let aiff = aiff_form(b"AIFF", &[(b"SSND", ssnd)]);
+    assert!(matches!(
+        PcmReader::new(&aiff),
+        Err(PcmReaderError::MissingChunk(id)) if id == *b"COMM"
+    ));
+
+    // CAF without desc
+    let caf = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.caf");
+    let data = PcmReader::new(caf).unwrap().chunk_data(b"data").unwrap();
+    let mut caf = b"caff\0\x01\0\0data".to_vec();
+    caf.extend_from_slice(&(data.len() as u64).to_be_bytes());
+    caf.extend_from_slice(data);
+    assert!(matches!(
+        PcmReader::new(&caf),
+        Err(PcmReaderError::MissingChunk(id)) if id == *b"desc"
+    ));
+}
+
+#[test]
+fn adpcm_chunk_capacity() {
+    // fmt, fact and data of the fixtures, plus 24 chunks, e.g. bext, iXML and padding of a BWF file.
+    let ima = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_4bit_IMAADPCM.wav");
+    let ima = insert_chunks(ima, b"JUNK", 24, false);
+    let ms = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_4bit_MSADPCM.wav");
+    let ms = insert_chunks(ms, b"JUNK", 24, false);
+
+    let mut player = ImaAdpcmPlayer::<32>::new_with_chunk_capacity(&ima).unwrap();
+    assert!(player.reader.chunk_data(b"data").is_some());
+    let mut frame = [I1F15::ZERO];
+    player.get_next_frame(&mut frame).unwrap();
+    let player = ImaAdpcmPlayer::new(&ima).unwrap();
+    assert_eq!(player.specs().num_samples, 240000);
+
+    let mut player = MsAdpcmPlayer::<32>::new_with_chunk_capacity(&ms).unwrap();
+    assert!(player.reader.chunk_data(b"data").is_some());
+    player.get_next_frame(&mut frame).unwrap();
+    let player = MsAdpcmPlayer::new(&ms).unwrap();
+    assert_eq!(player.specs().num_samples, 5000);
+}
+
+#[test]
+fn unsized_data_chunk() {
+    let lenient = ReaderOptions {
+        mode: ParseMode::Lenient,
+        ..Default::default()
+    };
+    let original =
+        PcmReader::new(include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav")).unwrap();
+    let fmt = original.chunk_data(b"fmt ").unwrap();
+    let data = original.chunk_data(b"data").unwrap();
+    let wav = riff_wave(&[(b"fmt ", fmt), (b"data", data)]);
+    // fmt chunk of 16 bytes
+    let data_size = 12 + 8 + 16 + 4;
+
+    for (riff_size, data_size_value) in [
+        (0u32, 0u32),
+        (u32::MAX, 0),
+        (0, u32::MAX),
+        (wav.len() as u32 - 8, 0),
+    ] {
+        let mut file = wav.clone();
+        file[4..8].copy_from_slice(&riff_size.to_le_bytes());
+        file[data_size..data_size + 4].copy_from_slice(&data_size_value.to_le_bytes());
+        assert!(PcmReader::new(&file).map_or(true, |r| r.specs().num_samples == 0));
+        let reader = PcmReader::<16>::new_with_options(&file, lenient).unwrap();
+        assert_eq!(reader.specs().num_samples, 240000);
+        assert_eq!(
+            reader.read_sample(0, 239999).unwrap(),
+            original.read_sample(0, 239999).unwrap()
+        );
+    }
+
+    // An empty data chunk followed by another chunk is kept empty.
+    let wav = riff_wave(&[(b"fmt ", fmt), (b"data", &[]), (b"LIST", b"INFO")]);
+    let reader = PcmReader::<16>::new_with_options(&wav, lenient).unwrap();
+    assert_eq!(reader.specs().num_samples, 0);
+    assert_eq!(reader.chunks().len(), 3);
+
+    let aiff = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.aif");
+    let original = PcmReader::new(aiff).unwrap();
+    // numSampleFrames is 0 as well, so the frames are counted from SSND.
+    let mut file = aiff.to_vec();
+    file[4..8].copy_from_slice(&0u32.to_be_bytes());
+    file[22..26].copy_from_slice(&0u32.to_be_bytes());
+    // SSND size
+    file[42..46].copy_from_slice(&0u32.to_be_bytes());
+    assert!(PcmReader::new(&file).is_err());
+    let reader = PcmReader::<16>::new_with_options(&file, lenient).unwrap();
+    assert_eq!(reader.specs().num_samples, 240000);
+    assert_eq!(
+        reader.read_sample(0, 239999).unwrap(),
+        original.read_sample(0, 239999).unwrap()
+    );
+}
+
+#[test]
+fn truncated_files() {
+    let lenient = ReaderOptions {
+        mode: ParseMode::Lenient,
+        ..Default::default()
+    };
+    let files: [(&[u8], &[u8; 4], usize); 3] = [
+        // ID of the chunk of the samples and the size of its header fields
+        (
+            include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav"),
+            b"data",
+            0,
+        ),
+        // offset and blockSize of SSND
+        (
+            include_bytes!("./resources/Sine440Hz_1ch_48000Hz_24.aif"),
+            b"SSND",
+            8,
+        ),
+        (
+            include_bytes!("./resources/Sine440Hz_2ch_48000Hz_4bit_IMAADPCM.wav"),
+            b"data",
+            0,
+        ),
+    ];
+    for (file, id, fields) in files {
+        let original = PcmReader::new(file).unwrap();
+        let payload = original.chunk_data(id).unwrap();
+        let data_offset = payload.as_ptr() as usize - file.as_ptr() as usize + fields;
+
+        for cut in [
+            data_offset + 1,
+            data_offset + 1001,
+            file.len() / 2,
+            file.len() - 1,
+        ] {
+            let truncated = &file[..cut];
+            assert!(matches!(
+                PcmReader::new(truncated),
+                Err(PcmReaderError::TruncatedData { expected, available })
+                    if expected == file.len() as u64 && available == cut as u64
+            ));
+
+            let reader = PcmReader::<16>::new_with_options(truncated, lenient).unwrap();
+            let num_samples = reader.specs().num_samples;
+            assert!(num_samples < original.specs().num_samples);
+            if original.specs().audio_format == AudioFormat::ImaAdpcmLe {
+                continue;
+            }
+            // Only whole frames are left.
+            let bytes_per_frame = usize::from(original.specs().container_bytes_per_sample);
+            assert_eq!(num_samples as usize, (cut - data_offset) / bytes_per_frame);
+            if num_samples > 0 {
+                let last = num_samples - 1;
+                assert_eq!(
+                    reader.read_sample(0, last).unwrap(),
+                    original.read_sample(0, last).unwrap()
+                );
+            }
+            assert!(matches!(
+                reader.read_sample(0, num_samples),
+                Err(PcmReaderError::InvalidSample)
+            ));
+        }
+
+        // Cut in the middle of the header
+        assert!(PcmReader::<16>::new_with_options(&file[..30], lenient).is_err());
+    }
+}
+
+#[test]
+fn wavl_list() {
+    let wav = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav");
+    let original = PcmReader::new(wav).unwrap();
+    let fmt = original.chunk_data(b"fmt ").unwrap();
+    let data = original.chunk_data(b"data").unwrap();
+
+    // 1000 frames, 500 frames of silence and 1001 frames
+    let mut list = b"wavl".to_vec();
+    list.extend_from_slice(
+        &riff_wave(&[
+            (b"data", &data[..2000]),
+            (b"slnt", &500u32.to_le_bytes()),
+            (b"data", &data[2000..4002]),
+        ])[12..],
+    );
+    let file = riff_wave(&[(b"fmt ", fmt), (b"LIST", &list)]);
+    let reader = PcmReader::new(&file).unwrap();
+    assert_eq!(reader.specs().num_samples, 2501);
+
+    let expected: Vec<f32> = (0..1000)
+        .map(|i| original.read_sample(0, i).unwrap())
+        .chain([0.0; 500])
+        .chain((1000..2001).map(|i| original.read_sample(0, i).unwrap()))
+        .collect();
+    for (i, &sample) in expected.iter().enumerate() {
+        assert_eq!(reader.read_sample(0, i as u32).unwrap(), sample);
+    }
+    assert!(matches!(
+        reader.read_sample(0, 2501),
+        Err(PcmReaderError::InvalidSample)
+    ));
+    assert_eq!(reader.read_sample_exact(0, 1200).unwrap(), 0);
+    assert_eq!(
+        reader.read_sample_exact(0, 1500).unwrap(),
+        original.read_sample_exact(0, 1000).unwrap()
+    );
+
+    // Blocks across the segments
+    let mut block = [1.0f32; 600];
+    assert_eq!(reader.decode_block_f32(990, &mut block).unwrap(), 600);
+    assert_eq!(block, expected[990..1590]);
+    let mut planar = [1.0f32; 600];
+    assert_eq!(
+        reader
+            .decode_block_planar_f32(990, &mut [&mut planar[..]])
+            .unwrap(),
+        600
+    );
+    assert_eq!(planar, expected[990..1590]);
+    let mut frame = [1.0f32];
+    assert_eq!(reader.read_frame(1200, &mut frame).unwrap(), 1);
+    assert_eq!(frame, [0.0]);
+    assert!(reader.raw_data().is_empty());
+    assert_eq!(reader.frame_bytes(1200), None);
+    assert_eq!(reader.frame_bytes(1500).unwrap(), &data[2000..2002]);
+
+    let mut player = PcmPlayer::new(reader);
+    let mut out = vec![1.0f32; 2600];
+    assert_eq!(player.fill_interleaved(&mut out).unwrap(), 2501);
+    assert_eq!(out[..2501], expected);
+
+    // The samples are either in a data chunk or in a wavl list.
+    let file = riff_wave(&[(b"fmt ", fmt), (b"data", data), (b"LIST", &list)]);
+    assert!(matches!(
+        PcmReader::new(&file),
+        Err(PcmReaderError::DuplicateChunk(id)) if &id == b"LIST"
+    ));
+
+    // More segments than the table holds
+    let mut list = b"wavl".to_vec();
+    for _ in 0..pacmog::MAX_NUM_SEGMENTS + 1 {
+        list.extend_from_slice(&riff_wave(&[(b"slnt", &10u32.to_le_bytes())])[12..]);
+    }
+    let file = riff_wave(&[(b"fmt ", fmt), (b"LIST", &list)]);
+    let lenient = ReaderOptions {
+        mode: ParseMode::Lenient,
+        ..Default::default()
+    };
+    let reader = PcmReader::<16>::new_with_options(&file, lenient).unwrap();
+    if cfg!(feature = "alloc") {
+        assert_eq!(reader.specs().num_samples, 90);
+    } else {
+        assert!(matches!(
+            PcmReader::new(&file),
+            Err(PcmReaderError::UnsupportedAudioFormat)
+        ));
+        assert_eq!(reader.specs().num_samples, 80);
+    }
+}
+
+#[test]
+fn fmt_chunk_sizes() {
+    let wav = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav");
+    let original = PcmReader::new(wav).unwrap();
+    let fmt = original.chunk_data(b"fmt ").unwrap();
+    let data = original.chunk_data(b"data").unwrap();
+
+    // 14 bytes without wBitsPerSample, 18 bytes with cbSize 0, and 40 bytes of a broken encoder
+    let mut extended = fmt[..16].to_vec();
+    extended.extend_from_slice(&[22, 0]);
+    extended.resize(40, 0xAA);
+    for fmt in [&fmt[..14], &[&fmt[..16], &[0, 0]].concat(), &extended] {
+        let file = riff_wave(&[(b"fmt ", fmt), (b"data", data)]);
+        let reader = PcmReader::new(&file).unwrap();
+        assert_eq!(reader.specs().bit_depth, 16);
+        assert_eq!(reader.specs().num_samples, original.specs().num_samples);
+        assert_eq!(
+            reader.read_sample(0, 100).unwrap(),
+            original.read_sample(0, 100).unwrap()
+        );
+
+        let mut scratch = [0u8; 64];
+        let streaming = StreamingPlayer::new(file.as_slice(), &mut scratch).unwrap();
+        assert_eq!(streaming.specs().bit_depth, 16);
+    }
+}
+
+#[test]
+fn ima_adpcm_samples_per_block() {
+    let wav = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_4bit_IMAADPCM.wav");
+    let original = PcmReader::new(wav).unwrap();
+    let fmt = original.chunk_data(b"fmt ").unwrap();
+    let fact = original.chunk_data(b"fact").unwrap();
+    let data = original.chunk_data(b"data").unwrap();
+    let samples_per_block = u16::from_le_bytes([fmt[18], fmt[19]]);
+    let block_align = u16::from_le_bytes([fmt[12], fmt[13]]);
+    let decode = |file: &[u8]| {
+        let mut player = ImaAdpcmPlayer::new(file).unwrap();
+        let mut frames = Vec::new();
+        let mut buffer = [I1F15::ZERO];
+        for _ in 0..player.specs().num_samples {
+            player.get_next_frame(&mut buffer).unwrap();
+            frames.push(buffer[0]);
+        }
+        frames
+    };
+    let expected = decode(wav);
+
+    // Without the extension, with cbSize 0, and with a value larger than a block holds
+    let too_large = [&fmt[..18], &(samples_per_block + 1).to_le_bytes()].concat();
+    for fmt in [&fmt[..16], &[&fmt[..16], &[0, 0]].concat(), &too_large] {
+        let file = riff_wave(&[(b"fmt ", fmt), (b"fact", fact), (b"data", data)]);
+        assert_eq!(decode(&file), expected);
+    }
+
+    // One less than the block holds. The last sample of each block is not decoded.
+    let off_by_one = [&fmt[..18], &(samples_per_block - 1).to_le_bytes()].concat();
+    let file = riff_wave(&[(b"fmt ", &off_by_one), (b"data", data)]);
+    let frames = decode(&file);
+    let full_blocks = data.len() / usize::from(block_align) * usize::from(samples_per_block - 1);
+    assert_eq!(frames.len(), full_blocks);
+    let skipped: Vec<I1F15> = expected
+        .chunks(usize::from(samples_per_block))
+        .flat_map(|block| &block[..block.len().min(usize::from(samples_per_block - 1))])
+        .copied()
+        .collect();
+    // The fact chunk of the original file cuts the last block.
+    assert_eq!(frames[..skipped.len()], skipped);
+}
+
+#[test]
+fn info_metadata() {
+    let wav = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16_INFO.wav");
+    let reader = PcmReader::new(wav).unwrap();
+    assert_eq!(reader.specs().num_samples, 4800);
+    // The tags are odd-sized and followed by pad bytes. ICMT is Latin-1.
+    let tags: Vec<_> = reader.info_metadata().collect();
+    assert_eq!(
+        tags,
+        [
+            (*b"INAM", &b"Sine 440Hz"[..]),
+            (*b"IART", &b"pacmog"[..]),
+            (*b"ICMT", &b"CC0 caf\xE9"[..]),
+            (*b"ICRD", &b"2024-05-01"[..]),
+        ]
+    );
+
+    // After the data chunk
+    let wav = include_bytes!("./resources/MLKDream.wav");
+    let reader = PcmReader::new(wav).unwrap();
+    let tags: Vec<_> = reader.info_metadata().collect();
+    assert_eq!(tags, [(*b"ISFT", &b"Converted by Streambox Ripper"[..])]);
+
+    // A LIST chunk of another type comes first.
+    let fmt = [1, 0, 1, 0, 0x80, 0xBB, 0, 0, 0, 0x77, 0x01, 0, 2, 0, 16, 0];
+    let file = riff_wave(&[
+        (b"fmt ", &fmt),
+        (b"LIST", b"adtlnote\x04\0\0\0text"),
+        (b"LIST", b"INFOINAM\x03\0\0\0abc"),
+        (b"data", &[0; 4]),
+    ]);
+    let reader = PcmReader::new(&file).unwrap();
+    assert_eq!(
+        reader.info_metadata().collect::<Vec<_>>(),
+        [(*b"INAM", &b"abc"[..])]
+    );
+
+    let wav = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav");
+    assert_eq!(PcmReader::new(wav).unwrap().info_metadata().count(), 0);
+}
+
+#[test]
+fn id3_chunk() {
+    // ID3v2.3 header and a TIT2 frame, 33 bytes
+    let mut id3 = b"ID3\x03\x00\x00\x00\x00\x00\x17".to_vec();
+    id3.extend_from_slice(b"TIT2\x00\x00\x00\x0D\x00\x00\x00Sine 440Hz\x00");
+    let wav = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav");
+    let original = PcmReader::new(wav).unwrap();
+    let fmt = original.chunk_data(b"fmt ").unwrap();
+    let data = original.chunk_data(b"data").unwrap();
+    for id in [b"id3 ", b"ID3 ", b"IDv3"] {
+        // After the data chunk, followed by a pad byte
+        let file = riff_wave(&[(b"fmt ", fmt), (b"data", data), (id, &id3)]);
+        let reader = PcmReader::new(&file).unwrap();
+        assert_eq!(reader.id3_chunk(), Some(&id3[..]));
+        assert_eq!(reader.specs().num_samples, original.specs().num_samples);
+    }
+    assert_eq!(original.id3_chunk(), None);
+
+    let aiff = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.aif");
+    let original = PcmReader::new(aiff).unwrap();
+    let comm = original.chunk_data(b"COMM").unwrap();
+    let ssnd = original.chunk_data(b"SSND").unwrap();
+    let file = aiff_form(
+        b"AIFF",
+        &[(b"COMM", comm), (b"ID3 ", &id3), (b"SSND", ssnd)],
+    );
+    let reader = PcmReader::new(&file).unwrap();
+    assert_eq!(reader.id3_chunk(), Some(&id3[..]));
+}
+
+#[test]
+fn sampler_info() {
+    use pacmog::metadata::{LoopType, SampleLoop, MAX_NUM_LOOPS};
+
+    let smpl = |unity_note: u32, loops: &[[u32; 4]]| {
+        let mut smpl = Vec::new();
+        for v in [
+            0,
+            0,
+            20833,
+            unity_note,
+            0x8000_0000,
+            0,
+            0,
+            loops.len() as u32,
+            0,
+        ] {
+            smpl.extend_from_slice(&v.to_le_bytes());
+        }
+        for (i, [loop_type, start, end, play_count]) in loops.iter().enumerate() {
+            for v in [i as u32, *loop_type, *start, *end, 0, *play_count] {
+                smpl.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+        smpl
+    };
+    let wav = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav");
+    let original = PcmReader::new(wav).unwrap();
+    let fmt = original.chunk_data(b"fmt ").unwrap();
+    let data = original.chunk_data(b"data").unwrap();
+    assert!(original.sampler_info().is_none());
+
+    let chunk = smpl(
+        69,
+        &[
+            [0, 1000, 1108, 0],
+            [1, 0, 239999, 3],
+            // After the end of the samples
+            [0, 1000, 240000, 0],
+            // The end before the start
+            [2, 2000, 1000, 0],
+            [7, 0, 0, 1],
+        ],
+    );
+    let file = riff_wave(&[(b"fmt ", fmt), (b"smpl", &chunk), (b"data", data)]);
+    let info = PcmReader::new(&file).unwrap().sampler_info().unwrap();
+    assert_eq!(info.midi_unity_note, 69);
+    assert_eq!(info.midi_pitch_fraction, 0x8000_0000);
+    assert_eq!(
+        info.loops(),
+        [
+            SampleLoop {
+                loop_type: LoopType::Forward,
+                start: 1000,
+                end: 1108,
+                play_count: 0,
+            },
+            SampleLoop {
+                loop_type: LoopType::Alternating,
+                start: 0,
+                end: 239999,
+                play_count: 3,
+            },
+            SampleLoop {
+                loop_type: LoopType::Other(7),
+                start: 0,
+                end: 0,
+                play_count: 1,
+            },
+        ]
+    );
+
+    // The number of loops is larger than the chunk holds, and than the list holds.
+    let mut chunk = smpl(60, &[[0, 0, 100, 0]; MAX_NUM_LOOPS + 2]);
+    chunk[28..32].copy_from_slice(&100u32.to_le_bytes());
+    let file = riff_wave(&[(b"fmt ", fmt), (b"data", data), (b"smpl", &chunk)]);
+    let info = PcmReader::new(&file).unwrap().sampler_info().unwrap();
+    if cfg!(feature = "alloc") {
+        assert_eq!(info.loops().len(), MAX_NUM_LOOPS + 2);
+    } else {
+        assert_eq!(info.loops().len(), MAX_NUM_LOOPS);
+    }
+    let file = riff_wave(&[
+        (b"fmt ", fmt),
+        (b"data", data),
+        (b"smpl", &chunk[..36 + 30]),
+    ]);
+    let info = PcmReader::new(&file).unwrap().sampler_info().unwrap();
+    assert_eq!(info.loops().len(), 1);
+
+    // Shorter than the header
+    let file = riff_wave(&[(b"fmt ", fmt), (b"data", data), (b"smpl", &chunk[..32])]);
+    assert!(PcmReader::new(&file).unwrap().sampler_info().is_none());
+}
+
+#[test]
+fn instrument() {
+    use pacmog::metadata::{Instrument, LoopType, SampleLoop};
+
+    let wav = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16_smpl_inst.wav");
+    let reader = PcmReader::new(wav).unwrap();
+    assert_eq!(
+        reader.instrument().unwrap(),
+        Some(Instrument {
+            unshifted_note: 69,
+            fine_tune: -3,
+            gain: -6,
+            low_note: 60,
+            high_note: 81,
+            low_velocity: 1,
+            high_velocity: 127,
+        })
+    );
+    let info = reader.sampler_info().unwrap();
+    assert_eq!(info.midi_unity_note, 69);
+    assert_eq!(
+        info.loops(),
+        [SampleLoop {
+            loop_type: LoopType::Forward,
+            start: 238800,
+            end: 239999,
+            play_count: 0,
+        }]
+    );
+
+    let original = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav");
+    assert_eq!(
+        PcmReader::new(original).unwrap().instrument().unwrap(),
+        None
+    );
+
+    // Malformed sizes are an error.
+    let reader = PcmReader::new(original).unwrap();
+    let fmt = reader.chunk_data(b"fmt ").unwrap();
+    let data = reader.chunk_data(b"data").unwrap();
+    let inst = find_riff_chunk(wav, b"inst").unwrap();
+    for size in [0, 6, 8] {
+        let mut payload = inst.to_vec();
+        payload.resize(size, 0);
+        let file = riff_wave(&[(b"fmt ", fmt), (b"inst", &payload), (b"data", data)]);
+        assert!(matches!(
+            PcmReader::new(&file).unwrap().instrument(),
+            Err(PcmReaderError::InvalidChunkSize { id, size: s }) if &id == b"inst" && s as usize == size
+        ));
+    }
+}
+
+#[test]
+fn broadcast_extension() {
+    let wav = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16_bext.wav");
+    let reader = PcmReader::new(wav).unwrap();
+    let bext = reader.broadcast_extension().unwrap();
+    assert_eq!(bext.description, b"Sine 440Hz take 1");
+    assert_eq!(bext.originator, b"pacmog");
+    assert_eq!(bext.originator_reference, b"USPAC0000000000001");
+    assert_eq!(bext.origination_date, b"2024-05-01");
+    assert_eq!(bext.origination_time, b"12:34:56");
+    // 10 hours and 12345 samples after midnight at 48kHz
+    assert_eq!(bext.time_reference, 1728012345);
+    assert_eq!(bext.version, 2);
+    let loudness = bext.loudness.unwrap();
+    assert_eq!(loudness.integrated_loudness(), Some(-23.0));
+    assert_eq!(loudness.range(), Some(4.5));
+    assert_eq!(loudness.true_peak(), Some(-1.1));
+    assert_eq!(loudness.max_momentary(), Some(-18.0));
+    assert_eq!(loudness.max_short_term(), Some(-20.0));
+    assert_eq!(reader.specs().num_samples, 240000);
+
+    let original = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav");
+    assert!(PcmReader::new(original)
+        .unwrap()
+        .broadcast_extension()
+        .is_none());
+
+    // TimeReferenceHigh, and a version 0 chunk which ends after the version.
+    let reader = PcmReader::new(original).unwrap();
+    let fmt = reader.chunk_data(b"fmt ").unwrap();
+    let data = reader.chunk_data(b"data").unwrap();
+    let mut chunk = find_riff_chunk(wav, b"bext").unwrap()[..348].to_vec();
+    chunk[338..346].copy_from_slice(&0x1_0000_0002u64.to_le_bytes());
+    chunk[346..348].copy_from_slice(&0u16.to_le_bytes());
+    let file = riff_wave(&[(b"fmt ", fmt), (b"bext", &chunk), (b"data", data)]);
+    let bext = PcmReader::new(&file)
+        .unwrap()
+        .broadcast_extension()
+        .unwrap();
+    assert_eq!(bext.time_reference, 0x1_0000_0002);
+    assert_eq!(bext.version, 0);
+    assert_eq!(bext.originator, b"pacmog");
+    assert_eq!(bext.loudness, None);
+
+    // The loudness is read from version 2, if the chunk is long enough.
+    let mut chunk = find_riff_chunk(wav, b"bext").unwrap().to_vec();
+    for (version, len, has_loudness) in [(1, 602, false), (2, 602, true), (2, 421, false)] {
+        chunk[346..348].copy_from_slice(&u16::to_le_bytes(version));
+        let file = riff_wave(&[(b"fmt ", fmt), (b"bext", &chunk[..len]), (b"data", data)]);
+        let bext = PcmReader::new(&file)
+            .unwrap()
+            .broadcast_extension()
+            .unwrap();
+        assert_eq!(bext.loudness.is_some(), has_loudness);
+    }
+
+    let file = riff_wave(&[(b"fmt ", fmt), (b"bext", &chunk[..347]), (b"data", data)]);
+    assert!(PcmReader::new(&file)
+        .unwrap()
+        .broadcast_extension()
+        .is_none());
+}
+
+#[test]
+fn cart_chunk() {
+    use pacmog::metadata::CartTimer;
+
+    let wav = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16_cart.wav");
+    let reader = PcmReader::new(wav).unwrap();
+    let cart = reader.cart_chunk().unwrap();
+    assert_eq!(cart.version, b"0101");
+    assert_eq!(cart.title, b"Sine 440Hz");
+    assert_eq!(cart.artist, b"pacmog");
+    assert_eq!(cart.cut_id, b"CUT-0440");
+    assert_eq!(cart.client_id, b"CLIENT-01");
+    assert_eq!(cart.category, b"TONE");
+    assert_eq!(cart.start_date, b"2024-05-01");
+    assert_eq!(cart.end_time, b"23:59:59");
+    assert_eq!(cart.producer_app_version, b"0.1");
+    assert_eq!(cart.user_def, b"");
+    assert_eq!(cart.level_reference, 32768);
+    assert_eq!(cart.url, b"https://example.com/cut/0440");
+    assert_eq!(cart.tag_text, b"tone\r\n");
+    assert_eq!(
+        cart.timers(),
+        [
+            CartTimer {
+                usage: *b"INT1",
+                value: 4800,
+            },
+            CartTimer {
+                usage: *b"SEC1",
+                value: 48000,
+            },
+            CartTimer {
+                usage: *b"EOD ",
+                value: 236000,
+            },
+        ]
+    );
+
+    let original = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav");
+    assert!(PcmReader::new(original).unwrap().cart_chunk().is_none());
+
+    // Without the tag text, and shorter than the fixed fields.
+    let reader = PcmReader::new(original).unwrap();
+    let fmt = reader.chunk_data(b"fmt ").unwrap();
+    let data = reader.chunk_data(b"data").unwrap();
+    let chunk = find_riff_chunk(wav, b"cart").unwrap();
+    let file = riff_wave(&[(b"fmt ", fmt), (b"cart", &chunk[..2048]), (b"data", data)]);
+    let cart = PcmReader::new(&file).unwrap().cart_chunk().unwrap();
+    assert_eq!(cart.cut_id, b"CUT-0440");
+    assert_eq!(cart.tag_text, b"");
+    let file = riff_wave(&[(b"fmt ", fmt), (b"cart", &chunk[..2047]), (b"data", data)]);
+    assert!(PcmReader::new(&file).unwrap().cart_chunk().is_none());
+}
+
+#[test]
+fn xml_chunks() {
+    let original = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav");
+    let reader = PcmReader::new(original).unwrap();
+    assert_eq!(reader.ixml(), None);
+    assert_eq!(reader.axml(), None);
+
+    let fmt = reader.chunk_data(b"fmt ").unwrap();
+    let data = reader.chunk_data(b"data").unwrap();
+    let ixml = b"<?xml version=\"1.0\"?><BWFXML><SCENE>1A</SCENE><TAKE>3</TAKE></BWFXML>";
+    let axml = b"<ebuCoreMain/>";
+    let file = riff_wave(&[
+        (b"fmt ", fmt),
+        (b"iXML", ixml),
+        (b"data", data),
+        (b"axml", axml),
+    ]);
+    let reader = PcmReader::new(&file).unwrap();
+    assert_eq!(reader.ixml(), Some(&ixml[..]));
+    assert_eq!(reader.axml(), Some(&axml[..]));
+    // The IDs are case sensitive.
+    let file = riff_wave(&[(b"fmt ", fmt), (b"ixml", ixml), (b"data", data)]);
+    assert_eq!(PcmReader::new(&file).unwrap().ixml(), None);
+}
+
+#[test]
+fn aiff_instrument() {
+    use pacmog::metadata::AiffInstrument;
+
+    let aiff = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.aif");
+    let original = PcmReader::new(aiff).unwrap();
+    assert_eq!(original.aiff_instrument(), None);
+    let comm = original.chunk_data(b"COMM").unwrap();
+    let ssnd = original.chunk_data(b"SSND").unwrap();
+    let num_samples = original.specs().num_samples;
+
+    let mut mark = 4u16.to_be_bytes().to_vec();
+    for (id, position, name) in [
+        (1i16, 1000u32, &b"beg"[..]),
+        (2, 2200, b""),
+        (3, num_samples, b"end"),
+        (4, num_samples + 1, b"after"),
+    ] {
+        mark.extend_from_slice(&id.to_be_bytes());
+        mark.extend_from_slice(&position.to_be_bytes());
+        mark.push(name.len() as u8);
+        mark.extend_from_slice(name);
+        if name.len() % 2 == 0 {
+            mark.push(0);
+        }
+    }
+    let inst = |sustain: [i16; 3], release: [i16; 3]| {
+        let mut inst = vec![60, (-5i8) as u8, 48, 72, 1, 127];
+        inst.extend_from_slice(&(-3i16).to_be_bytes());
+        for v in sustain.iter().chain(&release) {
+            inst.extend_from_slice(&v.to_be_bytes());
+        }
+        inst
+    };
+
+    let chunk = inst([1, 1, 2], [0, 0, 0]);
+    let file = aiff_form(
+        b"AIFF",
+        &[
+            (b"COMM", comm),
+            (b"MARK", &mark),
+            (b"INST", &chunk),
+            (b"SSND", ssnd),
+        ],
+    );
+    assert_eq!(
+        PcmReader::new(&file).unwrap().aiff_instrument(),
+        Some(AiffInstrument {
+            base_note: 60,
+            detune: -5,
+            low_note: 48,
+            high_note: 72,
+            low_velocity: 1,
+            high_velocity: 127,
+            gain: -3,
+            sustain_loop: Some((1000, 2200)),
+            release_loop: None,
+        })
+    );
+
+    for (sustain, expected) in [
+        // Forward and backward, up to the end of the samples
+        ([2, 2, 3], Some((2200, num_samples))),
+        // Missing marker
+        ([1, 1, 5], None),
+        // After the end of the samples
+        ([1, 2, 4], None),
+        // The end before the begin
+        ([1, 2, 1], None),
+    ] {
+        let chunk = inst(sustain, [1, 1, 2]);
+        let file = aiff_form(
+            b"AIFF",
+            &[
+                (b"COMM", comm),
+                (b"INST", &chunk),
+                (b"MARK", &mark),
+                (b"SSND", ssnd),
+            ],
+        );
+        let instrument = PcmReader::new(&file).unwrap().aiff_instrument().unwrap();
+        assert_eq!(instrument.sustain_loop, expected);
+        assert_eq!(instrument.release_loop, Some((1000, 2200)));
+    }
+
+    // Without MARK, the loops are None.
+    let chunk = inst([1, 1, 2], [1, 1, 2]);
+    let file = aiff_form(
+        b"AIFF",
+        &[(b"COMM", comm), (b"INST", &chunk), (b"SSND", ssnd)],
+    );
+    let instrument = PcmReader::new(&file).unwrap().aiff_instrument().unwrap();
+    assert_eq!(instrument.base_note, 60);
+    assert_eq!(instrument.sustain_loop, None);
+    assert_eq!(instrument.release_loop, None);
+
+    let file = aiff_form(
+        b"AIFF",
+        &[(b"COMM", comm), (b"INST", &chunk[..19]), (b"SSND", ssnd)],
+    );
+    assert_eq!(PcmReader::new(&file).unwrap().aiff_instrument(), None);
+}
+
+#[test]
+fn root_note() {
+    use pacmog::pitch::{note_ratio, Root, U16F16};
+
+    let wav = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav");
+    let original = PcmReader::new(wav).unwrap();
+    assert_eq!(original.root_note(), None);
+
+    // The smpl chunk is preferred over the inst chunk.
+    let wav = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16_smpl_inst.wav");
+    let reader = PcmReader::new(wav).unwrap();
+    let root = reader.root_note().unwrap();
+    assert_eq!(
+        root,
+        Root {
+            note: 69,
+            fine_cents: 0
+        }
+    );
+    assert_eq!(root.ratio(81), note_ratio(69, 81, 0));
+
+    let mut player = PcmPlayer::new(reader);
+    assert_eq!(player.speed_ratio(), U16F16::ONE);
+    player.set_note(root.note, 81, root.fine_cents);
+    assert_eq!(player.speed_ratio(), U16F16::from_num(2));
+    player.set_note(root.note, 57, root.fine_cents);
+    assert_eq!(player.speed_ratio(), U16F16::from_num(0.5));
+    player.set_note(root.note, 70, root.fine_cents);
+    assert_eq!(player.speed_ratio(), note_ratio(69, 70, 0));
+
+    let fmt = original.chunk_data(b"fmt ").unwrap();
+    let data = original.chunk_data(b"data").unwrap();
+    let inst = [48, (-12i8) as u8, 0, 0, 127, 1, 127];
+    let file = riff_wave(&[(b"fmt ", fmt), (b"data", data), (b"inst", &inst)]);
+    let root = PcmReader::new(&file).unwrap().root_note().unwrap();
+    assert_eq!(
+        root,
+        Root {
+            note: 48,
+            fine_cents: -12
+        }
+    );
+    assert_eq!(root.ratio(60), note_ratio(48, 60, -12));
+    // A malformed inst chunk is ignored.
+    let file = riff_wave(&[(b"fmt ", fmt), (b"data", data), (b"inst", &inst[..6])]);
+    assert_eq!(PcmReader::new(&file).unwrap().root_note(), None);
+
+    let aiff = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.aif");
+    let original = PcmReader::new(aiff).unwrap();
+    assert_eq!(original.root_note(), None);
+    let comm = original.chunk_data(b"COMM").unwrap();
+    let ssnd = original.chunk_data(b"SSND").unwrap();
+    let mut inst = vec![60, (-5i8) as u8, 48, 72, 1, 127, 0, 0];
+    inst.extend_from_slice(&[0; 12]);
+    let file = aiff_form(
+        b"AIFF",
+        &[(b"COMM", comm), (b"INST", &inst), (b"SSND", ssnd)],
+    );
+    let root = PcmReader::new(&file).unwrap().root_note().unwrap();
+    assert_eq!(
+        root,
+        Root {
+            note: 60,
+            fine_cents: -5
+        }
+    );
+    assert_eq!(root.ratio(72), note_ratio(60, 72, -5));
+}
+
+#[test]
+fn aiff_comments() {
+    use pacmog::metadata::{Comment, MAX_NUM_COMMENTS};
+
+    let aiff = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.aif");
+    let original = PcmReader::new(aiff).unwrap();
+    assert!(original.aiff_comments().is_empty());
+    let comm = original.chunk_data(b"COMM").unwrap();
+    let ssnd = original.chunk_data(b"SSND").unwrap();
+
+    let comt = |comments: &[(u32, i16, &[u8])]| {
+        let mut comt = (comments.len() as u16).to_be_bytes().to_vec();
+        for (timestamp, marker_id, text) in comments {
+            comt.extend_from_slice(&timestamp.to_be_bytes());
+            comt.extend_from_slice(&marker_id.to_be_bytes());
+            comt.extend_from_slice(&(text.len() as u16).to_be_bytes());
+            comt.extend_from_slice(text);
+            if text.len() % 2 == 1 {
+                comt.push(0);
+            }
+        }
+        comt
+    };
+    // 2024-05-01 00:00:00 in seconds since 1904
+    let timestamp = 3797366400;
+    let chunk = comt(&[
+        (timestamp, 0, b"Recorded at 48kHz"),
+        (timestamp + 60, 1, b"Caf\x8E"),
+        (timestamp + 120, 2, b""),
+    ]);
+    let file = aiff_form(
+        b"AIFF",
+        &[(b"COMM", comm), (b"COMT", &chunk), (b"SSND", ssnd)],
+    );
+    let comments = PcmReader::new(&file).unwrap().aiff_comments();
+    assert_eq!(
+        comments[..],
+        [
+            Comment {
+                timestamp,
+                marker_id: 0,
+                text: b"Recorded at 48kHz",
+            },
+            // Mac Roman is not decoded.
+            Comment {
+                timestamp: timestamp + 60,
+                marker_id: 1,
+                text: b"Caf\x8E",
+            },
+            Comment {
+                timestamp: timestamp + 120,
+                marker_id: 2,
+                text: b"",
+            },
+        ]
+    );
+
+    // The pad byte of the last comment is missing, and the count is larger than the chunk holds.
+    let mut chunk = comt(&[(timestamp, 0, b"odd")]);
+    chunk.pop();
+    chunk[..2].copy_from_slice(&3u16.to_be_bytes());
+    let file = aiff_form(
+        b"AIFF",
+        &[(b"COMM", comm), (b"COMT", &chunk), (b"SSND", ssnd)],
+    );
+    let comments = PcmReader::new(&file).unwrap().aiff_comments();
+    assert_eq!(comments.len(), 1);
+    assert_eq!(comments[0].text, b"odd");
+
+    // A comment cut off by the end of the chunk, and more comments than the list holds.
+    let chunk = comt(&[(timestamp, 0, b"cut off")]);
+    let file = aiff_form(
+        b"AIFF",
+        &[(b"COMM", comm), (b"COMT", &chunk[..12]), (b"SSND", ssnd)],
+    );
+    assert!(PcmReader::new(&file).unwrap().aiff_comments().is_empty());
+    let chunk = comt(&[(timestamp, 0, &b"text"[..]); MAX_NUM_COMMENTS + 1]);
+    let file = aiff_form(
+        b"AIFF",
+        &[(b"COMM", comm), (b"COMT", &chunk), (b"SSND", ssnd)],
+    );
+    let comments = PcmReader::new(&file).unwrap().aiff_comments();
+    if cfg!(feature = "alloc") {
+        assert_eq!(comments.len(), MAX_NUM_COMMENTS + 1);
+    } else {
+        assert_eq!(comments.len(), MAX_NUM_COMMENTS);
+    }
+}
+
+#[test]
+fn aiff_text_chunks() {
+    let aiff = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16_text.aif");
+    let reader = PcmReader::new(aiff).unwrap();
+    assert_eq!(reader.name(), Some(&b"Sine 440Hz"[..]));
+    assert_eq!(reader.author(), Some(&b"pacmog"[..]));
+    assert_eq!(reader.copyright(), Some(&b"2024 pacmog, CC0"[..]));
+    assert_eq!(
+        reader.annotations().collect::<Vec<_>>(),
+        [&b"Generated for the tests"[..], b"48kHz 16bit mono"]
+    );
+    let original = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.aif");
+    let original = PcmReader::new(original).unwrap();
+    assert_eq!(reader.specs().num_samples, original.specs().num_samples);
+    assert_eq!(
+        reader.read_sample(0, 100).unwrap(),
+        original.read_sample(0, 100).unwrap()
+    );
+
+    assert_eq!(original.name(), None);
+    assert_eq!(original.author(), None);
+    assert_eq!(original.copyright(), None);
+    assert_eq!(original.annotations().count(), 0);
+}
+
+#[test]
+fn application_chunks() {
+    let aiff = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.aif");
+    let original = PcmReader::new(aiff).unwrap();
+    assert_eq!(original.application_chunks().count(), 0);
+    assert_eq!(original.application_chunk(b"CALB"), None);
+    let comm = original.chunk_data(b"COMM").unwrap();
+    let ssnd = original.chunk_data(b"SSND").unwrap();
+
+    let file = aiff_form(
+        b"AIFF",
+        &[
+            (b"COMM", comm),
+            (b"APPL", b"CALBgain=0.98"),
+            (b"SSND", ssnd),
+            (b"APPL", b"pdos\x01\x02\x03\x04"),
+            // Shorter than the signature
+            (b"APPL", b"ab"),
+            (b"APPL", b"CALBsecond"),
+        ],
+    );
+    let reader = PcmReader::new(&file).unwrap();
+    assert_eq!(
+        reader.application_chunks().collect::<Vec<_>>(),
+        [
+            (*b"CALB", &b"gain=0.98"[..]),
+            (*b"pdos", b"\x01\x02\x03\x04"),
+            (*b"CALB", b"second"),
+        ]
+    );
+    assert_eq!(reader.application_chunk(b"CALB"), Some(&b"gain=0.98"[..]));
+    assert_eq!(
+        reader.application_chunk(b"pdos"),
+        Some(&b"\x01\x02\x03\x04"[..])
+    );
+    assert_eq!(reader.application_chunk(b"stoc"), None);
+}
+
+#[test]
+fn container_format() {
+    use pacmog::{ContainerFormat, AIFC_VERSION_1};
+
+    for (file, container) in [
+        (
+            &include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav")[..],
+            ContainerFormat::Wav,
+        ),
+        (
+            include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16_RF64.wav"),
+            ContainerFormat::Rf64,
+        ),
+        (
+            include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.w64"),
+            ContainerFormat::Wave64,
+        ),
+        (
+            include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.aif"),
+            ContainerFormat::Aiff,
+        ),
+        (
+            include_bytes!("./resources/Sine440Hz_1ch_48000Hz_8_ALaw.aifc"),
+            ContainerFormat::AiffC,
+        ),
+        (
+            include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.caf"),
+            ContainerFormat::Caf,
+        ),
+    ] {
+        let reader = PcmReader::new(file).unwrap();
+        assert_eq!(reader.container(), container);
+        let expected = (container == ContainerFormat::AiffC).then_some(AIFC_VERSION_1);
+        assert_eq!(reader.aifc_version(), expected);
+    }
+    let specs = PcmSpecs::new(AudioFormat::LinearPcmLe, 1, 48000, 16);
+    let reader = PcmReader::new_raw(&[0; 4], specs).unwrap();
+    assert_eq!(reader.container(), ContainerFormat::Raw);
+
+    // A reloaded reader reports the new container.
+    let mut reader = PcmReader::new(include_bytes!(
+        "./resources/Sine440Hz_1ch_48000Hz_8_MuLaw.aifc"
+    ))
+    .unwrap();
+    assert_eq!(reader.container(), ContainerFormat::AiffC);
+    reader
+        .reload(include_bytes!(
+            "./resources/Sine440Hz_1ch_48000Hz_8_MuLaw.wav"
+        ))
+        .unwrap();
+    assert_eq!(reader.container(), ContainerFormat::Wav);
+
+    // An unknown version of AIFF-C is rejected in strict mode.
+    let aifc = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_8_ALaw.aifc");
+    let original = PcmReader::new(aifc).unwrap();
+    let comm = original.chunk_data(b"COMM").unwrap();
+    let ssnd = original.chunk_data(b"SSND").unwrap();
+    let fver = 0xA280_5141u32.to_be_bytes();
+    let file = aiff_form(
+        b"AIFC",
+        &[(b"FVER", &fver), (b"COMM", comm), (b"SSND", ssnd)],
+    );
+    assert!(matches!(
+        PcmReader::new(&file),
+        Err(PcmReaderError::UnsupportedAudioFormat)
+    ));
+    let options = ReaderOptions {
+        mode: ParseMode::Lenient,
+        ..Default::default()
+    };
+    let reader = PcmReader::<16>::new_with_options(&file, options).unwrap();
+    assert_eq!(reader.aifc_version(), Some(0xA280_5141));
+    assert_eq!(reader.specs().num_samples, original.specs().num_samples);
+
+    // FVER is optional, and ignored in AIFF.
+    let file = aiff_form(b"AIFC", &[(b"COMM", comm), (b"SSND", ssnd)]);
+    let reader = PcmReader::new(&file).unwrap();
+    assert_eq!(reader.container(), ContainerFormat::AiffC);
+    assert_eq!(reader.aifc_version(), None);
+    let aiff = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.aif");
+    let original = PcmReader::new(aiff).unwrap();
+    let comm = original.chunk_data(b"COMM").unwrap();
+    let ssnd = original.chunk_data(b"SSND").unwrap();
+    let file = aiff_form(
+        b"AIFF",
+        &[(b"FVER", &fver), (b"COMM", comm), (b"SSND", ssnd)],
+    );
+    let reader = PcmReader::new(&file).unwrap();
+    assert_eq!(reader.container(), ContainerFormat::Aiff);
+    assert_eq!(reader.aifc_version(), None);
+}
+
+#[test]
+fn ssnd_offset() {
+    let original = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.aif");
+    let original = PcmReader::new(original).unwrap();
+    // offset 8 and blockSize 16, with 8 bytes of padding after the samples
+    let aiff = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16_ssnd_offset.aif");
+    let reader = PcmReader::new(aiff).unwrap();
+    assert_eq!(reader.specs().num_samples, original.specs().num_samples);
+    for i in 0..reader.specs().num_samples {
+        assert_eq!(
+            reader.read_sample(0, i).unwrap(),
+            original.read_sample(0, i).unwrap()
+        );
+    }
+    let mut player = PcmPlayer::new(reader);
+    let mut frame = [0f32; 1];
+    for _ in 0..original.specs().num_samples {
+        player.get_next_frame(&mut frame).unwrap();
+    }
+    assert!(player.get_next_frame(&mut frame).is_err());
+
+    let mut scratch = [0u8; 64];
+    let mut streaming = StreamingPlayer::new(&aiff[..], &mut scratch).unwrap();
+    let mut streamed = [0f32; 1000];
+    assert_eq!(streaming.fill_interleaved(&mut streamed).unwrap(), 1000);
+    for (i, sample) in streamed.into_iter().enumerate() {
+        assert_eq!(sample, original.read_sample(0, i as u32).unwrap());
+    }
+
+    // A negative offset, and an offset beyond the sound data.
+    let comm = original.chunk_data(b"COMM").unwrap();
+    for offset in [-1i32, 17] {
+        let mut ssnd = offset.to_be_bytes().to_vec();
+        ssnd.extend_from_slice(&[0; 4 + 16]);
+        let file = aiff_form(b"AIFF", &[(b"COMM", comm), (b"SSND", &ssnd)]);
+        assert!(matches!(
+            PcmReader::new(&file),
+            Err(PcmReaderError::UnsupportedAudioFormat)
+        ));
+        assert!(StreamingPlayer::new(&file[..], &mut scratch).is_err());
+    }
+}
+
+#[test]
+fn channel_layout() {
+    use pacmog::metadata::ChannelLayout;
+
+    let wav = include_bytes!("./resources/Sine_6ch_48000Hz_16_chna.wav");
+    let aifc = include_bytes!("./resources/Sine_6ch_48000Hz_16_CHAN.aifc");
+    let wav_reader = PcmReader::new(wav).unwrap();
+    let aifc_reader = PcmReader::new(aifc).unwrap();
+    assert_eq!(wav_reader.channel_layout(), Some(ChannelLayout::Surround51));
+    assert_eq!(
+        aifc_reader.channel_layout(),
+        Some(ChannelLayout::Surround51)
+    );
+    assert_eq!(aifc_reader.specs().num_channels, 6);
+    for channel in 0..6 {
+        for i in [0, 1, 1000, 4799] {
+            assert_eq!(
+                wav_reader.read_sample(channel, i).unwrap(),
+                aifc_reader.read_sample(channel, i).unwrap()
+            );
+        }
+    }
+    let original = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav");
+    assert_eq!(PcmReader::new(original).unwrap().channel_layout(), None);
+
+    // CHAN by a bitmap, by the descriptions, and tags which do not match.
+    let comm = aifc_reader.chunk_data(b"COMM").unwrap();
+    let ssnd = aifc_reader.chunk_data(b"SSND").unwrap();
+    let chan = |tag: u32, bitmap: u32, labels: &[u32]| {
+        let mut chan = Vec::new();
+        for v in [tag, bitmap, labels.len() as u32] {
+            chan.extend_from_slice(&v.to_be_bytes());
+        }
+        for label in labels {
+            chan.extend_from_slice(&label.to_be_bytes());
+            chan.extend_from_slice(&[0; 16]);
+        }
+        chan
+    };
+    for (chunk, layout) in [
+        (chan(1 << 16, 0x3F, &[]), ChannelLayout::Surround51),
+        (chan(0, 0, &[1, 2, 3, 4, 5, 6]), ChannelLayout::Surround51),
+        // Another order of the channels
+        (chan(0, 0, &[1, 2, 3, 5, 6, 4]), ChannelLayout::Unknown),
+        // MPEG_5_1_C, L C R Ls Rs LFE
+        (chan((123 << 16) | 6, 0, &[]), ChannelLayout::Unknown),
+        // Stereo, which is not 6 channels
+        (chan((101 << 16) | 2, 0, &[]), ChannelLayout::Unknown),
+        (
+            chan(0, 0, &[1, 2, 3, 4, 5, 6])[..100].to_vec(),
+            ChannelLayout::Unknown,
+        ),
+    ] {
+        let file = aiff_form(
+            b"AIFC",
+            &[(b"COMM", comm), (b"CHAN", &chunk), (b"SSND", ssnd)],
+        );
+        assert_eq!(
+            PcmReader::new(&file).unwrap().channel_layout(),
+            Some(layout)
+        );
+    }
+
+    // chna with the tracks in another order, and a track index out of the tracks.
+    let fmt = wav_reader.chunk_data(b"fmt ").unwrap();
+    let data = wav_reader.chunk_data(b"data").unwrap();
+    let chna = wav_reader.chunk_data(b"chna").unwrap();
+    let mut swapped = chna.to_vec();
+    swapped[4..6].copy_from_slice(&2u16.to_le_bytes());
+    swapped[44..46].copy_from_slice(&1u16.to_le_bytes());
+    let mut out_of_tracks = chna.to_vec();
+    out_of_tracks[4..6].copy_from_slice(&7u16.to_le_bytes());
+    for chunk in [swapped, out_of_tracks] {
+        let file = riff_wave(&[(b"fmt ", fmt), (b"chna", &chunk), (b"data", data)]);
+        assert_eq!(
+            PcmReader::new(&file).unwrap().channel_layout(),
+            Some(ChannelLayout::Unknown)
+        );
+    }
+}
+
+#[test]
+fn read_sample_raw() {
+    // Full-scale 32bit samples round-trip through the integers bit-exactly.
+    let values = [i32::MIN, i32::MAX, -1, 0, 1, 0x1234_5678];
+    let mut fmt = Vec::new();
+    for v in [1u16, 2] {
+        fmt.extend_from_slice(&v.to_le_bytes());
+    }
+    for v in [48000u32, 48000 * 8] {
+        fmt.extend_from_slice(&v.to_le_bytes());
+    }
+    for v in [8u16, 32] {
+        fmt.extend_from_slice(&v.to_le_bytes());
+    }
+    let data: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+    let wav = riff_wave(&[(b"fmt ", &fmt), (b"data", &data)]);
+    let reader = PcmReader::new(&wav).unwrap();
+    let mut round_trip = Vec::new();
+    for i in 0..values.len() / 2 {
+        for channel in 0..2 {
+            let raw = reader.read_sample_raw(channel, i as u32).unwrap();
+            assert_eq!(raw, reader.read_sample_exact(channel, i as u32).unwrap());
+            round_trip.extend_from_slice(&raw.to_le_bytes());
+        }
+    }
+    assert_eq!(round_trip, data);
+    assert_eq!(reader.read_sample_raw(0, 0).unwrap(), i32::MIN);
+    assert_eq!(reader.read_sample(0, 0).unwrap(), -1.0);
+
+    // The padding bits of 20bit samples in 24bit containers are kept.
+    let reader =
+        PcmReader::new(include_bytes!("./resources/Sine440Hz_1ch_48000Hz_20.wav")).unwrap();
+    let mut has_nonzero = false;
+    for i in 0..reader.specs().num_samples {
+        let raw = reader.read_sample_raw(0, i).unwrap();
+        assert_eq!(raw, reader.read_sample_exact(0, i).unwrap() << 4);
+        has_nonzero |= raw != 0;
+    }
+    assert!(has_nonzero);
+
+    // Unsigned 8bit and big-endian 24bit
+    let wav = PcmReader::new(include_bytes!("./resources/Sine440Hz_1ch_48000Hz_8.wav")).unwrap();
+    assert_eq!(wav.read_sample_raw(0, 1).unwrap(), 135 - 128);
+    let wav = PcmReader::new(include_bytes!("./resources/Sine440Hz_1ch_48000Hz_24.wav")).unwrap();
+    let aiff = PcmReader::new(include_bytes!("./resources/Sine440Hz_1ch_48000Hz_24.aif")).unwrap();
+    for i in 0..1000 {
+        assert_eq!(
+            aiff.read_sample_raw(0, i).unwrap(),
+            wav.read_sample_raw(0, i).unwrap()
+        );
+    }
+
+    assert!(matches!(
+        wav.read_sample_raw(1, 0),
+        Err(PcmReaderError::InvalidChannel)
+    ));
+    assert!(matches!(
+        wav.read_sample_raw(0, 240000),
+        Err(PcmReaderError::InvalidSample)
+    ));
+    for file in [
+        &include_bytes!("./resources/Sine440Hz_1ch_48000Hz_32FP.wav")[..],
+        include_bytes!("./resources/Sine440Hz_1ch_48000Hz_8_ALaw.wav"),
+    ] {
+        assert!(matches!(
+            PcmReader::new(file).unwrap().read_sample_raw(0, 0),
+            Err(PcmReaderError::UnsupportedAudioFormat)
+        ));
+    }
+}
+
+#[test]
+fn read_frame() {
+    for data in [
+        &include_bytes!("./resources/Sine_6ch_48000Hz_16_chna.wav")[..],
+        include_bytes!("./resources/Sine_6ch_48000Hz_16_CHAN.aifc"),
+        include_bytes!("./resources/Sine440Hz_1ch_48000Hz_24.wav"),
+        include_bytes!("./resources/Sine440Hz_1ch_48000Hz_8_ALaw.wav"),
+    ] {
+        let reader = PcmReader::new(data).unwrap();
+        let num_channels = reader.specs().num_channels;
+        let num_samples = reader.specs().num_samples;
+        let mut frame = [9.0f32; 8];
+        for sample in (0..num_samples).step_by(97) {
+            assert_eq!(
+                reader.read_frame(sample, &mut frame).unwrap(),
+                num_channels as usize
+            );
+            for channel in 0..num_channels {
+                let expected = reader.read_sample(channel, sample).unwrap();
+                assert_eq!(frame[channel as usize], expected);
+            }
+            // The rest of the buffer is left untouched.
+            assert!(frame[num_channels as usize..].iter().all(|&v| v == 9.0));
+        }
+        #[cfg(feature = "f64")]
+        {
+            let mut frame64 = [0f64; 8];
+            reader.read_frame(num_samples - 1, &mut frame64).unwrap();
+            reader.read_frame(num_samples - 1, &mut frame).unwrap();
+            for (a, b) in frame64.iter().zip(frame).take(num_channels as usize) {
+                assert_eq!(*a, f64::from(b));
+            }
+        }
+        assert!(matches!(
+            reader.read_frame(num_samples, &mut frame),
+            Err(PcmReaderError::InvalidSample)
+        ));
+    }
+
+    let reader =
+        PcmReader::new(include_bytes!("./resources/Sine_6ch_48000Hz_16_chna.wav")).unwrap();
+    assert!(matches!(
+        reader.read_frame(0, &mut [0f32; 5]),
+        Err(PcmReaderError::InvalidChannel)
+    ));
+    let reader = PcmReader::new(include_bytes!(
+        "./resources/Sine440Hz_1ch_48000Hz_4bit_IMAADPCM.wav"
+    ))
+    .unwrap();
+    assert!(reader.read_frame(0, &mut [0f32; 2]).is_err());
+
+    // The player decodes the same frames.
+    let reader =
+        PcmReader::new(include_bytes!("./resources/Sine_6ch_48000Hz_16_CHAN.aifc")).unwrap();
+    let mut player = PcmPlayer::new(reader.clone());
+    let mut expected = [0f32; 6];
+    let mut out = [0f32; 6];
+    for sample in 0..100 {
+        player.get_next_frame(&mut out).unwrap();
+        reader.read_frame(sample, &mut expected).unwrap();
+        assert_eq!(out, expected);
+    }
+    assert!(matches!(
+        player.get_next_frame(&mut out[..5]),
+        Err(PcmPlayerError::OutputBufferTooShort)
+    ));
+}
+
+#[test]
+fn read_frames() {
+    for data in [
+        &include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav")[..],
+        include_bytes!("./resources/Sine440Hz_1ch_48000Hz_32FP.wav"),
+        include_bytes!("./resources/Sine_6ch_48000Hz_16_CHAN.aifc"),
+        include_bytes!("./resources/Sine440Hz_1ch_48000Hz_8_MuLaw.wav"),
+    ] {
+        let reader = PcmReader::new(data).unwrap();
+        let num_channels = reader.specs().num_channels as usize;
+        let num_samples = reader.specs().num_samples;
+        let mut buffer = [0f32; 512];
+        let mut frame = 0;
+        while frame < num_samples {
+            let n = reader.read_frames(frame, &mut buffer).unwrap();
+            assert!(n > 0);
+            for (i, samples) in buffer[..n * num_channels].chunks(num_channels).enumerate() {
+                for (ch, sample) in samples.iter().enumerate() {
+                    let expected = reader.read_sample(ch as u16, frame + i as u32).unwrap();
+                    assert_eq!(*sample, expected);
+                }
+            }
+            frame += n as u32;
+        }
+        // Stops at the end of the file.
+        assert_eq!(frame, num_samples);
+        assert_eq!(reader.read_frames(num_samples, &mut buffer).unwrap(), 0);
+        assert!(matches!(
+            reader.read_frames(num_samples + 1, &mut buffer),
+            Err(PcmReaderError::InvalidSample)
+        ));
+
+        #[cfg(feature = "f64")]
+        {
+            let mut buffer64 = [0f64; 512];
+            let n = reader.read_frames(num_samples - 10, &mut buffer64).unwrap();
+            assert_eq!(n, 10);
+            reader.read_frames(num_samples - 10, &mut buffer).unwrap();
+            for (a, b) in buffer64.iter().zip(buffer).take(n * num_channels) {
+                assert_eq!(*a, f64::from(b));
+            }
+        }
+    }
+
+    let reader = PcmReader::new(include_bytes!(
+        "./resources/Sine440Hz_1ch_48000Hz_4bit_IMAADPCM.wav"
+    ))
+    .unwrap();
+    assert!(reader.read_frames(0, &mut [0f32; 16]).is_err());
+}
+
+#[test]
+fn frames() {
+    let reader =
+        PcmReader::new(include_bytes!("./resources/Sine_6ch_48000Hz_16_CHAN.aifc")).unwrap();
+    let num_samples = reader.specs().num_samples;
+    let mut frames = reader.frames::<f32>().unwrap();
+    assert_eq!(frames.len(), num_samples as usize);
+    let mut expected = [0f32; 6];
+    for (i, frame) in frames.by_ref().take(100).enumerate() {
+        reader.read_frame(i as u32, &mut expected).unwrap();
+        assert_eq!(frame[..], expected);
+    }
+    assert_eq!(frames.len(), num_samples as usize - 100);
+
+    // nth jumps to the frame.
+    let frame = frames.nth(1000).unwrap();
+    reader.read_frame(1100, &mut expected).unwrap();
+    assert_eq!(frame[..], expected);
+    assert_eq!(frames.position(), 1101);
+
+    // Lending-style reads into the buffer of the caller.
+    let mut out = [0f32; 6];
+    assert_eq!(frames.next_into(&mut out), Some(6));
+    reader.read_frame(1101, &mut expected).unwrap();
+    assert_eq!(out, expected);
+    assert_eq!(frames.next_into(&mut out[..5]), None);
+    assert_eq!(frames.position(), 1102);
+
+    // Terminates at the number of samples.
+    assert_eq!(
+        reader.frames::<f32>().unwrap().count(),
+        num_samples as usize
+    );
+    let mut frames = reader
+        .frames::<f32>()
+        .unwrap()
+        .skip(num_samples as usize - 1);
+    assert!(frames.next().is_some());
+    assert!(frames.next().is_none());
+    assert!(reader.frames::<f32>().unwrap().nth(usize::MAX).is_none());
+
+    let mono = PcmReader::new(include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav")).unwrap();
+    let samples: Vec<f32> = mono.frames::<f32>().unwrap().map(|f| f[0]).collect();
+    assert_eq!(samples.len(), mono.specs().num_samples as usize);
+    assert_eq!(samples[123], mono.read_sample(0, 123).unwrap());
+    #[cfg(feature = "f64")]
+    assert_eq!(
+        mono.frames::<f64>().unwrap().nth(123).unwrap()[0],
+        f64::from(samples[123])
+    );
+
+    let reader = PcmReader::new(include_bytes!(
+        "./resources/Sine440Hz_1ch_48000Hz_4bit_IMAADPCM.wav"
+    ))
+    .unwrap();
+    assert!(matches!(
+        reader.frames::<f32>(),
+        Err(PcmReaderError::UnsupportedAudioFormat)
+    ));
+}
+
+#[test]
+fn raw_data() {
+    let wav = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_16.wav");
+    let reader = PcmReader::new(wav).unwrap();
+    assert_eq!(reader.raw_data(), reader.chunk_data(b"data").unwrap());
+    assert_eq!(reader.bytes_per_frame(), 2);
+    let value = i16::from_le_bytes(reader.frame_bytes(10).unwrap().try_into().unwrap());
+    assert_eq!(
+        f32::from(value) / 32768.0,
+        reader.read_sample(0, 10).unwrap()
+    );
+    let last = reader.specs().num_samples - 1;
+    assert_eq!(
+        reader.frame_bytes(last).unwrap(),
+        &reader.raw_data()[last as usize * 2..]
+    );
+    assert_eq!(reader.frame_bytes(last + 1), None);
+
+    // The bytes of AIFF are big endian, and the frames of 6 channels are interleaved.
+    let aiff = include_bytes!("./resources/Sine_6ch_48000Hz_16_CHAN.aifc");
+    let reader = PcmReader::new(aiff).unwrap();
+    assert_eq!(reader.bytes_per_frame(), 12);
+    assert_eq!(
+        reader.raw_data().len(),
+        reader.specs().num_samples as usize * 12
+    );
+    let frame = reader.frame_bytes(100).unwrap();
+    for channel in 0..6 {
+        let bytes = [frame[channel * 2], frame[channel * 2 + 1]];
+        assert_eq!(
+            i32::from(i16::from_be_bytes(bytes)),
+            reader.read_sample_exact(channel as u16, 100).unwrap()
+        );
+    }
+
+    // The blocks of IMA-ADPCM are exposed, but not the frames.
+    let wav = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_4bit_IMAADPCM.wav");
+    let reader = PcmReader::new(wav).unwrap();
+    assert_eq!(reader.raw_data(), reader.chunk_data(b"data").unwrap());
+    assert_eq!(reader.bytes_per_frame(), 0);
+    assert_eq!(reader.frame_bytes(0), None);
+}
@@ -0,0 +1,28 @@
+//! Run with `wasm-pack test --node -- --features wasm --test wasm`.
+#![cfg(all(target_arch = "wasm32", feature = "wasm"))]
+
+use pacmog::wasm::WasmPcm;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+#[wasm_bindgen_test]
+fn decode_range() {
+    let wav = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_24.aif");
+    let pcm = WasmPcm::new(wav).unwrap();
+    assert!(pcm.specs_json().contains(r#""num_channels":1"#));
+    let samples = pcm.decode_range_f32(0, 100).unwrap();
+    assert_eq!(samples.len(), 100);
+    assert!(samples.iter().all(|s| (-1.0..1.0).contains(s)));
+}
+
+#[wasm_bindgen_test]
+fn unsupported_file() {
+    assert!(WasmPcm::new(&[0u8; 44]).is_err());
+}
+
+#[wasm_bindgen_test]
+fn adpcm_is_not_decoded() {
+    let wav = include_bytes!("./resources/Sine440Hz_1ch_48000Hz_4bit_IMAADPCM.wav");
+    let pcm = WasmPcm::new(wav).unwrap();
+    assert!(pcm.specs_json().contains(r#""audio_format":"ImaAdpcmLe""#));
+    assert!(pcm.decode_range_f32(0, 100).is_err());
+}